@@ -1,33 +1,72 @@
 extern crate actix_web;
+extern crate bytes;
+extern crate chrono;
+extern crate csv;
 extern crate directories;
 extern crate exif;
 extern crate futures;
 extern crate image;
+extern crate indicatif;
 extern crate jpeg_decoder;
+extern crate notify;
 extern crate rayon;
+extern crate rusqlite;
 extern crate serde;
-extern crate serde_json;
 extern crate tinyfiledialogs;
 extern crate yore;
+extern crate zip;
 
 #[macro_use]
 extern crate serde_derive;
 #[macro_use]
+extern crate serde_json;
+#[macro_use]
 extern crate structopt;
 
+/// Print a line to stdout, or to stderr if stdout is reserved for machine-readable output.
+macro_rules! message {
+    ($to_stderr:expr) => {
+        if $to_stderr {
+            eprintln!()
+        } else {
+            println!()
+        }
+    };
+    ($to_stderr:expr, $($arg:tt)*) => {
+        if $to_stderr {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
 mod common;
 
+use std::fmt;
 use std::fs::File;
-use std::io::stdin;
+use std::io::{stdin, stdout, BufWriter};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use chrono::Duration;
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
 use structopt::StructOpt;
 use yore::{
-    get_location_suggestion, load_location_history, GoogleLocationHistory, PhotoError,
-    PhotoLocation,
+    load_location_history, media_format, read_fallback_timestamp, suggest_location,
+    GoogleLocationHistory, HistoryStats, Journal, JournalEntry, Metadata, Photo, PhotoError,
+    PhotoLocation, Place, ReverseGeocoder, SuggestionAccuracy, Timestamp, TimestampFallback,
+    WriteOptions,
 };
 
-use common::{exiv2_write_coordinates, photo_paths, server::Server, ApplicationError};
+use common::export::{export as export_photos, ExportFormat, ExportedPhoto};
+use common::output::{OutputFormat, Record, RecordWriter};
+use common::{
+    journal_path, photo_paths, server::Server, utc_date_time, ApplicationError, CopyFromPhotosArgs,
+    TimestampFallbackArgs, WriteArgs,
+};
 
 #[derive(StructOpt)]
 #[structopt(
@@ -35,16 +74,255 @@ use common::{exiv2_write_coordinates, photo_paths, server::Server, ApplicationEr
     about = "Yore uses an exported Google Location History JSON file to suggest locations for
             images"
 )]
-struct Options {
+enum Command {
+    #[structopt(
+        name = "suggest",
+        about = "Print suggested locations for photos without saving them"
+    )]
+    Suggest {
+        #[structopt(flatten)]
+        suggestion_args: SuggestionArgs,
+
+        #[structopt(
+            short = "f",
+            long = "format",
+            raw(possible_values = r#"&["json", "jsonl", "csv", "table"]"#),
+            help = "Print one machine-readable record per photo in the given format. Other \
+                    messages are printed to stderr"
+        )]
+        format: Option<OutputFormat>,
+    },
+
+    #[structopt(name = "apply", about = "Suggest locations for photos and save them")]
+    Apply {
+        #[structopt(flatten)]
+        suggestion_args: SuggestionArgs,
+
+        #[structopt(
+            short = "a",
+            long = "apply-all",
+            raw(conflicts_with = r#""apply_if""#),
+            help = "Save all suggested locations without asking"
+        )]
+        apply_all: bool,
+
+        #[structopt(
+            long = "apply-if",
+            help = "Save suggested locations that meet all the given conditions without asking, \
+                    and skip the rest. Conditions are a comma-separated list of maximum accuracy \
+                    metres and seconds and/or the confidence, e.g. \
+                    meters=50,seconds=300,confidence=high"
+        )]
+        apply_if: Option<ApplyCondition>,
+
+        #[structopt(
+            short = "n",
+            long = "dry-run",
+            help = "Print the locations that would be saved instead of saving them. Implies \
+                    --apply-all unless --apply-if is given"
+        )]
+        dry_run: bool,
+
+        #[structopt(
+            short = "f",
+            long = "format",
+            raw(possible_values = r#"&["json", "jsonl", "csv", "table"]"#),
+            help = "Print one machine-readable record per photo in the given format. Other \
+                    messages and prompts are printed to stderr"
+        )]
+        format: Option<OutputFormat>,
+
+        #[structopt(flatten)]
+        write_args: WriteArgs,
+    },
+
+    #[structopt(name = "gui", about = "Start a server for the browser-based GUI")]
+    Gui {
+        #[structopt(
+            short = "l",
+            long = "locations",
+            parse(from_os_str),
+            help = "The path to a Google Location History JSON file"
+        )]
+        location_history_path: Option<PathBuf>,
+
+        #[structopt(
+            short = "i",
+            long = "interpolate",
+            help = "Interpolate between locations if an exact match is not found"
+        )]
+        interpolate: bool,
+
+        #[structopt(flatten)]
+        copy_from_photos_args: CopyFromPhotosArgs,
+
+        #[structopt(flatten)]
+        timestamp_fallback_args: TimestampFallbackArgs,
+
+        #[structopt(
+            long = "places",
+            parse(from_os_str),
+            help = "The path to a GeoNames cities file, e.g. cities15000.txt, to name the places \
+                    that locations are in"
+        )]
+        places_path: Option<PathBuf>,
+
+        #[structopt(
+            long = "tiles",
+            parse(from_os_str),
+            help = "An MBTiles file or a directory of {z}/{x}/{y} map tiles to show the map \
+                    with, so that the GUI works offline"
+        )]
+        tiles_path: Option<PathBuf>,
+
+        #[structopt(
+            long = "cache-size",
+            default_value = "500",
+            help = "The maximum size in megabytes of the thumbnails cached between runs, beyond \
+                    which the least recently used thumbnails are removed"
+        )]
+        cache_size: u64,
+
+        #[structopt(
+            short = "p",
+            long = "port",
+            default_value = "8080",
+            help = "The port that the GUI server should listen on"
+        )]
+        port: u16,
+
+        #[structopt(flatten)]
+        write_args: WriteArgs,
+
+        #[structopt(
+            parse(from_os_str),
+            help = "The image or a directory of images to suggest a location for"
+        )]
+        photo_path: Option<PathBuf>,
+    },
+
+    #[structopt(
+        name = "inspect",
+        about = "Print the metadata that Yore reads from a photo"
+    )]
+    Inspect {
+        #[structopt(flatten)]
+        timestamp_fallback_args: TimestampFallbackArgs,
+
+        #[structopt(parse(from_os_str), help = "The photo to inspect")]
+        photo_path: PathBuf,
+    },
+
+    #[structopt(
+        name = "history",
+        about = "Inspect a Google Location History JSON file"
+    )]
+    History {
+        #[structopt(subcommand)]
+        command: HistoryCommand,
+    },
+
+    #[structopt(
+        name = "export",
+        about = "Write existing and suggested locations for photos to a GeoJSON, KMZ, GPX or CSV \
+                 file"
+    )]
+    Export {
+        #[structopt(flatten)]
+        suggestion_args: SuggestionArgs,
+
+        #[structopt(
+            short = "f",
+            long = "format",
+            default_value = "geojson",
+            raw(possible_values = r#"&["geojson", "kmz", "gpx", "csv"]"#),
+            help = "The format to write photo locations in"
+        )]
+        format: ExportFormat,
+
+        #[structopt(
+            short = "o",
+            long = "output",
+            parse(from_os_str),
+            help = "The path of the file to write"
+        )]
+        output_path: PathBuf,
+    },
+
+    #[structopt(name = "undo", about = "Undo the most recently saved location")]
+    Undo,
+
+    #[structopt(
+        name = "rollback",
+        about = "Undo all the locations saved by a run of Yore"
+    )]
+    Rollback {
+        #[structopt(
+            long = "session",
+            help = "The ID of the session to roll back. Defaults to the most recent session"
+        )]
+        session: Option<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum HistoryCommand {
+    #[structopt(
+        name = "stats",
+        about = "Print statistics about the locations in a location history file"
+    )]
+    Stats {
+        #[structopt(
+            short = "g",
+            long = "gap-minutes",
+            default_value = "60",
+            parse(try_from_str = "parse_positive_minutes"),
+            help = "The minimum time between consecutive locations, in minutes, that counts as a \
+                    gap in the location history"
+        )]
+        gap_minutes: i64,
+
+        #[structopt(
+            short = "d",
+            long = "days",
+            help = "Print how much of each day the location history covers"
+        )]
+        print_days: bool,
+
+        #[structopt(
+            short = "p",
+            long = "photos",
+            parse(from_os_str),
+            help = "An image or a directory of images to check against the location history"
+        )]
+        photo_path: Option<PathBuf>,
+
+        #[structopt(flatten)]
+        timestamp_fallback_args: TimestampFallbackArgs,
+
+        #[structopt(
+            parse(from_os_str),
+            help = "The path to a Google Location History JSON file"
+        )]
+        location_history_path: PathBuf,
+    },
+}
+
+/// Options for getting location suggestions, shared by the subcommands that get them.
+#[derive(StructOpt)]
+struct SuggestionArgs {
     #[structopt(
         short = "l",
         long = "locations",
         parse(from_os_str),
-        required_unless = "use_gui",
+        raw(required_unless = r#""copy_from_photos""#),
         help = "The path to a Google Location History JSON file"
     )]
     location_history_path: Option<PathBuf>,
 
+    #[structopt(flatten)]
+    copy_from_photos_args: CopyFromPhotosArgs,
+
     #[structopt(
         short = "i",
         long = "interpolate",
@@ -52,131 +330,930 @@ struct Options {
     )]
     interpolate: bool,
 
-    #[structopt(
-        short = "r",
-        long = "read-only",
-        help = "Don't offer to save suggested locations"
-    )]
-    read_only: bool,
+    #[structopt(flatten)]
+    timestamp_fallback_args: TimestampFallbackArgs,
 
     #[structopt(
-        short = "g",
-        long = "gui",
-        help = "Start a server for the browser-based GUI"
+        long = "places",
+        parse(from_os_str),
+        help = "The path to a GeoNames cities file, e.g. cities15000.txt, to name the places that \
+                locations are in"
     )]
-    use_gui: bool,
+    places_path: Option<PathBuf>,
 
     #[structopt(
-        short = "p",
-        long = "port",
-        default_value = "8080",
-        help = "The port that the GUI server should listen on"
+        short = "j",
+        long = "jobs",
+        default_value = "0",
+        help = "The number of photos to read in parallel. Defaults to the number of CPUs"
     )]
-    port: u16,
+    jobs: usize,
 
     #[structopt(
         parse(from_os_str),
-        required_unless = "use_gui",
         help = "The image or a directory of images to suggest a location for"
     )]
-    photo_path: Option<PathBuf>,
+    photo_path: PathBuf,
+}
+
+impl SuggestionArgs {
+    fn location_history_path(&self) -> Option<&PathBuf> {
+        self.location_history_path.as_ref()
+    }
+
+    fn copy_from_photos(&self) -> bool {
+        self.copy_from_photos_args.copy_from_photos()
+    }
+
+    fn timestamp_fallbacks(&self) -> &[TimestampFallback] {
+        self.timestamp_fallback_args.timestamp_fallbacks()
+    }
+
+    fn geocoder(&self) -> Result<Option<ReverseGeocoder>, ApplicationError> {
+        match self.places_path {
+            Some(ref path) => Ok(Some(ReverseGeocoder::load(path)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Parse a number of minutes, which must be greater than zero.
+fn parse_positive_minutes(value: &str) -> Result<i64, String> {
+    match value.parse::<i64>() {
+        Ok(minutes) if minutes > 0 => Ok(minutes),
+        Ok(_) => Err("The number of minutes must be greater than zero".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
 }
 
 fn main() {
-    let options = Options::from_args();
+    match Command::from_args() {
+        Command::Suggest {
+            suggestion_args,
+            format,
+        } => {
+            let settings = RunSettings {
+                interpolate: suggestion_args.interpolate,
+                copy_from_photos: suggestion_args.copy_from_photos(),
+                timestamp_fallbacks: suggestion_args.timestamp_fallbacks().to_vec(),
+                write_mode: WriteMode::Never,
+                dry_run: false,
+                write_options: WriteOptions::default(),
+                output_format: format,
+                jobs: suggestion_args.jobs,
+                geocoder: suggestion_args.geocoder().unwrap(),
+                write_place_names: false,
+            };
 
-    if options.use_gui {
-        let mut server = Server::new(options.port, options.interpolate);
+            let summary = run_cli(
+                &suggestion_args.photo_path,
+                suggestion_args.location_history_path(),
+                settings,
+            ).unwrap();
 
-        if let Some(path) = options.photo_path {
-            server.search_photos_path(&path);
+            print_summary(&summary, format.is_some());
         }
+        Command::Apply {
+            suggestion_args,
+            apply_all,
+            apply_if,
+            dry_run,
+            format,
+            write_args,
+        } => {
+            let write_mode = if let Some(condition) = apply_if {
+                WriteMode::If(condition)
+            } else if apply_all || dry_run {
+                WriteMode::Always
+            } else {
+                WriteMode::Prompt
+            };
+
+            let settings = RunSettings {
+                interpolate: suggestion_args.interpolate,
+                copy_from_photos: suggestion_args.copy_from_photos(),
+                timestamp_fallbacks: suggestion_args.timestamp_fallbacks().to_vec(),
+                write_mode,
+                dry_run,
+                write_options: write_args.write_options(),
+                output_format: format,
+                jobs: suggestion_args.jobs,
+                geocoder: suggestion_args.geocoder().unwrap(),
+                write_place_names: write_args.write_place_names(),
+            };
+
+            let summary = run_cli(
+                &suggestion_args.photo_path,
+                suggestion_args.location_history_path(),
+                settings,
+            ).unwrap();
 
-        if let Some(path) = options.location_history_path {
-            server.load_location_history(&path).unwrap();
+            print_summary(&summary, format.is_some());
         }
+        Command::Gui {
+            location_history_path,
+            interpolate,
+            copy_from_photos_args,
+            timestamp_fallback_args,
+            places_path,
+            tiles_path,
+            cache_size,
+            port,
+            write_args,
+            photo_path,
+        } => {
+            let mut server = Server::new(port, interpolate);
+            server.set_max_cache_size(cache_size * 1024 * 1024).unwrap();
+            server.set_copy_from_photos(copy_from_photos_args.copy_from_photos());
+            server.set_timestamp_fallbacks(timestamp_fallback_args.timestamp_fallbacks().to_vec());
+            server.set_write_options(write_args.write_options());
+            server.set_write_place_names(write_args.write_place_names());
 
-        server.run().unwrap();
-    } else {
-        run_cli(
-            &options.photo_path.unwrap(),
-            &options.location_history_path.unwrap(),
-            options.interpolate,
-            options.read_only,
-        ).unwrap();
+            if let Some(path) = photo_path {
+                server.search_photos_path(&path);
+            }
+
+            if let Some(path) = location_history_path {
+                server.load_location_history(&path).unwrap();
+            }
+
+            if let Some(path) = places_path {
+                server.load_places(&path).unwrap();
+            }
+
+            if let Some(path) = tiles_path {
+                server.set_tile_source(&path).unwrap();
+            }
+
+            server.run().unwrap();
+        }
+        Command::Inspect {
+            timestamp_fallback_args,
+            photo_path,
+        } => inspect(&photo_path, timestamp_fallback_args.timestamp_fallbacks()),
+        Command::History {
+            command:
+                HistoryCommand::Stats {
+                    gap_minutes,
+                    print_days,
+                    photo_path,
+                    timestamp_fallback_args,
+                    location_history_path,
+                },
+        } => {
+            let location_history = load_history(&location_history_path).unwrap();
+            let stats = HistoryStats::new(&location_history, Duration::minutes(gap_minutes));
+
+            println!("{}:", location_history_path.display());
+            print_history_stats(&stats, print_days);
+
+            if let Some(path) = photo_path {
+                let photo_paths = photo_paths(&path);
+                let coverage = PhotoCoverage::new(
+                    &stats,
+                    &photo_paths,
+                    timestamp_fallback_args.timestamp_fallbacks(),
+                );
+                println!();
+                print!("{}", coverage);
+            }
+        }
+        Command::Export {
+            suggestion_args,
+            format,
+            output_path,
+        } => export(&suggestion_args, format, &output_path).unwrap(),
+        Command::Undo => undo(&Journal::new(&journal_path())).unwrap(),
+        Command::Rollback { session } => rollback(&Journal::new(&journal_path()), session).unwrap(),
+    }
+}
+
+fn print_summary(summary: &Summary, to_stderr: bool) {
+    message!(to_stderr);
+    message!(to_stderr, "{}", summary);
+}
+
+/// Conditions that a suggestion must meet for it to be saved without asking.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ApplyCondition {
+    max_meters: Option<u32>,
+    max_seconds: Option<i64>,
+    high_confidence: bool,
+}
+
+impl ApplyCondition {
+    fn is_met_by(&self, accuracy: &SuggestionAccuracy) -> bool {
+        self.max_meters.map_or(true, |m| accuracy.meters() <= m)
+            && self
+                .max_seconds
+                .map_or(true, |s| accuracy.seconds().abs() <= s)
+            && !(self.high_confidence && accuracy.is_low_confidence())
     }
 }
 
+impl FromStr for ApplyCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut condition = ApplyCondition::default();
+
+        for part in s.split(',') {
+            let mut key_value = part.splitn(2, '=').map(str::trim);
+            let (key, value) = match (key_value.next(), key_value.next()) {
+                (Some(key), Some(value)) => (key, value),
+                _ => return Err(format!("Expected a key=value condition, got \"{}\"", part)),
+            };
+
+            match key {
+                "meters" | "metres" => {
+                    condition.max_meters = Some(
+                        value
+                            .parse()
+                            .map_err(|e| format!("Invalid metres value \"{}\": {}", value, e))?,
+                    )
+                }
+                "seconds" => {
+                    condition.max_seconds = Some(
+                        value
+                            .parse()
+                            .map_err(|e| format!("Invalid seconds value \"{}\": {}", value, e))?,
+                    )
+                }
+                "confidence" => match value {
+                    "high" => condition.high_confidence = true,
+                    "low" => condition.high_confidence = false,
+                    _ => return Err(format!("Invalid confidence value \"{}\"", value)),
+                },
+                _ => return Err(format!("Unrecognised condition \"{}\"", key)),
+            }
+        }
+
+        Ok(condition)
+    }
+}
+
+/// How to decide whether to save a suggested location.
+#[derive(Clone, Debug, PartialEq)]
+enum WriteMode {
+    Prompt,
+    Always,
+    Never,
+    If(ApplyCondition),
+}
+
+#[derive(Debug, PartialEq)]
+enum Decision {
+    Write,
+    Skip,
+    Quit,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+    read_only: bool,
+    dry_run: bool,
+    photos: usize,
+    errors: usize,
+    existing: usize,
+    no_suggestion: usize,
+    suggested: usize,
+    saved: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+impl Summary {
+    fn add_result(&mut self, result: &Result<PhotoLocation, PhotoError>) {
+        self.photos += 1;
+        match result {
+            Err(_) => self.errors += 1,
+            Ok(PhotoLocation::Existing(_)) => self.existing += 1,
+            Ok(PhotoLocation::Suggested(_, _)) => self.suggested += 1,
+            Ok(PhotoLocation::None) => self.no_suggestion += 1,
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Processed {} photos:", self.photos)?;
+        writeln!(f, "\t{} already had a location", self.existing)?;
+        writeln!(f, "\t{} had a suggested location", self.suggested)?;
+        writeln!(f, "\t{} had no suggested location", self.no_suggestion)?;
+        write!(f, "\t{} could not be read", self.errors)?;
+
+        if self.read_only {
+            return Ok(());
+        }
+
+        if self.dry_run {
+            write!(f, "\n{} locations would be saved", self.saved)?;
+        } else {
+            write!(f, "\n{} locations saved", self.saved)?;
+        }
+
+        write!(f, ", {} skipped", self.skipped)?;
+
+        if self.failed > 0 {
+            write!(f, ", {} failed to save", self.failed)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Settings for how the CLI gets suggestions and what it does with them.
+struct RunSettings {
+    interpolate: bool,
+    copy_from_photos: bool,
+    timestamp_fallbacks: Vec<TimestampFallback>,
+    write_mode: WriteMode,
+    dry_run: bool,
+    write_options: WriteOptions,
+    output_format: Option<OutputFormat>,
+    jobs: usize,
+    geocoder: Option<ReverseGeocoder>,
+    write_place_names: bool,
+}
+
 fn run_cli(
     root_path: &Path,
-    location_history_path: &Path,
-    interpolate: bool,
-    read_only: bool,
-) -> Result<(), ApplicationError> {
+    location_history_path: Option<&PathBuf>,
+    mut settings: RunSettings,
+) -> Result<Summary, ApplicationError> {
+    let photo_paths = photo_paths(root_path);
+
+    // Each photo is read once, both to copy its location into the history and to suggest one.
+    let photos = read_in_parallel(&photo_paths, settings.jobs, |path| {
+        Photo::with_fallbacks(path, &settings.timestamp_fallbacks)
+    })?;
+    let location_history =
+        suggestion_history(location_history_path, &photos, settings.copy_from_photos)?;
+
+    let dry_run = settings.dry_run;
+    let mut summary = Summary {
+        read_only: settings.write_mode == WriteMode::Never,
+        dry_run,
+        ..Summary::default()
+    };
+
+    let mut records = settings
+        .output_format
+        .map(|format| RecordWriter::new(format, stdout()));
+    let to_stderr = records.is_some();
+
+    let journal = Journal::new(&journal_path());
+
+    let results = get_location_suggestions(photos, &location_history, settings.interpolate);
+
+    for (photo_path, result) in photo_paths.into_iter().zip(results) {
+        let place = nearest_place(settings.geocoder.as_ref(), &result);
+        match records {
+            Some(ref mut writer) => writer.write(&Record::new(&photo_path, &result, place))?,
+            None => print_location_result(&photo_path, &result, place),
+        }
+        summary.add_result(&result);
+
+        // Suggestions aren't offered for saving in read-only mode, so none are skipped.
+        if summary.read_only {
+            continue;
+        }
+
+        if let Ok(PhotoLocation::Suggested(location, accuracy)) = result {
+            match decide(&mut settings.write_mode, &accuracy, to_stderr) {
+                Decision::Quit => break,
+                Decision::Skip => summary.skipped += 1,
+                Decision::Write if dry_run => {
+                    message!(
+                        to_stderr,
+                        "Would save location {} for {}",
+                        location,
+                        photo_path.display()
+                    );
+                    summary.saved += 1;
+                }
+                Decision::Write => {
+                    let place = if settings.write_place_names {
+                        place
+                    } else {
+                        None
+                    };
+                    let result = journal.write_coordinates(
+                        &photo_path,
+                        &location,
+                        place,
+                        &settings.write_options,
+                    );
+                    match result {
+                        Ok(_) => {
+                            message!(to_stderr, "Location saved for {}", photo_path.display());
+                            summary.saved += 1;
+                        }
+                        Err(e) => {
+                            eprintln!(
+                                "Error: Failed to save location for \"{}\": {}",
+                                photo_path.display(),
+                                e
+                            );
+                            summary.failed += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(writer) = records {
+        writer.finish()?;
+    }
+
+    if !dry_run && summary.saved > 0 {
+        message!(to_stderr);
+        message!(
+            to_stderr,
+            "To undo the saved locations, run: yore rollback --session {}",
+            journal.session()
+        );
+    }
+
+    Ok(summary)
+}
+
+fn undo(journal: &Journal) -> Result<(), ApplicationError> {
+    match journal.undo_last()? {
+        Some(entry) => print_undone_entry(&entry),
+        None => println!("There are no saved locations to undo"),
+    }
+
+    Ok(())
+}
+
+fn rollback(journal: &Journal, session: Option<String>) -> Result<(), ApplicationError> {
+    let session = match session {
+        Some(session) => session,
+        None => match journal.latest_session()? {
+            Some(session) => session,
+            None => {
+                println!("There are no saved locations to roll back");
+                return Ok(());
+            }
+        },
+    };
+
+    let entries = journal.rollback(&session)?;
+    for entry in &entries {
+        print_undone_entry(entry);
+    }
+
+    println!(
+        "Rolled back {} saved locations from session {}",
+        entries.len(),
+        session
+    );
+
+    Ok(())
+}
+
+fn print_undone_entry(entry: &JournalEntry) {
+    match entry.original_coordinates() {
+        Some(coordinates) => println!(
+            "Restored location {} for {}",
+            coordinates,
+            entry.path().display()
+        ),
+        None => println!(
+            "Removed location {} from {}",
+            entry.coordinates(),
+            entry.path().display()
+        ),
+    }
+}
+
+fn load_history(location_history_path: &Path) -> Result<GoogleLocationHistory, ApplicationError> {
     let location_history_file = File::open(location_history_path)?;
     let location_history = unsafe { load_location_history(&location_history_file)? };
 
-    for photo_path in photo_paths(root_path) {
-        process_photo(&photo_path, &location_history, interpolate, read_only)?;
+    Ok(location_history)
+}
+
+/// Load the location history to suggest locations from, if one is given, and add the locations
+/// of the given photos to it if copying locations from photos.
+fn suggestion_history(
+    location_history_path: Option<&PathBuf>,
+    photos: &[Result<Photo, PhotoError>],
+    copy_from_photos: bool,
+) -> Result<GoogleLocationHistory, ApplicationError> {
+    let mut location_history = match location_history_path {
+        Some(path) => load_history(path)?,
+        None => GoogleLocationHistory::default(),
+    };
+
+    if copy_from_photos {
+        location_history.extend(GoogleLocationHistory::from_photos(
+            photos.iter().flat_map(|photo| photo.as_ref().ok()),
+        ));
+    }
+
+    Ok(location_history)
+}
+
+fn inspect(path: &Path, timestamp_fallbacks: &[TimestampFallback]) {
+    println!("{}:", path.display());
+
+    let format = match media_format(path) {
+        Some(format) => format,
+        None => {
+            eprintln!("\tError loading photo: {}", PhotoError::UnsupportedFormat);
+            return;
+        }
+    };
+    println!("\tFormat: {}", format.name());
+
+    let metadata = match format.read_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(PhotoError::ExifError(exif::Error::NotFound(_))) => Metadata::default(),
+        Err(e) => {
+            eprintln!("\tError loading photo: {}", e);
+            return;
+        }
+    };
+
+    let timestamp = metadata
+        .timestamp()
+        .or_else(|| read_fallback_timestamp(path, timestamp_fallbacks));
+    match timestamp {
+        Some(timestamp) => println!(
+            "\tTimestamp: {} ({})",
+            format_timestamp(timestamp.ms()),
+            timestamp.source()
+        ),
+        None => println!("\tTimestamp: none"),
     }
 
+    match metadata.coordinates() {
+        Some(coordinates) => {
+            println!("\tLocation: {}", coordinates);
+            println!("\tView on map: {}", coordinates.map_url());
+        }
+        None => println!("\tLocation: none"),
+    }
+
+    match metadata.orientation() {
+        Some(orientation) => println!("\tOrientation: {}", orientation),
+        None => println!("\tOrientation: none"),
+    }
+}
+
+fn print_history_stats(stats: &HistoryStats, print_days: bool) {
+    println!("\tLocations: {}", stats.count());
+
+    if let (Some(first), Some(last)) = (stats.first_timestamp_ms(), stats.last_timestamp_ms()) {
+        println!("\tFrom: {}", format_timestamp(first));
+        println!("\tTo: {}", format_timestamp(last));
+        println!("\tSpan: {}", format_duration(stats.span()));
+    }
+
+    println!("\tAccuracy:");
+    let mut previous_max = 0;
+    for bucket in stats.accuracy_distribution() {
+        let label = match bucket.max_meters() {
+            Some(max) if previous_max == 0 => format!("Up to {} metres", max),
+            Some(max) => format!("{} to {} metres", previous_max + 1, max),
+            None => format!("Over {} metres", previous_max),
+        };
+        println!(
+            "\t\t{}: {} ({:.1}%)",
+            label,
+            bucket.count(),
+            percentage(bucket.count(), stats.count())
+        );
+        previous_max = bucket.max_meters().unwrap_or(previous_max);
+    }
+
+    println!("\tGaps: {}", stats.gaps().len());
+    for gap in stats.gaps() {
+        println!(
+            "\t\t{} to {} ({})",
+            format_timestamp(gap.start_ms()),
+            format_timestamp(gap.end_ms()),
+            format_duration(gap.duration())
+        );
+    }
+
+    let days = stats.days();
+    let days_with_locations = days.iter().filter(|d| d.locations() > 0).count();
+    let total_coverage: f64 = days.iter().map(|d| d.fraction_covered()).sum();
+    println!(
+        "\tDays: {}, {} with locations, {:.1}% covered",
+        stats.day_count(),
+        days_with_locations,
+        if stats.day_count() == 0 {
+            0.0
+        } else {
+            total_coverage * 100.0 / stats.day_count() as f64
+        }
+    );
+
+    if print_days {
+        for day in days {
+            println!(
+                "\t\t{}: {} locations, {:.1}% covered",
+                day.date(),
+                day.locations(),
+                day.fraction_covered() * 100.0
+            );
+        }
+    }
+}
+
+/// Which photos were taken during periods that a location history covers.
+#[derive(Debug, Default, PartialEq)]
+struct PhotoCoverage {
+    covered: usize,
+    uncovered: Vec<(PathBuf, i64)>,
+    errors: usize,
+}
+
+impl PhotoCoverage {
+    fn new(
+        stats: &HistoryStats,
+        photo_paths: &[PathBuf],
+        timestamp_fallbacks: &[TimestampFallback],
+    ) -> PhotoCoverage {
+        let timestamps: Vec<Option<i64>> = photo_paths
+            .par_iter()
+            .map(|path| {
+                Photo::with_fallbacks(path, timestamp_fallbacks)
+                    .ok()
+                    .map(|photo| photo.timestamp_ms())
+            }).collect();
+
+        let mut coverage = PhotoCoverage::default();
+        for (path, timestamp) in photo_paths.iter().zip(timestamps) {
+            match timestamp {
+                Some(timestamp_ms) if stats.is_covered(timestamp_ms) => coverage.covered += 1,
+                Some(timestamp_ms) => coverage.uncovered.push((path.clone(), timestamp_ms)),
+                None => coverage.errors += 1,
+            }
+        }
+
+        coverage
+    }
+}
+
+impl fmt::Display for PhotoCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let photos = self.covered + self.uncovered.len() + self.errors;
+        writeln!(f, "Checked {} photos:", photos)?;
+        writeln!(f, "\t{} taken during covered periods", self.covered)?;
+        writeln!(
+            f,
+            "\t{} taken during uncovered periods",
+            self.uncovered.len()
+        )?;
+        for (path, timestamp_ms) in &self.uncovered {
+            writeln!(
+                f,
+                "\t\t{} ({})",
+                path.display(),
+                format_timestamp(*timestamp_ms)
+            )?;
+        }
+        writeln!(f, "\t{} could not be read", self.errors)
+    }
+}
+
+fn percentage(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 * 100.0 / total as f64
+    }
+}
+
+fn export(
+    suggestion_args: &SuggestionArgs,
+    format: ExportFormat,
+    output_path: &Path,
+) -> Result<(), ApplicationError> {
+    let photo_paths = photo_paths(&suggestion_args.photo_path);
+
+    // Each photo is read once, both to copy its location into the history and to export it.
+    let read_photos = read_in_parallel(&photo_paths, suggestion_args.jobs, |path| {
+        Photo::with_fallbacks(path, suggestion_args.timestamp_fallbacks()).ok()
+    })?;
+
+    let mut location_history = match suggestion_args.location_history_path() {
+        Some(path) => load_history(path)?,
+        None => GoogleLocationHistory::default(),
+    };
+    if suggestion_args.copy_from_photos() {
+        location_history.extend(GoogleLocationHistory::from_photos(
+            read_photos.iter().flatten(),
+        ));
+    }
+
+    let photos: Vec<ExportedPhoto> = photo_paths
+        .iter()
+        .zip(&read_photos)
+        .filter_map(|(path, photo)| {
+            let photo = photo.as_ref()?;
+            let location = suggest_location(photo, &location_history, suggestion_args.interpolate);
+            let timestamp = Timestamp::new(photo.timestamp_ms(), photo.timestamp_source());
+
+            ExportedPhoto::new(path, Some(timestamp), location)
+        }).collect();
+
+    let file = BufWriter::new(File::create(output_path)?);
+    export_photos(format, &photos, file)?;
+
+    println!(
+        "Exported the locations of {} of {} photos to {}",
+        photos.len(),
+        photo_paths.len(),
+        output_path.display()
+    );
+
     Ok(())
 }
 
-fn process_photo(
-    photo_path: &Path,
+/// Format a Unix timestamp in milliseconds as a UTC date and time, or as a number of
+/// milliseconds if it is out of range.
+fn format_timestamp(timestamp_ms: i64) -> String {
+    match utc_date_time(timestamp_ms) {
+        Some(date_time) => date_time.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+        None => format!("{} ms", timestamp_ms),
+    }
+}
+
+/// Format a duration as a number of days, hours and minutes.
+fn format_duration(duration: Duration) -> String {
+    let periods = [
+        (duration.num_days(), "day", "days"),
+        (duration.num_hours() % 24, "hour", "hours"),
+        (duration.num_minutes() % 60, "minute", "minutes"),
+    ];
+
+    let periods: Vec<String> = periods
+        .iter()
+        .filter(|p| p.0 != 0)
+        .map(|&(count, singular, plural)| {
+            format!("{} {}", count, if count == 1 { singular } else { plural })
+        }).collect();
+
+    if periods.is_empty() {
+        "0 minutes".to_string()
+    } else {
+        periods.join(", ")
+    }
+}
+
+/// Get location suggestions for the given photos, which have already been read. The results are
+/// in the same order as the photos.
+fn get_location_suggestions(
+    photos: Vec<Result<Photo, PhotoError>>,
     location_history: &GoogleLocationHistory,
     interpolate: bool,
-    read_only: bool,
-) -> Result<(), ApplicationError> {
-    let result = get_location_suggestion(&photo_path, &location_history, interpolate);
+) -> Vec<Result<PhotoLocation, PhotoError>> {
+    photos
+        .into_iter()
+        .map(|photo| photo.map(|photo| suggest_location(&photo, location_history, interpolate)))
+        .collect()
+}
 
-    print_location_result(&photo_path, &result);
+/// Call the given function for each photo using a pool of the given number of threads, or one
+/// thread per CPU if zero. The results are in the same order as the paths. A progress bar is
+/// displayed if stderr is a terminal.
+fn read_in_parallel<T, F>(
+    photo_paths: &[PathBuf],
+    jobs: usize,
+    read: F,
+) -> Result<Vec<T>, ApplicationError>
+where
+    T: Send,
+    F: Fn(&Path) -> T + Sync,
+{
+    let pool = ThreadPoolBuilder::new().num_threads(jobs).build()?;
 
-    if let Ok(PhotoLocation::Suggested(location, _)) = result {
-        if !read_only && should_write() {
-            let output = exiv2_write_coordinates(&photo_path, &location)?;
+    let progress_bar = ProgressBar::new(photo_paths.len() as u64);
+    progress_bar.set_style(
+        ProgressStyle::default_bar().template("{wide_bar} {pos}/{len} photos, ETA {eta}"),
+    );
 
-            if output.status.success() {
-                println!("Location saved for {}", photo_path.display());
-            } else {
-                eprintln!(
-                    "Error: Failed to save location for \"{}\"!",
-                    photo_path.display()
-                );
+    let results = pool.install(|| {
+        photo_paths
+            .par_iter()
+            .map(|path| {
+                let result = read(path);
+                progress_bar.inc(1);
+                result
+            }).collect()
+    });
+
+    progress_bar.finish_and_clear();
+
+    Ok(results)
+}
+
+fn decide(write_mode: &mut WriteMode, accuracy: &SuggestionAccuracy, to_stderr: bool) -> Decision {
+    match write_mode.clone() {
+        WriteMode::Always => Decision::Write,
+        WriteMode::Never => Decision::Skip,
+        WriteMode::If(ref condition) if condition.is_met_by(accuracy) => Decision::Write,
+        WriteMode::If(_) => Decision::Skip,
+        WriteMode::Prompt => match prompt(to_stderr) {
+            PromptAnswer::Yes => Decision::Write,
+            PromptAnswer::No => Decision::Skip,
+            PromptAnswer::All => {
+                *write_mode = WriteMode::Always;
+                Decision::Write
             }
-        }
+            PromptAnswer::None => {
+                *write_mode = WriteMode::Never;
+                Decision::Skip
+            }
+            PromptAnswer::Quit => Decision::Quit,
+        },
     }
+}
 
-    Ok(())
+/// Get the named place nearest to a photo's existing or suggested location.
+fn nearest_place<'a>(
+    geocoder: Option<&'a ReverseGeocoder>,
+    location: &Result<PhotoLocation, PhotoError>,
+) -> Option<&'a Place> {
+    let coordinates = match location {
+        Ok(PhotoLocation::Existing(ref coordinates)) => coordinates,
+        Ok(PhotoLocation::Suggested(ref coordinates, _)) => coordinates,
+        _ => return None,
+    };
+
+    geocoder?.nearest(coordinates).map(|(place, _)| place)
 }
 
-fn print_location_result(path: &Path, location: &Result<PhotoLocation, PhotoError>) {
+fn print_location_result(
+    path: &Path,
+    location: &Result<PhotoLocation, PhotoError>,
+    place: Option<&Place>,
+) {
     println!();
     match location {
         Err(ref e) => {
-            eprintln!("{:?}:", path);
-            eprintln!("\tError loading photo: {:?}", e);
+            eprintln!("{}:", path.display());
+            eprintln!("\tError loading photo: {}", e);
         }
         Ok(PhotoLocation::Existing(ref location)) => {
-            println!("{:?}:", path);
+            println!("{}:", path.display());
             println!("\tAlready has a location: {}", location);
+            if let Some(place) = place {
+                println!("\tPlace: {}", place);
+            }
         }
         Ok(PhotoLocation::Suggested(ref location, ref accuracy)) => {
-            println!("{:?}:", path);
+            println!("{}:", path.display());
             println!("\tSuggested location: {}", location);
+            if let Some(place) = place {
+                println!("\tPlace: {}", place);
+            }
             println!("\tSuggestion accuracy: {}", accuracy);
             println!("\tView on map: {}", location.map_url());
         }
         Ok(PhotoLocation::None) => {
-            println!("{:?}:\n\tNo suggested location found", path);
+            println!("{}:\n\tNo suggested location found", path.display());
         }
     }
 }
 
-fn should_write() -> bool {
-    println!();
-    println!("Save the suggested location to this image? (y/n)");
+#[derive(Debug, PartialEq)]
+enum PromptAnswer {
+    Yes,
+    No,
+    All,
+    None,
+    Quit,
+}
+
+impl FromStr for PromptAnswer {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "y" | "yes" => Ok(PromptAnswer::Yes),
+            "n" | "no" => Ok(PromptAnswer::No),
+            "a" | "all" => Ok(PromptAnswer::All),
+            "none" => Ok(PromptAnswer::None),
+            "q" | "quit" => Ok(PromptAnswer::Quit),
+            _ => Err(()),
+        }
+    }
+}
+
+fn prompt(to_stderr: bool) -> PromptAnswer {
+    message!(to_stderr);
+    message!(
+        to_stderr,
+        "Save the suggested location to this image? (y/n/all/none/quit)"
+    );
 
     loop {
         let mut input = String::new();
@@ -184,10 +1261,429 @@ fn should_write() -> bool {
             .read_line(&mut input)
             .expect("Couldn't read input line");
 
-        match input.trim() {
-            "y" => return true,
-            "n" => return false,
-            _ => println!("Unrecognised input, please try again."),
+        match input.parse() {
+            Ok(answer) => return answer,
+            Err(_) => message!(to_stderr, "Unrecognised input, please try again."),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use yore::{get_location_suggestion, Coordinates, TimestampSource};
+
+    fn accuracy(meters: u32, seconds: i64) -> SuggestionAccuracy {
+        SuggestionAccuracy::new(meters, seconds, TimestampSource::DateTimeOriginal)
+    }
+
+    #[test]
+    fn apply_condition_from_str_should_parse_a_comma_separated_list_of_conditions() {
+        let condition: ApplyCondition = "meters=50, seconds=300,confidence=high".parse().unwrap();
+
+        assert_eq!(
+            ApplyCondition {
+                max_meters: Some(50),
+                max_seconds: Some(300),
+                high_confidence: true,
+            },
+            condition
+        );
+    }
+
+    #[test]
+    fn apply_condition_from_str_should_error_for_unrecognised_or_invalid_conditions() {
+        assert!("meters".parse::<ApplyCondition>().is_err());
+        assert!("meters=-1".parse::<ApplyCondition>().is_err());
+        assert!("distance=50".parse::<ApplyCondition>().is_err());
+        assert!("confidence=medium".parse::<ApplyCondition>().is_err());
+    }
+
+    #[test]
+    fn apply_condition_is_met_by_should_compare_absolute_time_differences() {
+        let condition: ApplyCondition = "meters=50,seconds=300".parse().unwrap();
+
+        assert!(condition.is_met_by(&accuracy(50, -300)));
+        assert!(!condition.is_met_by(&accuracy(51, 0)));
+        assert!(!condition.is_met_by(&accuracy(0, -301)));
+    }
+
+    #[test]
+    fn apply_condition_is_met_by_should_reject_low_confidence_suggestions_if_required() {
+        let low_confidence = SuggestionAccuracy::new(10, 10, TimestampSource::FileName);
+
+        assert!(ApplyCondition::default().is_met_by(&low_confidence));
+
+        let condition: ApplyCondition = "confidence=high".parse().unwrap();
+        assert!(!condition.is_met_by(&low_confidence));
+        assert!(condition.is_met_by(&accuracy(10, 10)));
+    }
+
+    #[test]
+    fn decide_should_not_prompt_for_non_interactive_write_modes() {
+        let mut mode = WriteMode::Always;
+        assert_eq!(Decision::Write, decide(&mut mode, &accuracy(10, 10), false));
+
+        let mut mode = WriteMode::Never;
+        assert_eq!(Decision::Skip, decide(&mut mode, &accuracy(10, 10), false));
+
+        let mut mode = WriteMode::If("meters=5".parse().unwrap());
+        assert_eq!(Decision::Skip, decide(&mut mode, &accuracy(10, 10), false));
+    }
+
+    #[test]
+    fn prompt_answer_from_str_should_accept_all_none_and_quit() {
+        assert_eq!(Ok(PromptAnswer::Yes), "y\n".parse());
+        assert_eq!(Ok(PromptAnswer::All), "all\n".parse());
+        assert_eq!(Ok(PromptAnswer::None), "None\n".parse());
+        assert_eq!(Ok(PromptAnswer::Quit), "q\n".parse());
+        assert_eq!(Err(()), "maybe\n".parse::<PromptAnswer>());
+    }
+
+    #[test]
+    fn command_should_require_a_subcommand() {
+        assert!(Command::from_iter_safe(&["yore"]).is_err());
+        assert!(Command::from_iter_safe(&["yore", "-l", "history.json", "photos"]).is_err());
+    }
+
+    #[test]
+    fn command_should_parse_subcommand_options() {
+        let command =
+            Command::from_iter_safe(&["yore", "suggest", "-i", "-l", "history.json", "photos"])
+                .unwrap();
+        match command {
+            Command::Suggest {
+                suggestion_args,
+                format,
+            } => {
+                assert!(suggestion_args.interpolate);
+                assert_eq!(
+                    Some(PathBuf::from("history.json")),
+                    suggestion_args.location_history_path
+                );
+                assert!(!suggestion_args.copy_from_photos());
+                assert_eq!(Path::new("photos"), suggestion_args.photo_path);
+                assert!(format.is_none());
+            }
+            _ => panic!("Expected the suggest subcommand"),
+        }
+
+        let command = Command::from_iter_safe(&["yore", "history", "stats", "history.json"]);
+        assert!(command.is_ok());
+    }
+
+    #[test]
+    fn command_should_require_a_positive_gap_for_history_stats() {
+        for gap in &["0", "-5", "ten"] {
+            let args = &["yore", "history", "stats", "-g", gap, "history.json"];
+            assert!(Command::from_iter_safe(args).is_err());
+        }
+
+        let args = &["yore", "history", "stats", "-g", "5", "history.json"];
+        assert!(Command::from_iter_safe(args).is_ok());
+    }
+
+    #[test]
+    fn command_should_require_a_location_history_unless_copying_from_photos() {
+        assert!(Command::from_iter_safe(&["yore", "suggest", "photos"]).is_err());
+
+        let command = Command::from_iter_safe(&["yore", "suggest", "-c", "photos"]).unwrap();
+        match command {
+            Command::Suggest {
+                suggestion_args, ..
+            } => {
+                assert!(suggestion_args.copy_from_photos());
+                assert!(suggestion_args.location_history_path.is_none());
+            }
+            _ => panic!("Expected the suggest subcommand"),
+        }
+    }
+
+    #[test]
+    fn command_should_read_timestamp_fallbacks_in_the_given_order() {
+        let args = &[
+            "yore",
+            "inspect",
+            "-t",
+            "xmp,filename",
+            "-t",
+            "mtime",
+            "photo.jpg",
+        ];
+        match Command::from_iter_safe(args).unwrap() {
+            Command::Inspect {
+                timestamp_fallback_args,
+                ..
+            } => assert_eq!(
+                &[
+                    TimestampFallback::XmpCreateDate,
+                    TimestampFallback::FileName,
+                    TimestampFallback::FileModificationTime,
+                ],
+                timestamp_fallback_args.timestamp_fallbacks()
+            ),
+            _ => panic!("Expected the inspect subcommand"),
+        }
+
+        let args = &["yore", "suggest", "-c", "-t", "mtime", "photos"];
+        match Command::from_iter_safe(args).unwrap() {
+            Command::Suggest {
+                suggestion_args, ..
+            } => assert_eq!(
+                &[TimestampFallback::FileModificationTime],
+                suggestion_args.timestamp_fallbacks()
+            ),
+            _ => panic!("Expected the suggest subcommand"),
+        }
+    }
+
+    #[test]
+    fn command_should_require_places_to_write_place_names() {
+        let args = &["yore", "apply", "--write-place-names", "-c", "photos"];
+        assert!(Command::from_iter_safe(args).is_err());
+
+        let args = &["yore", "gui", "--write-place-names"];
+        assert!(Command::from_iter_safe(args).is_err());
+
+        let args = &[
+            "yore",
+            "gui",
+            "--write-place-names",
+            "--places",
+            "cities.txt",
+        ];
+        assert!(Command::from_iter_safe(args).is_ok());
+
+        let args = &[
+            "yore",
+            "apply",
+            "--write-place-names",
+            "--places",
+            "cities15000.txt",
+            "-c",
+            "photos",
+        ];
+        match Command::from_iter_safe(args).unwrap() {
+            Command::Apply {
+                suggestion_args,
+                write_args,
+                ..
+            } => {
+                assert!(write_args.write_place_names());
+                assert_eq!(
+                    Some(PathBuf::from("cities15000.txt")),
+                    suggestion_args.places_path
+                );
+            }
+            _ => panic!("Expected the apply subcommand"),
+        }
+    }
+
+    #[test]
+    fn command_should_default_the_gui_cache_size_to_500_megabytes() {
+        match Command::from_iter_safe(&["yore", "gui"]).unwrap() {
+            Command::Gui { cache_size, .. } => assert_eq!(500, cache_size),
+            _ => panic!("Expected the gui subcommand"),
+        }
+
+        match Command::from_iter_safe(&["yore", "gui", "--cache-size", "20"]).unwrap() {
+            Command::Gui { cache_size, .. } => assert_eq!(20, cache_size),
+            _ => panic!("Expected the gui subcommand"),
+        }
+    }
+
+    #[test]
+    fn nearest_place_should_name_existing_and_suggested_locations() {
+        let geocoder =
+            ReverseGeocoder::load(Path::new("tests/assets/geonames/cities.txt")).unwrap();
+        let coordinates = Coordinates::new(52.0796733, 1.1965831);
+        let accuracy = SuggestionAccuracy::new(18, 0, TimestampSource::DateTimeOriginal);
+
+        let existing = Ok(PhotoLocation::Existing(coordinates.clone()));
+        let suggested = Ok(PhotoLocation::Suggested(coordinates, accuracy));
+
+        assert_eq!(
+            "Ipswich, England, United Kingdom",
+            nearest_place(Some(&geocoder), &existing)
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            "Ipswich, England, United Kingdom",
+            nearest_place(Some(&geocoder), &suggested)
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            None,
+            nearest_place(Some(&geocoder), &Ok(PhotoLocation::None))
+        );
+        assert_eq!(None, nearest_place(None, &existing));
+    }
+
+    #[test]
+    fn command_should_not_accept_options_for_other_subcommands() {
+        let args = &["yore", "suggest", "-a", "-l", "history.json", "photos"];
+        assert!(Command::from_iter_safe(args).is_err());
+
+        let args = &["yore", "gui", "--dry-run"];
+        assert!(Command::from_iter_safe(args).is_err());
+
+        let args = &[
+            "yore",
+            "apply",
+            "-a",
+            "--apply-if",
+            "meters=5",
+            "-l",
+            "history.json",
+            "photos",
+        ];
+        assert!(Command::from_iter_safe(args).is_err());
+    }
+
+    #[test]
+    fn summary_should_not_count_saved_locations_if_read_only() {
+        let summary = Summary {
+            read_only: true,
+            photos: 1,
+            suggested: 1,
+            ..Summary::default()
         };
+
+        assert!(summary.to_string().ends_with("\t0 could not be read"));
+    }
+
+    #[test]
+    fn format_timestamp_should_handle_timestamps_before_the_epoch_and_out_of_range() {
+        assert_eq!("2016-09-06 09:59:15.000", format_timestamp(1473155955000));
+        assert_eq!("1969-12-31 23:59:58.500", format_timestamp(-1500));
+        assert_eq!(
+            format!("{} ms", i64::max_value()),
+            format_timestamp(i64::max_value())
+        );
+    }
+
+    #[test]
+    fn format_duration_should_print_non_zero_days_hours_and_minutes() {
+        assert_eq!("0 minutes", format_duration(Duration::seconds(59)));
+        assert_eq!(
+            "1 day, 1 minute",
+            format_duration(Duration::minutes(24 * 60 + 1))
+        );
+        assert_eq!(
+            "2 days, 3 hours, 4 minutes",
+            format_duration(Duration::minutes(2 * 24 * 60 + 3 * 60 + 4))
+        );
+    }
+
+    #[test]
+    fn photo_coverage_new_should_sort_photos_by_whether_they_are_covered() {
+        let file = File::open("tests/assets/location_history.json").unwrap();
+        let history = unsafe { load_location_history(&file).unwrap() };
+        let paths = photo_paths(Path::new("tests/assets"));
+
+        // With a long enough gap threshold, the whole history is covered.
+        let stats = HistoryStats::new(&history, Duration::days(365));
+        let coverage = PhotoCoverage::new(&stats, &paths, &[]);
+
+        assert_eq!(10, coverage.covered);
+        assert_eq!(2, coverage.uncovered.len());
+        assert_eq!(2, coverage.errors);
+
+        let stats = HistoryStats::new(&history, Duration::hours(1));
+        let coverage = PhotoCoverage::new(&stats, &paths, &[]);
+
+        assert_eq!(0, coverage.covered);
+        assert_eq!(12, coverage.uncovered.len());
+    }
+
+    #[test]
+    fn get_location_suggestions_should_return_results_in_path_order() {
+        let file = File::open("tests/assets/location_history.json").unwrap();
+        let history = unsafe { load_location_history(&file).unwrap() };
+        let paths = photo_paths(Path::new("tests/assets"));
+
+        let photos = paths.iter().map(|path| Photo::new(path)).collect();
+        let results = get_location_suggestions(photos, &history, false);
+
+        assert_eq!(paths.len(), results.len());
+        for (path, result) in paths.iter().zip(results) {
+            let expected = get_location_suggestion(path, &history, false, &[]);
+            assert_eq!(format!("{:?}", expected), format!("{:?}", result));
+        }
+    }
+
+    #[test]
+    fn run_cli_should_count_photos_by_outcome_without_writing_in_dry_run_mode() {
+        let summary = run_cli(
+            Path::new("tests/assets"),
+            Some(&PathBuf::from("tests/assets/location_history.json")),
+            RunSettings {
+                interpolate: false,
+                copy_from_photos: false,
+                timestamp_fallbacks: Vec::new(),
+                write_mode: WriteMode::Always,
+                dry_run: true,
+                write_options: WriteOptions::default(),
+                output_format: Some(OutputFormat::JsonLines),
+                jobs: 2,
+                geocoder: None,
+                write_place_names: false,
+            },
+        ).unwrap();
+
+        assert_eq!(
+            Summary {
+                read_only: false,
+                dry_run: true,
+                photos: 14,
+                errors: 2,
+                existing: 6,
+                no_suggestion: 2,
+                suggested: 4,
+                saved: 4,
+                skipped: 0,
+                failed: 0,
+            },
+            summary
+        );
+    }
+
+    #[test]
+    fn run_cli_should_suggest_locations_copied_from_photos_without_a_location_history() {
+        let summary = run_cli(
+            Path::new("tests/assets"),
+            None,
+            RunSettings {
+                interpolate: false,
+                copy_from_photos: true,
+                timestamp_fallbacks: Vec::new(),
+                write_mode: WriteMode::Never,
+                dry_run: false,
+                write_options: WriteOptions::default(),
+                output_format: Some(OutputFormat::JsonLines),
+                jobs: 2,
+                geocoder: None,
+                write_place_names: false,
+            },
+        ).unwrap();
+
+        assert_eq!(
+            Summary {
+                read_only: true,
+                dry_run: false,
+                photos: 14,
+                errors: 2,
+                existing: 6,
+                no_suggestion: 4,
+                suggested: 2,
+                saved: 0,
+                skipped: 0,
+                failed: 0,
+            },
+            summary
+        );
     }
 }
@@ -0,0 +1,439 @@
+use std::borrow::Cow;
+use std::io::{self, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use csv::WriterBuilder;
+use rayon::prelude::*;
+use serde_json::{self, Value};
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+use yore::{Coordinates, PhotoLocation, SuggestionAccuracy, Timestamp, TimestampSource};
+
+use super::server::thumbnail;
+use super::utc_date_time;
+
+const HEADERS: &[&str] = &[
+    "path",
+    "status",
+    "latitude",
+    "longitude",
+    "timestamp",
+    "timestamp_source",
+    "accuracy_meters",
+    "accuracy_seconds",
+];
+
+/// A row of a CSV export, with fields in the same order as `HEADERS`.
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    path: Cow<'a, str>,
+    status: &'static str,
+    latitude: f64,
+    longitude: f64,
+    timestamp: Option<String>,
+    timestamp_source: Option<TimestampSource>,
+    accuracy_meters: Option<u32>,
+    accuracy_seconds: Option<i64>,
+}
+
+/// The maximum width and height of the thumbnails included in KMZ files.
+const THUMBNAIL_SIZE: u32 = 200;
+
+/// A file format for sharing photo locations with other tools.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// A GeoJSON FeatureCollection of points.
+    GeoJson,
+    /// A zipped KML document of placemarks, with a thumbnail of each photo.
+    Kmz,
+    /// A GPX file of waypoints.
+    Gpx,
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "geojson" => Ok(ExportFormat::GeoJson),
+            "kmz" => Ok(ExportFormat::Kmz),
+            "gpx" => Ok(ExportFormat::Gpx),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(format!(
+                "Unrecognised export format \"{}\", expected one of geojson, kmz, gpx or csv",
+                s
+            )),
+        }
+    }
+}
+
+/// A photo with an existing or suggested location.
+#[derive(Debug)]
+pub struct ExportedPhoto {
+    path: PathBuf,
+    timestamp: Option<Timestamp>,
+    coordinates: Coordinates,
+    accuracy: Option<SuggestionAccuracy>,
+}
+
+impl ExportedPhoto {
+    /// Returns `None` if the photo has no existing or suggested location.
+    pub fn new(
+        path: &Path,
+        timestamp: Option<Timestamp>,
+        location: PhotoLocation,
+    ) -> Option<ExportedPhoto> {
+        let (coordinates, accuracy) = match location {
+            PhotoLocation::Existing(coordinates) => (coordinates, None),
+            PhotoLocation::Suggested(coordinates, accuracy) => (coordinates, Some(accuracy)),
+            PhotoLocation::None => return None,
+        };
+
+        Some(ExportedPhoto {
+            path: path.to_path_buf(),
+            timestamp,
+            coordinates,
+            accuracy,
+        })
+    }
+
+    fn status(&self) -> &'static str {
+        if self.accuracy.is_some() {
+            "suggested"
+        } else {
+            "existing"
+        }
+    }
+
+    fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .unwrap_or_else(|| self.path.as_os_str())
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// The photo's timestamp as an ISO 8601 UTC date and time.
+    fn timestamp(&self) -> Option<String> {
+        self.timestamp
+            .and_then(|t| utc_date_time(t.ms()))
+            .map(|t| t.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+    }
+
+    fn timestamp_source(&self) -> Option<TimestampSource> {
+        self.timestamp.map(|t| t.source())
+    }
+
+    fn description(&self) -> String {
+        match self.accuracy {
+            Some(ref accuracy) => format!(
+                "{}\nSuggested location, accurate to {}",
+                self.path.display(),
+                accuracy
+            ),
+            None => format!("{}\nExisting location", self.path.display()),
+        }
+    }
+}
+
+/// Write the given photos in the given format.
+pub fn export<W: Write + Seek>(
+    format: ExportFormat,
+    photos: &[ExportedPhoto],
+    writer: W,
+) -> io::Result<()> {
+    match format {
+        ExportFormat::GeoJson => write_geojson(photos, writer),
+        ExportFormat::Kmz => write_kmz(photos, writer),
+        ExportFormat::Gpx => write_gpx(photos, writer),
+        ExportFormat::Csv => write_csv(photos, writer),
+    }
+}
+
+fn write_geojson<W: Write>(photos: &[ExportedPhoto], mut writer: W) -> io::Result<()> {
+    let features: Vec<Value> = photos
+        .iter()
+        .map(|photo| {
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [photo.coordinates.longitude(), photo.coordinates.latitude()],
+                },
+                "properties": {
+                    "path": photo.path.to_string_lossy(),
+                    "status": photo.status(),
+                    "timestamp": photo.timestamp(),
+                    "timestamp_source": photo.timestamp_source(),
+                    "accuracy_meters": photo.accuracy.as_ref().map(|a| a.meters()),
+                    "accuracy_seconds": photo.accuracy.as_ref().map(|a| a.seconds()),
+                },
+            })
+        }).collect();
+
+    let collection = json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+
+    serde_json::to_writer_pretty(&mut writer, &collection)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+fn write_gpx<W: Write>(photos: &[ExportedPhoto], mut writer: W) -> io::Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<gpx version="1.1" creator="Yore" xmlns="http://www.topografix.com/GPX/1/1">"#
+    )?;
+
+    for photo in photos {
+        writeln!(
+            writer,
+            r#"  <wpt lat="{}" lon="{}">"#,
+            photo.coordinates.latitude(),
+            photo.coordinates.longitude()
+        )?;
+        if let Some(timestamp) = photo.timestamp() {
+            writeln!(writer, "    <time>{}</time>", timestamp)?;
+        }
+        writeln!(
+            writer,
+            "    <name>{}</name>",
+            escape_xml(&photo.file_name())
+        )?;
+        writeln!(
+            writer,
+            "    <desc>{}</desc>",
+            escape_xml(&photo.description())
+        )?;
+        writeln!(writer, "    <type>{}</type>", photo.status())?;
+        writeln!(writer, "  </wpt>")?;
+    }
+
+    writeln!(writer, "</gpx>")?;
+    writer.flush()
+}
+
+fn write_kmz<W: Write + Seek>(photos: &[ExportedPhoto], writer: W) -> io::Result<()> {
+    let thumbnails: Vec<Option<Vec<u8>>> = photos
+        .par_iter()
+        .map(|photo| thumbnail(&photo.path, THUMBNAIL_SIZE, THUMBNAIL_SIZE).ok())
+        .collect();
+
+    let mut zip = ZipWriter::new(writer);
+
+    zip.start_file("doc.kml", FileOptions::default())?;
+    write_kml(photos, &thumbnails, &mut zip)?;
+
+    for (index, thumbnail) in thumbnails.iter().enumerate() {
+        if let Some(ref thumbnail) = thumbnail {
+            zip.start_file(thumbnail_path(index), FileOptions::default())?;
+            zip.write_all(thumbnail)?;
+        }
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+fn write_kml<W: Write>(
+    photos: &[ExportedPhoto],
+    thumbnails: &[Option<Vec<u8>>],
+    mut writer: W,
+) -> io::Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(writer, r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#)?;
+    writeln!(writer, "<Document>")?;
+    writeln!(writer, "  <name>Photo locations</name>")?;
+
+    for (index, (photo, thumbnail)) in photos.iter().zip(thumbnails).enumerate() {
+        let mut description = String::new();
+        if thumbnail.is_some() {
+            description.push_str(&format!(r#"<img src="{}"/><br/>"#, thumbnail_path(index)));
+        }
+        description.push_str(&escape_xml(&photo.description()).replace('\n', "<br/>"));
+
+        writeln!(writer, "  <Placemark>")?;
+        writeln!(
+            writer,
+            "    <name>{}</name>",
+            escape_xml(&photo.file_name())
+        )?;
+        if let Some(timestamp) = photo.timestamp() {
+            writeln!(
+                writer,
+                "    <TimeStamp><when>{}</when></TimeStamp>",
+                timestamp
+            )?;
+        }
+        writeln!(
+            writer,
+            "    <description><![CDATA[{}]]></description>",
+            description
+        )?;
+        writeln!(
+            writer,
+            "    <Point><coordinates>{},{}</coordinates></Point>",
+            photo.coordinates.longitude(),
+            photo.coordinates.latitude()
+        )?;
+        writeln!(writer, "  </Placemark>")?;
+    }
+
+    writeln!(writer, "</Document>")?;
+    writeln!(writer, "</kml>")
+}
+
+fn write_csv<W: Write>(photos: &[ExportedPhoto], writer: W) -> io::Result<()> {
+    // The header row is written explicitly so that it's there even if there are no photos.
+    let mut csv_writer = WriterBuilder::new().has_headers(false).from_writer(writer);
+    csv_writer.write_record(HEADERS)?;
+
+    for photo in photos {
+        csv_writer.serialize(CsvRow {
+            path: photo.path.to_string_lossy(),
+            status: photo.status(),
+            latitude: photo.coordinates.latitude(),
+            longitude: photo.coordinates.longitude(),
+            timestamp: photo.timestamp(),
+            timestamp_source: photo.timestamp_source(),
+            accuracy_meters: photo.accuracy.as_ref().map(|a| a.meters()),
+            accuracy_seconds: photo.accuracy.as_ref().map(|a| a.seconds()),
+        })?;
+    }
+
+    csv_writer.flush()
+}
+
+fn thumbnail_path(index: usize) -> String {
+    format!("images/{}.jpg", index)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Cursor, Read};
+
+    use zip::ZipArchive;
+
+    fn photos() -> Vec<ExportedPhoto> {
+        vec![
+            ExportedPhoto::new(
+                Path::new("tests/assets/photo.jpg"),
+                Some(Timestamp::new(1473154755000, TimestampSource::GpsTime)),
+                PhotoLocation::Existing(Coordinates::new(38.76544, -9.5)),
+            ).unwrap(),
+            ExportedPhoto::new(
+                Path::new("b & c.jpg"),
+                None,
+                PhotoLocation::Suggested(
+                    Coordinates::new(55.5, 12.25),
+                    SuggestionAccuracy::new(6, -3, TimestampSource::DateTimeOriginal),
+                ),
+            ).unwrap(),
+        ]
+    }
+
+    fn export_to_vec(format: ExportFormat) -> Vec<u8> {
+        let mut buffer = Cursor::new(Vec::new());
+        export(format, &photos(), &mut buffer).unwrap();
+        buffer.into_inner()
+    }
+
+    #[test]
+    fn export_format_from_str_should_parse_format_names() {
+        assert_eq!(Ok(ExportFormat::GeoJson), "geojson".parse());
+        assert_eq!(Ok(ExportFormat::Kmz), "kmz".parse());
+        assert_eq!(Ok(ExportFormat::Gpx), "gpx".parse());
+        assert_eq!(Ok(ExportFormat::Csv), "csv".parse());
+        assert!("json".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    fn exported_photo_new_should_be_none_if_there_is_no_location() {
+        assert!(ExportedPhoto::new(Path::new("a.jpg"), None, PhotoLocation::None).is_none());
+    }
+
+    #[test]
+    fn geojson_export_should_be_a_feature_collection_of_points() {
+        let value: Value = serde_json::from_slice(&export_to_vec(ExportFormat::GeoJson)).unwrap();
+
+        assert_eq!("FeatureCollection", value["type"]);
+        assert_eq!(2, value["features"].as_array().unwrap().len());
+
+        let feature = &value["features"][0];
+        assert_eq!(json!([-9.5, 38.76544]), feature["geometry"]["coordinates"]);
+        assert_eq!("existing", feature["properties"]["status"]);
+        assert_eq!(
+            "2016-09-06T09:39:15.000Z",
+            feature["properties"]["timestamp"]
+        );
+        assert_eq!("GpsTime", feature["properties"]["timestamp_source"]);
+        assert_eq!(Value::Null, feature["properties"]["accuracy_meters"]);
+
+        let feature = &value["features"][1];
+        assert_eq!("suggested", feature["properties"]["status"]);
+        assert_eq!(Value::Null, feature["properties"]["timestamp"]);
+        assert_eq!(6, feature["properties"]["accuracy_meters"]);
+    }
+
+    #[test]
+    fn gpx_export_should_have_a_waypoint_per_photo() {
+        let gpx = String::from_utf8(export_to_vec(ExportFormat::Gpx)).unwrap();
+
+        assert!(gpx.contains(r#"<wpt lat="38.76544" lon="-9.5">"#));
+        assert!(gpx.contains("<time>2016-09-06T09:39:15.000Z</time>"));
+        assert!(gpx.contains("<name>b &amp; c.jpg</name>"));
+        assert!(gpx.contains(
+            "<desc>b &amp; c.jpg\nSuggested location, accurate to 6 metres, -3 seconds</desc>"
+        ));
+        assert_eq!(2, gpx.matches("</wpt>").count());
+    }
+
+    #[test]
+    fn kmz_export_should_contain_placemarks_and_thumbnails_that_could_be_created() {
+        let mut archive = ZipArchive::new(Cursor::new(export_to_vec(ExportFormat::Kmz))).unwrap();
+
+        let mut kml = String::new();
+        archive
+            .by_name("doc.kml")
+            .unwrap()
+            .read_to_string(&mut kml)
+            .unwrap();
+
+        assert_eq!(2, kml.matches("<Placemark>").count());
+        assert!(kml.contains(r#"<img src="images/0.jpg"/>"#));
+        assert!(kml.contains("<coordinates>12.25,55.5</coordinates>"));
+        assert!(kml.contains("<TimeStamp><when>2016-09-06T09:39:15.000Z</when></TimeStamp>"));
+        assert!(!kml.contains("images/1.jpg"));
+
+        assert!(archive.by_name("images/0.jpg").is_ok());
+        assert_eq!(2, archive.len());
+    }
+
+    #[test]
+    fn csv_export_should_have_a_header_row_and_a_row_per_photo() {
+        let expected = "path,status,latitude,longitude,timestamp,timestamp_source,accuracy_meters,accuracy_seconds\n\
+                        tests/assets/photo.jpg,existing,38.76544,-9.5,2016-09-06T09:39:15.000Z,GpsTime,,\n\
+                        b & c.jpg,suggested,55.5,12.25,,,6,-3\n";
+
+        assert_eq!(
+            expected,
+            String::from_utf8(export_to_vec(ExportFormat::Csv)).unwrap()
+        );
+    }
+}
@@ -1,26 +1,151 @@
 use std::io;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Output, Stdio};
 
-use yore::HistoryError;
-use yore::{find_jpegs, Coordinates};
+use chrono::{DateTime, TimeZone, Utc};
+use directories::ProjectDirs;
+use rayon::ThreadPoolBuildError;
+use yore::find_photos;
+use yore::{
+    BackupLocation, GeocoderError, HistoryError, JournalError, TimestampFallback, WriteOptions,
+};
 
+use self::server::TileError;
+
+pub mod export;
+pub mod output;
 pub mod server;
 
 pub fn photo_paths(root_path: &Path) -> Vec<PathBuf> {
     if root_path.is_file() {
         vec![root_path.to_path_buf()]
     } else if root_path.is_dir() {
-        find_jpegs(root_path)
+        find_photos(root_path)
     } else {
         vec![]
     }
 }
 
+/// Convert a Unix timestamp in milliseconds to a UTC date and time, or `None` if it is out of
+/// range. This doesn't use `timestamp_millis_opt`, as it gives an invalid nanosecond for
+/// timestamps before the epoch that aren't a whole number of seconds.
+pub fn utc_date_time(timestamp_ms: i64) -> Option<DateTime<Utc>> {
+    let seconds = timestamp_ms.div_euclid(1000);
+    let nanoseconds = timestamp_ms.rem_euclid(1000) as u32 * 1_000_000;
+
+    Utc.timestamp_opt(seconds, nanoseconds).single()
+}
+
+/// The option to also suggest locations copied from other photos, shared by everything that
+/// suggests locations.
+#[derive(StructOpt)]
+pub struct CopyFromPhotosArgs {
+    #[structopt(
+        short = "c",
+        long = "copy-from-photos",
+        help = "Also suggest locations copied from the photos nearest in time that already have \
+                one"
+    )]
+    copy_from_photos: bool,
+}
+
+impl CopyFromPhotosArgs {
+    pub fn copy_from_photos(&self) -> bool {
+        self.copy_from_photos
+    }
+}
+
+/// The option to read timestamps from other sources when a photo's metadata has none, shared by
+/// everything that reads photo timestamps.
+#[derive(StructOpt)]
+pub struct TimestampFallbackArgs {
+    #[structopt(
+        short = "t",
+        long = "timestamp-fallback",
+        raw(possible_values = r#"&["filename", "xmp", "mtime"]"#),
+        raw(use_delimiter = "true", number_of_values = "1"),
+        help = "Where to read a photo's timestamp from if its metadata has none, in order of \
+                preference. Suggestions made using these timestamps are low confidence"
+    )]
+    timestamp_fallbacks: Vec<TimestampFallback>,
+}
+
+impl TimestampFallbackArgs {
+    pub fn timestamp_fallbacks(&self) -> &[TimestampFallback] {
+        &self.timestamp_fallbacks
+    }
+}
+
+/// Options for how locations are saved, shared by all binaries that can save them.
+#[derive(StructOpt)]
+pub struct WriteArgs {
+    #[structopt(
+        long = "backup",
+        help = "Copy each file to <file name>.orig before saving its location"
+    )]
+    backup: bool,
+
+    #[structopt(
+        long = "backup-dir",
+        parse(from_os_str),
+        raw(conflicts_with = r#""backup""#),
+        help = "Copy each file into the given directory before saving its location"
+    )]
+    backup_dir: Option<PathBuf>,
+
+    #[structopt(
+        long = "verify",
+        help = "Check that each file's location was saved and its image is unchanged after \
+                saving, and restore the file if not"
+    )]
+    verify: bool,
+
+    #[structopt(
+        long = "write-place-names",
+        raw(requires = r#""places_path""#),
+        help = "Also save the names of the town, region and country nearest to each location in \
+                the file's IPTC and XMP metadata"
+    )]
+    write_place_names: bool,
+}
+
+impl WriteArgs {
+    pub fn write_options(&self) -> WriteOptions {
+        let backup = match self.backup_dir {
+            Some(ref directory) => Some(BackupLocation::Directory(directory.clone())),
+            None if self.backup => Some(BackupLocation::Sibling),
+            None => None,
+        };
+
+        WriteOptions::new(backup, self.verify)
+    }
+
+    pub fn write_place_names(&self) -> bool {
+        self.write_place_names
+    }
+}
+
+/// The directory that the journal of saved locations is kept in.
+pub fn journal_path() -> PathBuf {
+    ProjectDirs::from("", "", "Yore")
+        .expect("project directory to be calculable")
+        .data_dir()
+        .join("journal")
+}
+
 #[derive(Debug)]
 pub enum ApplicationError {
+    GeocoderError(GeocoderError),
     HistoryError(HistoryError),
     IoError(io::Error),
+    JournalError(JournalError),
+    ThreadPoolBuildError(ThreadPoolBuildError),
+    TileError(TileError),
+}
+
+impl From<GeocoderError> for ApplicationError {
+    fn from(error: GeocoderError) -> Self {
+        ApplicationError::GeocoderError(error)
+    }
 }
 
 impl From<HistoryError> for ApplicationError {
@@ -35,39 +160,20 @@ impl From<io::Error> for ApplicationError {
     }
 }
 
-pub fn exiv2_write_coordinates(path: &Path, coordinates: &Coordinates) -> io::Result<Output> {
-    let latitude_degrees = dms_string(coordinates.latitude());
-    let longitude_degrees = dms_string(coordinates.longitude());
-
-    Command::new("exiv2")
-        .arg("-k")
-        .arg(format!(
-            "-Mset Exif.GPSInfo.GPSLatitude {}",
-            latitude_degrees
-        )).arg(format!(
-            "-Mset Exif.GPSInfo.GPSLatitudeRef {}",
-            coordinates.latitude_ref()
-        )).arg(format!(
-            "-Mset Exif.GPSInfo.GPSLongitude {}",
-            longitude_degrees
-        )).arg(format!(
-            "-Mset Exif.GPSInfo.GPSLongitudeRef {}",
-            coordinates.longitude_ref()
-        )).arg(path)
-        .stderr(Stdio::inherit())
-        .output()
-}
-
-fn dms_string(coordinate: f64) -> String {
-    format!("{}/10000000 0/1 0/1", (coordinate * 1e7) as u32)
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn dms_string_should_print_coordinate_in_exif_degrees_minutes_seconds_format() {
-        assert_eq!("556382576/10000000 0/1 0/1", dms_string(55.6382576));
+impl From<JournalError> for ApplicationError {
+    fn from(error: JournalError) -> Self {
+        ApplicationError::JournalError(error)
+    }
+}
+
+impl From<TileError> for ApplicationError {
+    fn from(error: TileError) -> Self {
+        ApplicationError::TileError(error)
+    }
+}
+
+impl From<ThreadPoolBuildError> for ApplicationError {
+    fn from(error: ThreadPoolBuildError) -> Self {
+        ApplicationError::ThreadPoolBuildError(error)
     }
 }
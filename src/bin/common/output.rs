@@ -0,0 +1,339 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use csv;
+use serde_json;
+
+use yore::{Coordinates, PhotoError, PhotoLocation, Place, SuggestionAccuracy};
+
+/// A machine-readable format for per-photo location results.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// A single JSON array of records.
+    Json,
+    /// One JSON record per line.
+    JsonLines,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// Human-readable aligned columns.
+    Table,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::JsonLines),
+            "csv" => Ok(OutputFormat::Csv),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!(
+                "Unrecognised output format \"{}\", expected one of json, jsonl, csv or table",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordStatus {
+    Existing,
+    Suggested,
+    NoSuggestion,
+    Error,
+}
+
+impl RecordStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            RecordStatus::Existing => "existing",
+            RecordStatus::Suggested => "suggested",
+            RecordStatus::NoSuggestion => "no_suggestion",
+            RecordStatus::Error => "error",
+        }
+    }
+}
+
+/// The location result for a single photo.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Record<'a> {
+    path: String,
+    status: RecordStatus,
+    location: Option<&'a Coordinates>,
+    place: Option<String>,
+    accuracy: Option<&'a SuggestionAccuracy>,
+    error: Option<String>,
+}
+
+impl<'a> Record<'a> {
+    pub fn new(
+        path: &Path,
+        result: &'a Result<PhotoLocation, PhotoError>,
+        place: Option<&Place>,
+    ) -> Record<'a> {
+        let (status, location, accuracy, error) = match result {
+            Err(ref e) => (RecordStatus::Error, None, None, Some(e.to_string())),
+            Ok(PhotoLocation::Existing(ref location)) => {
+                (RecordStatus::Existing, Some(location), None, None)
+            }
+            Ok(PhotoLocation::Suggested(ref location, ref accuracy)) => (
+                RecordStatus::Suggested,
+                Some(location),
+                Some(accuracy),
+                None,
+            ),
+            Ok(PhotoLocation::None) => (RecordStatus::NoSuggestion, None, None, None),
+        };
+
+        Record {
+            path: path.to_string_lossy().into_owned(),
+            status,
+            location,
+            place: place.map(Place::to_string),
+            accuracy,
+            error,
+        }
+    }
+
+    fn fields(&self) -> Vec<String> {
+        fn optional<T: ToString>(value: Option<T>) -> String {
+            value.map(|v| v.to_string()).unwrap_or_default()
+        }
+
+        vec![
+            self.path.clone(),
+            self.status.as_str().to_string(),
+            optional(self.location.map(|l| l.latitude())),
+            optional(self.location.map(|l| l.longitude())),
+            self.place.clone().unwrap_or_default(),
+            optional(self.accuracy.map(|a| a.meters())),
+            optional(self.accuracy.map(|a| a.seconds())),
+            optional(self.accuracy.map(|a| format!("{:?}", a.timestamp_source()))),
+            self.error.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+const HEADERS: &[&str] = &[
+    "path",
+    "status",
+    "latitude",
+    "longitude",
+    "place",
+    "accuracy_meters",
+    "accuracy_seconds",
+    "timestamp_source",
+    "error",
+];
+
+/// Writes records in the given format. JSON Lines and CSV records are written as they are given,
+/// while JSON and table output is only complete once `finish()` has been called.
+pub struct RecordWriter<W: Write> {
+    format: OutputFormat,
+    writer: W,
+    record_count: usize,
+    rows: Vec<Vec<String>>,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(format: OutputFormat, writer: W) -> RecordWriter<W> {
+        RecordWriter {
+            format,
+            writer,
+            record_count: 0,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Json => {
+                let separator = if self.record_count == 0 { "[\n" } else { ",\n" };
+                self.writer.write_all(separator.as_bytes())?;
+                serde_json::to_writer(&mut self.writer, record)?;
+            }
+            OutputFormat::JsonLines => {
+                serde_json::to_writer(&mut self.writer, record)?;
+                self.writer.write_all(b"\n")?;
+            }
+            OutputFormat::Csv => {
+                if self.record_count == 0 {
+                    self.write_csv_row(HEADERS)?;
+                }
+                self.write_csv_row(&record.fields())?;
+            }
+            OutputFormat::Table => self.rows.push(record.fields()),
+        }
+
+        self.record_count += 1;
+        self.writer.flush()
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Json if self.record_count == 0 => self.writer.write_all(b"[]\n")?,
+            OutputFormat::Json => self.writer.write_all(b"\n]\n")?,
+            OutputFormat::Csv if self.record_count == 0 => self.write_csv_row(HEADERS)?,
+            OutputFormat::Table => self.write_table()?,
+            _ => {}
+        }
+
+        self.writer.flush()
+    }
+
+    fn write_csv_row<T: AsRef<[u8]>>(&mut self, fields: &[T]) -> io::Result<()> {
+        let mut csv_writer = csv::Writer::from_writer(&mut self.writer);
+        csv_writer.write_record(fields)?;
+        csv_writer.flush()
+    }
+
+    fn write_table(&mut self) -> io::Result<()> {
+        let mut widths: Vec<usize> = HEADERS.iter().map(|h| h.len()).collect();
+        for row in &self.rows {
+            for (width, field) in widths.iter_mut().zip(row) {
+                *width = (*width).max(field.chars().count());
+            }
+        }
+
+        let headers: Vec<String> = HEADERS.iter().map(|h| h.to_uppercase()).collect();
+        write_table_row(&mut self.writer, &headers, &widths)?;
+        for row in &self.rows {
+            write_table_row(&mut self.writer, row, &widths)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_table_row<W: Write>(writer: &mut W, row: &[String], widths: &[usize]) -> io::Result<()> {
+    let line = row
+        .iter()
+        .zip(widths)
+        .map(|(field, width)| format!("{:1$}", field, width))
+        .collect::<Vec<_>>()
+        .join("  ");
+
+    writeln!(writer, "{}", line.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use yore::TimestampSource;
+
+    fn results() -> Vec<Result<PhotoLocation, PhotoError>> {
+        vec![
+            Ok(PhotoLocation::Existing(Coordinates::new(38.76544, -9.5))),
+            Ok(PhotoLocation::Suggested(
+                Coordinates::new(55.5, 12.25),
+                SuggestionAccuracy::new(6, -3, TimestampSource::DateTimeOriginal),
+            )),
+            Ok(PhotoLocation::None),
+        ]
+    }
+
+    fn write_all(format: OutputFormat) -> String {
+        let results = results();
+        let place = Place::new("Copenhagen", Some("Capital Region"), "Denmark", "DK");
+        let paths = ["a.jpg", "b, \"c\".jpg", "d.jpg"];
+
+        let mut buffer = Vec::new();
+        {
+            let mut writer = RecordWriter::new(format, &mut buffer);
+            for (path, result) in paths.iter().zip(&results) {
+                let place = match result {
+                    Ok(PhotoLocation::Suggested(_, _)) => Some(&place),
+                    _ => None,
+                };
+                writer
+                    .write(&Record::new(Path::new(path), result, place))
+                    .unwrap();
+            }
+            writer.finish().unwrap();
+        }
+
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn output_format_from_str_should_parse_format_names() {
+        assert_eq!(Ok(OutputFormat::Json), "json".parse());
+        assert_eq!(Ok(OutputFormat::JsonLines), "jsonl".parse());
+        assert_eq!(Ok(OutputFormat::Csv), "csv".parse());
+        assert_eq!(Ok(OutputFormat::Table), "table".parse());
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn record_new_should_set_the_status_and_error_from_the_result() {
+        let result = Err(PhotoError::TimestampMissing);
+        let record = Record::new(Path::new("a.jpg"), &result, None);
+
+        assert_eq!(RecordStatus::Error, record.status);
+        assert_eq!(
+            Some("The image has no timestamp metadata".to_string()),
+            record.error
+        );
+        assert!(record.location.is_none());
+        assert!(record.accuracy.is_none());
+    }
+
+    #[test]
+    fn json_lines_output_should_have_one_record_per_line() {
+        let expected = concat!(
+            r#"{"path":"a.jpg","status":"existing","location":{"latitude":38.76544,"longitude":-9.5},"place":null,"accuracy":null,"error":null}"#,
+            "\n",
+            r#"{"path":"b, \"c\".jpg","status":"suggested","location":{"latitude":55.5,"longitude":12.25},"place":"Copenhagen, Capital Region, Denmark","accuracy":{"meters":6,"seconds":-3,"timestamp_source":"DateTimeOriginal"},"error":null}"#,
+            "\n",
+            r#"{"path":"d.jpg","status":"no_suggestion","location":null,"place":null,"accuracy":null,"error":null}"#,
+            "\n",
+        );
+
+        assert_eq!(expected, write_all(OutputFormat::JsonLines));
+    }
+
+    #[test]
+    fn json_output_should_be_an_array_of_records() {
+        let output = write_all(OutputFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(3, value.as_array().unwrap().len());
+        assert_eq!("suggested", value[1]["status"]);
+        assert_eq!(6, value[1]["accuracy"]["meters"]);
+    }
+
+    #[test]
+    fn json_output_should_be_an_empty_array_if_there_are_no_records() {
+        let mut buffer = Vec::new();
+        RecordWriter::new(OutputFormat::Json, &mut buffer)
+            .finish()
+            .unwrap();
+
+        assert_eq!(b"[]\n", buffer.as_slice());
+    }
+
+    #[test]
+    fn csv_output_should_have_a_header_row_and_quote_fields_if_necessary() {
+        let expected = "path,status,latitude,longitude,place,accuracy_meters,accuracy_seconds,timestamp_source,error\n\
+                        a.jpg,existing,38.76544,-9.5,,,,,\n\
+                        \"b, \"\"c\"\".jpg\",suggested,55.5,12.25,\"Copenhagen, Capital Region, Denmark\",6,-3,DateTimeOriginal,\n\
+                        d.jpg,no_suggestion,,,,,,,\n";
+
+        assert_eq!(expected, write_all(OutputFormat::Csv));
+    }
+
+    #[test]
+    fn table_output_should_align_columns() {
+        let output = write_all(OutputFormat::Table);
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(4, lines.len());
+        assert!(lines[0].starts_with("PATH        STATUS         LATITUDE  LONGITUDE"));
+        assert!(lines[2].starts_with("b, \"c\".jpg  suggested      55.5      12.25"));
+        assert_eq!("d.jpg       no_suggestion", lines[3]);
+    }
+}
@@ -1,33 +1,57 @@
+use std::collections::hash_map::DefaultHasher;
 use std::ffi::OsStr;
+use std::fs::{self, Metadata};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
 
-use actix_web::http::StatusCode;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::{self, EntityTag, HttpDate};
+use actix_web::http::{ContentEncoding, StatusCode};
 use actix_web::{
-    App, Body, HttpRequest, HttpResponse, Json, Path as PathExtractor, Query, Result, State,
+    App, Body, HttpMessage, HttpRequest, HttpResponse, Json, Path as PathExtractor, Query, Result,
+    State,
 };
+use bytes::Bytes;
+use futures::sync::mpsc::{unbounded, UnboundedSender};
+use futures::Stream;
+use serde_json;
 use tinyfiledialogs::{open_file_dialog, select_folder_dialog};
 use yore::Coordinates;
 
+use super::bulk_save::{save_suggested_locations, SaveInputs, SaveProgress, SaveRequest};
 use super::error::ServiceError;
-use super::image::{oriented_image, thumbnail};
+use super::image::{photo, thumbnail};
 use super::responses::{
-    read_file_bytes, InterpolateResponse, LocationHistoryPathResponse, LocationResponse,
-    LocationsResponse, PhotosResponse, RootPathResponse,
+    read_file_bytes, CopyFromPhotosResponse, InterpolateResponse, LocationHistoryPathResponse,
+    LocationResponse, LocationsResponse, PhotosQuery, PhotosResponse, RootPathResponse,
+    TilesResponse, TrackResponse, UndoResponse,
 };
-use super::state::GuiState;
-use common::exiv2_write_coordinates;
+use super::scan::scan_in_background;
+use super::state::{combine_histories_in_background, GuiState};
 
+const APPLICATION_NDJSON: &str = "application/x-ndjson";
 const IMAGE_JPEG: &str = "image/jpeg";
 const TEXT_CSS: &str = "text/css";
+const TEXT_EVENT_STREAM: &str = "text/event-stream";
 const TEXT_HTML_UTF_8: &str = "text/html; charset=utf-8";
 const TEXT_JAVASCRIPT: &str = "text/javascript";
 const TEXT_PLAIN: &str = "text/plain";
 
-#[derive(Deserialize)]
-struct GetPhotosQueryParams {
-    filter: Option<bool>,
-}
+/// How many seconds of location history either side of a photo's timestamp to include in its
+/// track if no window is queried.
+const DEFAULT_TRACK_WINDOW_SECONDS: u32 = 3600;
+
+/// Tiles rarely change, so browsers can reuse them for a day before revalidating them.
+const TILE_CACHE_CONTROL: &str = "public, max-age=86400";
+
+/// Photos can change at any time, so browsers must revalidate them before reusing them. That's
+/// cheap because their validators come from the photo file's metadata.
+const PHOTO_CACHE_CONTROL: &str = "no-cache";
 
 #[derive(Deserialize)]
 struct Indices {
@@ -40,6 +64,17 @@ struct QueriedPath {
     path: PathBuf,
 }
 
+#[derive(Deserialize)]
+struct TrackQueryParams {
+    path: PathBuf,
+    window: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct UndoQueryParams {
+    session: Option<String>,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ThumbnailQueryParams {
@@ -48,11 +83,29 @@ struct ThumbnailQueryParams {
     max_height: u32,
 }
 
+enum ByteRange {
+    Full,
+    Partial(Range<usize>),
+    Unsatisfiable,
+}
+
+#[derive(Deserialize)]
+struct PutLocationsRequestBody {
+    paths: Vec<PathBuf>,
+    coordinates: Coordinates,
+}
+
 #[derive(Deserialize)]
 struct InterpolateRequestBody {
     interpolate: bool,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CopyFromPhotosRequestBody {
+    copy_from_photos: bool,
+}
+
 type SharedGuiState = Arc<RwLock<GuiState>>;
 type Request = HttpRequest<SharedGuiState>;
 type RequestState = State<SharedGuiState>;
@@ -70,13 +123,25 @@ pub fn build_server_app(state: SharedGuiState) -> App<SharedGuiState> {
         }).resource("/interpolate", |r| {
             r.get().with(get_interpolate);
             r.put().with(put_interpolate);
-        }).resource("/locations", |r| r.get().with(get_locations))
-        .resource("/location", |r| {
+        }).resource("/copyFromPhotos", |r| {
+            r.get().with(get_copy_from_photos);
+            r.put().with(put_copy_from_photos);
+        }).resource("/locations", |r| {
+            r.get().with(get_locations);
+            r.put().with(put_locations);
+            r.post().with(post_locations);
+        }).resource("/location", |r| {
             r.get().with(get_location);
             r.put().with(put_location);
-        }).resource("/photos", |r| r.get().with(get_photos))
+        }).resource("/track", |r| r.get().with(get_track))
+        .resource("/undo", |r| r.post().with(post_undo))
+        .resource("/events", |r| r.get().with(get_events))
+        .resource("/photos", |r| r.get().with(get_photos))
         .resource("/photo", |r| r.get().with(get_photo))
         .resource("/thumbnail", |r| r.get().with(get_thumbnail))
+        .resource("/cache", |r| r.delete().with(delete_cache))
+        .resource("/tiles", |r| r.get().with(get_tiles))
+        .resource("/tiles/{z}/{x}/{y}", |r| r.get().with(get_tile))
         .resource("/{file}", |r| r.get().with(get_static_file))
         .resource("/", |r| r.get().f(get_index))
 }
@@ -90,7 +155,7 @@ fn get_root_path(state: RequestState) -> JsonResult<RootPathResponse> {
 #[allow(unknown_lints, needless_pass_by_value)]
 fn get_new_root_path(state: RequestState) -> JsonResult<RootPathResponse> {
     if let Some(path) = select_folder_dialog("", "") {
-        state.write()?.search_new_root_path(PathBuf::from(path));
+        scan_in_background(&state, PathBuf::from(path))?;
     }
 
     let state = state.read()?;
@@ -107,6 +172,7 @@ fn get_location_history_path(state: RequestState) -> JsonResult<LocationHistoryP
 fn get_new_location_history(state: RequestState) -> JsonResult<LocationHistoryPathResponse> {
     if let Some(path) = open_file_dialog("", "", None) {
         state.write()?.load_location_history(PathBuf::from(&path))?;
+        combine_histories_in_background(&state);
     }
     let state = state.read()?;
     Ok(Json(LocationHistoryPathResponse::new(&state)))
@@ -118,6 +184,12 @@ fn get_interpolate(state: RequestState) -> JsonResult<InterpolateResponse> {
     Ok(Json(InterpolateResponse::new(&state)))
 }
 
+#[allow(unknown_lints, needless_pass_by_value)]
+fn get_copy_from_photos(state: RequestState) -> JsonResult<CopyFromPhotosResponse> {
+    let state = state.read()?;
+    Ok(Json(CopyFromPhotosResponse::new(&state)))
+}
+
 #[allow(unknown_lints, needless_pass_by_value)]
 fn get_locations(
     (indices, state): (Query<Indices>, RequestState),
@@ -135,58 +207,256 @@ fn get_location(
 }
 
 #[allow(unknown_lints, needless_pass_by_value)]
-fn get_photos(
-    (query_params, state): (Query<GetPhotosQueryParams>, RequestState),
-) -> JsonResult<PhotosResponse> {
+fn get_track(
+    (query_params, state): (Query<TrackQueryParams>, RequestState),
+) -> JsonResult<TrackResponse> {
     let state = state.read()?;
+    let window = query_params.window.unwrap_or(DEFAULT_TRACK_WINDOW_SECONDS);
 
-    if let Some(true) = query_params.filter {
-        PhotosResponse::filtered(&state).map(Json)
-    } else {
-        PhotosResponse::new(&state).map(Json)
-    }
+    TrackResponse::new(&query_params.path, window, &state).map(Json)
 }
 
 #[allow(unknown_lints, needless_pass_by_value)]
-fn get_photo(query_params: Query<QueriedPath>) -> HttpResult {
-    let body = oriented_image(&query_params.path).map(Body::from)?;
+fn get_photos((query, state): (Query<PhotosQuery>, RequestState)) -> JsonResult<PhotosResponse> {
+    let state = state.read()?;
 
-    Ok(HttpResponse::Ok().content_type(IMAGE_JPEG).body(body))
+    PhotosResponse::query(&state, &query).map(Json)
 }
 
+/// Stream `GuiEvent`s as server-sent events, starting with the photos found so far.
 #[allow(unknown_lints, needless_pass_by_value)]
-fn get_thumbnail((query_params, state): (Query<ThumbnailQueryParams>, RequestState)) -> HttpResult {
-    let state = state.read()?;
+fn get_events(state: RequestState) -> HttpResult {
+    let receiver = state.read()?.subscribe_to_events();
+    let stream = receiver.map_err(|_| ErrorInternalServerError("The event stream was closed"));
 
-    let cached_path = state.cached_image_path(
-        &query_params.path,
-        query_params.max_width,
-        query_params.max_height,
-    );
+    // Compressing the stream would buffer events until enough had been sent to compress.
+    Ok(HttpResponse::Ok()
+        .content_type(TEXT_EVENT_STREAM)
+        .content_encoding(ContentEncoding::Identity)
+        .header(header::CACHE_CONTROL, "no-cache")
+        .streaming(stream))
+}
 
-    use std::fs::{create_dir_all, read, write};
+#[allow(unknown_lints, needless_pass_by_value)]
+fn get_photo((request, query_params): (Request, Query<QueriedPath>)) -> HttpResult {
+    let path = &query_params.path;
 
-    let image = if cached_path.exists() {
-        read(cached_path)?
+    photo_response(&request, path, (), || photo(path))
+}
+
+#[allow(unknown_lints, needless_pass_by_value)]
+fn get_thumbnail((request, query_params): (Request, Query<ThumbnailQueryParams>)) -> HttpResult {
+    let path = &query_params.path;
+    let max_width = query_params.max_width;
+    let max_height = query_params.max_height;
+
+    photo_response(&request, path, (max_width, max_height), || {
+        let state = request.state().read()?;
+        let cache = state.image_cache();
+
+        match cache.get(path, max_width, max_height)? {
+            Some(image) => Ok(image),
+            None => {
+                let image = thumbnail(path, max_width, max_height)?;
+                cache.insert(path, max_width, max_height, &image)?;
+                Ok(image)
+            }
+        }
+    })
+}
+
+/// Respond with a JPEG created from the photo at the given path, or with Not Modified if the
+/// request's validators show that the client already has it. The validators come from the
+/// photo file's metadata, so the JPEG is only created if it's needed. The variant is hashed
+/// into the entity tag to tell apart different JPEGs created from the same photo.
+fn photo_response<V, F>(request: &Request, path: &Path, variant: V, create_body: F) -> HttpResult
+where
+    V: Hash,
+    F: FnOnce() -> Result<Vec<u8>, ServiceError>,
+{
+    let metadata = fs::metadata(path)?;
+    let etag = photo_entity_tag(path, &metadata, variant)?;
+    let last_modified = last_modified(&metadata)?;
+
+    let mut response = HttpResponse::Ok();
+    response
+        .content_encoding(ContentEncoding::Identity)
+        .header(header::CACHE_CONTROL, PHOTO_CACHE_CONTROL)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .set(header::ETag(etag.clone()))
+        .set(header::LastModified(last_modified));
+
+    if is_not_modified(request, &etag, Some(last_modified)) {
+        return Ok(response.status(StatusCode::NOT_MODIFIED).finish());
+    }
+
+    let body = Bytes::from(create_body()?);
+    let length = body.len();
+
+    match requested_range(request, &etag, length) {
+        ByteRange::Full => Ok(response.content_type(IMAGE_JPEG).body(body)),
+        ByteRange::Partial(range) => Ok(response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .content_type(IMAGE_JPEG)
+            .header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", range.start, range.end - 1, length),
+            ).body(body.slice(range.start, range.end))),
+        ByteRange::Unsatisfiable => Ok(response
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", length))
+            .finish()),
+    }
+}
+
+fn photo_entity_tag<V: Hash>(
+    path: &Path,
+    metadata: &Metadata,
+    variant: V,
+) -> io::Result<EntityTag> {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    metadata.modified()?.hash(&mut hasher);
+    variant.hash(&mut hasher);
+
+    Ok(EntityTag::strong(format!("{:x}", hasher.finish())))
+}
+
+/// HTTP dates are only precise to the second, so the file's modification time is truncated to
+/// compare it with If-Modified-Since.
+fn last_modified(metadata: &Metadata) -> io::Result<HttpDate> {
+    let seconds = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    Ok(HttpDate::from(UNIX_EPOCH + Duration::from_secs(seconds)))
+}
+
+/// If-None-Match takes precedence over If-Modified-Since, which is ignored if the response
+/// has no modification time.
+fn is_not_modified(request: &Request, etag: &EntityTag, last_modified: Option<HttpDate>) -> bool {
+    match request.get_header::<header::IfNoneMatch>() {
+        Some(header::IfNoneMatch::Any) => true,
+        Some(header::IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        None => match (
+            request.get_header::<header::IfModifiedSince>(),
+            last_modified,
+        ) {
+            (Some(header::IfModifiedSince(since)), Some(last_modified)) => last_modified <= since,
+            _ => false,
+        },
+    }
+}
+
+/// The range of the body requested by the Range header. The whole body is sent if If-Range
+/// doesn't match the current entity tag, or if the Range header is invalid or asks for several
+/// ranges, which servers are allowed to do.
+fn requested_range(request: &Request, etag: &EntityTag, length: usize) -> ByteRange {
+    let is_current = match request.headers().get(header::IF_RANGE) {
+        Some(if_range) => if_range
+            .to_str()
+            .ok()
+            .and_then(|if_range| if_range.parse::<EntityTag>().ok())
+            .map(|tag| tag.strong_eq(etag))
+            .unwrap_or(false),
+        None => true,
+    };
+
+    match request.headers().get(header::RANGE) {
+        Some(range) if is_current => range
+            .to_str()
+            .ok()
+            .and_then(|range| parse_byte_range(range, length))
+            .unwrap_or(ByteRange::Full),
+        _ => ByteRange::Full,
+    }
+}
+
+/// Parse a Range header that asks for a single range of bytes, returning `None` if it's
+/// invalid.
+fn parse_byte_range(range: &str, length: usize) -> Option<ByteRange> {
+    if !range.starts_with("bytes=") || range.contains(',') {
+        return None;
+    }
+
+    let mut bounds = range["bytes=".len()..].splitn(2, '-');
+    let first = bounds.next()?.trim();
+    let last = bounds.next()?.trim();
+
+    let byte_range = if first.is_empty() {
+        let suffix_length = last.parse::<usize>().ok()?;
+        if suffix_length == 0 || length == 0 {
+            ByteRange::Unsatisfiable
+        } else {
+            ByteRange::Partial(length.saturating_sub(suffix_length)..length)
+        }
     } else {
-        let image = thumbnail(
-            &query_params.path,
-            query_params.max_width,
-            query_params.max_height,
-        )?;
-
-        if let Some(path) = cached_path.parent() {
-            create_dir_all(path)?;
+        let first = first.parse::<usize>().ok()?;
+        let end = if last.is_empty() {
+            length
+        } else {
+            let last = last.parse::<usize>().ok()?;
+            if last < first {
+                return None;
+            }
+            length.min(last.saturating_add(1))
+        };
+
+        if first >= length {
+            ByteRange::Unsatisfiable
+        } else {
+            ByteRange::Partial(first..end)
         }
+    };
 
-        write(cached_path, &image)?;
+    Some(byte_range)
+}
+
+/// Remove all the cached thumbnails, e.g. to free up disk space.
+#[allow(unknown_lints, needless_pass_by_value)]
+fn delete_cache(state: RequestState) -> HttpResult {
+    state.read()?.image_cache().clear()?;
+
+    Ok(HttpResponse::new(StatusCode::OK))
+}
+
+#[allow(unknown_lints, needless_pass_by_value)]
+fn get_tiles(state: RequestState) -> JsonResult<TilesResponse> {
+    let state = state.read()?;
+    Ok(Json(TilesResponse::new(&state)))
+}
+
+#[allow(unknown_lints, needless_pass_by_value)]
+fn get_tile((request, coordinates): (Request, PathExtractor<(u32, u32, u32)>)) -> HttpResult {
+    let (zoom, x, y) = coordinates.into_inner();
 
-        image
+    let tile = match request.state().read()?.tile_source() {
+        Some(tile_source) => tile_source.tile(zoom, x, y)?,
+        None => None,
     };
 
-    Ok(HttpResponse::Ok()
-        .content_type(IMAGE_JPEG)
-        .body(Body::from(image)))
+    let tile = match tile {
+        Some(tile) => tile,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let etag = entity_tag(tile.data());
+
+    let mut response = HttpResponse::Ok();
+    response
+        .header(header::CACHE_CONTROL, TILE_CACHE_CONTROL)
+        .set(header::ETag(etag.clone()));
+
+    if is_not_modified(&request, &etag, None) {
+        return Ok(response.status(StatusCode::NOT_MODIFIED).finish());
+    }
+
+    Ok(response
+        .content_type(tile.content_type())
+        .body(tile.into_data()))
 }
 
 #[allow(unknown_lints, needless_pass_by_value)]
@@ -211,12 +481,142 @@ fn put_interpolate((body, state): (Json<InterpolateRequestBody>, RequestState))
     Ok(HttpResponse::new(StatusCode::OK))
 }
 
-fn put_location((body, query_params): (Json<Coordinates>, Query<QueriedPath>)) -> HttpResult {
-    exiv2_write_coordinates(&query_params.path, &body)?;
+fn put_copy_from_photos(
+    (body, state): (Json<CopyFromPhotosRequestBody>, RequestState),
+) -> HttpResult {
+    state.write()?.set_copy_from_photos(body.copy_from_photos);
+    combine_histories_in_background(&state);
+
+    Ok(HttpResponse::new(StatusCode::OK))
+}
+
+#[allow(unknown_lints, needless_pass_by_value)]
+fn put_location(
+    (state, body, query_params): (RequestState, Json<Coordinates>, Query<QueriedPath>),
+) -> HttpResult {
+    write_location(&state, &[query_params.into_inner().path], &body, false)?;
+
+    Ok(HttpResponse::new(StatusCode::OK))
+}
+
+#[allow(unknown_lints, needless_pass_by_value)]
+fn put_locations((state, body): (RequestState, Json<PutLocationsRequestBody>)) -> HttpResult {
+    write_location(&state, &body.paths, &body.coordinates, true)?;
 
     Ok(HttpResponse::new(StatusCode::OK))
 }
 
+/// Save the suggested locations of the requested photos in the background, streaming the
+/// progress back as newline-delimited JSON.
+#[allow(unknown_lints, needless_pass_by_value)]
+fn post_locations((state, body): (RequestState, Json<SaveRequest>)) -> HttpResult {
+    if let Some(paths) = body.paths() {
+        let state = state.read()?;
+        if let Some(path) = paths.iter().find(|path| !state.contains_photo(path)) {
+            return Err(ServiceError::UnknownPhoto(path.clone()));
+        }
+    }
+
+    let shared_state = Arc::clone(&state);
+    let request = body.into_inner();
+    let (sender, receiver) = unbounded();
+
+    thread::spawn(move || save_in_background(&shared_state, &request, sender));
+
+    let stream = receiver.map_err(|_| ErrorInternalServerError("The save was interrupted"));
+
+    Ok(HttpResponse::Ok()
+        .content_type(APPLICATION_NDJSON)
+        .streaming(stream))
+}
+
+fn save_in_background(
+    state: &SharedGuiState,
+    request: &SaveRequest,
+    sender: UnboundedSender<Bytes>,
+) {
+    let sender = Mutex::new(sender);
+    let send_progress = |progress: SaveProgress| {
+        if let Ok(mut line) = serde_json::to_vec(&progress) {
+            line.push(b'\n');
+            if let Ok(sender) = sender.lock() {
+                // The client may have gone away, but the save should still finish.
+                sender.unbounded_send(Bytes::from(line)).ok();
+            }
+        }
+    };
+
+    let inputs = match state.read() {
+        Ok(state) => SaveInputs::new(&state, request),
+        Err(_) => return,
+    };
+    let saved = save_suggested_locations(&inputs, send_progress);
+
+    if let Ok(mut state) = state.write() {
+        for path in saved {
+            state.set_user_set(&path, false);
+        }
+    }
+}
+
+/// Write a location to each of the given photos, which must all be photos under the current
+/// root path. Nothing is written if the location or any of the paths are invalid. `user_set`
+/// should be true if the location was chosen by the user rather than suggested.
+///
+/// The files are written while only holding a read lock, so that other requests aren't blocked
+/// by exiv2, and the write lock is only taken to record which photos were written.
+fn write_location(
+    state: &SharedGuiState,
+    paths: &[PathBuf],
+    coordinates: &Coordinates,
+    user_set: bool,
+) -> Result<(), ServiceError> {
+    if !coordinates.is_valid() {
+        return Err(ServiceError::InvalidCoordinates(coordinates.clone()));
+    }
+
+    let mut written = Vec::new();
+    let result = {
+        let state = state.read()?;
+        if let Some(path) = paths.iter().find(|path| !state.contains_photo(path)) {
+            return Err(ServiceError::UnknownPhoto(path.clone()));
+        }
+
+        let place = state.place_to_write(coordinates);
+        paths.iter().try_for_each(|path| {
+            state
+                .journal()
+                .write_coordinates(path, coordinates, place, state.write_options())?;
+            written.push(path);
+            Ok(())
+        })
+    };
+
+    let mut state = state.write()?;
+    for path in written {
+        state.set_user_set(path, user_set);
+    }
+
+    result
+}
+
+#[allow(unknown_lints, needless_pass_by_value)]
+fn post_undo(
+    (state, query_params): (RequestState, Query<UndoQueryParams>),
+) -> JsonResult<UndoResponse> {
+    let mut state = state.write()?;
+    let response = UndoResponse::new(&mut state, query_params.into_inner().session)?;
+    Ok(Json(response))
+}
+
+/// A strong entity tag for the given response body.
+fn entity_tag(body: &[u8]) -> EntityTag {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+
+    EntityTag::strong(format!("{:x}", hasher.finish()))
+}
+
 fn file_mime_type(path: &Path) -> &'static str {
     match path.extension().and_then(OsStr::to_str) {
         Some("css") => TEXT_CSS,
@@ -232,7 +632,7 @@ mod tests {
 
     use super::*;
 
-    use std::fs::read;
+    use std::fs::{create_dir_all, read, write};
     use std::process::Command;
 
     use self::tempfile::tempdir;
@@ -242,10 +642,15 @@ mod tests {
         http::{Method, StatusCode},
         HttpMessage,
     };
-    use futures::Future;
+    use futures::{Future, Stream};
+    use yore::Journal;
+
+    use super::super::image::oriented_image;
+    use super::super::tiles::TileSource;
 
     fn test_state(cache_path: &Path) -> SharedGuiState {
-        let mut state = GuiState::new(cache_path);
+        let journal = Journal::new(&cache_path.join("journal"));
+        let mut state = GuiState::new(cache_path, journal);
         state.search_new_root_path(PathBuf::from("tests/assets"));
         state
             .load_location_history(PathBuf::from("tests/assets/location_history.json"))
@@ -272,13 +677,25 @@ mod tests {
                 }).resource("/interpolate", |r| {
                     r.get().with(get_interpolate);
                     r.put().with(put_interpolate);
-                }).resource("/locations", |r| r.get().with(get_locations))
-                .resource("/location", |r| {
+                }).resource("/copyFromPhotos", |r| {
+                    r.get().with(get_copy_from_photos);
+                    r.put().with(put_copy_from_photos);
+                }).resource("/locations", |r| {
+                    r.get().with(get_locations);
+                    r.put().with(put_locations);
+                    r.post().with(post_locations);
+                }).resource("/location", |r| {
                     r.get().with(get_location);
                     r.put().with(put_location);
-                }).resource("/photos", |r| r.get().with(get_photos))
+                }).resource("/track", |r| r.get().with(get_track))
+                .resource("/undo", |r| r.post().with(post_undo))
+                .resource("/events", |r| r.get().with(get_events))
+                .resource("/photos", |r| r.get().with(get_photos))
                 .resource("/photo", |r| r.get().with(get_photo))
                 .resource("/thumbnail", |r| r.get().with(get_thumbnail))
+                .resource("/cache", |r| r.delete().with(delete_cache))
+                .resource("/tiles", |r| r.get().with(get_tiles))
+                .resource("/tiles/{z}/{x}/{y}", |r| r.get().with(get_tile))
                 .resource("/{file}", |r| r.get().with(get_static_file))
                 .resource("/", |r| r.get().f(get_index));
         });
@@ -403,6 +820,73 @@ mod tests {
         assert_eq!(json(response), expected_json);
     }
 
+    #[test]
+    fn get_track_should_respond_with_the_location_history_around_the_queried_photo() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let request = srv
+            .client(
+                Method::GET,
+                "/track?path=tests/assets/photo_without_gps.jpg&window=500000",
+            ).finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            "application/json"
+        );
+
+        let expected_json = "{\"type\":\"FeatureCollection\",\"features\":[{\"type\":\"Feature\",\"geometry\":{\"type\":\"Point\",\"coordinates\":[1.1965831,52.0796733]},\"properties\":{\"timestampMs\":1472688000000,\"offsetSeconds\":-470321,\"accuracy\":18}}]}";
+
+        assert_eq!(json(response), expected_json);
+    }
+
+    #[test]
+    fn get_track_should_default_to_an_hour_either_side_of_the_photo() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let request = srv
+            .client(
+                Method::GET,
+                "/track?path=tests/assets/photo_without_gps.jpg",
+            ).finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            json(response),
+            "{\"type\":\"FeatureCollection\",\"features\":[]}"
+        );
+    }
+
+    #[test]
+    fn get_events_should_respond_with_an_event_stream() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let request = srv.client(Method::GET, "/events").finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            TEXT_EVENT_STREAM
+        );
+        assert_eq!(response.headers().get("Cache-Control").unwrap(), "no-cache");
+
+        // The stream doesn't end, so only read its first chunk.
+        let (chunk, _) = srv
+            .execute(response.payload().into_future().map_err(|(e, _)| e))
+            .unwrap();
+        assert!(String::from_utf8_lossy(&chunk.unwrap())
+            .starts_with("data: {\"ScanStarted\":{\"rootPath\":\"tests/assets\"}}\n\n"));
+    }
+
     #[test]
     fn get_photos_should_respond_with_all_photos_if_filter_is_not_set() {
         let tmp_dir = tempdir().unwrap();
@@ -417,7 +901,7 @@ mod tests {
             "application/json"
         );
 
-        let expected_json = "{\"photos\":[{\"path\":\"tests/assets/photo.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_rotated.jpg\",\"height\":50,\"width\":33},{\"path\":\"tests/assets/photo_without_exif.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_gps.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_orientation.jpg\",\"height\":33,\"width\":50},{\"path\":\"tests/assets/photo_without_timestamp.jpg\",\"height\":37,\"width\":55}]}";
+        let expected_json = "{\"photos\":[{\"path\":\"tests/assets/photo.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo.png\",\"height\":12,\"width\":16},{\"path\":\"tests/assets/photo.tiff\",\"height\":12,\"width\":16},{\"path\":\"tests/assets/photo.webp\",\"height\":16,\"width\":16},{\"path\":\"tests/assets/photo_rotated.jpg\",\"height\":50,\"width\":33},{\"path\":\"tests/assets/photo_with_gps_time.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_with_subsec_time.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_any_timestamp.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_exif.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_gps.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_orientation.jpg\",\"height\":33,\"width\":50},{\"path\":\"tests/assets/photo_without_timestamp.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/video.mp4\",\"height\":48,\"width\":64},{\"path\":\"tests/assets/video_without_gps.mov\",\"height\":48,\"width\":64}]}";
 
         assert_eq!(json(response), expected_json);
     }
@@ -439,7 +923,7 @@ mod tests {
             "application/json"
         );
 
-        let expected_json = "{\"photos\":[{\"path\":\"tests/assets/photo.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_rotated.jpg\",\"height\":50,\"width\":33},{\"path\":\"tests/assets/photo_without_exif.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_gps.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_orientation.jpg\",\"height\":33,\"width\":50},{\"path\":\"tests/assets/photo_without_timestamp.jpg\",\"height\":37,\"width\":55}]}";
+        let expected_json = "{\"photos\":[{\"path\":\"tests/assets/photo.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo.png\",\"height\":12,\"width\":16},{\"path\":\"tests/assets/photo.tiff\",\"height\":12,\"width\":16},{\"path\":\"tests/assets/photo.webp\",\"height\":16,\"width\":16},{\"path\":\"tests/assets/photo_rotated.jpg\",\"height\":50,\"width\":33},{\"path\":\"tests/assets/photo_with_gps_time.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_with_subsec_time.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_any_timestamp.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_exif.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_gps.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_orientation.jpg\",\"height\":33,\"width\":50},{\"path\":\"tests/assets/photo_without_timestamp.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/video.mp4\",\"height\":48,\"width\":64},{\"path\":\"tests/assets/video_without_gps.mov\",\"height\":48,\"width\":64}]}";
 
         assert_eq!(json(response), expected_json);
     }
@@ -461,57 +945,54 @@ mod tests {
             "application/json"
         );
 
-        let expected_json = "{\"photos\":[{\"path\":\"tests/assets/photo_without_gps.jpg\",\"height\":37,\"width\":55}]}";
+        let expected_json = "{\"photos\":[{\"path\":\"tests/assets/photo_with_gps_time.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_with_subsec_time.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_without_gps.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/video_without_gps.mov\",\"height\":48,\"width\":64}]}";
 
         assert_eq!(json(response), expected_json);
     }
 
     #[test]
-    fn get_photo_should_respond_with_image_in_body_and_image_jpeg_mime_type() {
+    fn get_photos_should_respond_with_a_page_of_sorted_and_filtered_photos_and_a_cursor() {
         let tmp_dir = tempdir().unwrap();
         let mut srv = test_server(tmp_dir.path());
 
         let request = srv
-            .client(Method::GET, "/photo?path=tests/assets/photo.jpg")
-            .finish()
+            .client(
+                Method::GET,
+                "/photos?status=suggested&sort=timestamp&order=desc&limit=2",
+            ).finish()
             .unwrap();
         let response = srv.execute(request.send()).unwrap();
 
         assert_eq!(response.status(), StatusCode::OK);
-        assert_eq!(
-            response.headers().get("Content-Type").unwrap(),
-            "image/jpeg"
-        );
 
-        let image = oriented_image(Path::new("tests/assets/photo.jpg")).unwrap();
+        let expected_json = "{\"photos\":[{\"path\":\"tests/assets/photo_with_gps_time.jpg\",\"height\":37,\"width\":55},{\"path\":\"tests/assets/photo_with_subsec_time.jpg\",\"height\":37,\"width\":55}],\"nextCursor\":\"1473158321270:0:tests/assets/photo_with_subsec_time.jpg\"}";
 
-        assert_eq!(body(response).as_ref(), image.as_slice());
+        assert_eq!(json(response), expected_json);
     }
 
     #[test]
-    fn get_thumbnail_should_respond_with_a_binary_body_and_image_jpeg_mime_type() {
+    fn get_photos_should_respond_with_bad_request_for_an_invalid_bounding_box() {
         let tmp_dir = tempdir().unwrap();
         let mut srv = test_server(tmp_dir.path());
 
-        let path = "/thumbnail?path=tests/assets/photo_rotated.jpg&maxWidth=300&maxHeight=300";
-        let request = srv.client(Method::GET, path).finish().unwrap();
+        let request = srv
+            .client(Method::GET, "/photos?bbox=1,2,3")
+            .finish()
+            .unwrap();
         let response = srv.execute(request.send()).unwrap();
 
-        assert_eq!(response.status(), StatusCode::OK);
-        assert_eq!(
-            response.headers().get("Content-Type").unwrap(),
-            "image/jpeg"
-        );
-        assert!(!body(response).as_ref().is_empty());
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 
     #[test]
-    fn get_thumbnail_should_not_respond_with_original_image() {
+    fn get_photo_should_respond_with_image_in_body_and_image_jpeg_mime_type() {
         let tmp_dir = tempdir().unwrap();
         let mut srv = test_server(tmp_dir.path());
 
-        let path = "/thumbnail?path=tests/assets/photo_rotated.jpg&maxWidth=300&maxHeight=300";
-        let request = srv.client(Method::GET, path).finish().unwrap();
+        let request = srv
+            .client(Method::GET, "/photo?path=tests/assets/photo_rotated.jpg")
+            .finish()
+            .unwrap();
         let response = srv.execute(request.send()).unwrap();
 
         assert_eq!(response.status(), StatusCode::OK);
@@ -522,73 +1003,398 @@ mod tests {
 
         let image = oriented_image(Path::new("tests/assets/photo_rotated.jpg")).unwrap();
 
-        assert_ne!(body(response).as_ref(), image.as_slice());
+        assert_eq!(body(response).as_ref(), image.as_slice());
     }
 
     #[test]
-    fn get_thumbnail_should_cache_generated_thumbnails() {
+    fn get_photo_should_respond_with_the_original_file_if_it_is_an_untransformed_jpeg() {
         let tmp_dir = tempdir().unwrap();
-        let (mut srv, state) = test_server_and_state(tmp_dir.path());
-
-        let cached_path = state.read().unwrap().cached_image_path(
-            Path::new("tests/assets/photo_rotated.jpg"),
-            300,
-            300,
-        );
-        assert!(!cached_path.exists());
+        let mut srv = test_server(tmp_dir.path());
 
-        let path = "/thumbnail?path=tests/assets/photo_rotated.jpg&maxWidth=300&maxHeight=300";
-        let request = srv.client(Method::GET, path).finish().unwrap();
+        let request = srv
+            .client(
+                Method::GET,
+                "/photo?path=tests/assets/photo_without_orientation.jpg",
+            ).finish()
+            .unwrap();
         let response = srv.execute(request.send()).unwrap();
 
-        assert!(cached_path.exists());
-        let file_content = read(cached_path).unwrap();
-
         assert_eq!(response.status(), StatusCode::OK);
-        assert_eq!(body(response).as_ref(), file_content.as_slice());
 
-        let request = srv.client(Method::GET, path).finish().unwrap();
-        let response = srv.execute(request.send()).unwrap();
+        let file_content = read("tests/assets/photo_without_orientation.jpg").unwrap();
 
-        assert_eq!(response.status(), StatusCode::OK);
         assert_eq!(body(response).as_ref(), file_content.as_slice());
     }
 
     #[test]
-    fn get_static_file_should_respond_with_a_non_empty_body() {
-        let mut srv = TestServer::new(|app| {
-            app.resource("/{file}", |r| r.method(Method::GET).with(get_static_file));
-        });
+    fn get_photo_should_respond_with_caching_headers() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
 
-        let request = srv.client(Method::GET, "/index.html").finish().unwrap();
+        let request = srv
+            .client(Method::GET, "/photo?path=tests/assets/photo.jpg")
+            .finish()
+            .unwrap();
         let response = srv.execute(request.send()).unwrap();
 
         assert_eq!(response.status(), StatusCode::OK);
-
-        let body = response.body().limit(1024).wait().unwrap();
-
-        assert!(!body.is_empty());
+        assert_eq!(
+            response.headers().get("Cache-Control").unwrap(),
+            PHOTO_CACHE_CONTROL
+        );
+        assert_eq!(response.headers().get("Accept-Ranges").unwrap(), "bytes");
+        assert!(response.headers().contains_key("ETag"));
+        assert!(response.headers().contains_key("Last-Modified"));
     }
 
     #[test]
-    fn get_index_should_respond_with_a_binary_body() {
+    fn get_photo_should_respond_with_not_modified_if_the_entity_tag_matches() {
         let tmp_dir = tempdir().unwrap();
-        let state = test_state(tmp_dir.path());
-        let response = test::TestRequest::with_state(state.clone())
-            .run(&get_index)
-            .unwrap();
-        assert_eq!(response.status(), StatusCode::OK);
-        assert!(response.body().is_binary());
-    }
+        let mut srv = test_server(tmp_dir.path());
 
-    #[test]
-    fn put_interpolate_should_set_interpolate_state_to_the_given_value() {
-        let tmp_dir = tempdir().unwrap();
-        let (mut srv, state) = test_server_and_state(tmp_dir.path());
+        let path = "/photo?path=tests/assets/photo_rotated.jpg";
+        let request = srv.client(Method::GET, path).finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+        let etag = response.headers().get("ETag").unwrap().clone();
 
         let request = srv
-            .client(Method::PUT, "/interpolate")
-            .header("Content-Type", "application/json")
+            .client(Method::GET, path)
+            .header("If-None-Match", etag.clone())
+            .finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get("ETag").unwrap(), etag);
+        assert!(body(response).as_ref().is_empty());
+
+        let request = srv
+            .client(Method::GET, path)
+            .header("If-None-Match", "\"other\"")
+            .finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn get_photo_should_respond_with_not_modified_if_unmodified_since_the_given_time() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let path = "/photo?path=tests/assets/photo.jpg";
+        let request = srv.client(Method::GET, path).finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+        let last_modified = response.headers().get("Last-Modified").unwrap().clone();
+
+        let request = srv
+            .client(Method::GET, path)
+            .header("If-Modified-Since", last_modified)
+            .finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let request = srv
+            .client(Method::GET, path)
+            .header("If-Modified-Since", "Thu, 01 Jan 1970 00:00:00 GMT")
+            .finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn get_photo_should_respond_with_the_requested_range_of_bytes() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let file_content = read("tests/assets/photo.jpg").unwrap();
+        let request = srv
+            .client(Method::GET, "/photo?path=tests/assets/photo.jpg")
+            .header("Range", "bytes=2-11")
+            .finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let content_range = format!("bytes 2-11/{}", file_content.len());
+        assert_eq!(
+            response.headers().get("Content-Range").unwrap(),
+            content_range.as_str()
+        );
+        assert_eq!(body(response).as_ref(), &file_content[2..12]);
+    }
+
+    #[test]
+    fn get_photo_should_respond_with_range_not_satisfiable_for_a_range_past_the_end() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let file_content = read("tests/assets/photo.jpg").unwrap();
+        let request = srv
+            .client(Method::GET, "/photo?path=tests/assets/photo.jpg")
+            .header("Range", format!("bytes={}-", file_content.len()))
+            .finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        let content_range = format!("bytes */{}", file_content.len());
+        assert_eq!(
+            response.headers().get("Content-Range").unwrap(),
+            content_range.as_str()
+        );
+    }
+
+    #[test]
+    fn get_photo_should_ignore_the_range_if_it_does_not_match_if_range() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let request = srv
+            .client(Method::GET, "/photo?path=tests/assets/photo.jpg")
+            .header("Range", "bytes=2-11")
+            .header("If-Range", "\"other\"")
+            .finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn get_tiles_should_respond_with_whether_a_tile_source_is_available() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let request = srv.client(Method::GET, "/tiles").finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json(response), "{\"available\":false}");
+    }
+
+    #[test]
+    fn get_tile_should_respond_with_not_found_if_there_is_no_tile_source() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let request = srv.client(Method::GET, "/tiles/0/0/0").finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn get_tile_should_respond_with_the_tile_and_caching_headers() {
+        let tmp_dir = tempdir().unwrap();
+        let tiles_path = tmp_dir.path().join("tiles");
+        create_dir_all(tiles_path.join("1").join("0")).unwrap();
+        write(tiles_path.join("1/0/1.png"), b"\x89PNG\r\n\x1a\n0000").unwrap();
+
+        let (mut srv, state) = test_server_and_state(tmp_dir.path());
+        state
+            .write()
+            .unwrap()
+            .set_tile_source(Some(TileSource::new(&tiles_path).unwrap()));
+
+        let request = srv.client(Method::GET, "/tiles/1/0/1").finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("Content-Type").unwrap(), "image/png");
+        assert_eq!(
+            response.headers().get("Cache-Control").unwrap(),
+            TILE_CACHE_CONTROL
+        );
+        let etag = response.headers().get("ETag").unwrap().clone();
+        assert_eq!(b"\x89PNG\r\n\x1a\n0000", body(response).as_ref());
+
+        let request = srv
+            .client(Method::GET, "/tiles/1/0/1")
+            .header("If-None-Match", etag.clone())
+            .finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let if_none_match = format!("\"other\", W/{}", etag.to_str().unwrap());
+        let request = srv
+            .client(Method::GET, "/tiles/1/0/1")
+            .header("If-None-Match", if_none_match)
+            .finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[test]
+    fn get_thumbnail_should_respond_with_a_binary_body_and_image_jpeg_mime_type() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let path = "/thumbnail?path=tests/assets/photo_rotated.jpg&maxWidth=300&maxHeight=300";
+        let request = srv.client(Method::GET, path).finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            "image/jpeg"
+        );
+        assert!(!body(response).as_ref().is_empty());
+    }
+
+    #[test]
+    fn get_thumbnail_should_not_respond_with_original_image() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let path = "/thumbnail?path=tests/assets/photo_rotated.jpg&maxWidth=300&maxHeight=300";
+        let request = srv.client(Method::GET, path).finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            "image/jpeg"
+        );
+
+        let image = oriented_image(Path::new("tests/assets/photo_rotated.jpg")).unwrap();
+
+        assert_ne!(body(response).as_ref(), image.as_slice());
+    }
+
+    #[test]
+    fn get_thumbnail_should_cache_generated_thumbnails() {
+        let tmp_dir = tempdir().unwrap();
+        let (mut srv, state) = test_server_and_state(tmp_dir.path());
+
+        let cached_path = state
+            .read()
+            .unwrap()
+            .image_cache()
+            .cached_image_path(Path::new("tests/assets/photo_rotated.jpg"), 300, 300)
+            .unwrap();
+        assert!(!cached_path.exists());
+
+        let path = "/thumbnail?path=tests/assets/photo_rotated.jpg&maxWidth=300&maxHeight=300";
+        let request = srv.client(Method::GET, path).finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert!(cached_path.exists());
+        let file_content = read(cached_path).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response).as_ref(), file_content.as_slice());
+
+        let request = srv.client(Method::GET, path).finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response).as_ref(), file_content.as_slice());
+    }
+
+    #[test]
+    fn get_thumbnail_should_respond_with_not_modified_if_the_entity_tag_matches() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let path = "/thumbnail?path=tests/assets/photo.jpg&maxWidth=300&maxHeight=300";
+        let request = srv.client(Method::GET, path).finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+        assert_eq!(
+            response.headers().get("Cache-Control").unwrap(),
+            PHOTO_CACHE_CONTROL
+        );
+        let etag = response.headers().get("ETag").unwrap().clone();
+
+        let request = srv
+            .client(Method::GET, path)
+            .header("If-None-Match", etag.clone())
+            .finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let path = "/thumbnail?path=tests/assets/photo.jpg&maxWidth=100&maxHeight=100";
+        let request = srv
+            .client(Method::GET, path)
+            .header("If-None-Match", etag)
+            .finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn delete_cache_should_remove_cached_thumbnails() {
+        let tmp_dir = tempdir().unwrap();
+        let cache_path = tmp_dir.path().join("cache");
+        let (mut srv, state) = test_server_and_state(&cache_path);
+
+        let path = "/thumbnail?path=tests/assets/photo.jpg&maxWidth=300&maxHeight=300";
+        let request = srv.client(Method::GET, path).finish().unwrap();
+        srv.execute(request.send()).unwrap();
+        assert!(cache_path.exists());
+
+        let request = srv.client(Method::DELETE, "/cache").finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!cache_path.exists());
+        assert!(state
+            .read()
+            .unwrap()
+            .image_cache()
+            .get(Path::new("tests/assets/photo.jpg"), 300, 300)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn get_static_file_should_respond_with_a_non_empty_body() {
+        let mut srv = TestServer::new(|app| {
+            app.resource("/{file}", |r| r.method(Method::GET).with(get_static_file));
+        });
+
+        let request = srv.client(Method::GET, "/index.html").finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.body().limit(1024).wait().unwrap();
+
+        assert!(!body.is_empty());
+    }
+
+    #[test]
+    fn get_index_should_respond_with_a_binary_body() {
+        let tmp_dir = tempdir().unwrap();
+        let state = test_state(tmp_dir.path());
+        let response = test::TestRequest::with_state(state.clone())
+            .run(&get_index)
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.body().is_binary());
+    }
+
+    #[test]
+    fn put_interpolate_should_set_interpolate_state_to_the_given_value() {
+        let tmp_dir = tempdir().unwrap();
+        let (mut srv, state) = test_server_and_state(tmp_dir.path());
+
+        let request = srv
+            .client(Method::PUT, "/interpolate")
+            .header("Content-Type", "application/json")
             .body("{\"interpolate\":true}")
             .unwrap();
         let response = srv.execute(request.send()).unwrap();
@@ -597,6 +1403,28 @@ mod tests {
         assert!(state.read().unwrap().interpolate());
     }
 
+    #[test]
+    fn put_copy_from_photos_should_set_copy_from_photos_state_to_the_given_value() {
+        let tmp_dir = tempdir().unwrap();
+        let (mut srv, state) = test_server_and_state(tmp_dir.path());
+
+        let request = srv
+            .client(Method::PUT, "/copyFromPhotos")
+            .header("Content-Type", "application/json")
+            .body("{\"copyFromPhotos\":true}")
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.read().unwrap().copy_from_photos());
+
+        let request = srv.client(Method::GET, "/copyFromPhotos").finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json(response), "{\"copyFromPhotos\":true}");
+    }
+
     #[test]
     fn put_location_should_fail_if_exiv2_is_not_available_and_succeed_otherwise() {
         let tmp_dir = tempdir().unwrap();
@@ -617,6 +1445,201 @@ mod tests {
         }
     }
 
+    #[test]
+    fn put_location_should_respond_with_bad_request_for_invalid_coordinates() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let path = "/location?path=tests/assets/photo_without_gps.jpg";
+        let request = srv
+            .client(Method::PUT, path)
+            .header("Content-Type", "application/json")
+            .body("{\"latitude\":91,\"longitude\":0}")
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn put_location_should_respond_with_not_found_for_a_path_that_is_not_a_known_photo() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let request = srv
+            .client(Method::PUT, "/location?path=Cargo.toml")
+            .header("Content-Type", "application/json")
+            .body("{\"latitude\":0,\"longitude\":0}")
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn put_locations_should_write_nothing_if_any_path_is_not_a_known_photo() {
+        let tmp_dir = tempdir().unwrap();
+        let (mut srv, state) = test_server_and_state(tmp_dir.path());
+
+        let request = srv
+            .client(Method::PUT, "/locations")
+            .header("Content-Type", "application/json")
+            .body(
+                "{\"paths\":[\"tests/assets/photo_without_gps.jpg\",\"Cargo.toml\"],\
+                 \"coordinates\":{\"latitude\":0,\"longitude\":0}}",
+            ).unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(state.read().unwrap().journal().entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn put_locations_should_respond_with_bad_request_for_invalid_coordinates() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let request = srv
+            .client(Method::PUT, "/locations")
+            .header("Content-Type", "application/json")
+            .body(
+                "{\"paths\":[\"tests/assets/photo_without_gps.jpg\"],\
+                 \"coordinates\":{\"latitude\":0,\"longitude\":-181}}",
+            ).unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn post_locations_should_respond_with_not_found_for_a_path_that_is_not_a_known_photo() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let request = srv
+            .client(Method::POST, "/locations")
+            .header("Content-Type", "application/json")
+            .body("{\"paths\":[\"Cargo.toml\"]}")
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn post_locations_should_stream_the_progress_of_each_photo() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let request = srv
+            .client(Method::POST, "/locations")
+            .header("Content-Type", "application/json")
+            .body("{\"paths\":[\"tests/assets/photo.jpg\"],\"maxAccuracyMeters\":100}")
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            APPLICATION_NDJSON
+        );
+        // The body is streamed, so it has to be read using the test server's event loop.
+        let body = srv.execute(response.body()).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&body),
+            "{\"Started\":{\"total\":1}}\n\
+             {\"Failed\":{\"path\":\"tests/assets/photo.jpg\",\
+             \"error\":\"The photo has no suggested location\"}}\n"
+        );
+    }
+
+    #[test]
+    fn post_undo_should_respond_with_nothing_undone_if_no_locations_have_been_saved() {
+        let tmp_dir = tempdir().unwrap();
+        let mut srv = test_server(tmp_dir.path());
+
+        let request = srv.client(Method::POST, "/undo").finish().unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json(response), "{\"undone\":[]}");
+
+        let request = srv
+            .client(Method::POST, "/undo?session=1")
+            .finish()
+            .unwrap();
+        let response = srv.execute(request.send()).unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json(response), "{\"undone\":[]}");
+    }
+
+    #[test]
+    fn parse_byte_range_should_parse_a_range_with_first_and_last_bytes() {
+        match parse_byte_range("bytes=2-11", 100) {
+            Some(ByteRange::Partial(range)) => assert_eq!(2..12, range),
+            _ => panic!("Expected a partial range"),
+        }
+    }
+
+    #[test]
+    fn parse_byte_range_should_end_an_open_or_overlong_range_at_the_last_byte() {
+        match parse_byte_range("bytes=90-", 100) {
+            Some(ByteRange::Partial(range)) => assert_eq!(90..100, range),
+            _ => panic!("Expected a partial range"),
+        }
+
+        match parse_byte_range("bytes=90-200", 100) {
+            Some(ByteRange::Partial(range)) => assert_eq!(90..100, range),
+            _ => panic!("Expected a partial range"),
+        }
+    }
+
+    #[test]
+    fn parse_byte_range_should_not_overflow_if_the_last_byte_is_the_largest_usize() {
+        let range = format!("bytes=5-{}", usize::max_value());
+
+        match parse_byte_range(&range, 100) {
+            Some(ByteRange::Partial(range)) => assert_eq!(5..100, range),
+            _ => panic!("Expected a partial range"),
+        }
+    }
+
+    #[test]
+    fn parse_byte_range_should_parse_a_suffix_range() {
+        match parse_byte_range("bytes=-10", 100) {
+            Some(ByteRange::Partial(range)) => assert_eq!(90..100, range),
+            _ => panic!("Expected a partial range"),
+        }
+
+        match parse_byte_range("bytes=-200", 100) {
+            Some(ByteRange::Partial(range)) => assert_eq!(0..100, range),
+            _ => panic!("Expected a partial range"),
+        }
+    }
+
+    #[test]
+    fn parse_byte_range_should_be_unsatisfiable_if_the_range_starts_past_the_end() {
+        match parse_byte_range("bytes=100-", 100) {
+            Some(ByteRange::Unsatisfiable) => {}
+            _ => panic!("Expected an unsatisfiable range"),
+        }
+
+        match parse_byte_range("bytes=-0", 100) {
+            Some(ByteRange::Unsatisfiable) => {}
+            _ => panic!("Expected an unsatisfiable range"),
+        }
+    }
+
+    #[test]
+    fn parse_byte_range_should_be_none_for_an_invalid_or_multiple_range() {
+        assert!(parse_byte_range("bytes=11-2", 100).is_none());
+        assert!(parse_byte_range("bytes=a-b", 100).is_none());
+        assert!(parse_byte_range("bytes=2", 100).is_none());
+        assert!(parse_byte_range("items=2-11", 100).is_none());
+        assert!(parse_byte_range("bytes=0-1,4-5", 100).is_none());
+    }
+
     #[test]
     fn file_mime_type_should_return_text_css_for_a_path_ending_in_dot_css() {
         assert_eq!(TEXT_CSS, file_mime_type(Path::new("test.css")));
@@ -0,0 +1,243 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use yore::{
+    get_location_suggestion, Coordinates, GoogleLocationHistory, Journal, PhotoLocation, Place,
+    ReverseGeocoder, TimestampFallback, WriteOptions,
+};
+
+use super::state::GuiState;
+
+/// The photos to save suggested locations for. If no paths are given, all photos with a
+/// suggested location are saved. If a maximum accuracy is given, suggestions that are less
+/// accurate are not saved.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveRequest {
+    paths: Option<Vec<PathBuf>>,
+    max_accuracy_meters: Option<u32>,
+}
+
+impl SaveRequest {
+    pub fn paths(&self) -> Option<&Vec<PathBuf>> {
+        self.paths.as_ref()
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub enum SaveProgress {
+    Started {
+        total: usize,
+    },
+    Saved {
+        path: PathBuf,
+        coordinates: Coordinates,
+    },
+    Failed {
+        path: PathBuf,
+        error: String,
+    },
+}
+
+/// Everything needed to save suggested locations, copied from the GUI state so that the state
+/// doesn't need to stay locked while the photos are written.
+pub struct SaveInputs {
+    paths: Vec<PathBuf>,
+    requested: bool,
+    max_accuracy_meters: Option<u32>,
+    location_history: Arc<GoogleLocationHistory>,
+    interpolate: bool,
+    timestamp_fallbacks: Vec<TimestampFallback>,
+    journal: Arc<Journal>,
+    write_options: WriteOptions,
+    geocoder: Option<Arc<ReverseGeocoder>>,
+}
+
+impl SaveInputs {
+    pub fn new(state: &GuiState, request: &SaveRequest) -> SaveInputs {
+        SaveInputs {
+            paths: request
+                .paths
+                .clone()
+                .unwrap_or_else(|| state.photo_paths().to_vec()),
+            requested: request.paths.is_some(),
+            max_accuracy_meters: request.max_accuracy_meters,
+            location_history: state.shared_location_history(),
+            interpolate: state.interpolate(),
+            timestamp_fallbacks: state.timestamp_fallbacks().to_vec(),
+            journal: state.shared_journal(),
+            write_options: state.write_options().clone(),
+            geocoder: state.place_geocoder(),
+        }
+    }
+
+    fn suggested_coordinates(&self, path: &Path) -> Result<Coordinates, String> {
+        let location = get_location_suggestion(
+            path,
+            &self.location_history,
+            self.interpolate,
+            &self.timestamp_fallbacks,
+        ).map_err(|e| format!("{}", e))?;
+
+        match (location, self.max_accuracy_meters) {
+            (PhotoLocation::Suggested(_, ref accuracy), Some(max)) if accuracy.meters() > max => {
+                Err(format!(
+                    "The suggested location is only accurate to {} meters",
+                    accuracy.meters()
+                ))
+            }
+            (PhotoLocation::Suggested(coordinates, _), _) => Ok(coordinates),
+            _ => Err("The photo has no suggested location".into()),
+        }
+    }
+
+    fn place_to_write(&self, coordinates: &Coordinates) -> Option<&Place> {
+        self.geocoder
+            .as_ref()?
+            .nearest(coordinates)
+            .map(|(place, _)| place)
+    }
+}
+
+/// Save the suggested locations of the requested photos in parallel, returning the paths of
+/// the photos that were saved.
+///
+/// `progress` is first called with the number of photos that will be saved, then once for
+/// each of those photos as it is saved or fails to be saved. Photos that were requested by
+/// path but don't have a suitable suggestion count as failures, but if no paths were
+/// requested they are skipped.
+pub fn save_suggested_locations<F>(inputs: &SaveInputs, progress: F) -> Vec<PathBuf>
+where
+    F: Fn(SaveProgress) + Sync,
+{
+    let candidates: Vec<(PathBuf, Result<Coordinates, String>)> = inputs
+        .paths
+        .par_iter()
+        .map(|path| (path.clone(), inputs.suggested_coordinates(path)))
+        .filter(|(_, suggestion)| inputs.requested || suggestion.is_ok())
+        .collect();
+
+    progress(SaveProgress::Started {
+        total: candidates.len(),
+    });
+
+    candidates
+        .into_par_iter()
+        .filter_map(|(path, suggestion)| {
+            let result = suggestion.and_then(|coordinates| {
+                inputs
+                    .journal
+                    .write_coordinates(
+                        &path,
+                        &coordinates,
+                        inputs.place_to_write(&coordinates),
+                        &inputs.write_options,
+                    )
+                    .map(|_| coordinates)
+                    .map_err(|e| format!("{}", e))
+            });
+
+            match result {
+                Ok(coordinates) => {
+                    progress(SaveProgress::Saved {
+                        path: path.clone(),
+                        coordinates,
+                    });
+                    Some(path)
+                }
+                Err(error) => {
+                    progress(SaveProgress::Failed { path, error });
+                    None
+                }
+            }
+        }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use self::tempfile::tempdir;
+
+    fn new_state(tmp_dir: &Path) -> GuiState {
+        let mut state = GuiState::new(tmp_dir, Journal::new(&tmp_dir.join("journal")));
+        state.search_new_root_path(PathBuf::from("tests/assets"));
+        state
+            .load_location_history(PathBuf::from("tests/assets/location_history.json"))
+            .unwrap();
+        state
+    }
+
+    fn save(state: &GuiState, request: &SaveRequest) -> (Vec<PathBuf>, Vec<SaveProgress>) {
+        let progress = Mutex::new(Vec::new());
+        let inputs = SaveInputs::new(state, request);
+        let saved = save_suggested_locations(&inputs, |p| progress.lock().unwrap().push(p));
+
+        (saved, progress.into_inner().unwrap())
+    }
+
+    #[test]
+    fn save_suggested_locations_should_fail_requested_photos_without_a_suggestion() {
+        let tmp_dir = tempdir().unwrap();
+        let state = new_state(tmp_dir.path());
+        let request = SaveRequest {
+            paths: Some(vec![PathBuf::from("tests/assets/photo.jpg")]),
+            max_accuracy_meters: None,
+        };
+
+        let (saved, progress) = save(&state, &request);
+
+        assert!(saved.is_empty());
+        assert_eq!(
+            vec![
+                SaveProgress::Started { total: 1 },
+                SaveProgress::Failed {
+                    path: PathBuf::from("tests/assets/photo.jpg"),
+                    error: "The photo has no suggested location".into(),
+                },
+            ],
+            progress
+        );
+    }
+
+    #[test]
+    fn save_suggested_locations_should_fail_requested_photos_with_an_inaccurate_suggestion() {
+        let tmp_dir = tempdir().unwrap();
+        let state = new_state(tmp_dir.path());
+        let request = SaveRequest {
+            paths: Some(vec![PathBuf::from("tests/assets/photo_without_gps.jpg")]),
+            max_accuracy_meters: Some(0),
+        };
+
+        let (saved, progress) = save(&state, &request);
+
+        assert!(saved.is_empty());
+        assert_eq!(2, progress.len());
+        match progress[1] {
+            SaveProgress::Failed { ref error, .. } => {
+                assert!(error.starts_with("The suggested location is only accurate to"))
+            }
+            ref p => panic!("Unexpected progress {:?}", p),
+        }
+    }
+
+    #[test]
+    fn save_suggested_locations_should_skip_unrequested_photos_without_a_suitable_suggestion() {
+        let tmp_dir = tempdir().unwrap();
+        let state = new_state(tmp_dir.path());
+        let request = SaveRequest {
+            paths: None,
+            max_accuracy_meters: Some(0),
+        };
+
+        let (saved, progress) = save(&state, &request);
+
+        assert!(saved.is_empty());
+        assert_eq!(vec![SaveProgress::Started { total: 0 }], progress);
+    }
+}
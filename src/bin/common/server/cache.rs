@@ -0,0 +1,440 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs::{self, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The default limit on the total size of the cached images, in bytes.
+pub const DEFAULT_MAX_CACHE_SIZE: u64 = 500 * 1024 * 1024;
+
+/// Caches images generated from photos on disk, so that they're kept between runs.
+///
+/// Cached images are named after their photo's path, size and modification time, so they're
+/// not used once the photo changes. Once the total size of the cached images exceeds the
+/// maximum size, the least recently used images are removed.
+pub struct ImageCache {
+    path: PathBuf,
+    max_size: u64,
+    index: Mutex<CacheIndex>,
+}
+
+impl ImageCache {
+    /// Use the cache directory at the given path, which may hold images cached by previous
+    /// runs.
+    pub fn new(path: &Path, max_size: u64) -> ImageCache {
+        ImageCache {
+            path: path.to_path_buf(),
+            max_size,
+            index: Mutex::new(CacheIndex::load(path).unwrap_or_default()),
+        }
+    }
+
+    /// Set the maximum total size of the cached images in bytes, removing the least recently
+    /// used images until they fit.
+    pub fn set_max_size(&mut self, max_size: u64) -> io::Result<()> {
+        self.max_size = max_size;
+        let mut index = self.index();
+        self.evict(&mut index)
+    }
+
+    /// The path that the image generated from the given photo at the given size is cached at,
+    /// which depends on the photo's current size and modification time.
+    #[cfg(test)]
+    pub fn cached_image_path(
+        &self,
+        original_image_path: &Path,
+        width: u32,
+        height: u32,
+    ) -> io::Result<PathBuf> {
+        cached_filename(original_image_path, width, height).map(|name| self.path.join(name))
+    }
+
+    /// Get the cached image generated from the given photo at the given size, if it's cached
+    /// and the photo hasn't changed since.
+    pub fn get(
+        &self,
+        original_image_path: &Path,
+        width: u32,
+        height: u32,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let name = cached_filename(original_image_path, width, height)?;
+        let path = self.path.join(&name);
+
+        let image = match fs::read(&path) {
+            Ok(image) => image,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                self.index().remove(&name);
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+
+        self.index().touch(name, image.len() as u64);
+
+        // The modification time records when the image was last used, so that the least
+        // recently used images can be found after a restart.
+        if let Ok(file) = OpenOptions::new().write(true).open(&path) {
+            file.set_modified(SystemTime::now()).ok();
+        }
+
+        Ok(Some(image))
+    }
+
+    /// Cache the image generated from the given photo at the given size.
+    pub fn insert(
+        &self,
+        original_image_path: &Path,
+        width: u32,
+        height: u32,
+        image: &[u8],
+    ) -> io::Result<()> {
+        let name = cached_filename(original_image_path, width, height)?;
+
+        fs::create_dir_all(&self.path)?;
+        fs::write(self.path.join(&name), image)?;
+
+        let mut index = self.index();
+        index.touch(name, image.len() as u64);
+        self.evict(&mut index)
+    }
+
+    /// Forget the cached images of the photo at the given path, whether or not it has changed
+    /// since they were cached, and return the paths of their files. Only the index is updated,
+    /// so that this is quick enough to do while the GUI state is locked, and the files should
+    /// be deleted afterwards using `delete_cached_images`.
+    pub fn forget(&self, original_image_path: &Path) -> Vec<PathBuf> {
+        let prefix = format!("{}-", path_hash(original_image_path));
+
+        self.index()
+            .remove_prefixed(&prefix)
+            .into_iter()
+            .map(|name| self.path.join(name))
+            .collect()
+    }
+
+    /// Remove all cached images.
+    pub fn clear(&self) -> io::Result<()> {
+        let mut index = self.index();
+        if self.path.exists() {
+            fs::remove_dir_all(&self.path)?;
+        }
+        *index = CacheIndex::default();
+
+        Ok(())
+    }
+
+    /// The index is only updated while the lock is held, so it's still usable if a thread
+    /// panicked while holding it.
+    fn index(&self) -> MutexGuard<'_, CacheIndex> {
+        self.index.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn evict(&self, index: &mut CacheIndex) -> io::Result<()> {
+        while index.total_size > self.max_size {
+            let name = match index.least_recently_used() {
+                Some(name) => name,
+                None => break,
+            };
+
+            match fs::remove_file(self.path.join(&name)) {
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+                Ok(()) => {}
+            }
+            index.remove(&name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Delete the given cached image files, which may have already been deleted.
+pub fn delete_cached_images(paths: &[PathBuf]) -> io::Result<()> {
+    for path in paths {
+        match fs::remove_file(path) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            result => result?,
+        }
+    }
+
+    Ok(())
+}
+
+/// The sizes of the cached images and the order in which they were last used. Entries are
+/// sorted by name, so that the images of a photo can be found by their path hash prefix.
+#[derive(Default)]
+struct CacheIndex {
+    entries: BTreeMap<String, CacheEntry>,
+    last_uses: BTreeMap<u64, String>,
+    next_use: u64,
+    total_size: u64,
+}
+
+struct CacheEntry {
+    size: u64,
+    last_use: u64,
+}
+
+impl CacheIndex {
+    /// Index the images in the cache directory at the given path, using their modification
+    /// times as the times they were last used.
+    fn load(path: &Path) -> io::Result<CacheIndex> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                files.push((metadata.modified()?, name, metadata.len()));
+            }
+        }
+        files.sort();
+
+        let mut index = CacheIndex::default();
+        for (_, name, size) in files {
+            index.touch(name, size);
+        }
+
+        Ok(index)
+    }
+
+    /// Record that the image with the given name and size was just used.
+    fn touch(&mut self, name: String, size: u64) {
+        self.remove(&name);
+
+        let last_use = self.next_use;
+        self.next_use += 1;
+
+        self.total_size += size;
+        self.last_uses.insert(last_use, name.clone());
+        self.entries.insert(name, CacheEntry { size, last_use });
+    }
+
+    fn remove(&mut self, name: &str) {
+        if let Some(entry) = self.entries.remove(name) {
+            self.total_size -= entry.size;
+            self.last_uses.remove(&entry.last_use);
+        }
+    }
+
+    fn remove_prefixed(&mut self, prefix: &str) -> Vec<String> {
+        let names: Vec<String> = self
+            .entries
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .map(|(name, _)| name)
+            .take_while(|name| name.starts_with(prefix))
+            .cloned()
+            .collect();
+
+        for name in &names {
+            self.remove(name);
+        }
+
+        names
+    }
+
+    fn least_recently_used(&self) -> Option<String> {
+        self.last_uses.values().next().cloned()
+    }
+}
+
+fn path_hash(path: &Path) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Cached images are named after the hash of their photo's path, so that all the images of a
+/// photo can be found, followed by a hash of the photo's size and modification time.
+fn cached_filename(path: &Path, width: u32, height: u32) -> io::Result<String> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    let mut hasher = DefaultHasher::new();
+    metadata.len().hash(&mut hasher);
+    modified.hash(&mut hasher);
+
+    let extension = path.extension().and_then(OsStr::to_str).unwrap_or(".jpg");
+
+    Ok(format!(
+        "{}-{}-{}-{}.{}",
+        path_hash(path),
+        hasher.finish(),
+        width,
+        height,
+        extension
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    use std::fs::copy;
+
+    use self::tempfile::tempdir;
+
+    const PHOTO_PATH: &str = "tests/assets/photo.jpg";
+    const OTHER_PHOTO_PATH: &str = "tests/assets/photo.png";
+
+    #[test]
+    fn get_should_return_none_if_the_image_is_not_cached() {
+        let tmp_dir = tempdir().unwrap();
+        let cache = ImageCache::new(tmp_dir.path(), DEFAULT_MAX_CACHE_SIZE);
+
+        assert_eq!(None, cache.get(Path::new(PHOTO_PATH), 300, 300).unwrap());
+    }
+
+    #[test]
+    fn get_should_return_an_inserted_image() {
+        let tmp_dir = tempdir().unwrap();
+        let cache = ImageCache::new(tmp_dir.path(), DEFAULT_MAX_CACHE_SIZE);
+
+        cache
+            .insert(Path::new(PHOTO_PATH), 300, 300, b"image")
+            .unwrap();
+
+        assert_eq!(
+            Some(b"image".to_vec()),
+            cache.get(Path::new(PHOTO_PATH), 300, 300).unwrap()
+        );
+        assert_eq!(None, cache.get(Path::new(PHOTO_PATH), 200, 200).unwrap());
+    }
+
+    #[test]
+    fn get_should_not_return_an_image_cached_before_the_photo_changed() {
+        let tmp_dir = tempdir().unwrap();
+        let photo_path = tmp_dir.path().join("photo.jpg");
+        copy(PHOTO_PATH, &photo_path).unwrap();
+        let cache = ImageCache::new(&tmp_dir.path().join("cache"), DEFAULT_MAX_CACHE_SIZE);
+
+        cache.insert(&photo_path, 300, 300, b"image").unwrap();
+        fs::write(&photo_path, b"changed").unwrap();
+
+        assert_eq!(None, cache.get(&photo_path, 300, 300).unwrap());
+    }
+
+    #[test]
+    fn new_should_use_images_cached_by_a_previous_run() {
+        let tmp_dir = tempdir().unwrap();
+        ImageCache::new(tmp_dir.path(), DEFAULT_MAX_CACHE_SIZE)
+            .insert(Path::new(PHOTO_PATH), 300, 300, b"image")
+            .unwrap();
+
+        let cache = ImageCache::new(tmp_dir.path(), DEFAULT_MAX_CACHE_SIZE);
+
+        assert_eq!(5, cache.index().total_size);
+        assert_eq!(
+            Some(b"image".to_vec()),
+            cache.get(Path::new(PHOTO_PATH), 300, 300).unwrap()
+        );
+    }
+
+    #[test]
+    fn insert_should_remove_the_least_recently_used_images_over_the_max_size() {
+        let tmp_dir = tempdir().unwrap();
+        let cache = ImageCache::new(tmp_dir.path(), 10);
+        let photo_path = Path::new(PHOTO_PATH);
+
+        cache.insert(photo_path, 100, 100, b"small").unwrap();
+        cache.insert(photo_path, 200, 200, b"large").unwrap();
+        cache.get(photo_path, 100, 100).unwrap();
+        cache.insert(photo_path, 300, 300, b"image").unwrap();
+
+        assert!(cache.get(photo_path, 100, 100).unwrap().is_some());
+        assert!(cache.get(photo_path, 200, 200).unwrap().is_none());
+        assert!(cache.get(photo_path, 300, 300).unwrap().is_some());
+        assert_eq!(10, cache.index().total_size);
+    }
+
+    #[test]
+    fn set_max_size_should_remove_images_until_they_fit() {
+        let tmp_dir = tempdir().unwrap();
+        let mut cache = ImageCache::new(tmp_dir.path(), DEFAULT_MAX_CACHE_SIZE);
+        let photo_path = Path::new(PHOTO_PATH);
+
+        cache.insert(photo_path, 100, 100, b"small").unwrap();
+        cache.insert(photo_path, 200, 200, b"large").unwrap();
+        cache.set_max_size(5).unwrap();
+
+        assert!(cache.get(photo_path, 100, 100).unwrap().is_none());
+        assert!(cache.get(photo_path, 200, 200).unwrap().is_some());
+    }
+
+    #[test]
+    fn forget_should_return_only_the_images_of_the_given_photo() {
+        let tmp_dir = tempdir().unwrap();
+        let cache = ImageCache::new(tmp_dir.path(), DEFAULT_MAX_CACHE_SIZE);
+
+        cache.insert(Path::new(PHOTO_PATH), 100, 100, b"a").unwrap();
+        cache.insert(Path::new(PHOTO_PATH), 200, 200, b"b").unwrap();
+        cache
+            .insert(Path::new(OTHER_PHOTO_PATH), 100, 100, b"c")
+            .unwrap();
+        let mut paths = cache.forget(Path::new(PHOTO_PATH));
+        paths.sort();
+
+        let mut expected_paths = vec![
+            cache
+                .cached_image_path(Path::new(PHOTO_PATH), 100, 100)
+                .unwrap(),
+            cache
+                .cached_image_path(Path::new(PHOTO_PATH), 200, 200)
+                .unwrap(),
+        ];
+        expected_paths.sort();
+        assert_eq!(expected_paths, paths);
+        assert!(paths.iter().all(|path| path.exists()));
+        assert_eq!(1, cache.index().total_size);
+
+        delete_cached_images(&paths).unwrap();
+
+        assert!(cache
+            .get(Path::new(PHOTO_PATH), 100, 100)
+            .unwrap()
+            .is_none());
+        assert!(cache
+            .get(Path::new(PHOTO_PATH), 200, 200)
+            .unwrap()
+            .is_none());
+        assert!(cache
+            .get(Path::new(OTHER_PHOTO_PATH), 100, 100)
+            .unwrap()
+            .is_some());
+        assert_eq!(1, cache.index().total_size);
+    }
+
+    #[test]
+    fn delete_cached_images_should_ignore_images_that_do_not_exist() {
+        let tmp_dir = tempdir().unwrap();
+
+        assert!(delete_cached_images(&[tmp_dir.path().join("missing.jpg")]).is_ok());
+    }
+
+    #[test]
+    fn clear_should_remove_all_images() {
+        let tmp_dir = tempdir().unwrap();
+        let cache_path = tmp_dir.path().join("cache");
+        let cache = ImageCache::new(&cache_path, DEFAULT_MAX_CACHE_SIZE);
+
+        cache.insert(Path::new(PHOTO_PATH), 100, 100, b"a").unwrap();
+        cache.clear().unwrap();
+
+        assert!(!cache_path.exists());
+        assert_eq!(0, cache.index().total_size);
+        assert!(cache
+            .get(Path::new(PHOTO_PATH), 100, 100)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,6 +1,7 @@
 use std::error;
 use std::fmt;
 use std::io;
+use std::path::PathBuf;
 use std::sync::PoisonError;
 
 use actix_web::error::ResponseError;
@@ -8,7 +9,10 @@ use actix_web::{self, http, HttpResponse};
 use exif;
 use image;
 use jpeg_decoder;
-use yore::HistoryError;
+use notify;
+use yore::{Coordinates, HistoryError, JournalError, PhotoError};
+
+use super::tiles::TileError;
 
 #[derive(Debug)]
 pub enum ServiceError {
@@ -19,6 +23,13 @@ pub enum ServiceError {
     ImageUnsupportedError(jpeg_decoder::UnsupportedFeature),
     ExifError(exif::Error),
     HistoryError(HistoryError),
+    PhotoError(PhotoError),
+    JournalError(JournalError),
+    InvalidCoordinates(Coordinates),
+    UnknownPhoto(PathBuf),
+    InvalidQuery(String),
+    TileError(TileError),
+    WatchError(notify::Error),
     PoisonError,
     ActixError(actix_web::Error),
 }
@@ -33,6 +44,13 @@ impl fmt::Display for ServiceError {
             ServiceError::ImageUnsupportedError(x) => write!(f, "The image makes use of a JPEG feature not (currently) supported by this library: {:?}", x),
             ServiceError::ExifError(e) => e.fmt(f),
             ServiceError::HistoryError(_) => write!(f, "Couldn't load location history"),
+            ServiceError::PhotoError(e) => e.fmt(f),
+            ServiceError::JournalError(e) => e.fmt(f),
+            ServiceError::InvalidCoordinates(c) => write!(f, "{} is not a valid location", c),
+            ServiceError::UnknownPhoto(p) => write!(f, "{} is not a known photo", p.display()),
+            ServiceError::InvalidQuery(e) => write!(f, "The query is not valid: {}", e),
+            ServiceError::TileError(e) => e.fmt(f),
+            ServiceError::WatchError(e) => e.fmt(f),
             ServiceError::PoisonError => write!(f, "Poisoned mutex"),
             ServiceError::ActixError(e) => e.fmt(f),
         }
@@ -51,6 +69,13 @@ impl error::Error for ServiceError {
             }
             ServiceError::ExifError(e) => e.description(),
             ServiceError::HistoryError(_) => "Couldn't load location history",
+            ServiceError::PhotoError(e) => e.description(),
+            ServiceError::JournalError(e) => e.description(),
+            ServiceError::InvalidCoordinates(_) => "The location is not valid",
+            ServiceError::UnknownPhoto(_) => "The path is not a known photo",
+            ServiceError::InvalidQuery(_) => "The query is not valid",
+            ServiceError::TileError(_) => "Couldn't read the map tile",
+            ServiceError::WatchError(_) => "Couldn't watch for file changes",
             ServiceError::PoisonError => "Poisoned mutex",
             ServiceError::ActixError(_) => "Unknown actix error",
         }
@@ -65,6 +90,13 @@ impl error::Error for ServiceError {
             ServiceError::ImageUnsupportedError(_) => None,
             ServiceError::ExifError(e) => Some(e),
             ServiceError::HistoryError(_) => None,
+            ServiceError::PhotoError(e) => Some(e),
+            ServiceError::JournalError(e) => Some(e),
+            ServiceError::InvalidCoordinates(_) => None,
+            ServiceError::UnknownPhoto(_) => None,
+            ServiceError::InvalidQuery(_) => None,
+            ServiceError::TileError(e) => Some(e),
+            ServiceError::WatchError(e) => Some(e),
             ServiceError::PoisonError => None,
             ServiceError::ActixError(_) => None,
         }
@@ -78,6 +110,15 @@ impl ResponseError for ServiceError {
             ServiceError::IoError(e) if e.kind() == io::ErrorKind::NotFound => {
                 HttpResponse::NotFound().finish()
             }
+            ServiceError::JournalError(JournalError::FileChanged(_)) => {
+                HttpResponse::with_body(http::StatusCode::CONFLICT, format!("{}", self))
+            }
+            ServiceError::InvalidCoordinates(_) | ServiceError::InvalidQuery(_) => {
+                HttpResponse::with_body(http::StatusCode::BAD_REQUEST, format!("{}", self))
+            }
+            ServiceError::UnknownPhoto(_) => {
+                HttpResponse::with_body(http::StatusCode::NOT_FOUND, format!("{}", self))
+            }
             ServiceError::ActixError(e) => e.as_response_error().error_response(),
             e => {
                 HttpResponse::with_body(http::StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e))
@@ -110,6 +151,43 @@ impl From<HistoryError> for ServiceError {
     }
 }
 
+impl From<PhotoError> for ServiceError {
+    fn from(error: PhotoError) -> Self {
+        match error {
+            PhotoError::IoError(e) => ServiceError::IoError(e),
+            PhotoError::ExifError(e) => ServiceError::ExifError(e),
+            e => ServiceError::PhotoError(e),
+        }
+    }
+}
+
+impl From<JournalError> for ServiceError {
+    fn from(error: JournalError) -> Self {
+        match error {
+            JournalError::IoError(e) => ServiceError::IoError(e),
+            e => ServiceError::JournalError(e),
+        }
+    }
+}
+
+impl From<TileError> for ServiceError {
+    fn from(error: TileError) -> Self {
+        match error {
+            TileError::IoError(e) => ServiceError::IoError(e),
+            e => ServiceError::TileError(e),
+        }
+    }
+}
+
+impl From<notify::Error> for ServiceError {
+    fn from(error: notify::Error) -> Self {
+        match error {
+            notify::Error::Io(e) => ServiceError::IoError(e),
+            e => ServiceError::WatchError(e),
+        }
+    }
+}
+
 impl<T> From<PoisonError<T>> for ServiceError {
     fn from(_error: PoisonError<T>) -> Self {
         ServiceError::PoisonError
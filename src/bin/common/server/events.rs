@@ -0,0 +1,135 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use bytes::Bytes;
+use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use serde_json;
+
+use super::image::ImageDimensions;
+
+/// Changes to the GUI state that happen in the background, such as photos being found by a scan
+/// or files changing on disk, which are streamed to the GUI as server-sent events.
+#[derive(Debug, Serialize)]
+pub enum GuiEvent {
+    #[serde(rename_all = "camelCase")]
+    ScanStarted {
+        root_path: PathBuf,
+    },
+    PhotosFound {
+        photos: Vec<ImageDimensions>,
+    },
+    PhotoFailed {
+        path: PathBuf,
+        error: String,
+    },
+    ScanFinished {
+        total: usize,
+    },
+    PhotoChanged {
+        photo: ImageDimensions,
+    },
+    PhotosRemoved {
+        paths: Vec<PathBuf>,
+    },
+    #[serde(rename_all = "camelCase")]
+    LocationHistoryReloaded {
+        location_history_path: PathBuf,
+    },
+}
+
+impl GuiEvent {
+    /// Format the event as a server-sent event message, with its JSON as the data.
+    fn to_message(&self) -> Option<Bytes> {
+        let json = serde_json::to_string(self).ok()?;
+        Some(Bytes::from(format!("data: {}\n\n", json)))
+    }
+}
+
+/// Sends GUI events to every subscriber that is still listening.
+#[derive(Default)]
+pub struct EventStream {
+    senders: Mutex<Vec<UnboundedSender<Bytes>>>,
+}
+
+impl EventStream {
+    /// Subscribe to events, receiving the given events first so that the subscriber can catch
+    /// up with events that were sent before it subscribed.
+    pub fn subscribe(&self, initial_events: &[GuiEvent]) -> UnboundedReceiver<Bytes> {
+        let (sender, receiver) = unbounded();
+
+        for message in initial_events.iter().filter_map(GuiEvent::to_message) {
+            sender.unbounded_send(message).ok();
+        }
+
+        if let Ok(mut senders) = self.senders.lock() {
+            senders.push(sender);
+        }
+
+        receiver
+    }
+
+    /// Send an event to all subscribers, forgetting those that have gone away.
+    pub fn send(&self, event: &GuiEvent) {
+        let message = match event.to_message() {
+            Some(message) => message,
+            None => return,
+        };
+
+        if let Ok(mut senders) = self.senders.lock() {
+            senders.retain(|sender| sender.unbounded_send(message.clone()).is_ok());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::Stream;
+
+    fn messages(receiver: UnboundedReceiver<Bytes>, count: usize) -> Vec<String> {
+        receiver
+            .wait()
+            .take(count)
+            .map(|message| String::from_utf8_lossy(&message.unwrap()).into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn subscribe_should_receive_initial_events_then_sent_events() {
+        let events = EventStream::default();
+
+        let receiver = events.subscribe(&[GuiEvent::ScanStarted {
+            root_path: PathBuf::from("photos"),
+        }]);
+        events.send(&GuiEvent::ScanFinished { total: 2 });
+
+        assert_eq!(
+            vec![
+                "data: {\"ScanStarted\":{\"rootPath\":\"photos\"}}\n\n",
+                "data: {\"ScanFinished\":{\"total\":2}}\n\n",
+            ],
+            messages(receiver, 2)
+        );
+    }
+
+    #[test]
+    fn send_should_forget_subscribers_that_have_gone_away() {
+        let events = EventStream::default();
+
+        let receiver = events.subscribe(&[]);
+        drop(events.subscribe(&[]));
+        assert_eq!(2, events.senders.lock().unwrap().len());
+
+        events.send(&GuiEvent::PhotoFailed {
+            path: PathBuf::from("photo.jpg"),
+            error: "Oh no!".to_string(),
+        });
+
+        assert_eq!(1, events.senders.lock().unwrap().len());
+        assert_eq!(
+            vec!["data: {\"PhotoFailed\":{\"path\":\"photo.jpg\",\"error\":\"Oh no!\"}}\n\n"],
+            messages(receiver, 1)
+        );
+    }
+}
@@ -1,31 +1,52 @@
-use std::fs::File;
-use std::io::BufReader;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
 
+use exif::{self, Tag};
 use image::{self, GenericImageView};
 use jpeg_decoder;
+use yore::{media_format, read_video_metadata, PhotoError};
 
 use super::error::ServiceError;
 use super::orientation::Orientation;
 
-#[derive(Serialize)]
+/// The size that a video's placeholder image is fitted within when it's displayed in full.
+const MAX_PLACEHOLDER_SIZE: u32 = 1024;
+
+#[derive(Clone, Debug, Serialize)]
 pub struct ImageDimensions {
     path: PathBuf,
-    height: u16,
-    width: u16,
+    height: u32,
+    width: u32,
 }
 
 impl ImageDimensions {
     pub fn new(path: &Path) -> Result<ImageDimensions, ServiceError> {
+        let format = image_format(path)?;
+        if format.is_none() {
+            let (width, height) = video_dimensions(path)?;
+
+            return Ok(ImageDimensions {
+                path: path.to_path_buf(),
+                height,
+                width,
+            });
+        }
+
         let orientation = Orientation::read(&path)?;
+        let dimensions = if format == Some(image::ImageFormat::JPEG) {
+            let file = File::open(path)?;
+            let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(&file));
 
-        let file = File::open(path)?;
-        let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(&file));
+            decoder.read_info()?;
 
-        decoder.read_info()?;
+            let image_info = decoder.info().expect("image had no dimensions");
+            (u32::from(image_info.width), u32::from(image_info.height))
+        } else {
+            open_image(path)?.dimensions()
+        };
 
-        let image_info = decoder.info().expect("image had no dimensions");
-        let (width, height) = viewing_dimensions(image_info, orientation);
+        let (width, height) = viewing_dimensions(dimensions, orientation);
 
         Ok(ImageDimensions {
             path: path.to_path_buf(),
@@ -33,6 +54,10 @@ impl ImageDimensions {
             width,
         })
     }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
 }
 
 pub fn thumbnail(path: &Path, max_width: u32, max_height: u32) -> Result<Vec<u8>, ServiceError> {
@@ -40,7 +65,7 @@ pub fn thumbnail(path: &Path, max_width: u32, max_height: u32) -> Result<Vec<u8>
 
     let mut content: Vec<u8> = Vec::new();
 
-    let image = image::open(&path)?;
+    let image = open_thumbnail_source(&path, max_width, max_height)?;
     //TODO: Once image#767 is fixed, just use thumbnail()
     let mut thumbnail = if max_width > image.width() || max_height > image.height() {
         image.resize(max_width, max_height, image::FilterType::Triangle)
@@ -54,8 +79,20 @@ pub fn thumbnail(path: &Path, max_width: u32, max_height: u32) -> Result<Vec<u8>
     Ok(content)
 }
 
+/// The photo as a JPEG that is displayed the right way up. JPEGs that don't need to be
+/// transformed are returned unmodified, as re-encoding them would be slower and lose quality.
+pub fn photo(path: &Path) -> Result<Vec<u8>, ServiceError> {
+    if image_format(path)? == Some(image::ImageFormat::JPEG)
+        && Orientation::read(&path)? == Orientation::Untransformed
+    {
+        fs::read(path).map_err(ServiceError::from)
+    } else {
+        oriented_image(path)
+    }
+}
+
 pub fn oriented_image(path: &Path) -> Result<Vec<u8>, ServiceError> {
-    let image = image::open(&path)?;
+    let image = open_image(&path)?;
     let orientation = Orientation::read(&path)?;
     let image = fix_image_orientation(image, orientation);
 
@@ -65,13 +102,188 @@ pub fn oriented_image(path: &Path) -> Result<Vec<u8>, ServiceError> {
     Ok(content)
 }
 
-fn viewing_dimensions(image_info: jpeg_decoder::ImageInfo, orientation: Orientation) -> (u16, u16) {
+/// The format of the file's image data, or `None` if it is a video, which has none.
+fn image_format(path: &Path) -> Result<Option<image::ImageFormat>, ServiceError> {
+    let format = media_format(path).ok_or(PhotoError::UnsupportedFormat)?;
+
+    Ok(format.image_format())
+}
+
+fn open_image(path: &Path) -> Result<image::DynamicImage, ServiceError> {
+    let format = media_format(path).ok_or(PhotoError::UnsupportedFormat)?;
+
+    if format.image_format().is_none() {
+        video_placeholder(path, MAX_PLACEHOLDER_SIZE, MAX_PLACEHOLDER_SIZE)
+    } else {
+        format
+            .decode_image(&fs::read(path)?)
+            .map_err(ServiceError::from)
+    }
+}
+
+/// Full size photos can be tens of megapixels, so avoid decoding all of a JPEG when a smaller
+/// version of it is enough to create a thumbnail. First try the thumbnail that may be embedded
+/// in its Exif metadata, then decode it at a reduced scale, and only decode it at full size if
+/// neither can be used.
+fn open_thumbnail_source(
+    path: &Path,
+    max_width: u32,
+    max_height: u32,
+) -> Result<image::DynamicImage, ServiceError> {
+    match image_format(path)? {
+        None => return video_placeholder(path, max_width, max_height),
+        Some(image::ImageFormat::JPEG) => {}
+        Some(_) => return open_image(path),
+    }
+
+    let file = File::open(path)?;
+    let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(&file));
+    decoder.read_info()?;
+
+    let dimensions = {
+        let image_info = decoder.info().expect("image had no dimensions");
+        (image_info.width, image_info.height)
+    };
+
+    if let Some(image) = exif_thumbnail(path, dimensions, max_width, max_height) {
+        return Ok(image);
+    }
+
+    match scaled_jpeg(decoder, max_width, max_height)? {
+        Some(image) => Ok(image),
+        None => open_image(path),
+    }
+}
+
+/// Read the thumbnail embedded in a JPEG's Exif metadata, if it has one that is at least as
+/// large as a thumbnail of the full image would be and shows the same area.
+fn exif_thumbnail(
+    path: &Path,
+    dimensions: (u16, u16),
+    max_width: u32,
+    max_height: u32,
+) -> Option<image::DynamicImage> {
+    let file = File::open(path).ok()?;
+    let reader = exif::Reader::new(&mut BufReader::new(&file)).ok()?;
+
+    let offset = reader
+        .get_field(Tag::JPEGInterchangeFormat, true)?
+        .value
+        .get_uint(0)? as usize;
+    let length = reader
+        .get_field(Tag::JPEGInterchangeFormatLength, true)?
+        .value
+        .get_uint(0)? as usize;
+    let data = reader.buf().get(offset..offset.checked_add(length)?)?;
+
+    let image = image::load_from_memory_with_format(data, image::ImageFormat::JPEG).ok()?;
+    let thumbnail_dimensions = (image.width(), image.height());
+
+    if is_usable_thumbnail(dimensions, thumbnail_dimensions, max_width, max_height) {
+        Some(image)
+    } else {
+        None
+    }
+}
+
+/// Embedded thumbnails are often letterboxed to a fixed aspect ratio, so only use one if its
+/// aspect ratio matches the full image's and it wouldn't need to be enlarged.
+fn is_usable_thumbnail(
+    (width, height): (u16, u16),
+    (thumbnail_width, thumbnail_height): (u32, u32),
+    max_width: u32,
+    max_height: u32,
+) -> bool {
+    if width == 0 || height == 0 || thumbnail_width == 0 || thumbnail_height == 0 {
+        return false;
+    }
+
+    let aspect_ratio = f64::from(width) / f64::from(height);
+    let thumbnail_aspect_ratio = f64::from(thumbnail_width) / f64::from(thumbnail_height);
+    if (aspect_ratio - thumbnail_aspect_ratio).abs() > aspect_ratio * 0.01 {
+        return false;
+    }
+
+    let scale = f64::min(
+        f64::from(max_width) / f64::from(width),
+        f64::from(max_height) / f64::from(height),
+    );
+
+    f64::from(thumbnail_width) >= (f64::from(width) * scale).round()
+        && f64::from(thumbnail_height) >= (f64::from(height) * scale).round()
+}
+
+/// Decode a JPEG at the smallest of 1/8, 1/4, 1/2 or full scale that is still at least as large
+/// as the thumbnail, by skipping the higher frequency DCT coefficients. Returns `None` for
+/// CMYK images, which the image crate can't represent.
+fn scaled_jpeg<R: Read>(
+    mut decoder: jpeg_decoder::Decoder<R>,
+    max_width: u32,
+    max_height: u32,
+) -> Result<Option<image::DynamicImage>, ServiceError> {
+    use image::{DynamicImage, ImageBuffer};
+    use jpeg_decoder::PixelFormat;
+
+    let max_width = max_width.min(u32::from(u16::max_value())) as u16;
+    let max_height = max_height.min(u32::from(u16::max_value())) as u16;
+    let (width, height) = decoder.scale(max_width, max_height)?;
+
+    let pixels = decoder.decode()?;
+    let info = decoder.info().expect("image had no dimensions");
+    let (width, height) = (u32::from(width), u32::from(height));
+
+    let image = match info.pixel_format {
+        PixelFormat::L8 => {
+            ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8)
+        }
+        PixelFormat::RGB24 => {
+            ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+        }
+        PixelFormat::CMYK32 => None,
+    };
+
+    Ok(image)
+}
+
+/// The dimensions of a video, which default to 320x240 if they aren't recorded.
+fn video_dimensions(path: &Path) -> Result<(u32, u32), ServiceError> {
+    Ok(read_video_metadata(path)?
+        .dimensions()
+        .unwrap_or((320, 240)))
+}
+
+/// Video frames can't be decoded, so videos are displayed as a blank image with the same aspect
+/// ratio as the video, at the largest size that fits within the given size. The video's own
+/// dimensions aren't used, as they come from the file and may be arbitrarily large.
+fn video_placeholder(
+    path: &Path,
+    max_width: u32,
+    max_height: u32,
+) -> Result<image::DynamicImage, ServiceError> {
+    let (width, height) = fit_within(video_dimensions(path)?, max_width, max_height);
+
+    let buffer = image::ImageBuffer::from_pixel(width, height, image::Rgb([64, 64, 64]));
+
+    Ok(image::DynamicImage::ImageRgb8(buffer))
+}
+
+fn fit_within((width, height): (u32, u32), max_width: u32, max_height: u32) -> (u32, u32) {
+    let (width, height) = (f64::from(width.max(1)), f64::from(height.max(1)));
+    let scale = f64::min(f64::from(max_width) / width, f64::from(max_height) / height);
+
+    (
+        ((width * scale).round() as u32).max(1),
+        ((height * scale).round() as u32).max(1),
+    )
+}
+
+fn viewing_dimensions((width, height): (u32, u32), orientation: Orientation) -> (u32, u32) {
     match orientation {
         Orientation::Untransformed
         | Orientation::FlippedHorizontally
         | Orientation::HalfRotated
-        | Orientation::FlippedVertically => (image_info.width, image_info.height),
-        _ => (image_info.height, image_info.width),
+        | Orientation::FlippedVertically => (width, height),
+        _ => (height, width),
     }
 }
 
@@ -93,10 +305,14 @@ fn fix_image_orientation(
 
 #[cfg(test)]
 mod tests {
+    extern crate tempfile;
+
     use super::*;
 
     use image::{GenericImage, Pixel};
 
+    use self::tempfile::tempdir;
+
     fn test_image_and_pixel() -> (image::DynamicImage, image::Rgba<u8>) {
         let mut img = image::DynamicImage::ImageLuma8(image::ImageBuffer::new(5, 10));
         let pixel = image::Rgba::from_channels(1, 1, 1, 255);
@@ -105,13 +321,7 @@ mod tests {
         (img, pixel)
     }
 
-    fn image_info() -> jpeg_decoder::ImageInfo {
-        jpeg_decoder::ImageInfo {
-            width: 1,
-            height: 2,
-            pixel_format: jpeg_decoder::PixelFormat::L8,
-        }
-    }
+    const DIMENSIONS: (u32, u32) = (1, 2);
 
     #[test]
     fn image_dimensions_new_should_read_image_dimensions() {
@@ -133,6 +343,38 @@ mod tests {
         assert_eq!(50, image.height);
     }
 
+    #[test]
+    fn image_dimensions_new_should_read_video_dimensions() {
+        let path = Path::new("tests/assets/video.mp4");
+        let image = ImageDimensions::new(path).unwrap();
+
+        assert_eq!(path, image.path);
+        assert_eq!(64, image.width);
+        assert_eq!(48, image.height);
+    }
+
+    #[test]
+    fn image_dimensions_new_should_read_png_tiff_and_webp_dimensions() {
+        let image = ImageDimensions::new(Path::new("tests/assets/photo.png")).unwrap();
+        assert_eq!((16, 12), (image.width, image.height));
+
+        let image = ImageDimensions::new(Path::new("tests/assets/photo.tiff")).unwrap();
+        assert_eq!((16, 12), (image.width, image.height));
+
+        let image = ImageDimensions::new(Path::new("tests/assets/photo.webp")).unwrap();
+        assert_eq!((16, 16), (image.width, image.height));
+    }
+
+    #[test]
+    fn image_dimensions_new_should_read_dimensions_that_do_not_fit_in_a_u16() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("panorama.png");
+        image::save_buffer(&path, &[0; 70_000], 70_000, 1, image::ColorType::Gray(8)).unwrap();
+
+        let image = ImageDimensions::new(&path).unwrap();
+        assert_eq!((70_000, 1), (image.width, image.height));
+    }
+
     #[test]
     fn thumbnail_should_scale_image_to_given_width_or_height() {
         let path = Path::new("tests/assets/photo.jpg");
@@ -153,6 +395,152 @@ mod tests {
         assert_eq!(500, image.height());
     }
 
+    #[test]
+    fn thumbnail_should_create_a_placeholder_image_for_a_video() {
+        let path = Path::new("tests/assets/video.mp4");
+        let thumbnail = thumbnail(path, 32, 32).unwrap();
+        let image = image::load_from_memory(&thumbnail).unwrap();
+
+        assert_eq!(32, image.width());
+        assert_eq!(24, image.height());
+    }
+
+    #[test]
+    fn thumbnail_should_create_a_placeholder_at_the_thumbnail_size_not_the_video_size() {
+        let path = Path::new("tests/assets/video.mp4");
+        let thumbnail = thumbnail(path, 200, 100).unwrap();
+        let image = image::load_from_memory(&thumbnail).unwrap();
+
+        assert_eq!(133, image.width());
+        assert_eq!(100, image.height());
+    }
+
+    #[test]
+    fn fit_within_should_scale_dimensions_to_fit_within_the_given_size() {
+        assert_eq!((300, 200), fit_within((6000, 4000), 300, 300));
+        assert_eq!((200, 300), fit_within((4000, 6000), 300, 300));
+        assert_eq!((400, 300), fit_within((64, 48), 400, 400));
+        assert_eq!((1024, 1024), fit_within((65535, 65535), 1024, 1024));
+    }
+
+    #[test]
+    fn fit_within_should_not_produce_an_empty_image() {
+        assert_eq!((1, 300), fit_within((1, 65535), 300, 300));
+        assert_eq!((300, 300), fit_within((0, 0), 300, 300));
+    }
+
+    #[test]
+    fn thumbnail_should_decode_an_extended_format_webp_image() {
+        let path = Path::new("tests/assets/photo.webp");
+        let thumbnail = thumbnail(path, 8, 8).unwrap();
+        let image = image::load_from_memory(&thumbnail).unwrap();
+
+        assert_eq!(8, image.width());
+        assert_eq!(8, image.height());
+    }
+
+    #[test]
+    fn exif_thumbnail_should_read_the_thumbnail_embedded_in_a_jpeg() {
+        let path = Path::new("tests/assets/photo.jpg");
+        let image = exif_thumbnail(path, (640, 480), 100, 100).unwrap();
+
+        assert_eq!(160, image.width());
+        assert_eq!(120, image.height());
+    }
+
+    #[test]
+    fn exif_thumbnail_should_be_none_if_its_aspect_ratio_differs_from_the_image() {
+        let path = Path::new("tests/assets/photo.jpg");
+
+        assert!(exif_thumbnail(path, (55, 37), 10, 10).is_none());
+    }
+
+    #[test]
+    fn exif_thumbnail_should_be_none_if_the_jpeg_has_no_exif_metadata() {
+        let path = Path::new("tests/assets/photo_without_exif.jpg");
+
+        assert!(exif_thumbnail(path, (55, 37), 10, 10).is_none());
+    }
+
+    #[test]
+    fn is_usable_thumbnail_should_be_true_if_the_thumbnail_would_not_be_enlarged() {
+        assert!(is_usable_thumbnail((6000, 4000), (300, 200), 300, 300));
+        assert!(is_usable_thumbnail((6000, 4000), (640, 427), 300, 300));
+        assert!(is_usable_thumbnail((4000, 6000), (200, 300), 300, 300));
+    }
+
+    #[test]
+    fn is_usable_thumbnail_should_be_false_if_the_thumbnail_is_too_small() {
+        assert!(!is_usable_thumbnail((6000, 4000), (160, 107), 300, 300));
+        assert!(!is_usable_thumbnail((6000, 4000), (300, 200), 600, 600));
+    }
+
+    #[test]
+    fn is_usable_thumbnail_should_be_false_if_the_thumbnail_is_letterboxed() {
+        assert!(!is_usable_thumbnail((6000, 4000), (640, 480), 300, 300));
+    }
+
+    #[test]
+    fn scaled_jpeg_should_decode_at_the_smallest_scale_that_fits_the_thumbnail() {
+        let file = File::open("tests/assets/photo.jpg").unwrap();
+        let decoder = jpeg_decoder::Decoder::new(BufReader::new(&file));
+        let image = scaled_jpeg(decoder, 8, 8).unwrap().unwrap();
+
+        assert_eq!(14, image.width());
+        assert_eq!(10, image.height());
+    }
+
+    #[test]
+    fn scaled_jpeg_should_decode_at_full_scale_if_the_thumbnail_is_larger() {
+        let file = File::open("tests/assets/photo.jpg").unwrap();
+        let decoder = jpeg_decoder::Decoder::new(BufReader::new(&file));
+        let image = scaled_jpeg(decoder, 500, 500).unwrap().unwrap();
+
+        assert_eq!(55, image.width());
+        assert_eq!(37, image.height());
+    }
+
+    /// Compare creating thumbnails by fully decoding each JPEG with the faster sources used by
+    /// `thumbnail()`. Run with
+    /// `YORE_BENCH_DIR=<folder of JPEGs> cargo test --release --bin yore thumbnail_benchmark -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn thumbnail_benchmark() {
+        use std::env;
+        use std::time::{Duration, Instant};
+
+        let dir = env::var("YORE_BENCH_DIR").expect("YORE_BENCH_DIR should be set");
+        let paths: Vec<PathBuf> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| image_format(path).ok() == Some(Some(image::ImageFormat::JPEG)))
+            .collect();
+
+        let mut full_decode_time = Duration::from_secs(0);
+        let mut thumbnail_time = Duration::from_secs(0);
+        for path in &paths {
+            let start = Instant::now();
+            let image = open_image(path).unwrap();
+            let mut content: Vec<u8> = Vec::new();
+            image
+                .thumbnail(300, 300)
+                .write_to(&mut content, image::ImageFormat::JPEG)
+                .unwrap();
+            full_decode_time += start.elapsed();
+
+            let start = Instant::now();
+            thumbnail(path, 300, 300).unwrap();
+            thumbnail_time += start.elapsed();
+        }
+
+        println!(
+            "{} JPEGs: full decode {:?}, thumbnail() {:?}",
+            paths.len(),
+            full_decode_time,
+            thumbnail_time
+        );
+    }
+
     #[test]
     fn oriented_image_should_rotate_image_according_to_its_orientation() {
         let path = Path::new("tests/assets/photo_rotated.jpg");
@@ -164,54 +552,71 @@ mod tests {
     }
 
     #[test]
-    fn viewing_dimensions_should_be_unchanged_if_orientation_has_same_axes() {
-        let image_info = image_info();
+    fn photo_should_be_the_original_file_if_it_is_an_untransformed_jpeg() {
+        let path = Path::new("tests/assets/photo_without_orientation.jpg");
+
+        assert_eq!(fs::read(path).unwrap(), photo(path).unwrap());
+    }
+
+    #[test]
+    fn photo_should_be_the_oriented_image_if_it_needs_to_be_transformed() {
+        let path = Path::new("tests/assets/photo_rotated.jpg");
 
-        let (width, height) = viewing_dimensions(image_info.clone(), Orientation::Untransformed);
-        assert_eq!(image_info.height, height);
-        assert_eq!(image_info.width, width);
+        assert_eq!(oriented_image(path).unwrap(), photo(path).unwrap());
+    }
+
+    #[test]
+    fn photo_should_be_the_oriented_image_if_it_is_not_a_jpeg() {
+        let path = Path::new("tests/assets/photo.png");
+
+        assert_eq!(oriented_image(path).unwrap(), photo(path).unwrap());
+    }
+
+    #[test]
+    fn viewing_dimensions_should_be_unchanged_if_orientation_has_same_axes() {
+        let (width, height) = viewing_dimensions(DIMENSIONS, Orientation::Untransformed);
+        assert_eq!(DIMENSIONS.1, height);
+        assert_eq!(DIMENSIONS.0, width);
 
         let (width, height) =
-            viewing_dimensions(image_info.clone(), Orientation::FlippedHorizontally);
-        assert_eq!(image_info.height, height);
-        assert_eq!(image_info.width, width);
+            viewing_dimensions(DIMENSIONS, Orientation::FlippedHorizontally);
+        assert_eq!(DIMENSIONS.1, height);
+        assert_eq!(DIMENSIONS.0, width);
 
-        let (width, height) = viewing_dimensions(image_info.clone(), Orientation::HalfRotated);
-        assert_eq!(image_info.height, height);
-        assert_eq!(image_info.width, width);
+        let (width, height) = viewing_dimensions(DIMENSIONS, Orientation::HalfRotated);
+        assert_eq!(DIMENSIONS.1, height);
+        assert_eq!(DIMENSIONS.0, width);
 
         let (width, height) =
-            viewing_dimensions(image_info.clone(), Orientation::FlippedVertically);
-        assert_eq!(image_info.height, height);
-        assert_eq!(image_info.width, width);
+            viewing_dimensions(DIMENSIONS, Orientation::FlippedVertically);
+        assert_eq!(DIMENSIONS.1, height);
+        assert_eq!(DIMENSIONS.0, width);
     }
 
     #[test]
     fn viewing_dimensions_should_flip_width_and_height_if_orientation_has_flipped_axes() {
-        let image_info = image_info();
-
         let (width, height) = viewing_dimensions(
-            image_info.clone(),
+            DIMENSIONS,
             Orientation::QuarterRotatedAndFlippedHorizontally,
         );
-        assert_eq!(image_info.width, height);
-        assert_eq!(image_info.height, width);
+        assert_eq!(DIMENSIONS.0, height);
+        assert_eq!(DIMENSIONS.1, width);
 
         let (width, height) =
-            viewing_dimensions(image_info.clone(), Orientation::ThreeQuarterRotated);
-        assert_eq!(image_info.width, height);
-        assert_eq!(image_info.height, width);
+            viewing_dimensions(DIMENSIONS, Orientation::ThreeQuarterRotated);
+        assert_eq!(DIMENSIONS.0, height);
+        assert_eq!(DIMENSIONS.1, width);
 
         let (width, height) = viewing_dimensions(
-            image_info.clone(),
+            DIMENSIONS,
             Orientation::QuarterRotatedAndFlippedVertically,
         );
-        assert_eq!(image_info.width, height);
-        assert_eq!(image_info.height, width);
+        assert_eq!(DIMENSIONS.0, height);
+        assert_eq!(DIMENSIONS.1, width);
 
-        let (width, height) = viewing_dimensions(image_info.clone(), Orientation::QuarterRotated);
-        assert_eq!(image_info.width, height);
-        assert_eq!(image_info.height, width);
+        let (width, height) = viewing_dimensions(DIMENSIONS, Orientation::QuarterRotated);
+        assert_eq!(DIMENSIONS.0, height);
+        assert_eq!(DIMENSIONS.1, width);
     }
 
     #[test]
@@ -1,26 +1,40 @@
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{mpsc, Arc, RwLock};
 use std::thread;
 
 use actix_web::server;
 use directories::ProjectDirs;
+use yore::{Journal, ReverseGeocoder, TimestampFallback, WriteOptions};
 
-use common::ApplicationError;
+use common::{journal_path, ApplicationError};
 
 mod actix;
+mod bulk_save;
+mod cache;
 mod error;
+mod events;
 mod image;
 mod orientation;
 mod responses;
+mod scan;
 mod state;
+mod summary;
+mod tiles;
+mod watch;
 
 use self::actix::build_server_app;
+pub use self::image::thumbnail;
+use self::scan::spawn_scan;
 use self::state::GuiState;
+pub use self::tiles::TileError;
+use self::tiles::TileSource;
+use self::watch::watch_in_background;
 
 pub struct Server {
     address: SocketAddr,
     state: GuiState,
+    photos_path: Option<PathBuf>,
 }
 
 impl Server {
@@ -28,14 +42,46 @@ impl Server {
         let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port);
         let project_dir =
             ProjectDirs::from("", "", "Yore").expect("project directory to be calculable");
-        let mut state = GuiState::new(project_dir.cache_dir());
+        let journal = Journal::new(&journal_path());
+        let mut state = GuiState::new(project_dir.cache_dir(), journal);
         state.set_interpolate(interpolate);
 
-        Server { address, state }
+        Server {
+            address,
+            state,
+            photos_path: None,
+        }
     }
 
+    pub fn set_copy_from_photos(&mut self, copy_from_photos: bool) {
+        self.state.set_copy_from_photos(copy_from_photos);
+    }
+
+    pub fn set_timestamp_fallbacks(&mut self, timestamp_fallbacks: Vec<TimestampFallback>) {
+        self.state.set_timestamp_fallbacks(timestamp_fallbacks);
+    }
+
+    pub fn set_write_options(&mut self, write_options: WriteOptions) {
+        self.state.set_write_options(write_options);
+    }
+
+    /// Save the names of the place nearest to each location along with it. Has no effect unless
+    /// places have been loaded.
+    pub fn set_write_place_names(&mut self, write_place_names: bool) {
+        self.state.set_write_place_names(write_place_names);
+    }
+
+    /// Limit the total size of the thumbnails cached on disk, removing the least recently used
+    /// thumbnails until they fit.
+    pub fn set_max_cache_size(&mut self, max_size: u64) -> Result<(), ApplicationError> {
+        self.state
+            .set_max_cache_size(max_size)
+            .map_err(ApplicationError::from)
+    }
+
+    /// Search the given path for photos in the background once the server starts.
     pub fn search_photos_path(&mut self, path: &Path) {
-        self.state.search_new_root_path(path.to_path_buf());
+        self.photos_path = Some(path.to_path_buf());
     }
 
     pub fn load_location_history(&mut self, path: &Path) -> Result<(), ApplicationError> {
@@ -44,14 +90,27 @@ impl Server {
             .map_err(ApplicationError::from)
     }
 
+    /// Name the places that locations are in using the GeoNames cities file at the given path.
+    pub fn load_places(&mut self, path: &Path) -> Result<(), ApplicationError> {
+        let geocoder = ReverseGeocoder::load(path)?;
+        self.state.set_geocoder(Some(geocoder));
+        Ok(())
+    }
+
+    /// Serve map tiles from the MBTiles file or tile directory at the given path, so that the
+    /// GUI's map works offline.
+    pub fn set_tile_source(&mut self, path: &Path) -> Result<(), ApplicationError> {
+        let tile_source = TileSource::new(path)?;
+        self.state.set_tile_source(Some(tile_source));
+        Ok(())
+    }
+
     #[allow(dead_code)]
     pub fn run(self) -> Result<(), ApplicationError> {
-        self.state.clear_cache()?;
+        let address = self.address;
+        let shared_state = self.start_scan();
 
-        let shared_state = Arc::new(RwLock::new(self.state));
-
-        let server =
-            server::new(move || build_server_app(shared_state.clone())).bind(&self.address)?;
+        let server = server::new(move || build_server_app(shared_state.clone())).bind(&address)?;
 
         println!("Listening on http://{}", server.addrs()[0]);
 
@@ -61,16 +120,15 @@ impl Server {
 
     #[allow(dead_code)]
     pub fn spawn(self) -> Result<SocketAddr, ApplicationError> {
-        self.state.clear_cache()?;
-
         let (tx, rx) = mpsc::channel();
 
-        thread::spawn(move || {
-            let shared_state = Arc::new(RwLock::new(self.state));
+        let address = self.address;
+        let shared_state = self.start_scan();
 
+        thread::spawn(move || {
             let server = server::new(move || build_server_app(shared_state.clone()))
-                .bind(&self.address)
-                .expect(&format!("Failed to bind HTTP server to {}", self.address));
+                .bind(&address)
+                .expect(&format!("Failed to bind HTTP server to {}", address));
 
             tx.send(server.addrs()[0])
                 .expect("Failed to send the server's listen address to the main thread");
@@ -86,6 +144,27 @@ impl Server {
 
         Ok(address)
     }
+
+    /// Share the GUI state with the server, then scan for photos in the background so that
+    /// the GUI can be used while they're found, and watch for changes to them and the location
+    /// history.
+    fn start_scan(self) -> Arc<RwLock<GuiState>> {
+        let mut state = self.state;
+        let scan = self
+            .photos_path
+            .map(|path| (state.start_scan(path.clone()), path));
+
+        let shared_state = Arc::new(RwLock::new(state));
+        if let Some((scan_id, path)) = scan {
+            spawn_scan(&shared_state, scan_id, path);
+        }
+
+        if let Err(e) = watch_in_background(&shared_state) {
+            eprintln!("Error watching for file changes: {}", e);
+        }
+
+        shared_state
+    }
 }
 
 #[cfg(test)]
@@ -1,8 +1,7 @@
-use std::fs::File;
-use std::io::BufReader;
 use std::path::Path;
 
 use exif;
+use yore::{read_metadata, PhotoError};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Orientation {
@@ -17,24 +16,19 @@ pub enum Orientation {
 }
 
 impl Orientation {
-    pub fn read(path: &Path) -> Result<Orientation, exif::Error> {
-        let file = File::open(path)?;
-
-        let reader = match exif::Reader::new(&mut BufReader::new(&file)) {
+    pub fn read(path: &Path) -> Result<Orientation, PhotoError> {
+        let metadata = match read_metadata(path) {
             Ok(x) => x,
-            Err(exif::Error::NotFound(_)) => return Ok(Orientation::Untransformed),
+            Err(PhotoError::ExifError(exif::Error::NotFound(_))) => {
+                return Ok(Orientation::Untransformed)
+            }
             Err(e) => return Err(e),
         };
 
-        let orientation = reader
-            .get_field(exif::Tag::Orientation, false)
-            .map(|field| {
-                if let exif::Value::Short(ref x) = field.value {
-                    Orientation::from_exif_value(x[0])
-                } else {
-                    Orientation::Untransformed
-                }
-            }).unwrap_or(Orientation::Untransformed);
+        let orientation = metadata
+            .orientation()
+            .map(Orientation::from_exif_value)
+            .unwrap_or(Orientation::Untransformed);
 
         Ok(orientation)
     }
@@ -81,6 +75,22 @@ mod tests {
         assert_eq!(Orientation::QuarterRotated, orientation);
     }
 
+    #[test]
+    fn read_should_return_untransformed_for_a_png_without_orientation_metadata() {
+        let path = Path::new("tests/assets/photo.png");
+        let orientation = Orientation::read(path).unwrap();
+
+        assert_eq!(Orientation::Untransformed, orientation);
+    }
+
+    #[test]
+    fn read_should_return_untransformed_for_a_video() {
+        let path = Path::new("tests/assets/video.mp4");
+        let orientation = Orientation::read(path).unwrap();
+
+        assert_eq!(Orientation::Untransformed, orientation);
+    }
+
     #[test]
     fn from_exif_value_should_construct_from_u16_values_correctly() {
         assert_eq!(Orientation::Untransformed, Orientation::from_exif_value(0));
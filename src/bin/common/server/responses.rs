@@ -1,8 +1,10 @@
+use std::cmp::Ordering;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use rayon::prelude::*;
-use yore::get_location_suggestion;
-use yore::{Photo, PhotoLocation};
+use yore::{get_location_suggestion, suggest_location};
+use yore::{Coordinates, JournalEntry, Photo, PhotoLocation};
 
 use super::error::ServiceError;
 use super::image::ImageDimensions;
@@ -52,36 +54,404 @@ impl InterpolateResponse {
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyFromPhotosResponse {
+    copy_from_photos: bool,
+}
+
+impl CopyFromPhotosResponse {
+    pub fn new(state: &GuiState) -> CopyFromPhotosResponse {
+        CopyFromPhotosResponse {
+            copy_from_photos: state.copy_from_photos(),
+        }
+    }
+}
+
+/// Whether the server has a source of map tiles, so the GUI can use them instead of an online
+/// map.
+#[derive(Serialize)]
+pub struct TilesResponse {
+    available: bool,
+}
+
+impl TilesResponse {
+    pub fn new(state: &GuiState) -> TilesResponse {
+        TilesResponse {
+            available: state.tile_source().is_some(),
+        }
+    }
+}
+
+/// Which photos to respond with, in what order and how many of them at a time. Photos are
+/// in the order they were found unless sorted, and sorting by timestamp or accuracy puts photos
+/// without one last. Dates are Unix timestamps in milliseconds, from inclusive and to exclusive,
+/// and the bounding box is `minLongitude,minLatitude,maxLongitude,maxLatitude` as in GeoJSON.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhotosQuery {
+    filter: Option<bool>,
+    status: Option<LocationStatus>,
+    from: Option<i64>,
+    to: Option<i64>,
+    camera: Option<String>,
+    bbox: Option<String>,
+    sort: Option<PhotoSort>,
+    order: Option<SortOrder>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LocationStatus {
+    Existing,
+    Suggested,
+    None,
+    Error,
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PhotoSort {
+    Path,
+    Timestamp,
+    Accuracy,
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+struct BoundingBox {
+    min_longitude: f64,
+    min_latitude: f64,
+    max_longitude: f64,
+    max_latitude: f64,
+}
+
+/// The metadata and location of a photo that photos can be filtered and sorted by.
+#[derive(Default)]
+pub struct PhotoSummary {
+    status: Option<LocationStatus>,
+    timestamp_ms: Option<i64>,
+    camera_model: Option<String>,
+    coordinates: Option<Coordinates>,
+    accuracy: Option<(i64, i64)>,
+}
+
+/// Where a photo comes in the sort order. Photos with the same value are sorted by path, so
+/// that every photo has a different key that a cursor can hold.
+#[derive(Debug, PartialEq)]
+struct SortKey {
+    value: Option<(i64, i64)>,
+    path: PathBuf,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PhotosResponse {
     photos: Vec<ImageDimensions>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
 }
 
 impl PhotosResponse {
-    pub fn new(state: &GuiState) -> Result<PhotosResponse, ServiceError> {
-        state
-            .photo_paths()
+    pub fn new(state: &GuiState) -> PhotosResponse {
+        PhotosResponse {
+            photos: state.photos().to_vec(),
+            next_cursor: None,
+        }
+    }
+
+    /// Respond with the page of photos that match the query and come after its cursor, along
+    /// with the cursor for the next page if there are more.
+    pub fn query(state: &GuiState, query: &PhotosQuery) -> Result<PhotosResponse, ServiceError> {
+        let bounding_box = query.bounding_box()?;
+        let cursor = query.cursor()?;
+        if query.limit == Some(0) {
+            return Err(ServiceError::InvalidQuery(
+                "The limit must be greater than zero".to_string(),
+            ));
+        }
+
+        let only_suggested = query.filter == Some(true);
+
+        let mut photos: Vec<(SortKey, ImageDimensions)> = state
+            .photos()
             .par_iter()
-            .map(|path| ImageDimensions::new(path))
-            .collect::<Result<Vec<ImageDimensions>, ServiceError>>()
-            .map(|photos| PhotosResponse { photos })
+            .enumerate()
+            .filter_map(|(index, photo)| {
+                let summary = if only_suggested || query.needs_summary() {
+                    PhotoSummary::cached(photo.path(), state)
+                } else {
+                    Arc::new(PhotoSummary::default())
+                };
+
+                if only_suggested && !summary.is_suggested() {
+                    None
+                } else if query.matches(&summary, bounding_box.as_ref()) {
+                    Some((query.sort_key(index, &summary, photo.path()), photo.clone()))
+                } else {
+                    None
+                }
+            }).collect();
+
+        photos.par_sort_by(|a, b| query.compare(&a.0, &b.0));
+
+        let start = match cursor {
+            Some(ref cursor) => photos
+                .iter()
+                .position(|(key, _)| query.compare(key, cursor) == Ordering::Greater)
+                .unwrap_or(photos.len()),
+            None => 0,
+        };
+        let end = query
+            .limit
+            .map(|limit| photos.len().min(start.saturating_add(limit)))
+            .unwrap_or_else(|| photos.len());
+
+        let next_cursor = if end < photos.len() {
+            Some(photos[end - 1].0.to_cursor())
+        } else {
+            None
+        };
+
+        Ok(PhotosResponse {
+            photos: photos.drain(start..end).map(|(_, photo)| photo).collect(),
+            next_cursor,
+        })
     }
 
-    pub fn filtered(state: &GuiState) -> Result<PhotosResponse, ServiceError> {
-        state
-            .photo_paths()
+    pub fn filtered(state: &GuiState) -> PhotosResponse {
+        let photos = state
+            .photos()
             .par_iter()
-            .filter_map(|path| {
-                Photo::new(path).ok().and_then(|photo| {
-                    if photo.gps_coordinates().is_some() {
-                        None
-                    } else if state.location_history().contains(photo.timestamp()) {
-                        Some(ImageDimensions::new(path))
-                    } else {
-                        None
-                    }
-                })
-            }).collect::<Result<Vec<ImageDimensions>, ServiceError>>()
-            .map(|photos| PhotosResponse { photos })
+            .filter(|photo| PhotoSummary::cached(photo.path(), state).is_suggested())
+            .cloned()
+            .collect();
+
+        PhotosResponse {
+            photos,
+            next_cursor: None,
+        }
+    }
+}
+
+impl PhotosQuery {
+    fn bounding_box(&self) -> Result<Option<BoundingBox>, ServiceError> {
+        match self.bbox {
+            Some(ref bbox) => BoundingBox::parse(bbox).map(Some).ok_or_else(|| {
+                ServiceError::InvalidQuery(format!("{} is not a valid bounding box", bbox))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    fn cursor(&self) -> Result<Option<SortKey>, ServiceError> {
+        match self.cursor {
+            Some(ref cursor) => SortKey::from_cursor(cursor).map(Some).ok_or_else(|| {
+                ServiceError::InvalidQuery(format!("{} is not a valid cursor", cursor))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Reading every photo's metadata is slow for large libraries, so only do it if the query
+    /// needs it.
+    fn needs_summary(&self) -> bool {
+        self.status.is_some()
+            || self.from.is_some()
+            || self.to.is_some()
+            || self.camera.is_some()
+            || self.bbox.is_some()
+            || self.sort == Some(PhotoSort::Timestamp)
+            || self.sort == Some(PhotoSort::Accuracy)
+    }
+
+    fn matches(&self, summary: &PhotoSummary, bounding_box: Option<&BoundingBox>) -> bool {
+        if self.status.is_some() && self.status != summary.status {
+            return false;
+        }
+
+        if self.from.is_some() || self.to.is_some() {
+            let is_in_range = summary.timestamp_ms.is_some_and(|timestamp_ms| {
+                self.from.is_none_or(|from| timestamp_ms >= from)
+                    && self.to.is_none_or(|to| timestamp_ms < to)
+            });
+            if !is_in_range {
+                return false;
+            }
+        }
+
+        if let Some(ref camera) = self.camera {
+            let is_camera = summary
+                .camera_model
+                .as_ref()
+                .is_some_and(|model| model.eq_ignore_ascii_case(camera.trim()));
+            if !is_camera {
+                return false;
+            }
+        }
+
+        match bounding_box {
+            Some(bounding_box) => summary
+                .coordinates
+                .as_ref()
+                .is_some_and(|coordinates| bounding_box.contains(coordinates)),
+            None => true,
+        }
+    }
+
+    fn sort_key(&self, index: usize, summary: &PhotoSummary, path: &Path) -> SortKey {
+        let value = match self.sort {
+            None => Some((index as i64, 0)),
+            Some(PhotoSort::Path) => None,
+            Some(PhotoSort::Timestamp) => {
+                summary.timestamp_ms.map(|timestamp_ms| (timestamp_ms, 0))
+            }
+            Some(PhotoSort::Accuracy) => summary.accuracy,
+        };
+
+        SortKey {
+            value,
+            path: path.to_path_buf(),
+        }
+    }
+
+    fn compare(&self, a: &SortKey, b: &SortKey) -> Ordering {
+        let ordering = match (a.value, b.value) {
+            (Some(a_value), Some(b_value)) => a_value.cmp(&b_value),
+            (Some(_), None) => return Ordering::Less,
+            (None, Some(_)) => return Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }.then_with(|| a.path.cmp(&b.path));
+
+        match self.order {
+            Some(SortOrder::Desc) => ordering.reverse(),
+            _ => ordering,
+        }
+    }
+}
+
+impl BoundingBox {
+    fn parse(bbox: &str) -> Option<BoundingBox> {
+        let values = bbox
+            .split(',')
+            .map(|value| value.trim().parse::<f64>().ok())
+            .collect::<Option<Vec<f64>>>()?;
+
+        if values.len() != 4 {
+            return None;
+        }
+
+        let bounding_box = BoundingBox {
+            min_longitude: values[0],
+            min_latitude: values[1],
+            max_longitude: values[2],
+            max_latitude: values[3],
+        };
+
+        let is_valid = Coordinates::new(bounding_box.min_latitude, bounding_box.min_longitude)
+            .is_valid()
+            && Coordinates::new(bounding_box.max_latitude, bounding_box.max_longitude).is_valid()
+            && bounding_box.min_latitude <= bounding_box.max_latitude;
+
+        if is_valid {
+            Some(bounding_box)
+        } else {
+            None
+        }
+    }
+
+    /// A box whose minimum longitude is greater than its maximum crosses the antimeridian.
+    fn contains(&self, coordinates: &Coordinates) -> bool {
+        let latitude = coordinates.latitude();
+        let longitude = coordinates.longitude();
+
+        let is_within_longitudes = if self.min_longitude <= self.max_longitude {
+            longitude >= self.min_longitude && longitude <= self.max_longitude
+        } else {
+            longitude >= self.min_longitude || longitude <= self.max_longitude
+        };
+
+        is_within_longitudes && latitude >= self.min_latitude && latitude <= self.max_latitude
+    }
+}
+
+impl PhotoSummary {
+    /// The photo's summary from the state's cache, which is created if it isn't cached.
+    fn cached(path: &Path, state: &GuiState) -> Arc<PhotoSummary> {
+        state
+            .summary_cache()
+            .get_or_insert_with(path, || PhotoSummary::new(path, state))
+    }
+
+    fn is_suggested(&self) -> bool {
+        self.status == Some(LocationStatus::Suggested)
+    }
+
+    fn new(path: &Path, state: &GuiState) -> PhotoSummary {
+        let photo = match Photo::with_fallbacks(path, state.timestamp_fallbacks()) {
+            Ok(photo) => photo,
+            Err(_) => {
+                return PhotoSummary {
+                    status: Some(LocationStatus::Error),
+                    ..PhotoSummary::default()
+                }
+            }
+        };
+
+        let location = suggest_location(&photo, state.location_history(), state.interpolate());
+        let (status, coordinates, accuracy) = match location {
+            PhotoLocation::Existing(coordinates) => {
+                (LocationStatus::Existing, Some(coordinates), None)
+            }
+            PhotoLocation::Suggested(coordinates, accuracy) => (
+                LocationStatus::Suggested,
+                Some(coordinates),
+                Some((i64::from(accuracy.meters()), accuracy.seconds().abs())),
+            ),
+            PhotoLocation::None => (LocationStatus::None, None, None),
+        };
+
+        PhotoSummary {
+            status: Some(status),
+            timestamp_ms: Some(photo.timestamp_ms()),
+            camera_model: photo.camera_model().map(str::to_string),
+            coordinates,
+            accuracy,
+        }
+    }
+}
+
+/// Cursors are `value:tiebreak:path`, with an empty value and tiebreak if the photo has no
+/// value to sort by. Without a sort the value is the photo's index in the order photos were
+/// found, so a cursor may skip or repeat a photo if photos are removed between requests.
+impl SortKey {
+    fn to_cursor(&self) -> String {
+        match self.value {
+            Some((value, tiebreak)) => format!("{}:{}:{}", value, tiebreak, self.path.display()),
+            None => format!("::{}", self.path.display()),
+        }
+    }
+
+    fn from_cursor(cursor: &str) -> Option<SortKey> {
+        let mut parts = cursor.splitn(3, ':');
+        let value = parts.next()?;
+        let tiebreak = parts.next()?;
+        let path = PathBuf::from(parts.next()?);
+
+        let value = if value.is_empty() && tiebreak.is_empty() {
+            None
+        } else {
+            Some((value.parse().ok()?, tiebreak.parse().ok()?))
+        };
+
+        Some(SortKey { value, path })
     }
 }
 
@@ -117,14 +487,24 @@ pub struct LocationResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     location: Option<PhotoLocation>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    place: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
+
+    #[serde(rename = "userSet", skip_serializing_if = "is_false")]
+    user_set: bool,
 }
 
 impl LocationResponse {
     pub fn new(path: &Path, state: &GuiState) -> Result<LocationResponse, ServiceError> {
-        let result = get_location_suggestion(path, &state.location_history(), state.interpolate())
-            .map_err(|e| format!("{}", e));
+        let result = get_location_suggestion(
+            path,
+            &state.location_history(),
+            state.interpolate(),
+            state.timestamp_fallbacks(),
+        ).map_err(|e| format!("{}", e));
 
         let (location, error) = match result {
             Ok(PhotoLocation::None) => (None, None),
@@ -132,14 +512,157 @@ impl LocationResponse {
             Err(e) => (None, Some(e)),
         };
 
+        let user_set = match location {
+            Some(PhotoLocation::Existing(_)) => state.is_user_set(path),
+            _ => false,
+        };
+
+        let coordinates = match location {
+            Some(PhotoLocation::Existing(ref coordinates)) => Some(coordinates),
+            Some(PhotoLocation::Suggested(ref coordinates, _)) => Some(coordinates),
+            _ => None,
+        };
+        let place = match (state.geocoder(), coordinates) {
+            (Some(geocoder), Some(coordinates)) => geocoder
+                .nearest(coordinates)
+                .map(|(place, _)| place.to_string()),
+            _ => None,
+        };
+
         Ok(LocationResponse {
             path: path.to_path_buf(),
             location,
+            place,
             error,
+            user_set,
         })
     }
 }
 
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// The location history data points in a time window around a photo's timestamp, as a GeoJSON
+/// feature collection. Each data point is a point feature with its timestamp, its offset from
+/// the photo's timestamp and the radius of its accuracy circle in meters, and if there are at
+/// least two data points they are also joined in time order by a line string feature.
+#[derive(Serialize)]
+pub struct TrackResponse {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    features: Vec<TrackFeature>,
+}
+
+#[derive(Serialize)]
+struct TrackFeature {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    geometry: TrackGeometry,
+    properties: TrackProperties,
+}
+
+/// GeoJSON positions are longitude then latitude.
+#[derive(Serialize)]
+#[serde(tag = "type", content = "coordinates")]
+enum TrackGeometry {
+    Point([f64; 2]),
+    LineString(Vec<[f64; 2]>),
+}
+
+#[derive(Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TrackProperties {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp_ms: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset_seconds: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accuracy: Option<u32>,
+}
+
+impl TrackFeature {
+    fn new(geometry: TrackGeometry, properties: TrackProperties) -> TrackFeature {
+        TrackFeature {
+            kind: "Feature",
+            geometry,
+            properties,
+        }
+    }
+}
+
+impl TrackResponse {
+    pub fn new(
+        path: &Path,
+        window_seconds: u32,
+        state: &GuiState,
+    ) -> Result<TrackResponse, ServiceError> {
+        let photo = Photo::with_fallbacks(path, state.timestamp_fallbacks())?;
+        let timestamp_ms = photo.timestamp_ms();
+        let window_ms = i64::from(window_seconds) * 1000;
+
+        let locations = state
+            .location_history()
+            .locations_between(timestamp_ms - window_ms, timestamp_ms + window_ms);
+
+        let positions: Vec<[f64; 2]> = locations
+            .iter()
+            .map(|location| {
+                let coordinates = location.coordinates();
+                [coordinates.longitude(), coordinates.latitude()]
+            }).collect();
+
+        let mut features: Vec<TrackFeature> = Vec::with_capacity(locations.len() + 1);
+        if positions.len() > 1 {
+            features.push(TrackFeature::new(
+                TrackGeometry::LineString(positions.clone()),
+                TrackProperties::default(),
+            ));
+        }
+
+        for (location, position) in locations.iter().zip(positions) {
+            features.push(TrackFeature::new(
+                TrackGeometry::Point(position),
+                TrackProperties {
+                    timestamp_ms: Some(location.timestamp_ms()),
+                    offset_seconds: Some((location.timestamp_ms() - timestamp_ms) / 1000),
+                    accuracy: Some(location.accuracy()),
+                },
+            ));
+        }
+
+        Ok(TrackResponse {
+            kind: "FeatureCollection",
+            features,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct UndoResponse {
+    undone: Vec<JournalEntry>,
+}
+
+impl UndoResponse {
+    pub fn new(
+        state: &mut GuiState,
+        session: Option<String>,
+    ) -> Result<UndoResponse, ServiceError> {
+        let undone = match session {
+            Some(session) => state.journal().rollback(&session)?,
+            None => state.journal().undo_last()?.into_iter().collect(),
+        };
+
+        for entry in &undone {
+            state.set_user_set(entry.path(), false);
+        }
+
+        Ok(UndoResponse { undone })
+    }
+}
+
 #[cfg(feature = "filesystem-serve")]
 pub fn read_file_bytes(path: &Path) -> Result<Vec<u8>, ServiceError> {
     use std::fs::File;
@@ -183,9 +706,14 @@ mod tests {
     use std::env::temp_dir;
 
     use serde_json::to_string;
+    use yore::{Journal, ReverseGeocoder, TimestampFallback};
+
+    fn new_state() -> GuiState {
+        GuiState::new(&temp_dir(), Journal::new(&temp_dir().join("journal")))
+    }
 
     fn state_with_root_path(root_path: &Path) -> GuiState {
-        let mut state = GuiState::new(&temp_dir());
+        let mut state = new_state();
         state.search_new_root_path(root_path.to_path_buf());
         state
     }
@@ -222,21 +750,48 @@ mod tests {
 
     #[test]
     fn interpolate_response_new_should_get_the_root_path() {
-        let state = GuiState::new(&temp_dir());
+        let state = new_state();
         let response = InterpolateResponse::new(&state);
 
         assert_eq!(state.interpolate(), response.interpolate);
     }
 
+    #[test]
+    fn copy_from_photos_response_new_should_get_the_copy_from_photos_state() {
+        let mut state = new_state();
+        state.set_copy_from_photos(true);
+        let response = CopyFromPhotosResponse::new(&state);
+
+        assert!(response.copy_from_photos);
+    }
+
+    #[test]
+    fn tiles_response_new_should_be_unavailable_if_there_is_no_tile_source() {
+        let state = new_state();
+        let response = TilesResponse::new(&state);
+
+        assert!(!response.available);
+    }
+
     #[test]
     fn photos_response_new_should_get_data_for_all_found_photos() {
         let state = state_with_root_path(Path::new("tests/assets"));
-        let response = PhotosResponse::new(&state).unwrap();
+        let response = PhotosResponse::new(&state);
 
         assert_eq!(
             "{\"photos\":[\
              {\"path\":\"tests/assets/photo.jpg\",\"height\":37,\"width\":55},\
+             {\"path\":\"tests/assets/photo.png\",\"height\":12,\"width\":16},\
+             {\"path\":\"tests/assets/photo.tiff\",\"height\":12,\"width\":16},\
+             {\"path\":\"tests/assets/photo.webp\",\"height\":16,\"width\":16},\
              {\"path\":\"tests/assets/photo_rotated.jpg\",\"height\":50,\"width\":33},\
+             {\"path\":\"tests/assets/photo_with_gps_time.jpg\",\"height\":37,\"width\":55},\
+             {\"path\":\"tests/assets/photo_with_subsec_time.jpg\",\"height\":37,\"width\":55},\
+             {\
+             \"path\":\"tests/assets/photo_without_any_timestamp.jpg\",\
+             \"height\":37,\
+             \"width\":55\
+             },\
              {\"path\":\"tests/assets/photo_without_exif.jpg\",\"height\":37,\"width\":55},\
              {\"path\":\"tests/assets/photo_without_gps.jpg\",\"height\":37,\"width\":55},\
              {\
@@ -248,7 +803,9 @@ mod tests {
              \"path\":\"tests/assets/photo_without_timestamp.jpg\",\
              \"height\":37,\
              \"width\":55\
-             }\
+             },\
+             {\"path\":\"tests/assets/video.mp4\",\"height\":48,\"width\":64},\
+             {\"path\":\"tests/assets/video_without_gps.mov\",\"height\":48,\"width\":64}\
              ]}",
             to_string(&response).unwrap().replace("\\\\", "/")
         );
@@ -260,16 +817,315 @@ mod tests {
             Path::new("tests/assets"),
             Path::new("tests/assets/location_history.json"),
         );
-        let response = PhotosResponse::filtered(&state).unwrap();
+        let response = PhotosResponse::filtered(&state);
 
         assert_eq!(
             "{\"photos\":[\
-             {\"path\":\"tests/assets/photo_without_gps.jpg\",\"height\":37,\"width\":55}\
+             {\"path\":\"tests/assets/photo_with_gps_time.jpg\",\"height\":37,\"width\":55},\
+             {\"path\":\"tests/assets/photo_with_subsec_time.jpg\",\"height\":37,\"width\":55},\
+             {\"path\":\"tests/assets/photo_without_gps.jpg\",\"height\":37,\"width\":55},\
+             {\"path\":\"tests/assets/video_without_gps.mov\",\"height\":48,\"width\":64}\
              ]}",
             to_string(&response).unwrap().replace("\\\\", "/")
         );
     }
 
+    fn query_state() -> GuiState {
+        state_with_paths(
+            Path::new("tests/assets"),
+            Path::new("tests/assets/location_history.json"),
+        )
+    }
+
+    fn file_name(path: &Path) -> String {
+        path.file_name().unwrap().to_string_lossy().into_owned()
+    }
+
+    fn file_names(response: &PhotosResponse) -> Vec<String> {
+        response
+            .photos
+            .iter()
+            .map(|photo| file_name(photo.path()))
+            .collect()
+    }
+
+    #[test]
+    fn photos_response_query_should_get_all_photos_in_the_order_they_were_found_by_default() {
+        let state = query_state();
+        let response = PhotosResponse::query(&state, &PhotosQuery::default()).unwrap();
+
+        assert_eq!(
+            to_string(&PhotosResponse::new(&state)).unwrap(),
+            to_string(&response).unwrap()
+        );
+    }
+
+    #[test]
+    fn photos_response_query_should_filter_photos_by_location_status() {
+        let state = query_state();
+        let query = PhotosQuery {
+            status: Some(LocationStatus::Suggested),
+            ..PhotosQuery::default()
+        };
+        let response = PhotosResponse::query(&state, &query).unwrap();
+
+        assert_eq!(
+            vec![
+                "photo_with_gps_time.jpg",
+                "photo_with_subsec_time.jpg",
+                "photo_without_gps.jpg",
+                "video_without_gps.mov",
+            ],
+            file_names(&response)
+        );
+
+        let query = PhotosQuery {
+            status: Some(LocationStatus::Error),
+            ..PhotosQuery::default()
+        };
+        let response = PhotosResponse::query(&state, &query).unwrap();
+
+        assert_eq!(
+            vec!["photo_without_any_timestamp.jpg", "photo_without_exif.jpg"],
+            file_names(&response)
+        );
+    }
+
+    #[test]
+    fn photos_response_query_should_cache_summaries_until_the_suggestion_settings_change() {
+        let mut state = query_state();
+        let path = Path::new("tests/assets/photo_without_gps.jpg");
+        let query = PhotosQuery {
+            status: Some(LocationStatus::Suggested),
+            ..PhotosQuery::default()
+        };
+
+        PhotosResponse::query(&state, &PhotosQuery::default()).unwrap();
+        assert!(!state.summary_cache().contains(path));
+
+        PhotosResponse::query(&state, &query).unwrap();
+        assert!(state.summary_cache().contains(path));
+
+        state.set_user_set(path, true);
+        assert!(!state.summary_cache().contains(path));
+
+        PhotosResponse::query(&state, &query).unwrap();
+        state.set_interpolate(true);
+        assert!(!state.summary_cache().contains(path));
+    }
+
+    #[test]
+    fn photos_response_query_should_cache_the_summaries_used_to_filter_photos() {
+        let state = query_state();
+        let query = PhotosQuery {
+            filter: Some(true),
+            ..PhotosQuery::default()
+        };
+
+        let response = PhotosResponse::query(&state, &query).unwrap();
+
+        assert_eq!(
+            to_string(&PhotosResponse::filtered(&state)).unwrap(),
+            to_string(&response).unwrap()
+        );
+        for photo in state.photos() {
+            assert!(state.summary_cache().contains(photo.path()));
+        }
+    }
+
+    #[test]
+    fn photos_response_query_should_filter_photos_by_date_range() {
+        let state = query_state();
+        let query = PhotosQuery {
+            from: Some(1473158321270),
+            to: Some(1473158321500),
+            ..PhotosQuery::default()
+        };
+        let response = PhotosResponse::query(&state, &query).unwrap();
+
+        assert_eq!(vec!["photo_with_subsec_time.jpg"], file_names(&response));
+    }
+
+    #[test]
+    fn photos_response_query_should_filter_photos_by_camera_model_ignoring_case() {
+        let state = query_state();
+        let query = PhotosQuery {
+            camera: Some("canon powershot g7 x".to_string()),
+            ..PhotosQuery::default()
+        };
+        let response = PhotosResponse::query(&state, &query).unwrap();
+
+        assert_eq!(
+            vec![
+                "photo.jpg",
+                "photo_rotated.jpg",
+                "photo_without_gps.jpg",
+                "photo_without_orientation.jpg",
+                "photo_without_timestamp.jpg",
+            ],
+            file_names(&response)
+        );
+    }
+
+    #[test]
+    fn photos_response_query_should_filter_photos_by_bounding_box() {
+        let state = query_state();
+        let query = PhotosQuery {
+            bbox: Some("-10,38,-9,39".to_string()),
+            ..PhotosQuery::default()
+        };
+        let response = PhotosResponse::query(&state, &query).unwrap();
+
+        assert_eq!(
+            vec![
+                "photo.jpg",
+                "photo.png",
+                "photo.tiff",
+                "photo.webp",
+                "photo_without_timestamp.jpg",
+                "video.mp4",
+            ],
+            file_names(&response)
+        );
+    }
+
+    #[test]
+    fn photos_response_query_should_sort_photos_without_a_timestamp_last() {
+        let state = query_state();
+        let query = PhotosQuery {
+            sort: Some(PhotoSort::Timestamp),
+            order: Some(SortOrder::Desc),
+            ..PhotosQuery::default()
+        };
+        let response = PhotosResponse::query(&state, &query).unwrap();
+        let file_names = file_names(&response);
+
+        assert_eq!(
+            vec![
+                "photo_with_gps_time.jpg",
+                "photo_with_subsec_time.jpg",
+                "video_without_gps.mov",
+            ],
+            &file_names[..3]
+        );
+        assert_eq!(
+            vec!["photo_without_exif.jpg", "photo_without_any_timestamp.jpg"],
+            &file_names[12..]
+        );
+    }
+
+    #[test]
+    fn photos_response_query_should_sort_photos_by_suggestion_accuracy() {
+        let state = query_state();
+        let query = PhotosQuery {
+            sort: Some(PhotoSort::Accuracy),
+            ..PhotosQuery::default()
+        };
+        let response = PhotosResponse::query(&state, &query).unwrap();
+
+        assert_eq!(
+            vec![
+                "photo_with_gps_time.jpg",
+                "photo_with_subsec_time.jpg",
+                "photo_without_gps.jpg",
+                "video_without_gps.mov",
+                "photo.jpg",
+            ],
+            &file_names(&response)[..5]
+        );
+    }
+
+    #[test]
+    fn photos_response_query_should_page_through_photos_using_cursors() {
+        let state = query_state();
+        let mut pages = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let query = PhotosQuery {
+                sort: Some(PhotoSort::Path),
+                order: Some(SortOrder::Desc),
+                limit: Some(5),
+                cursor: cursor.take(),
+                ..PhotosQuery::default()
+            };
+            let response = PhotosResponse::query(&state, &query).unwrap();
+            pages.push(file_names(&response));
+
+            match response.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => break,
+            }
+        }
+
+        let mut all_photos: Vec<String> = state
+            .photos()
+            .iter()
+            .map(|photo| file_name(photo.path()))
+            .collect();
+        all_photos.reverse();
+
+        assert_eq!(
+            vec![5, 5, 4],
+            pages.iter().map(Vec::len).collect::<Vec<_>>()
+        );
+        assert_eq!(all_photos, pages.concat());
+    }
+
+    #[test]
+    fn photos_response_query_should_error_for_an_invalid_query() {
+        let state = query_state();
+
+        for query in &[
+            PhotosQuery {
+                bbox: Some("1,2,3".to_string()),
+                ..PhotosQuery::default()
+            },
+            PhotosQuery {
+                bbox: Some("0,90,1,-90".to_string()),
+                ..PhotosQuery::default()
+            },
+            PhotosQuery {
+                cursor: Some("a:b:photo.jpg".to_string()),
+                ..PhotosQuery::default()
+            },
+            PhotosQuery {
+                limit: Some(0),
+                ..PhotosQuery::default()
+            },
+        ] {
+            match PhotosResponse::query(&state, query) {
+                Err(ServiceError::InvalidQuery(_)) => {}
+                _ => panic!("Expected an invalid query error"),
+            }
+        }
+    }
+
+    #[test]
+    fn bounding_box_contains_should_handle_a_box_that_crosses_the_antimeridian() {
+        let bounding_box = BoundingBox::parse("170,-10,-170,10").unwrap();
+
+        assert!(bounding_box.contains(&Coordinates::new(0.0, 175.0)));
+        assert!(bounding_box.contains(&Coordinates::new(0.0, -175.0)));
+        assert!(!bounding_box.contains(&Coordinates::new(0.0, 0.0)));
+        assert!(!bounding_box.contains(&Coordinates::new(20.0, 175.0)));
+    }
+
+    #[test]
+    fn sort_key_should_round_trip_through_a_cursor() {
+        let key = SortKey {
+            value: Some((-3, 12)),
+            path: PathBuf::from("a:b/c.jpg"),
+        };
+        assert_eq!(Some(&key), SortKey::from_cursor(&key.to_cursor()).as_ref());
+
+        let key = SortKey {
+            value: None,
+            path: PathBuf::from("c.jpg"),
+        };
+        assert_eq!(Some(&key), SortKey::from_cursor(&key.to_cursor()).as_ref());
+    }
+
     #[test]
     fn locations_response_new_should_get_locations_for_the_given_photo_index_range() {
         let state = state_with_root_path(Path::new("tests/assets"));
@@ -293,6 +1149,20 @@ mod tests {
         assert!(response.error.is_some());
     }
 
+    #[test]
+    fn location_response_new_should_use_timestamp_fallbacks_for_a_photo_without_a_timestamp() {
+        let path = Path::new("tests/assets/photo_without_exif.jpg");
+        let mut state = state_with_root_path(Path::new("tests/assets"));
+
+        let response = LocationResponse::new(path, &state).unwrap();
+        assert!(response.error.is_some());
+
+        state.set_timestamp_fallbacks(vec![TimestampFallback::FileModificationTime]);
+        let response = LocationResponse::new(path, &state).unwrap();
+        assert!(response.location.is_none());
+        assert!(response.error.is_none());
+    }
+
     #[test]
     fn location_response_new_should_set_only_a_path_for_empty_location_history() {
         let path = Path::new("tests/assets/photo_without_gps.jpg");
@@ -315,6 +1185,120 @@ mod tests {
         assert!(response.error.is_none());
     }
 
+    #[test]
+    fn location_response_new_should_mark_an_existing_location_set_by_the_user() {
+        let path = Path::new("tests/assets/photo.jpg");
+        let mut state = state_with_root_path(Path::new("tests/assets"));
+
+        let response = LocationResponse::new(path, &state).unwrap();
+        assert!(!response.user_set);
+        assert!(!to_string(&response).unwrap().contains("userSet"));
+
+        state.set_user_set(path, true);
+        let response = LocationResponse::new(path, &state).unwrap();
+        assert!(response.user_set);
+        let json = to_string(&response).unwrap();
+        assert!(json.ends_with(",\"userSet\":true}"));
+    }
+
+    #[test]
+    fn location_response_new_should_not_mark_a_suggested_location_as_set_by_the_user() {
+        let path = Path::new("tests/assets/photo_without_gps.jpg");
+        let mut state = state_with_paths(
+            Path::new("tests/assets"),
+            Path::new("tests/assets/location_history.json"),
+        );
+        state.set_user_set(path, true);
+
+        let response = LocationResponse::new(path, &state).unwrap();
+        assert!(response.location.is_some());
+        assert!(!response.user_set);
+    }
+
+    #[test]
+    fn location_response_new_should_name_the_place_nearest_to_the_location() {
+        let path = Path::new("tests/assets/photo_without_gps.jpg");
+        let mut state = state_with_paths(
+            Path::new("tests/assets"),
+            Path::new("tests/assets/location_history.json"),
+        );
+
+        let response = LocationResponse::new(path, &state).unwrap();
+        assert!(response.place.is_none());
+        assert!(!to_string(&response).unwrap().contains("place"));
+
+        let geocoder =
+            ReverseGeocoder::load(Path::new("tests/assets/geonames/cities.txt")).unwrap();
+        state.set_geocoder(Some(geocoder));
+
+        let response = LocationResponse::new(path, &state).unwrap();
+        assert_eq!(
+            Some("Ipswich, England, United Kingdom".to_string()),
+            response.place
+        );
+
+        let response = LocationResponse::new(Path::new("tests/assets/photo.jpg"), &state).unwrap();
+        assert_eq!(
+            Some("Sacavém, Lisbon, Portugal".to_string()),
+            response.place
+        );
+    }
+
+    #[test]
+    fn track_response_new_should_get_the_location_history_in_the_window_around_the_photo() {
+        let path = Path::new("tests/assets/photo_without_gps.jpg");
+        let state = state_with_paths(
+            Path::new("tests/assets"),
+            Path::new("tests/assets/location_history.json"),
+        );
+
+        let response = TrackResponse::new(path, 60, &state).unwrap();
+        assert!(response.features.is_empty());
+
+        let response = TrackResponse::new(path, 500_000, &state).unwrap();
+        assert_eq!(1, response.features.len());
+        assert_eq!(
+            "{\"type\":\"FeatureCollection\",\"features\":[{\"type\":\"Feature\",\"geometry\":{\"type\":\"Point\",\"coordinates\":[1.1965831,52.0796733]},\"properties\":{\"timestampMs\":1472688000000,\"offsetSeconds\":-470321,\"accuracy\":18}}]}",
+            to_string(&response).unwrap()
+        );
+
+        let response = TrackResponse::new(path, 30_000_000, &state).unwrap();
+        assert_eq!(4, response.features.len());
+        match response.features[0].geometry {
+            TrackGeometry::LineString(ref positions) => assert_eq!(3, positions.len()),
+            _ => panic!("Expected the first feature to be a line string"),
+        }
+        assert_eq!("{}", to_string(&response.features[0].properties).unwrap());
+    }
+
+    #[test]
+    fn track_response_new_should_error_if_the_photo_has_no_timestamp() {
+        let path = Path::new("tests/assets/photo_without_exif.jpg");
+        let state = state_with_root_path(Path::new("tests/assets"));
+
+        assert!(TrackResponse::new(path, 60, &state).is_err());
+    }
+
+    #[test]
+    fn gui_state_place_to_write_should_only_name_places_if_writing_place_names() {
+        let coordinates = Coordinates::new(38.76544, -9.094802);
+        let mut state = state_with_root_path(Path::new("tests/assets"));
+
+        state.set_write_place_names(true);
+        assert!(state.place_to_write(&coordinates).is_none());
+
+        let geocoder =
+            ReverseGeocoder::load(Path::new("tests/assets/geonames/cities.txt")).unwrap();
+        state.set_geocoder(Some(geocoder));
+        assert_eq!(
+            "Sacavém",
+            state.place_to_write(&coordinates).unwrap().name()
+        );
+
+        state.set_write_place_names(false);
+        assert!(state.place_to_write(&coordinates).is_none());
+    }
+
     #[test]
     fn read_file_bytes_should_error_for_an_unrecognised_path() {
         assert!(read_file_bytes(Path::new("README.md")).is_err());
@@ -0,0 +1,183 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use rayon::prelude::*;
+
+use common::photo_paths;
+
+use super::error::ServiceError;
+use super::events::GuiEvent;
+use super::image::ImageDimensions;
+use super::state::{combine_histories, GuiState};
+
+/// The number of photos to read the dimensions of before adding them to the GUI state, which
+/// is small enough for the GUI to show the first photos quickly.
+const BATCH_SIZE: usize = 50;
+
+/// Scan the given root path for photos in the background, replacing the photos found by any
+/// previous scan.
+///
+/// Photos are added to the GUI state in batches as their dimensions are read, and `GuiEvent`s
+/// are sent as the scan finds photos and finishes. The state is only locked for writing while
+/// the scan starts and each batch is added, so other requests aren't blocked while photos are
+/// read. Photos with dimensions that can't be read are skipped.
+pub fn scan_in_background(
+    state: &Arc<RwLock<GuiState>>,
+    root_path: PathBuf,
+) -> Result<(), ServiceError> {
+    let scan_id = state.write()?.start_scan(root_path.clone());
+    spawn_scan(state, scan_id, root_path);
+
+    Ok(())
+}
+
+/// Continue the scan with the given ID, which was started using `GuiState::start_scan`, in the
+/// background.
+pub fn spawn_scan(state: &Arc<RwLock<GuiState>>, scan_id: u64, root_path: PathBuf) {
+    let state = Arc::clone(state);
+    thread::spawn(move || {
+        if let Err(e) = scan(&state, scan_id, &root_path) {
+            eprintln!("Error scanning {}: {}", root_path.display(), e);
+        }
+    });
+}
+
+fn scan(state: &RwLock<GuiState>, scan_id: u64, root_path: &Path) -> Result<(), ServiceError> {
+    for paths in photo_paths(root_path).chunks(BATCH_SIZE) {
+        let results: Vec<Result<ImageDimensions, (PathBuf, ServiceError)>> = paths
+            .par_iter()
+            .map(|path| ImageDimensions::new(path).map_err(|e| (path.clone(), e)))
+            .collect();
+
+        // Events are sent while the state is locked so that they're in the same order as the
+        // changes they describe.
+        let mut state = state.write()?;
+        if state.scan_id() != scan_id {
+            return Ok(());
+        }
+
+        let mut photos = Vec::with_capacity(results.len());
+        for result in results {
+            match result {
+                Ok(photo) => photos.push(photo),
+                Err((path, error)) => state.events().send(&GuiEvent::PhotoFailed {
+                    path,
+                    error: format!("{}", error),
+                }),
+            }
+        }
+
+        let photos = state.add_photos(photos);
+        state.events().send(&GuiEvent::PhotosFound { photos });
+    }
+
+    combine_histories(state)?;
+
+    let mut state = state.write()?;
+    if state.finish_scan(scan_id) {
+        let total = state.photos().len();
+        state.events().send(&GuiEvent::ScanFinished { total });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env::temp_dir;
+
+    use futures::Stream;
+    use yore::Journal;
+
+    fn new_state() -> Arc<RwLock<GuiState>> {
+        let state = GuiState::new(&temp_dir(), Journal::new(&temp_dir().join("journal")));
+        Arc::new(RwLock::new(state))
+    }
+
+    #[test]
+    fn scan_in_background_should_add_photos_and_send_events_until_finished() {
+        let state = new_state();
+        let receiver = state.read().unwrap().subscribe_to_events();
+
+        scan_in_background(&state, PathBuf::from("tests/assets")).unwrap();
+
+        let messages: Vec<String> = receiver
+            .wait()
+            .map(|message| String::from_utf8_lossy(&message.unwrap()).into_owned())
+            .take_while(|message| !message.contains("ScanFinished"))
+            .collect();
+
+        assert!(messages[0].starts_with("data: {\"ScanStarted\":{\"rootPath\":\"tests/assets\"}}"));
+        assert!(messages[1..]
+            .iter()
+            .all(|message| message.starts_with("data: {\"PhotosFound\":")));
+
+        let state = state.read().unwrap();
+        assert!(!state.is_scanning());
+        assert_eq!(14, state.photos().len());
+        assert_eq!(14, state.photo_paths().len());
+    }
+
+    #[test]
+    fn scan_should_ignore_photos_found_by_a_superseded_scan() {
+        let state = new_state();
+        let scan_id = state
+            .write()
+            .unwrap()
+            .start_scan(PathBuf::from("tests/assets"));
+        state
+            .write()
+            .unwrap()
+            .start_scan(PathBuf::from("tests/assets/photo.jpg"));
+
+        scan(&state, scan_id, Path::new("tests/assets")).unwrap();
+
+        let state = state.read().unwrap();
+        assert!(state.is_scanning());
+        assert!(state.photos().is_empty());
+    }
+
+    #[test]
+    fn scan_should_combine_histories_if_copying_locations_from_photos() {
+        let state = new_state();
+        state.write().unwrap().set_copy_from_photos(true);
+        let scan_id = state
+            .write()
+            .unwrap()
+            .start_scan(PathBuf::from("tests/assets"));
+
+        scan(&state, scan_id, Path::new("tests/assets")).unwrap();
+
+        assert_ne!(0, state.read().unwrap().location_history().len());
+    }
+
+    #[test]
+    fn subscribe_to_events_should_start_with_the_photos_found_so_far() {
+        let state = new_state();
+        state
+            .write()
+            .unwrap()
+            .search_new_root_path(PathBuf::from("tests/assets/photo.jpg"));
+
+        let receiver = state.read().unwrap().subscribe_to_events();
+        let messages: Vec<String> = receiver
+            .wait()
+            .take(3)
+            .map(|message| String::from_utf8_lossy(&message.unwrap()).into_owned())
+            .collect();
+
+        assert_eq!(
+            vec![
+                "data: {\"ScanStarted\":{\"rootPath\":\"tests/assets/photo.jpg\"}}\n\n",
+                "data: {\"PhotosFound\":{\"photos\":[\
+                 {\"path\":\"tests/assets/photo.jpg\",\"height\":37,\"width\":55}\
+                 ]}}\n\n",
+                "data: {\"ScanFinished\":{\"total\":1}}\n\n",
+            ],
+            messages
+        );
+    }
+}
@@ -1,32 +1,79 @@
-use std::collections::hash_map::DefaultHasher;
-use std::ffi::OsStr;
-use std::fs::{remove_dir_all, File};
-use std::hash::{Hash, Hasher};
+use std::collections::HashSet;
+use std::fs::File;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
 
-use yore::{load_location_history, GoogleLocationHistory, HistoryError};
+use rayon::prelude::*;
+use yore::{
+    load_location_history, Coordinates, GoogleLocationHistory, HistoryError, Journal, Photo, Place,
+    ReverseGeocoder, TimestampFallback, WriteOptions,
+};
 
-use common::photo_paths;
+use bytes::Bytes;
+use futures::sync::mpsc::UnboundedReceiver;
+
+use super::cache::{ImageCache, DEFAULT_MAX_CACHE_SIZE};
+use super::error::ServiceError;
+use super::events::{EventStream, GuiEvent};
+use super::image::ImageDimensions;
+use super::summary::SummaryCache;
+use super::tiles::TileSource;
+use super::watch::FileWatcher;
 
 pub struct GuiState {
     root_path: Option<PathBuf>,
     photo_paths: Vec<PathBuf>,
+    photo_path_set: HashSet<PathBuf>,
+    photos: Vec<ImageDimensions>,
+    scan_id: u64,
+    scanning: bool,
+    events: EventStream,
+    file_watcher: Option<FileWatcher>,
     location_history_path: Option<PathBuf>,
-    location_history: GoogleLocationHistory,
+    location_history: Arc<GoogleLocationHistory>,
     interpolate: bool,
-    cache_path: PathBuf,
+    copy_from_photos: bool,
+    combined_history: Option<Arc<GoogleLocationHistory>>,
+    history_id: u64,
+    timestamp_fallbacks: Vec<TimestampFallback>,
+    image_cache: ImageCache,
+    summary_cache: SummaryCache,
+    journal: Arc<Journal>,
+    write_options: WriteOptions,
+    user_set_paths: HashSet<PathBuf>,
+    tile_source: Option<TileSource>,
+    geocoder: Option<Arc<ReverseGeocoder>>,
+    write_place_names: bool,
 }
 
 impl GuiState {
-    pub fn new(cache_path: &Path) -> GuiState {
+    pub fn new(cache_path: &Path, journal: Journal) -> GuiState {
         GuiState {
             root_path: None,
             photo_paths: Vec::default(),
+            photo_path_set: HashSet::default(),
+            photos: Vec::default(),
+            scan_id: 0,
+            scanning: false,
+            events: EventStream::default(),
+            file_watcher: None,
             location_history_path: None,
-            location_history: GoogleLocationHistory::default(),
+            location_history: Arc::default(),
             interpolate: false,
-            cache_path: cache_path.to_path_buf(),
+            copy_from_photos: false,
+            combined_history: None,
+            history_id: 0,
+            timestamp_fallbacks: Vec::default(),
+            image_cache: ImageCache::new(cache_path, DEFAULT_MAX_CACHE_SIZE),
+            summary_cache: SummaryCache::default(),
+            journal: Arc::new(journal),
+            write_options: WriteOptions::default(),
+            user_set_paths: HashSet::default(),
+            tile_source: None,
+            geocoder: None,
+            write_place_names: false,
         }
     }
 
@@ -38,59 +85,385 @@ impl GuiState {
         &self.photo_paths
     }
 
+    /// The dimensions of the photos found under the root path so far, in the same order as
+    /// their paths.
+    pub fn photos(&self) -> &[ImageDimensions] {
+        &self.photos
+    }
+
+    /// Identifies the latest scan of the root path, so that the photos found by a scan that
+    /// has been superseded can be ignored.
+    pub fn scan_id(&self) -> u64 {
+        self.scan_id
+    }
+
+    #[cfg(test)]
+    pub fn is_scanning(&self) -> bool {
+        self.scanning
+    }
+
+    pub fn events(&self) -> &EventStream {
+        &self.events
+    }
+
     pub fn location_history_path(&self) -> Option<&PathBuf> {
         self.location_history_path.as_ref()
     }
 
+    /// The location history to suggest locations from, which includes the locations of the
+    /// photos under the root path if copying locations from photos.
     pub fn location_history(&self) -> &GoogleLocationHistory {
-        &self.location_history
+        self.combined_history
+            .as_ref()
+            .unwrap_or(&self.location_history)
+    }
+
+    /// A handle to the location history returned by `location_history`, which stays valid
+    /// after the state is unlocked.
+    pub fn shared_location_history(&self) -> Arc<GoogleLocationHistory> {
+        Arc::clone(
+            self.combined_history
+                .as_ref()
+                .unwrap_or(&self.location_history),
+        )
     }
 
     pub fn interpolate(&self) -> bool {
         self.interpolate
     }
 
-    pub fn cached_image_path(
-        &self,
-        original_image_path: &Path,
-        width: u32,
-        height: u32,
-    ) -> PathBuf {
-        self.cache_path
-            .join(&cached_filename(original_image_path, width, height))
+    pub fn copy_from_photos(&self) -> bool {
+        self.copy_from_photos
+    }
+
+    pub fn timestamp_fallbacks(&self) -> &[TimestampFallback] {
+        &self.timestamp_fallbacks
     }
 
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    /// A handle to the journal, so that locations can be written without the state locked.
+    pub fn shared_journal(&self) -> Arc<Journal> {
+        Arc::clone(&self.journal)
+    }
+
+    pub fn write_options(&self) -> &WriteOptions {
+        &self.write_options
+    }
+
+    pub fn tile_source(&self) -> Option<&TileSource> {
+        self.tile_source.as_ref()
+    }
+
+    pub fn geocoder(&self) -> Option<&ReverseGeocoder> {
+        self.geocoder.as_deref()
+    }
+
+    /// A handle to the geocoder to find the names of places to save with, if saving place names.
+    pub fn place_geocoder(&self) -> Option<Arc<ReverseGeocoder>> {
+        if self.write_place_names {
+            self.geocoder.clone()
+        } else {
+            None
+        }
+    }
+
+    /// The names of the place to save along with the given coordinates, if saving place names.
+    pub fn place_to_write(&self, coordinates: &Coordinates) -> Option<&Place> {
+        if !self.write_place_names {
+            return None;
+        }
+
+        self.geocoder()?
+            .nearest(coordinates)
+            .map(|(place, _)| place)
+    }
+
+    pub fn contains_photo(&self, path: &Path) -> bool {
+        self.photo_path_set.contains(path)
+    }
+
+    /// Whether the location of the photo at the given path was last set by the user, rather
+    /// than being a saved suggestion.
+    pub fn is_user_set(&self, path: &Path) -> bool {
+        self.user_set_paths.contains(path)
+    }
+
+    pub fn image_cache(&self) -> &ImageCache {
+        &self.image_cache
+    }
+
+    pub fn summary_cache(&self) -> &SummaryCache {
+        &self.summary_cache
+    }
+
+    /// Subscribe to GUI events, starting with events that describe the photos found so far.
+    pub fn subscribe_to_events(&self) -> UnboundedReceiver<Bytes> {
+        let mut initial_events = Vec::new();
+        if let Some(ref root_path) = self.root_path {
+            initial_events.push(GuiEvent::ScanStarted {
+                root_path: root_path.clone(),
+            });
+            initial_events.push(GuiEvent::PhotosFound {
+                photos: self.photos.clone(),
+            });
+            if !self.scanning {
+                initial_events.push(GuiEvent::ScanFinished {
+                    total: self.photos.len(),
+                });
+            }
+        }
+
+        self.events.subscribe(&initial_events)
+    }
+
+    /// Search the given root path for photos and read their dimensions, blocking until done.
+    /// Photos with dimensions that can't be read are skipped.
+    #[cfg(test)]
     pub fn search_new_root_path(&mut self, root_path: PathBuf) {
-        self.photo_paths = photo_paths(&root_path);
+        use common::photo_paths;
+
+        let photos = photo_paths(&root_path)
+            .par_iter()
+            .filter_map(|path| ImageDimensions::new(path).ok())
+            .collect();
+
+        let scan_id = self.start_scan(root_path);
+        self.add_photos(photos);
+        self.finish_scan(scan_id);
+        if self.copy_from_photos {
+            let history = build_combined_history(&self.location_history, &self.photo_paths);
+            self.set_combined_history(history);
+        }
+    }
+
+    /// Start a new scan of the given root path, forgetting the photos found by any previous
+    /// scan. Returns the new scan's ID.
+    pub fn start_scan(&mut self, root_path: PathBuf) -> u64 {
+        self.events.send(&GuiEvent::ScanStarted {
+            root_path: root_path.clone(),
+        });
+
+        self.scan_id += 1;
+        self.scanning = true;
         self.root_path = Some(root_path);
+        self.photo_paths.clear();
+        self.photo_path_set.clear();
+        self.photos.clear();
+        self.clear_combined_history();
+        self.watch_paths();
+
+        self.scan_id
+    }
+
+    /// Add the given photos, skipping any that have already been added, e.g. by the file
+    /// watcher during a scan. Returns the photos that were added.
+    pub fn add_photos(&mut self, photos: Vec<ImageDimensions>) -> Vec<ImageDimensions> {
+        let photo_path_set = &mut self.photo_path_set;
+        let photos: Vec<ImageDimensions> = photos
+            .into_iter()
+            .filter(|photo| photo_path_set.insert(photo.path().to_path_buf()))
+            .collect();
+
+        self.photo_paths
+            .extend(photos.iter().map(|photo| photo.path().to_path_buf()));
+        self.photos.extend(photos.iter().cloned());
+
+        photos
+    }
+
+    /// Replace the photo that has the same path as the given photo. Returns false if there is
+    /// no such photo.
+    pub fn update_photo(&mut self, photo: ImageDimensions) -> bool {
+        let index = self
+            .photo_paths
+            .iter()
+            .position(|path| path == photo.path());
+        match index {
+            Some(index) => {
+                self.photos[index] = photo;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget the photo at the given path, or all the photos in it if it's a directory.
+    /// Returns the paths of the photos that were forgotten.
+    pub fn remove_photos(&mut self, path: &Path) -> Vec<PathBuf> {
+        let removed_paths: Vec<PathBuf> = self
+            .photo_paths
+            .iter()
+            .filter(|photo_path| photo_path.starts_with(path))
+            .cloned()
+            .collect();
+
+        if !removed_paths.is_empty() {
+            self.photo_paths
+                .retain(|photo_path| !photo_path.starts_with(path));
+            self.photo_path_set
+                .retain(|photo_path| !photo_path.starts_with(path));
+            self.photos.retain(|photo| !photo.path().starts_with(path));
+        }
+
+        removed_paths
+    }
+
+    /// Finish the scan with the given ID. Returns false if the scan has been superseded.
+    pub fn finish_scan(&mut self, scan_id: u64) -> bool {
+        if scan_id != self.scan_id {
+            return false;
+        }
+
+        self.scanning = false;
+        true
     }
 
     pub fn load_location_history(&mut self, path: PathBuf) -> Result<(), HistoryError> {
         let file = File::open(&path)?;
-        self.location_history = unsafe { load_location_history(&file)? };
+        self.location_history = Arc::new(unsafe { load_location_history(&file)? });
         self.location_history_path = Some(path);
+        self.clear_combined_history();
+        self.watch_paths();
 
         Ok(())
     }
 
+    /// Replace the loaded location history with one that has been read again from the same
+    /// path, e.g. after it was exported again.
+    pub fn reload_location_history(&mut self, location_history: GoogleLocationHistory) {
+        self.location_history = Arc::new(location_history);
+        self.clear_combined_history();
+    }
+
+    /// Use the given watcher to watch the root path and location history path for changes,
+    /// including after either path changes.
+    pub fn set_file_watcher(&mut self, file_watcher: FileWatcher) {
+        self.file_watcher = Some(file_watcher);
+        self.watch_paths();
+    }
+
     pub fn set_interpolate(&mut self, interpolate: bool) {
         self.interpolate = interpolate;
+        self.summary_cache.clear();
+    }
+
+    pub fn set_copy_from_photos(&mut self, copy_from_photos: bool) {
+        self.copy_from_photos = copy_from_photos;
+        self.clear_combined_history();
+    }
+
+    pub fn set_timestamp_fallbacks(&mut self, timestamp_fallbacks: Vec<TimestampFallback>) {
+        self.timestamp_fallbacks = timestamp_fallbacks;
+        self.summary_cache.clear();
+    }
+
+    pub fn set_write_options(&mut self, write_options: WriteOptions) {
+        self.write_options = write_options;
     }
 
-    pub fn clear_cache(&self) -> io::Result<()> {
-        if self.cache_path.exists() {
-            remove_dir_all(&self.cache_path)
+    pub fn set_tile_source(&mut self, tile_source: Option<TileSource>) {
+        self.tile_source = tile_source;
+    }
+
+    pub fn set_geocoder(&mut self, geocoder: Option<ReverseGeocoder>) {
+        self.geocoder = geocoder.map(Arc::new);
+    }
+
+    pub fn set_write_place_names(&mut self, write_place_names: bool) {
+        self.write_place_names = write_place_names;
+    }
+
+    /// Set the maximum total size of the cached images in bytes, removing the least recently
+    /// used images until they fit.
+    pub fn set_max_cache_size(&mut self, max_size: u64) -> io::Result<()> {
+        self.image_cache.set_max_size(max_size)
+    }
+
+    /// Record whether the location that was just saved to or restored in the photo at the given
+    /// path was set by the user.
+    pub fn set_user_set(&mut self, path: &Path, user_set: bool) {
+        self.summary_cache.remove(path);
+        if user_set {
+            self.user_set_paths.insert(path.to_path_buf());
         } else {
-            Ok(())
+            self.user_set_paths.remove(path);
+        }
+    }
+
+    fn watch_paths(&mut self) {
+        if let Some(ref mut file_watcher) = self.file_watcher {
+            file_watcher.watch(self.root_path.as_ref(), self.location_history_path.as_ref());
         }
     }
+
+    /// Forget the combined history, as the history or photos that it was built from have
+    /// changed. Until `combine_histories` is next called, suggestions only use the loaded
+    /// location history.
+    fn clear_combined_history(&mut self) {
+        self.history_id += 1;
+        self.combined_history = None;
+        self.summary_cache.clear();
+    }
+
+    fn set_combined_history(&mut self, history: GoogleLocationHistory) {
+        self.combined_history = Some(Arc::new(history));
+        self.summary_cache.clear();
+    }
+}
+
+/// Add the locations of the photos under the root path to a copy of the loaded location history
+/// if copying locations from photos, so that suggestions don't need to read every photo again.
+/// Locations saved after this are not included until it is next called.
+///
+/// The photos are read and the history is built while the state is unlocked, and the result is
+/// discarded if the history or photos changed in the meantime.
+pub fn combine_histories(state: &RwLock<GuiState>) -> Result<(), ServiceError> {
+    let (history_id, location_history, photo_paths) = {
+        let state = state.read()?;
+        if !state.copy_from_photos {
+            return Ok(());
+        }
+
+        (
+            state.history_id,
+            Arc::clone(&state.location_history),
+            state.photo_paths.clone(),
+        )
+    };
+
+    let history = build_combined_history(&location_history, &photo_paths);
+
+    let mut state = state.write()?;
+    if state.history_id == history_id {
+        state.set_combined_history(history);
+    }
+
+    Ok(())
 }
 
-fn cached_filename(path: &Path, width: u32, height: u32) -> String {
-    let mut hasher = DefaultHasher::new();
-    path.hash(&mut hasher);
+/// Call `combine_histories` in the background.
+pub fn combine_histories_in_background(state: &Arc<RwLock<GuiState>>) {
+    let state = Arc::clone(state);
+    thread::spawn(move || {
+        if let Err(e) = combine_histories(&state) {
+            eprintln!("Error combining location histories: {}", e);
+        }
+    });
+}
 
-    let extension = path.extension().and_then(OsStr::to_str).unwrap_or(".jpg");
+fn build_combined_history(
+    location_history: &GoogleLocationHistory,
+    photo_paths: &[PathBuf],
+) -> GoogleLocationHistory {
+    let photos: Vec<Photo> = photo_paths
+        .par_iter()
+        .filter_map(|path| Photo::new(path).ok())
+        .collect();
 
-    format!("{}-{}-{}.{}", hasher.finish(), width, height, extension)
+    let mut history = location_history.clone();
+    history.extend(GoogleLocationHistory::from_photos(&photos));
+    history
 }
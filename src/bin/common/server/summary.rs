@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::SystemTime;
+
+use super::responses::PhotoSummary;
+
+/// Caches the summaries that photos are filtered and sorted by, so that paging through photos
+/// doesn't read every photo again for each page.
+///
+/// Summaries are kept with their photo's modification time, so they're not used once the photo
+/// changes. They also depend on the location history and settings that suggestions are made
+/// with, so the cache must be cleared when those change.
+#[derive(Default)]
+pub struct SummaryCache {
+    summaries: Mutex<Summaries>,
+}
+
+/// The generation is incremented whenever summaries are forgotten, so that a summary created
+/// from the old settings or photo while that happened is not cached.
+#[derive(Default)]
+struct Summaries {
+    generation: u64,
+    entries: HashMap<PathBuf, CachedSummary>,
+}
+
+struct CachedSummary {
+    modified: Option<SystemTime>,
+    summary: Arc<PhotoSummary>,
+}
+
+impl SummaryCache {
+    /// Get the cached summary of the photo at the given path if it hasn't changed since, or
+    /// create and cache a new summary with the given function.
+    pub fn get_or_insert_with<F>(&self, path: &Path, create: F) -> Arc<PhotoSummary>
+    where
+        F: FnOnce() -> PhotoSummary,
+    {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+
+        let generation = {
+            let summaries = self.summaries();
+            if let Some(cached) = summaries.entries.get(path) {
+                if cached.modified == modified {
+                    return Arc::clone(&cached.summary);
+                }
+            }
+            summaries.generation
+        };
+
+        // The lock isn't held while the summary is created, so that other photos can be
+        // summarised in parallel.
+        let summary = Arc::new(create());
+
+        let mut summaries = self.summaries();
+        if summaries.generation == generation {
+            summaries.entries.insert(
+                path.to_path_buf(),
+                CachedSummary {
+                    modified,
+                    summary: Arc::clone(&summary),
+                },
+            );
+        }
+
+        summary
+    }
+
+    /// Forget the summary of the photo at the given path, e.g. because its location was saved.
+    pub fn remove(&self, path: &Path) {
+        let mut summaries = self.summaries();
+        summaries.generation += 1;
+        summaries.entries.remove(path);
+    }
+
+    /// Forget all the summaries, e.g. because the location history has changed.
+    pub fn clear(&self) {
+        let mut summaries = self.summaries();
+        summaries.generation += 1;
+        summaries.entries.clear();
+    }
+
+    #[cfg(test)]
+    pub fn contains(&self, path: &Path) -> bool {
+        self.summaries().entries.contains_key(path)
+    }
+
+    fn summaries(&self) -> MutexGuard<'_, Summaries> {
+        self.summaries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    use std::cell::Cell;
+    use std::fs::{copy, OpenOptions};
+    use std::time::Duration;
+
+    use self::tempfile::tempdir;
+
+    const PHOTO_PATH: &str = "tests/assets/photo.jpg";
+
+    fn get(cache: &SummaryCache, path: &Path, created: &Cell<u32>) -> Arc<PhotoSummary> {
+        cache.get_or_insert_with(path, || {
+            created.set(created.get() + 1);
+            PhotoSummary::default()
+        })
+    }
+
+    #[test]
+    fn get_or_insert_with_should_reuse_the_cached_summary() {
+        let cache = SummaryCache::default();
+        let created = Cell::new(0);
+
+        let summary = get(&cache, Path::new(PHOTO_PATH), &created);
+        let cached_summary = get(&cache, Path::new(PHOTO_PATH), &created);
+
+        assert_eq!(1, created.get());
+        assert!(Arc::ptr_eq(&summary, &cached_summary));
+    }
+
+    #[test]
+    fn get_or_insert_with_should_create_a_new_summary_if_the_photo_has_been_modified() {
+        let tmp_dir = tempdir().unwrap();
+        let photo_path = tmp_dir.path().join("photo.jpg");
+        copy(PHOTO_PATH, &photo_path).unwrap();
+        let cache = SummaryCache::default();
+        let created = Cell::new(0);
+
+        get(&cache, &photo_path, &created);
+
+        let file = OpenOptions::new().write(true).open(&photo_path).unwrap();
+        let modified = fs::metadata(&photo_path).unwrap().modified().unwrap();
+        file.set_modified(modified + Duration::from_secs(1))
+            .unwrap();
+
+        get(&cache, &photo_path, &created);
+
+        assert_eq!(2, created.get());
+    }
+
+    #[test]
+    fn get_or_insert_with_should_not_cache_a_summary_if_the_cache_is_cleared_while_creating_it() {
+        let cache = SummaryCache::default();
+
+        cache.get_or_insert_with(Path::new(PHOTO_PATH), || {
+            cache.clear();
+            PhotoSummary::default()
+        });
+
+        assert!(!cache.contains(Path::new(PHOTO_PATH)));
+    }
+
+    #[test]
+    fn get_or_insert_with_should_not_cache_a_summary_if_it_is_removed_while_being_created() {
+        let cache = SummaryCache::default();
+
+        cache.get_or_insert_with(Path::new(PHOTO_PATH), || {
+            cache.remove(Path::new(PHOTO_PATH));
+            PhotoSummary::default()
+        });
+
+        assert!(!cache.contains(Path::new(PHOTO_PATH)));
+    }
+
+    #[test]
+    fn remove_should_forget_the_summary_of_the_given_photo() {
+        let cache = SummaryCache::default();
+        let created = Cell::new(0);
+
+        get(&cache, Path::new(PHOTO_PATH), &created);
+        get(&cache, Path::new("tests/assets/photo.png"), &created);
+        cache.remove(Path::new(PHOTO_PATH));
+
+        assert!(!cache.contains(Path::new(PHOTO_PATH)));
+        assert!(cache.contains(Path::new("tests/assets/photo.png")));
+    }
+
+    #[test]
+    fn clear_should_forget_all_summaries() {
+        let cache = SummaryCache::default();
+        let created = Cell::new(0);
+
+        get(&cache, Path::new(PHOTO_PATH), &created);
+        get(&cache, Path::new("tests/assets/photo.png"), &created);
+        cache.clear();
+
+        assert!(!cache.contains(Path::new(PHOTO_PATH)));
+        assert!(!cache.contains(Path::new("tests/assets/photo.png")));
+    }
+}
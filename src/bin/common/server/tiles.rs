@@ -0,0 +1,300 @@
+use std::error;
+use std::fmt;
+use std::fs::read;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use rusqlite::{self, Connection, OpenFlags, OptionalExtension, ToSql};
+
+const IMAGE_JPEG: &str = "image/jpeg";
+const IMAGE_PNG: &str = "image/png";
+const IMAGE_WEBP: &str = "image/webp";
+const APPLICATION_OCTET_STREAM: &str = "application/octet-stream";
+
+/// The file extensions that tiles in a tile directory may have, in order of preference.
+const TILE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "webp"];
+
+#[derive(Debug)]
+pub enum TileError {
+    IoError(io::Error),
+    SqliteError(rusqlite::Error),
+}
+
+impl fmt::Display for TileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TileError::IoError(e) => e.fmt(f),
+            TileError::SqliteError(e) => write!(f, "Couldn't read the MBTiles file: {}", e),
+        }
+    }
+}
+
+impl error::Error for TileError {
+    fn description(&self) -> &str {
+        match self {
+            TileError::IoError(e) => e.description(),
+            TileError::SqliteError(_) => "Couldn't read the MBTiles file",
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            TileError::IoError(e) => Some(e),
+            TileError::SqliteError(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for TileError {
+    fn from(error: io::Error) -> Self {
+        TileError::IoError(error)
+    }
+}
+
+impl From<rusqlite::Error> for TileError {
+    fn from(error: rusqlite::Error) -> Self {
+        TileError::SqliteError(error)
+    }
+}
+
+/// A source of raster map tiles in the XYZ tiling scheme used by most web maps, so that maps can
+/// be displayed without a network connection.
+#[derive(Debug)]
+pub enum TileSource {
+    /// An open MBTiles SQLite database. MBTiles stores tile rows in the TMS scheme, so rows are
+    /// flipped when reading from it. A connection can't be used by several threads at once, so
+    /// requests take turns to read from it.
+    MBTiles(Mutex<Connection>),
+    /// A directory laid out as `{z}/{x}/{y}.{extension}`.
+    Directory(PathBuf),
+}
+
+impl TileSource {
+    /// Use the given path as a tile source, checking that it is either a directory or an
+    /// MBTiles file that tiles can be read from.
+    pub fn new(path: &Path) -> Result<TileSource, TileError> {
+        if path.is_dir() {
+            return Ok(TileSource::Directory(path.to_path_buf()));
+        }
+
+        if !path.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Tile source not found").into());
+        }
+
+        let connection = open_mbtiles(path)?;
+        connection.prepare("SELECT tile_data FROM tiles LIMIT 1")?;
+
+        Ok(TileSource::MBTiles(Mutex::new(connection)))
+    }
+
+    /// Read the tile at the given zoom level and coordinates, returning `None` if the source
+    /// has no such tile.
+    pub fn tile(&self, zoom: u32, x: u32, y: u32) -> Result<Option<Tile>, TileError> {
+        if zoom > 30 || x >> zoom != 0 || y >> zoom != 0 {
+            return Ok(None);
+        }
+
+        let data = match self {
+            TileSource::MBTiles(connection) => {
+                let connection = connection.lock().unwrap_or_else(|e| e.into_inner());
+                read_mbtiles_tile(&connection, zoom, x, y)?
+            }
+            TileSource::Directory(path) => read_directory_tile(path, zoom, x, y)?,
+        };
+
+        Ok(data.map(Tile::new))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Tile {
+    data: Vec<u8>,
+    content_type: &'static str,
+}
+
+impl Tile {
+    fn new(data: Vec<u8>) -> Tile {
+        let content_type = content_type(&data);
+        Tile { data, content_type }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        self.content_type
+    }
+}
+
+fn open_mbtiles(path: &Path) -> Result<Connection, rusqlite::Error> {
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+}
+
+fn read_mbtiles_tile(
+    connection: &Connection,
+    zoom: u32,
+    x: u32,
+    y: u32,
+) -> Result<Option<Vec<u8>>, TileError> {
+    let row = (1u32 << zoom) - 1 - y;
+
+    let data = connection
+        .prepare_cached(
+            "SELECT tile_data FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+        )?.query_row(
+            &[&zoom as &ToSql, &x, &row],
+            |row| row.get(0),
+        ).optional()?;
+
+    Ok(data)
+}
+
+fn read_directory_tile(
+    path: &Path,
+    zoom: u32,
+    x: u32,
+    y: u32,
+) -> Result<Option<Vec<u8>>, TileError> {
+    let tile_path = path.join(zoom.to_string()).join(x.to_string());
+
+    for extension in &TILE_EXTENSIONS {
+        match read(tile_path.join(format!("{}.{}", y, extension))) {
+            Ok(data) => return Ok(Some(data)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Get the MIME type of a tile from its magic bytes, as MBTiles files don't store the format of
+/// each tile and directories of tiles may have misleading extensions.
+fn content_type(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        IMAGE_PNG
+    } else if data.starts_with(b"\xff\xd8\xff") {
+        IMAGE_JPEG
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        IMAGE_WEBP
+    } else {
+        APPLICATION_OCTET_STREAM
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    use std::fs::{create_dir_all, write};
+
+    use self::tempfile::tempdir;
+
+    const PNG_DATA: &[u8] = b"\x89PNG\r\n\x1a\n0000";
+    const JPEG_DATA: &[u8] = b"\xff\xd8\xff\xe00000";
+
+    fn create_mbtiles(path: &Path) {
+        let connection = Connection::open(path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE metadata (name TEXT, value TEXT);
+                 CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER,
+                    tile_data BLOB);",
+            ).unwrap();
+        connection
+            .execute(
+                "INSERT INTO tiles VALUES (2, 1, 0, ?1)",
+                &[&PNG_DATA as &ToSql],
+            ).unwrap();
+    }
+
+    #[test]
+    fn new_should_error_if_the_path_does_not_exist() {
+        let tmp_dir = tempdir().unwrap();
+
+        match TileSource::new(&tmp_dir.path().join("missing.mbtiles")) {
+            Err(TileError::IoError(ref e)) if e.kind() == io::ErrorKind::NotFound => {}
+            r => panic!("Unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn new_should_error_if_the_file_is_not_an_mbtiles_file() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("tiles.mbtiles");
+        write(&path, "not a database").unwrap();
+
+        assert!(TileSource::new(&path).is_err());
+    }
+
+    #[test]
+    fn new_should_recognise_directories_and_mbtiles_files() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("tiles.mbtiles");
+        create_mbtiles(&path);
+
+        match TileSource::new(tmp_dir.path()).unwrap() {
+            TileSource::Directory(ref directory) => assert_eq!(tmp_dir.path(), directory),
+            s => panic!("Expected a directory tile source, got {:?}", s),
+        }
+        match TileSource::new(&path).unwrap() {
+            TileSource::MBTiles(_) => {}
+            s => panic!("Expected an MBTiles tile source, got {:?}", s),
+        }
+    }
+
+    #[test]
+    fn tile_should_flip_the_row_when_reading_from_an_mbtiles_file() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("tiles.mbtiles");
+        create_mbtiles(&path);
+        let source = TileSource::new(&path).unwrap();
+
+        let tile = source.tile(2, 1, 3).unwrap().unwrap();
+
+        assert_eq!(PNG_DATA, tile.data());
+        assert_eq!(IMAGE_PNG, tile.content_type());
+        assert_eq!(None, source.tile(2, 1, 0).unwrap());
+    }
+
+    #[test]
+    fn tile_should_read_tiles_with_any_supported_extension_from_a_directory() {
+        let tmp_dir = tempdir().unwrap();
+        let tile_dir = tmp_dir.path().join("2").join("1");
+        create_dir_all(&tile_dir).unwrap();
+        write(tile_dir.join("3.jpg"), JPEG_DATA).unwrap();
+        let source = TileSource::new(tmp_dir.path()).unwrap();
+
+        let tile = source.tile(2, 1, 3).unwrap().unwrap();
+
+        assert_eq!(JPEG_DATA, tile.data());
+        assert_eq!(IMAGE_JPEG, tile.content_type());
+        assert_eq!(None, source.tile(2, 1, 2).unwrap());
+    }
+
+    #[test]
+    fn tile_should_return_none_for_coordinates_outside_the_zoom_level() {
+        let tmp_dir = tempdir().unwrap();
+        let source = TileSource::new(tmp_dir.path()).unwrap();
+
+        assert_eq!(None, source.tile(2, 4, 0).unwrap());
+        assert_eq!(None, source.tile(2, 0, 4).unwrap());
+        assert_eq!(None, source.tile(31, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn content_type_should_detect_png_jpeg_and_webp_data() {
+        assert_eq!(IMAGE_PNG, content_type(PNG_DATA));
+        assert_eq!(IMAGE_JPEG, content_type(JPEG_DATA));
+        assert_eq!(IMAGE_WEBP, content_type(b"RIFF0000WEBPVP8 "));
+        assert_eq!(APPLICATION_OCTET_STREAM, content_type(b"{}"));
+    }
+}
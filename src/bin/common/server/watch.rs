@@ -0,0 +1,531 @@
+use std::env::current_dir;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, RwLock, Weak};
+use std::thread;
+use std::time::Duration;
+
+use notify::{self, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use rayon::prelude::*;
+use yore::{is_supported_file, load_location_history};
+
+use common::photo_paths;
+
+use super::cache::delete_cached_images;
+use super::error::ServiceError;
+use super::events::GuiEvent;
+use super::image::ImageDimensions;
+use super::scan::scan_in_background;
+use super::state::{combine_histories, GuiState};
+
+/// How long a file must go unchanged before its changes are handled, so that photos and
+/// location histories aren't read while they're still being copied or exported.
+const DEBOUNCE_DELAY: Duration = Duration::from_secs(2);
+
+/// Watches the root path and location history path for changes, which are handled in the
+/// background.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    watched_paths: Vec<(PathBuf, RecursiveMode)>,
+}
+
+impl FileWatcher {
+    /// Watch the given root path and location history path, and stop watching any paths that
+    /// were previously watched for them.
+    pub fn watch(&mut self, root_path: Option<&PathBuf>, location_history_path: Option<&PathBuf>) {
+        let watched_paths = paths_to_watch(
+            root_path.map(PathBuf::as_path),
+            location_history_path.map(PathBuf::as_path),
+        );
+
+        for (path, _) in &self.watched_paths {
+            if !watched_paths.iter().any(|(p, _)| p == path) {
+                self.watcher.unwatch(path).ok();
+            }
+        }
+
+        for (path, mode) in &watched_paths {
+            if self.watched_paths.contains(&(path.clone(), *mode)) {
+                continue;
+            }
+
+            if let Err(e) = self.watcher.watch(path, *mode) {
+                eprintln!("Error watching {} for changes: {}", path.display(), e);
+            }
+        }
+
+        self.watched_paths = watched_paths;
+    }
+}
+
+/// Watch the root path and location history path of the given state for changes, including
+/// after either path changes, and apply the changes to the state in the background.
+///
+/// Changed photos have their cached images removed and their dimensions read again, new
+/// photos are added and removed photos are forgotten. The location history is loaded again
+/// when its file changes. `GuiEvent`s are sent for each change.
+pub fn watch_in_background(state: &Arc<RwLock<GuiState>>) -> Result<(), ServiceError> {
+    let (sender, receiver) = channel();
+    let watcher = notify::watcher(sender, DEBOUNCE_DELAY)?;
+
+    state.write()?.set_file_watcher(FileWatcher {
+        watcher,
+        watched_paths: Vec::new(),
+    });
+
+    // The state owns the watcher, so only hold a weak reference to it so that the watcher
+    // can be dropped along with the state, which ends the thread.
+    let state = Arc::downgrade(state);
+    thread::spawn(move || handle_events(&state, &receiver));
+
+    Ok(())
+}
+
+/// Files are watched by watching the directories that contain them, so that they're still
+/// watched if they're replaced.
+fn paths_to_watch(
+    root_path: Option<&Path>,
+    location_history_path: Option<&Path>,
+) -> Vec<(PathBuf, RecursiveMode)> {
+    let mut paths = Vec::new();
+
+    if let Some(root_path) = root_path {
+        if root_path.is_dir() {
+            paths.push((root_path.to_path_buf(), RecursiveMode::Recursive));
+        } else {
+            paths.push((parent_dir(root_path), RecursiveMode::NonRecursive));
+        }
+    }
+
+    if let Some(location_history_path) = location_history_path {
+        let directory = parent_dir(location_history_path);
+        let is_watched = paths.iter().any(|(path, mode)| {
+            directory == *path || (*mode == RecursiveMode::Recursive && directory.starts_with(path))
+        });
+
+        if !is_watched {
+            paths.push((directory, RecursiveMode::NonRecursive));
+        }
+    }
+
+    paths
+}
+
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn handle_events(state: &Weak<RwLock<GuiState>>, receiver: &Receiver<DebouncedEvent>) {
+    for event in receiver.iter() {
+        let state = match state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+
+        if let Err(e) = handle_event(&state, event) {
+            eprintln!("Error handling a file change: {}", e);
+        }
+    }
+}
+
+fn handle_event(state: &Arc<RwLock<GuiState>>, event: DebouncedEvent) -> Result<(), ServiceError> {
+    match event {
+        DebouncedEvent::Create(path) | DebouncedEvent::Write(path) => handle_change(state, &path),
+        DebouncedEvent::Remove(path) => handle_removal(state, &path),
+        DebouncedEvent::Rename(from, to) => {
+            handle_removal(state, &from)?;
+            handle_change(state, &to)
+        }
+        DebouncedEvent::Rescan => rescan(state),
+        DebouncedEvent::Error(error, path) => {
+            let path = path.map(|p| p.display().to_string()).unwrap_or_default();
+            eprintln!("Error watching {} for changes: {}", path, error);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn handle_change(state: &RwLock<GuiState>, path: &Path) -> Result<(), ServiceError> {
+    let (root_path, location_history_path) = {
+        let state = state.read()?;
+        (
+            state.root_path().cloned(),
+            state.location_history_path().cloned(),
+        )
+    };
+
+    if let Some(location_history_path) = location_history_path {
+        if within(path, &location_history_path).is_some() {
+            return reload_location_history(state, &location_history_path);
+        }
+    }
+
+    let path = match root_path.and_then(|root_path| within(path, &root_path)) {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    let paths = if path.is_dir() {
+        photo_paths(&path)
+    } else if is_supported_file(&path) {
+        vec![path]
+    } else {
+        return Ok(());
+    };
+
+    let results: Vec<Result<ImageDimensions, (PathBuf, ServiceError)>> = paths
+        .par_iter()
+        .map(|path| ImageDimensions::new(path).map_err(|e| (path.clone(), e)))
+        .collect();
+
+    let mut state = state.write()?;
+    let mut added_photos = Vec::new();
+    let mut stale_images = Vec::new();
+    for result in results {
+        match result {
+            Ok(photo) => {
+                stale_images.extend(state.image_cache().forget(photo.path()));
+                state.summary_cache().remove(photo.path());
+
+                if state.update_photo(photo.clone()) {
+                    state.events().send(&GuiEvent::PhotoChanged { photo });
+                } else {
+                    added_photos.push(photo);
+                }
+            }
+            Err((path, error)) => state.events().send(&GuiEvent::PhotoFailed {
+                path,
+                error: format!("{}", error),
+            }),
+        }
+    }
+
+    let added_photos = state.add_photos(added_photos);
+    if !added_photos.is_empty() {
+        state.events().send(&GuiEvent::PhotosFound {
+            photos: added_photos,
+        });
+    }
+    drop(state);
+
+    // The files are deleted after unlocking the state, so that requests aren't blocked.
+    delete_cached_images(&stale_images)?;
+
+    Ok(())
+}
+
+/// A removed location history is kept loaded, as it's probably about to be replaced.
+fn handle_removal(state: &RwLock<GuiState>, path: &Path) -> Result<(), ServiceError> {
+    let mut state = state.write()?;
+
+    let root_path = state.root_path().cloned();
+    let path = match root_path.and_then(|root_path| within(path, &root_path)) {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    let paths = state.remove_photos(&path);
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    let mut stale_images = Vec::new();
+    for path in &paths {
+        stale_images.extend(state.image_cache().forget(path));
+        state.summary_cache().remove(path);
+    }
+    state.events().send(&GuiEvent::PhotosRemoved { paths });
+    drop(state);
+
+    // The files are deleted after unlocking the state, so that requests aren't blocked.
+    delete_cached_images(&stale_images)?;
+
+    Ok(())
+}
+
+fn reload_location_history(
+    state: &RwLock<GuiState>,
+    location_history_path: &Path,
+) -> Result<(), ServiceError> {
+    let file = File::open(location_history_path)?;
+    let location_history = unsafe { load_location_history(&file)? };
+
+    {
+        let mut state = state.write()?;
+        if state.location_history_path().map(PathBuf::as_path) != Some(location_history_path) {
+            return Ok(());
+        }
+
+        state.reload_location_history(location_history);
+        state.events().send(&GuiEvent::LocationHistoryReloaded {
+            location_history_path: location_history_path.to_path_buf(),
+        });
+    }
+
+    combine_histories(state)
+}
+
+/// Some changes were missed, so scan for photos and load the location history again.
+fn rescan(state: &Arc<RwLock<GuiState>>) -> Result<(), ServiceError> {
+    let (root_path, location_history_path) = {
+        let state = state.read()?;
+        (
+            state.root_path().cloned(),
+            state.location_history_path().cloned(),
+        )
+    };
+
+    if let Some(root_path) = root_path {
+        scan_in_background(state, root_path)?;
+    }
+
+    if let Some(location_history_path) = location_history_path {
+        reload_location_history(state, &location_history_path)?;
+    }
+
+    Ok(())
+}
+
+/// If the given path reported by the watcher is or is inside the given watched path, get it
+/// relative to the watched path in the same way, as the watcher always reports absolute paths,
+/// which are also canonical on macOS.
+fn within(path: &Path, watched_path: &Path) -> Option<PathBuf> {
+    if path.starts_with(watched_path) {
+        return Some(path.to_path_buf());
+    }
+
+    let absolute_watched_paths = current_dir()
+        .map(|dir| dir.join(watched_path))
+        .into_iter()
+        .chain(watched_path.canonicalize());
+
+    for absolute_watched_path in absolute_watched_paths {
+        if let Ok(relative_path) = path.strip_prefix(&absolute_watched_path) {
+            if relative_path.as_os_str().is_empty() {
+                return Some(watched_path.to_path_buf());
+            }
+            return Some(watched_path.join(relative_path));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    use std::fs::{copy, create_dir, remove_file};
+    use std::sync::mpsc;
+
+    use futures::Stream;
+    use yore::Journal;
+
+    use self::tempfile::tempdir;
+
+    /// Create a state with photos from a "photos" directory in the given directory.
+    fn new_state(dir: &Path) -> Arc<RwLock<GuiState>> {
+        let mut state = GuiState::new(&dir.join("cache"), Journal::new(&dir.join("journal")));
+        state.search_new_root_path(dir.join("photos"));
+        Arc::new(RwLock::new(state))
+    }
+
+    /// Receive event messages on another thread so that waiting for them can time out.
+    fn receive_events(state: &RwLock<GuiState>) -> mpsc::Receiver<String> {
+        let receiver = state.read().unwrap().events().subscribe(&[]);
+        let (sender, messages) = mpsc::channel();
+
+        thread::spawn(move || {
+            for message in receiver.wait() {
+                let message = String::from_utf8_lossy(&message.unwrap()).into_owned();
+                if sender.send(message).is_err() {
+                    return;
+                }
+            }
+        });
+
+        messages
+    }
+
+    fn next_message(messages: &mpsc::Receiver<String>) -> String {
+        messages.recv_timeout(Duration::from_secs(10)).unwrap()
+    }
+
+    #[test]
+    fn paths_to_watch_should_watch_the_root_directory_recursively() {
+        let paths = paths_to_watch(
+            Some(Path::new("tests/assets")),
+            Some(Path::new("tests/assets/location_history.json")),
+        );
+
+        assert_eq!(
+            vec![(PathBuf::from("tests/assets"), RecursiveMode::Recursive)],
+            paths
+        );
+    }
+
+    #[test]
+    fn paths_to_watch_should_watch_the_directories_containing_files() {
+        let paths = paths_to_watch(
+            Some(Path::new("tests/assets/photo.jpg")),
+            Some(Path::new("location_history.json")),
+        );
+
+        assert_eq!(
+            vec![
+                (PathBuf::from("tests/assets"), RecursiveMode::NonRecursive),
+                (PathBuf::from("."), RecursiveMode::NonRecursive),
+            ],
+            paths
+        );
+    }
+
+    #[test]
+    fn within_should_express_absolute_paths_like_the_relative_watched_path() {
+        let path = current_dir().unwrap().join("tests/assets/photo.jpg");
+
+        assert_eq!(
+            Some(PathBuf::from("tests/assets/photo.jpg")),
+            within(&path, Path::new("tests/assets"))
+        );
+        assert_eq!(
+            Some(PathBuf::from("tests/assets/photo.jpg")),
+            within(&path, Path::new("tests/assets/photo.jpg"))
+        );
+        assert_eq!(None, within(&path, Path::new("tests/js")));
+    }
+
+    #[test]
+    fn handle_event_should_add_new_photos() {
+        let tmp_dir = tempdir().unwrap();
+        let state = new_state(tmp_dir.path());
+
+        let subdir = tmp_dir.path().join("photos/subdir");
+        create_dir(tmp_dir.path().join("photos")).unwrap();
+        create_dir(&subdir).unwrap();
+        copy("tests/assets/photo.jpg", subdir.join("photo.jpg")).unwrap();
+        copy("Cargo.toml", subdir.join("Cargo.toml")).unwrap();
+
+        handle_event(&state, DebouncedEvent::Create(subdir.clone())).unwrap();
+
+        let state = state.read().unwrap();
+        assert_eq!(vec![subdir.join("photo.jpg")], state.photo_paths());
+        assert_eq!(1, state.photos().len());
+    }
+
+    #[test]
+    fn handle_event_should_not_duplicate_photos_that_a_scan_adds_later() {
+        let tmp_dir = tempdir().unwrap();
+        let photo_path = tmp_dir.path().join("photos/photo.jpg");
+        create_dir(tmp_dir.path().join("photos")).unwrap();
+        let state = new_state(tmp_dir.path());
+        state
+            .write()
+            .unwrap()
+            .start_scan(tmp_dir.path().join("photos"));
+
+        copy("tests/assets/photo.jpg", &photo_path).unwrap();
+        handle_event(&state, DebouncedEvent::Create(photo_path.clone())).unwrap();
+
+        let photo = ImageDimensions::new(&photo_path).unwrap();
+        let added_photos = state.write().unwrap().add_photos(vec![photo]);
+
+        assert!(added_photos.is_empty());
+        let state = state.read().unwrap();
+        assert_eq!(vec![photo_path], state.photo_paths());
+        assert_eq!(1, state.photos().len());
+    }
+
+    #[test]
+    fn handle_event_should_replace_changed_photos_and_remove_their_cached_images() {
+        let tmp_dir = tempdir().unwrap();
+        let photo_path = tmp_dir.path().join("photos/photo.jpg");
+        create_dir(tmp_dir.path().join("photos")).unwrap();
+        copy("tests/assets/photo.jpg", &photo_path).unwrap();
+        let state = new_state(tmp_dir.path());
+
+        let cached_path = {
+            let state = state.read().unwrap();
+            let cache = state.image_cache();
+            cache.insert(&photo_path, 300, 300, b"cached").unwrap();
+            cache.cached_image_path(&photo_path, 300, 300).unwrap()
+        };
+        assert!(cached_path.exists());
+
+        let messages = receive_events(&state);
+        copy("tests/assets/photo_rotated.jpg", &photo_path).unwrap();
+        handle_event(&state, DebouncedEvent::Write(photo_path.clone())).unwrap();
+
+        assert!(!cached_path.exists());
+        assert_eq!(1, state.read().unwrap().photos().len());
+        assert!(next_message(&messages).starts_with("data: {\"PhotoChanged\":"));
+    }
+
+    #[test]
+    fn handle_event_should_forget_removed_photos() {
+        let tmp_dir = tempdir().unwrap();
+        let photo_path = tmp_dir.path().join("photos/photo.jpg");
+        create_dir(tmp_dir.path().join("photos")).unwrap();
+        copy("tests/assets/photo.jpg", &photo_path).unwrap();
+        let state = new_state(tmp_dir.path());
+        assert_eq!(1, state.read().unwrap().photos().len());
+
+        let messages = receive_events(&state);
+        remove_file(&photo_path).unwrap();
+        handle_event(&state, DebouncedEvent::Remove(photo_path.clone())).unwrap();
+
+        assert!(state.read().unwrap().photos().is_empty());
+        assert_eq!(
+            format!(
+                "data: {{\"PhotosRemoved\":{{\"paths\":[\"{}\"]}}}}\n\n",
+                photo_path.display()
+            ),
+            next_message(&messages)
+        );
+    }
+
+    #[test]
+    fn handle_event_should_ignore_photos_outside_the_root_path() {
+        let tmp_dir = tempdir().unwrap();
+        let state = new_state(tmp_dir.path());
+
+        let photo_path = tmp_dir.path().join("photo.jpg");
+        copy("tests/assets/photo.jpg", &photo_path).unwrap();
+        handle_event(&state, DebouncedEvent::Create(photo_path)).unwrap();
+
+        assert!(state.read().unwrap().photos().is_empty());
+    }
+
+    #[test]
+    fn watch_in_background_should_reload_a_changed_location_history() {
+        let tmp_dir = tempdir().unwrap();
+        let state = new_state(tmp_dir.path());
+
+        let history_path = tmp_dir.path().join("location_history.json");
+        copy("tests/assets/location_history.json", &history_path).unwrap();
+        state
+            .write()
+            .unwrap()
+            .load_location_history(history_path.clone())
+            .unwrap();
+        watch_in_background(&state).unwrap();
+
+        let messages = receive_events(&state);
+        copy("tests/assets/location_history.json", &history_path).unwrap();
+
+        assert_eq!(
+            format!(
+                "data: {{\"LocationHistoryReloaded\":{{\"locationHistoryPath\":\"{}\"}}}}\n\n",
+                history_path.display()
+            ),
+            next_message(&messages)
+        );
+    }
+}
@@ -1,21 +1,28 @@
 #![windows_subsystem = "windows"]
 
 extern crate actix_web;
+extern crate bytes;
+extern crate chrono;
+extern crate csv;
 extern crate directories;
 extern crate exif;
 extern crate futures;
 extern crate image;
 extern crate jpeg_decoder;
+extern crate notify;
 extern crate rayon;
+extern crate rusqlite;
 extern crate serde;
-extern crate serde_json;
 extern crate tinyfiledialogs;
 extern crate web_view;
 extern crate yore;
+extern crate zip;
 
 #[macro_use]
 extern crate serde_derive;
 #[macro_use]
+extern crate serde_json;
+#[macro_use]
 extern crate structopt;
 
 mod common;
@@ -25,6 +32,7 @@ use std::path::PathBuf;
 use structopt::StructOpt;
 
 use common::server::Server;
+use common::{CopyFromPhotosArgs, TimestampFallbackArgs, WriteArgs};
 
 #[derive(StructOpt)]
 #[structopt(
@@ -48,6 +56,39 @@ struct Options {
     )]
     interpolate: bool,
 
+    #[structopt(flatten)]
+    copy_from_photos_args: CopyFromPhotosArgs,
+
+    #[structopt(flatten)]
+    timestamp_fallback_args: TimestampFallbackArgs,
+
+    #[structopt(
+        long = "places",
+        parse(from_os_str),
+        help = "The path to a GeoNames cities file, e.g. cities15000.txt, to name the places that \
+                locations are in"
+    )]
+    places_path: Option<PathBuf>,
+
+    #[structopt(
+        long = "tiles",
+        parse(from_os_str),
+        help = "An MBTiles file or a directory of {z}/{x}/{y} map tiles to show the map with, so \
+                that the GUI works offline"
+    )]
+    tiles_path: Option<PathBuf>,
+
+    #[structopt(
+        long = "cache-size",
+        default_value = "500",
+        help = "The maximum size in megabytes of the thumbnails cached between runs, beyond which \
+                the least recently used thumbnails are removed"
+    )]
+    cache_size: u64,
+
+    #[structopt(flatten)]
+    write_args: WriteArgs,
+
     #[structopt(
         parse(from_os_str),
         help = "The image or a directory of images to suggest a location for"
@@ -59,6 +100,18 @@ fn main() {
     let options = Options::from_args();
 
     let mut server = Server::new(0, options.interpolate);
+    server
+        .set_max_cache_size(options.cache_size * 1024 * 1024)
+        .unwrap();
+    server.set_copy_from_photos(options.copy_from_photos_args.copy_from_photos());
+    server.set_timestamp_fallbacks(
+        options
+            .timestamp_fallback_args
+            .timestamp_fallbacks()
+            .to_vec(),
+    );
+    server.set_write_options(options.write_args.write_options());
+    server.set_write_place_names(options.write_args.write_place_names());
 
     if let Some(path) = options.photo_path {
         server.search_photos_path(&path);
@@ -68,6 +121,14 @@ fn main() {
         server.load_location_history(&path).unwrap();
     }
 
+    if let Some(path) = options.places_path {
+        server.load_places(&path).unwrap();
+    }
+
+    if let Some(path) = options.tiles_path {
+        server.set_tile_source(&path).unwrap();
+    }
+
     run_webview(server);
 }
 
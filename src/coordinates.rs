@@ -42,6 +42,44 @@ impl Coordinates {
         }
     }
 
+    /// Check that the latitude is within ±90° and the longitude is within ±180°, which also
+    /// rules out non-finite values.
+    pub fn is_valid(&self) -> bool {
+        self.latitude.abs() <= 90.0 && self.longitude.abs() <= 180.0
+    }
+
+    /// Parse a point from an ISO 6709 string, e.g. "+52.0796+001.1965+012.345/", as written
+    /// in QuickTime location metadata. Any altitude or coordinate reference system is
+    /// ignored.
+    pub fn from_iso6709(value: &str) -> Option<Coordinates> {
+        let mut components: Vec<String> = Vec::new();
+        for character in value.chars() {
+            match character {
+                '+' | '-' => components.push(character.to_string()),
+                '0'..='9' | '.' => components.last_mut()?.push(character),
+                _ => break,
+            }
+        }
+
+        if components.len() < 2 {
+            return None;
+        }
+
+        let latitude = iso6709_component_to_decimal(&components[0], 2)?;
+        let longitude = iso6709_component_to_decimal(&components[1], 3)?;
+
+        let coordinates = Coordinates::new(latitude, longitude);
+        if coordinates.is_valid() {
+            Some(coordinates)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_iso6709(&self) -> String {
+        format!("{:+010.6}{:+011.6}/", self.latitude, self.longitude)
+    }
+
     pub fn map_url(&self) -> String {
         format!(
             "<https://www.google.co.uk/maps/place/{}%2C{}>",
@@ -70,6 +108,34 @@ impl fmt::Display for Coordinates {
     }
 }
 
+/// ISO 6709 allows degrees, degrees and minutes, or degrees, minutes and seconds, which are
+/// distinguished by the number of digits before the decimal point.
+fn iso6709_component_to_decimal(component: &str, degree_digits: usize) -> Option<f64> {
+    let (sign, digits) = component.split_at(1);
+    let integer_digits = digits.find('.').unwrap_or(digits.len());
+
+    let value = if integer_digits == degree_digits {
+        digits.parse::<f64>().ok()?
+    } else if integer_digits == degree_digits + 2 {
+        let degrees = digits[..degree_digits].parse::<f64>().ok()?;
+        let minutes = digits[degree_digits..].parse::<f64>().ok()?;
+        degrees + minutes / 60.0
+    } else if integer_digits == degree_digits + 4 {
+        let degrees = digits[..degree_digits].parse::<f64>().ok()?;
+        let minutes = digits[degree_digits..degree_digits + 2].parse::<f64>().ok()?;
+        let seconds = digits[degree_digits + 2..].parse::<f64>().ok()?;
+        degrees + minutes / 60.0 + seconds / 3600.0
+    } else {
+        return None;
+    };
+
+    if sign == "-" {
+        Some(-value)
+    } else {
+        Some(value)
+    }
+}
+
 fn haversine(angle_in_radians: f64) -> f64 {
     (1.0 - angle_in_radians.cos()) / 2.0
 }
@@ -98,6 +164,61 @@ mod tests {
         assert_eq!(10036.0, distance.round());
     }
 
+    #[test]
+    fn from_iso6709_should_parse_decimal_degrees() {
+        let coordinates = Coordinates::from_iso6709("+52.0796+001.1965/").unwrap();
+
+        assert_eq!(52.0796, coordinates.latitude());
+        assert_eq!(1.1965, coordinates.longitude());
+
+        let coordinates = Coordinates::from_iso6709("-13.163333-072.545556+2430.0/").unwrap();
+
+        assert_eq!(-13.163333, coordinates.latitude());
+        assert_eq!(-72.545556, coordinates.longitude());
+    }
+
+    #[test]
+    fn from_iso6709_should_parse_degrees_minutes_and_seconds() {
+        let coordinates = Coordinates::from_iso6709("+4003-07500/").unwrap();
+
+        assert_eq!(40.05, coordinates.latitude());
+        assert_eq!(-75.0, coordinates.longitude());
+
+        let coordinates = Coordinates::from_iso6709("+400330-0750036CRSWGS_84/").unwrap();
+
+        assert_eq!(40.0583, (coordinates.latitude() * 1e4).round() / 1e4);
+        assert_eq!(-75.01, coordinates.longitude());
+    }
+
+    #[test]
+    fn from_iso6709_should_return_none_for_invalid_strings() {
+        assert_eq!(None, Coordinates::from_iso6709(""));
+        assert_eq!(None, Coordinates::from_iso6709("52.0796+001.1965/"));
+        assert_eq!(None, Coordinates::from_iso6709("+52.0796/"));
+        assert_eq!(None, Coordinates::from_iso6709("+95.0+001.1965/"));
+    }
+
+    #[test]
+    fn to_iso6709_should_print_signed_zero_padded_decimal_degrees() {
+        let coordinates = Coordinates::new(52.0796733, -1.1965831);
+
+        assert_eq!("+52.079673-001.196583/", coordinates.to_iso6709());
+        assert_eq!(
+            Some(Coordinates::new(52.079673, -1.196583)),
+            Coordinates::from_iso6709(&coordinates.to_iso6709())
+        );
+    }
+
+    #[test]
+    fn is_valid_should_be_false_for_out_of_range_or_non_finite_values() {
+        assert!(Coordinates::new(90.0, -180.0).is_valid());
+        assert!(Coordinates::new(-90.0, 180.0).is_valid());
+        assert!(!Coordinates::new(90.1, 0.0).is_valid());
+        assert!(!Coordinates::new(0.0, -180.1).is_valid());
+        assert!(!Coordinates::new(f64::NAN, 0.0).is_valid());
+        assert!(!Coordinates::new(0.0, f64::INFINITY).is_valid());
+    }
+
     #[test]
     fn latitude_ref_should_be_north_for_0_and_greater_and_south_otherwise() {
         let coordinates = Coordinates::new(0.0, 0.0);
@@ -0,0 +1,474 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use coordinates::Coordinates;
+
+/// The GeoNames file of first-level administrative division names, which is looked for next to
+/// the cities file.
+const ADMIN1_CODES_FILENAME: &str = "admin1CodesASCII.txt";
+
+/// The GeoNames file of country names, which is looked for next to the cities file.
+const COUNTRY_INFO_FILENAME: &str = "countryInfo.txt";
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Debug)]
+pub enum GeocoderError {
+    IoError(io::Error),
+    /// A line of a GeoNames file couldn't be parsed. The line number starts at 1.
+    ParseError(usize, String),
+}
+
+impl fmt::Display for GeocoderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GeocoderError::IoError(e) => e.fmt(f),
+            GeocoderError::ParseError(line, reason) => {
+                write!(f, "Invalid GeoNames data on line {}: {}", line, reason)
+            }
+        }
+    }
+}
+
+impl error::Error for GeocoderError {
+    fn description(&self) -> &str {
+        match self {
+            GeocoderError::IoError(e) => e.description(),
+            GeocoderError::ParseError(_, _) => "Invalid GeoNames data",
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            GeocoderError::IoError(e) => Some(e),
+            GeocoderError::ParseError(_, _) => None,
+        }
+    }
+}
+
+impl From<io::Error> for GeocoderError {
+    fn from(error: io::Error) -> Self {
+        GeocoderError::IoError(error)
+    }
+}
+
+/// A named place, e.g. a town, along with the region and country it's in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Place {
+    name: String,
+    region: Option<String>,
+    country: String,
+    country_code: String,
+}
+
+impl Place {
+    pub fn new(name: &str, region: Option<&str>, country: &str, country_code: &str) -> Place {
+        Place {
+            name: name.to_string(),
+            region: region.map(str::to_string),
+            country: country.to_string(),
+            country_code: country_code.to_string(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The first-level administrative division that the place is in, e.g. a state or county,
+    /// if known.
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_ref().map(String::as_str)
+    }
+
+    /// The name of the country the place is in, or its ISO 3166-1 alpha-2 code if its name is
+    /// not known.
+    pub fn country(&self) -> &str {
+        &self.country
+    }
+
+    pub fn country_code(&self) -> &str {
+        &self.country_code
+    }
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(ref region) = self.region {
+            write!(f, ", {}", region)?;
+        }
+        if !self.country.is_empty() {
+            write!(f, ", {}", self.country)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct IndexedPoint {
+    position: [f64; 3],
+    place: usize,
+}
+
+/// Finds the nearest named place to given coordinates, using GeoNames data so that no network
+/// connection is needed.
+///
+/// Places are indexed in a k-d tree of points on the unit sphere, as straight-line distances
+/// between those points are ordered the same way as great-circle distances and don't need any
+/// special handling at the poles or the antimeridian.
+#[derive(Debug)]
+pub struct ReverseGeocoder {
+    places: Vec<Place>,
+    tree: Vec<IndexedPoint>,
+}
+
+impl ReverseGeocoder {
+    /// Load a GeoNames cities file, e.g. `cities15000.txt`. If `admin1CodesASCII.txt` and
+    /// `countryInfo.txt` are in the same directory they are used for region and country names,
+    /// otherwise regions are omitted and countries are given by their codes.
+    pub fn load(cities_path: &Path) -> Result<ReverseGeocoder, GeocoderError> {
+        let cities = BufReader::new(File::open(cities_path)?);
+        let directory = cities_path.parent().unwrap_or_else(|| Path::new(""));
+
+        let admin1_codes = open_if_exists(&directory.join(ADMIN1_CODES_FILENAME))?;
+        let country_info = open_if_exists(&directory.join(COUNTRY_INFO_FILENAME))?;
+
+        ReverseGeocoder::from_readers(cities, admin1_codes, country_info)
+    }
+
+    /// Read places from the contents of a GeoNames cities file and optionally its first-level
+    /// administrative division codes and country info files.
+    pub fn from_readers<R: BufRead>(
+        cities: R,
+        admin1_codes: Option<R>,
+        country_info: Option<R>,
+    ) -> Result<ReverseGeocoder, GeocoderError> {
+        let regions = match admin1_codes {
+            Some(reader) => read_names(reader, 0, 1)?,
+            None => HashMap::new(),
+        };
+        let countries = match country_info {
+            Some(reader) => read_names(reader, 0, 4)?,
+            None => HashMap::new(),
+        };
+
+        let mut places = Vec::new();
+        let mut tree = Vec::new();
+        for (index, line) in cities.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 11 {
+                return Err(GeocoderError::ParseError(
+                    index + 1,
+                    format!("expected at least 11 fields, found {}", fields.len()),
+                ));
+            }
+
+            let coordinates = Coordinates::new(
+                parse_degrees(fields[4], index)?,
+                parse_degrees(fields[5], index)?,
+            );
+            let country_code = fields[8];
+            let region = regions
+                .get(&format!("{}.{}", country_code, fields[10]))
+                .cloned();
+            let country = countries
+                .get(country_code)
+                .cloned()
+                .unwrap_or_else(|| country_code.to_string());
+
+            tree.push(IndexedPoint {
+                position: unit_vector(&coordinates),
+                place: places.len(),
+            });
+            places.push(Place {
+                name: fields[1].to_string(),
+                region,
+                country,
+                country_code: country_code.to_string(),
+            });
+        }
+
+        build_tree(&mut tree, 0);
+
+        Ok(ReverseGeocoder { places, tree })
+    }
+
+    pub fn len(&self) -> usize {
+        self.places.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.places.is_empty()
+    }
+
+    /// Get the place nearest to the given coordinates, and its great-circle distance from them
+    /// in kilometres.
+    pub fn nearest(&self, coordinates: &Coordinates) -> Option<(&Place, f64)> {
+        let target = unit_vector(coordinates);
+        let mut best = (f64::INFINITY, None);
+        find_nearest(&self.tree, &target, 0, &mut best);
+
+        let (squared_chord, index) = best;
+        index.map(|index| {
+            let angle = 2.0 * (squared_chord.sqrt() / 2.0).min(1.0).asin();
+            (&self.places[index], angle * EARTH_RADIUS_KM)
+        })
+    }
+}
+
+fn open_if_exists(path: &Path) -> io::Result<Option<BufReader<File>>> {
+    match File::open(path) {
+        Ok(file) => Ok(Some(BufReader::new(file))),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Read a map of codes to names from a tab-separated GeoNames file, skipping comment lines.
+fn read_names<R: BufRead>(
+    reader: R,
+    code_field: usize,
+    name_field: usize,
+) -> Result<HashMap<String, String>, GeocoderError> {
+    let mut names = HashMap::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        match (fields.get(code_field), fields.get(name_field)) {
+            (Some(code), Some(name)) => names.insert(code.to_string(), name.to_string()),
+            _ => {
+                return Err(GeocoderError::ParseError(
+                    index + 1,
+                    format!(
+                        "expected at least {} fields",
+                        name_field.max(code_field) + 1
+                    ),
+                ))
+            }
+        };
+    }
+
+    Ok(names)
+}
+
+fn parse_degrees(value: &str, index: usize) -> Result<f64, GeocoderError> {
+    value
+        .parse()
+        .map_err(|_| GeocoderError::ParseError(index + 1, format!("invalid coordinate {}", value)))
+}
+
+fn unit_vector(coordinates: &Coordinates) -> [f64; 3] {
+    let latitude = coordinates.latitude().to_radians();
+    let longitude = coordinates.longitude().to_radians();
+
+    [
+        latitude.cos() * longitude.cos(),
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+    ]
+}
+
+fn squared_distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// Arrange the points as an implicit k-d tree: the median point along the current axis is in
+/// the middle of the slice, with the points before it in its left subtree and the points after
+/// it in its right subtree.
+fn build_tree(points: &mut [IndexedPoint], depth: usize) {
+    if points.len() <= 1 {
+        return;
+    }
+
+    let axis = depth % 3;
+    points.sort_unstable_by(|a, b| {
+        a.position[axis]
+            .partial_cmp(&b.position[axis])
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let middle = points.len() / 2;
+    let (left, right) = points.split_at_mut(middle);
+    build_tree(left, depth + 1);
+    build_tree(&mut right[1..], depth + 1);
+}
+
+fn find_nearest(
+    points: &[IndexedPoint],
+    target: &[f64; 3],
+    depth: usize,
+    best: &mut (f64, Option<usize>),
+) {
+    if points.is_empty() {
+        return;
+    }
+
+    let middle = points.len() / 2;
+    let point = &points[middle];
+
+    let distance = squared_distance(&point.position, target);
+    if distance < best.0 {
+        *best = (distance, Some(point.place));
+    }
+
+    let axis = depth % 3;
+    let axis_distance = target[axis] - point.position[axis];
+    let (near, far) = if axis_distance < 0.0 {
+        (&points[..middle], &points[middle + 1..])
+    } else {
+        (&points[middle + 1..], &points[..middle])
+    };
+
+    find_nearest(near, target, depth + 1, best);
+
+    // The other side of the splitting plane can only hold a nearer point if the plane is nearer
+    // than the best point found so far.
+    if axis_distance * axis_distance < best.0 {
+        find_nearest(far, target, depth + 1, best);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geocoder() -> ReverseGeocoder {
+        ReverseGeocoder::load(Path::new("tests/assets/geonames/cities.txt")).unwrap()
+    }
+
+    fn nearest_name(geocoder: &ReverseGeocoder, latitude: f64, longitude: f64) -> String {
+        let coordinates = Coordinates::new(latitude, longitude);
+        geocoder.nearest(&coordinates).unwrap().0.name().to_string()
+    }
+
+    #[test]
+    fn load_should_read_region_and_country_names_from_sibling_files() {
+        let geocoder = geocoder();
+
+        assert_eq!(9, geocoder.len());
+
+        let (place, _) = geocoder
+            .nearest(&Coordinates::new(52.0796733, 1.1965831))
+            .unwrap();
+        assert_eq!("Ipswich", place.name());
+        assert_eq!(Some("England"), place.region());
+        assert_eq!("United Kingdom", place.country());
+        assert_eq!("GB", place.country_code());
+    }
+
+    #[test]
+    fn from_readers_should_use_country_codes_if_there_are_no_country_names() {
+        let cities = "2646057\tIpswich\tIpswich\t\t52.05917\t1.15545\tP\tPPLA2\tGB\t\tENG\n";
+
+        let geocoder = ReverseGeocoder::from_readers(cities.as_bytes(), None, None).unwrap();
+        let (place, _) = geocoder.nearest(&Coordinates::new(52.0, 1.0)).unwrap();
+
+        assert_eq!(None, place.region());
+        assert_eq!("GB", place.country());
+        assert_eq!("Ipswich, GB", place.to_string());
+    }
+
+    #[test]
+    fn from_readers_should_error_on_lines_with_too_few_fields() {
+        let cities = "2646057\tIpswich\n";
+
+        match ReverseGeocoder::from_readers(cities.as_bytes(), None, None) {
+            Err(GeocoderError::ParseError(1, _)) => {}
+            r => panic!("Unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn from_readers_should_error_on_invalid_coordinates() {
+        let cities = "2646057\tIpswich\tIpswich\t\tnorth\t1.15545\tP\tPPLA2\tGB\t\tENG\n";
+
+        match ReverseGeocoder::from_readers(cities.as_bytes(), None, None) {
+            Err(GeocoderError::ParseError(1, _)) => {}
+            r => panic!("Unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn nearest_should_return_none_if_there_are_no_places() {
+        let geocoder = ReverseGeocoder::from_readers("".as_bytes(), None, None).unwrap();
+
+        assert!(geocoder.is_empty());
+        assert_eq!(None, geocoder.nearest(&Coordinates::new(0.0, 0.0)));
+    }
+
+    #[test]
+    fn nearest_should_find_the_closest_place_by_great_circle_distance() {
+        let geocoder = geocoder();
+
+        assert_eq!("Sacavém", nearest_name(&geocoder, 38.76544, -9.094802));
+        assert_eq!("Lisbon", nearest_name(&geocoder, 38.72, -9.14));
+        assert_eq!("Woodbridge", nearest_name(&geocoder, 52.09, 1.3));
+        assert_eq!(
+            "Copenhagen",
+            nearest_name(&geocoder, 55.6382576, 12.6572722)
+        );
+        assert_eq!("Suva", nearest_name(&geocoder, -18.1, -179.9));
+    }
+
+    #[test]
+    fn nearest_should_match_a_linear_search() {
+        let geocoder = geocoder();
+
+        for latitude in (-9..10).map(|l| f64::from(l) * 10.0) {
+            for longitude in (-18..19).map(|l| f64::from(l) * 10.0) {
+                let coordinates = Coordinates::new(latitude, longitude);
+                let target = unit_vector(&coordinates);
+                let expected = geocoder
+                    .tree
+                    .iter()
+                    .min_by(|a, b| {
+                        squared_distance(&a.position, &target)
+                            .partial_cmp(&squared_distance(&b.position, &target))
+                            .unwrap()
+                    })
+                    .map(|p| &geocoder.places[p.place]);
+
+                assert_eq!(expected, geocoder.nearest(&coordinates).map(|n| n.0));
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_should_give_the_distance_in_kilometres() {
+        let geocoder = geocoder();
+
+        let (_, distance) = geocoder
+            .nearest(&Coordinates::new(51.50853, -0.12574))
+            .unwrap();
+        assert!(distance < 0.001);
+
+        let (place, distance) = geocoder.nearest(&Coordinates::new(52.0, 1.0)).unwrap();
+        let expected =
+            Coordinates::new(52.05917, 1.15545).distance_in_km(&Coordinates::new(52.0, 1.0));
+        assert_eq!("Ipswich", place.name());
+        assert!((distance - expected).abs() < 0.1);
+    }
+
+    #[test]
+    fn place_display_should_join_name_region_and_country() {
+        let place = Place::new("Ipswich", Some("England"), "United Kingdom", "GB");
+
+        assert_eq!("Ipswich, England, United Kingdom", place.to_string());
+    }
+}
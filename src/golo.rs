@@ -1,3 +1,4 @@
+use std::collections::btree_map::Values;
 use std::collections::BTreeMap;
 use std::fs::File;
 use std::io;
@@ -8,6 +9,11 @@ use serde::{de, Deserialize, Deserializer};
 use serde_json;
 
 use coordinates;
+use photo::Photo;
+
+/// The accuracy in meters assumed for the GPS coordinates of a photo, which usually come from a
+/// phone's or camera's GPS receiver.
+const PHOTO_LOCATION_ACCURACY: u32 = 20;
 
 #[derive(Debug)]
 pub enum HistoryError {
@@ -34,14 +40,71 @@ enum LocationMatch<'a> {
     Between(&'a Location, &'a Location),
 }
 
-#[derive(Deserialize, PartialEq, Debug, Default)]
+/// Location data points keyed by their timestamps. All timestamps passed to its methods are Unix
+/// timestamps in milliseconds.
+#[derive(Clone, Deserialize, PartialEq, Debug, Default)]
 pub struct GoogleLocationHistory {
     #[serde(deserialize_with = "deserialize_locations")]
     locations: BTreeMap<i64, Location>,
 }
 
 impl GoogleLocationHistory {
-    pub fn contains(&self, timestamp: i64) -> bool {
+    /// Create a location history from the photos that have GPS coordinates, so that those
+    /// locations can be suggested for other photos. Photos with fallback timestamps are
+    /// skipped, as their timestamps are too unreliable to place them in time.
+    pub fn from_photos<'a, I>(photos: I) -> GoogleLocationHistory
+    where
+        I: IntoIterator<Item = &'a Photo>,
+    {
+        let locations = photos
+            .into_iter()
+            .filter(|photo| !photo.timestamp_source().is_fallback())
+            .filter_map(|photo| {
+                photo.gps_coordinates().map(|coordinates| {
+                    Location::new(photo.timestamp_ms(), coordinates, PHOTO_LOCATION_ACCURACY)
+                })
+            }).map(|l| (l.timestamp_ms, l));
+
+        GoogleLocationHistory {
+            locations: BTreeMap::from_iter(locations),
+        }
+    }
+
+    /// Add the location data points of another history. If both histories have a data point
+    /// with the same timestamp, the data point in this history is kept.
+    pub fn extend(&mut self, other: GoogleLocationHistory) {
+        for (timestamp_ms, location) in other.locations {
+            self.locations.entry(timestamp_ms).or_insert(location);
+        }
+    }
+
+    /// The location data points in timestamp order.
+    pub fn locations(&self) -> Values<i64, Location> {
+        self.locations.values()
+    }
+
+    /// The location data points with timestamps between the given start and end timestamps
+    /// inclusive, in timestamp order.
+    pub fn locations_between(&self, start_ms: i64, end_ms: i64) -> Vec<&Location> {
+        if start_ms > end_ms {
+            return Vec::new();
+        }
+
+        self.locations
+            .range(start_ms..=end_ms)
+            .map(|l| l.1)
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    pub fn contains_ms(&self, timestamp_ms: i64) -> bool {
         let first_timestamp = match self.locations.iter().next() {
             Some(l) => l.0,
             None => return false,
@@ -52,17 +115,15 @@ impl GoogleLocationHistory {
             None => return false,
         };
 
-        let timestamp_ms = timestamp * 1000;
-
         timestamp_ms >= *first_timestamp && timestamp_ms <= *last_timestamp
     }
 
-    pub fn get_most_likely_location(&self, timestamp: i64) -> Option<&Location> {
-        match self.location_at_time(timestamp) {
+    pub fn get_most_likely_location_ms(&self, timestamp_ms: i64) -> Option<&Location> {
+        match self.location_at_time_ms(timestamp_ms) {
             None => None,
             Some(LocationMatch::Exact(location)) => Some(location),
             Some(LocationMatch::Between(before, after)) => {
-                if timestamp - before.timestamp_ms > after.timestamp_ms - timestamp {
+                if timestamp_ms - before.timestamp_ms > after.timestamp_ms - timestamp_ms {
                     Some(after)
                 } else {
                     Some(before)
@@ -76,15 +137,14 @@ impl GoogleLocationHistory {
     /// between their two latitudes and longitudes. This is inaccurate at large gradients, as it
     /// doesn't take into account the curvature of the Earth, but in such cases interpolation
     /// is probably meaningless anyway as the points are probably not part of the same journey.
-    pub fn interpolate_location(&self, timestamp: i64) -> Option<Location> {
-        match self.location_at_time(timestamp) {
+    pub fn interpolate_location_ms(&self, timestamp_ms: i64) -> Option<Location> {
+        match self.location_at_time_ms(timestamp_ms) {
             Some(LocationMatch::Exact(location)) => Some(location.clone()),
             Some(LocationMatch::Between(before, after)) => {
                 let latitude_difference = after.latitude_e7 - before.latitude_e7;
                 let longitude_difference = after.longitude_e7 - before.longitude_e7;
                 let time_difference = after.timestamp_ms - before.timestamp_ms;
 
-                let timestamp_ms = timestamp * 1000;
                 let time_offset = timestamp_ms - before.timestamp_ms;
 
                 let latitude_e7 =
@@ -104,9 +164,7 @@ impl GoogleLocationHistory {
         }
     }
 
-    fn location_at_time(&self, timestamp: i64) -> Option<LocationMatch> {
-        let timestamp_ms = timestamp * 1000;
-
+    fn location_at_time_ms(&self, timestamp_ms: i64) -> Option<LocationMatch> {
         if let Some(location) = self.locations.get(&timestamp_ms) {
             return Some(LocationMatch::Exact(location));
         }
@@ -159,6 +217,19 @@ pub struct Location {
 }
 
 impl Location {
+    pub fn new(
+        timestamp_ms: i64,
+        coordinates: &coordinates::Coordinates,
+        accuracy: u32,
+    ) -> Location {
+        Location {
+            timestamp_ms,
+            latitude_e7: (coordinates.latitude() * 1e7).round() as i64,
+            longitude_e7: (coordinates.longitude() * 1e7).round() as i64,
+            accuracy,
+        }
+    }
+
     pub fn coordinates(&self) -> coordinates::Coordinates {
         coordinates::Coordinates::new(
             self.latitude_e7 as f64 / 1e7,
@@ -173,6 +244,10 @@ impl Location {
     pub fn timestamp(&self) -> i64 {
         self.timestamp_ms / 1000 as i64
     }
+
+    pub fn timestamp_ms(&self) -> i64 {
+        self.timestamp_ms
+    }
 }
 
 fn deserialize_locations<'de, D>(deserializer: D) -> Result<BTreeMap<i64, Location>, D::Error>
@@ -199,6 +274,8 @@ where
 mod tests {
     use super::*;
 
+    use std::path::Path;
+
     use serde_json;
 
     #[test]
@@ -277,16 +354,64 @@ mod tests {
     }
 
     #[test]
-    fn contains_should_be_false_if_history_is_empty() {
+    fn locations_should_iterate_over_locations_in_timestamp_order() {
+        let mut locations: BTreeMap<i64, Location> = BTreeMap::new();
+        for timestamp_ms in &[3000, 1000, 2000] {
+            locations.insert(
+                *timestamp_ms,
+                Location {
+                    timestamp_ms: *timestamp_ms,
+                    latitude_e7: 0,
+                    longitude_e7: 0,
+                    accuracy: 18,
+                },
+            );
+        }
+        let history = GoogleLocationHistory { locations };
+
+        let timestamps: Vec<i64> = history.locations().map(|l| l.timestamp_ms()).collect();
+
+        assert_eq!(3, history.len());
+        assert_eq!(vec![1000, 2000, 3000], timestamps);
+    }
+
+    #[test]
+    fn locations_between_should_include_locations_at_the_start_and_end_timestamps() {
+        let mut locations: BTreeMap<i64, Location> = BTreeMap::new();
+        for timestamp_ms in &[1000, 2000, 3000, 4000] {
+            locations.insert(
+                *timestamp_ms,
+                Location {
+                    timestamp_ms: *timestamp_ms,
+                    latitude_e7: 0,
+                    longitude_e7: 0,
+                    accuracy: 18,
+                },
+            );
+        }
+        let history = GoogleLocationHistory { locations };
+
+        let timestamps: Vec<i64> = history
+            .locations_between(2000, 3000)
+            .iter()
+            .map(|l| l.timestamp_ms())
+            .collect();
+
+        assert_eq!(vec![2000, 3000], timestamps);
+        assert!(history.locations_between(3000, 2000).is_empty());
+    }
+
+    #[test]
+    fn contains_ms_should_be_false_if_history_is_empty() {
         let history = GoogleLocationHistory {
             locations: BTreeMap::new(),
         };
 
-        assert!(!history.contains(1));
+        assert!(!history.contains_ms(1000));
     }
 
     #[test]
-    fn contains_should_be_false_if_timestamp_is_before_first_timestamp_in_history() {
+    fn contains_ms_should_be_false_if_timestamp_is_before_first_timestamp_in_history() {
         let mut locations: BTreeMap<i64, Location> = BTreeMap::new();
         locations.insert(
             2000,
@@ -299,11 +424,11 @@ mod tests {
         );
         let history = GoogleLocationHistory { locations };
 
-        assert!(!history.contains(1));
+        assert!(!history.contains_ms(1000));
     }
 
     #[test]
-    fn contains_should_be_false_if_timestamp_is_after_last_timestamp_in_history() {
+    fn contains_ms_should_be_false_if_timestamp_is_after_last_timestamp_in_history() {
         let mut locations: BTreeMap<i64, Location> = BTreeMap::new();
         locations.insert(
             1000,
@@ -316,11 +441,11 @@ mod tests {
         );
         let history = GoogleLocationHistory { locations };
 
-        assert!(!history.contains(2));
+        assert!(!history.contains_ms(2000));
     }
 
     #[test]
-    fn contains_should_be_true_if_timestamp_is_equal_to_first_timestamp_in_history() {
+    fn contains_ms_should_be_true_if_timestamp_is_equal_to_first_timestamp_in_history() {
         let mut locations: BTreeMap<i64, Location> = BTreeMap::new();
         locations.insert(
             1000,
@@ -342,11 +467,11 @@ mod tests {
         );
         let history = GoogleLocationHistory { locations };
 
-        assert!(history.contains(1));
+        assert!(history.contains_ms(1000));
     }
 
     #[test]
-    fn contains_should_be_true_if_timestamp_is_equal_to_last_timestamp_in_history() {
+    fn contains_ms_should_be_true_if_timestamp_is_equal_to_last_timestamp_in_history() {
         let mut locations: BTreeMap<i64, Location> = BTreeMap::new();
         locations.insert(
             1000,
@@ -368,11 +493,11 @@ mod tests {
         );
         let history = GoogleLocationHistory { locations };
 
-        assert!(history.contains(2));
+        assert!(history.contains_ms(2000));
     }
 
     #[test]
-    fn contains_should_be_true_if_timestamp_is_between_first_and_last_timestamps_in_history() {
+    fn contains_ms_should_be_true_if_timestamp_is_between_first_and_last_timestamps_in_history() {
         let mut locations: BTreeMap<i64, Location> = BTreeMap::new();
         locations.insert(
             1000,
@@ -394,7 +519,7 @@ mod tests {
         );
         let history = GoogleLocationHistory { locations };
 
-        assert!(history.contains(2));
+        assert!(history.contains_ms(2000));
     }
 
     #[test]
@@ -403,7 +528,7 @@ mod tests {
             locations: BTreeMap::new(),
         };
 
-        let location = ghl.get_most_likely_location(0);
+        let location = ghl.get_most_likely_location_ms(0);
 
         assert_eq!(None, location);
     }
@@ -422,10 +547,10 @@ mod tests {
         );
         let ghl = GoogleLocationHistory { locations };
 
-        let location = ghl.get_most_likely_location(0);
+        let location = ghl.get_most_likely_location_ms(0);
         assert_eq!(None, location);
 
-        let location = ghl.get_most_likely_location(2000);
+        let location = ghl.get_most_likely_location_ms(2000);
         assert_eq!(None, location);
     }
 
@@ -443,7 +568,7 @@ mod tests {
         );
         let ghl = GoogleLocationHistory { locations };
 
-        let location = ghl.get_most_likely_location(1).unwrap();
+        let location = ghl.get_most_likely_location_ms(1000).unwrap();
 
         assert_eq!(1000, location.timestamp_ms);
     }
@@ -471,11 +596,39 @@ mod tests {
         );
         let ghl = GoogleLocationHistory { locations };
 
-        let location = ghl.get_most_likely_location(4).unwrap();
+        let location = ghl.get_most_likely_location_ms(4000).unwrap();
 
         assert_eq!(3000, location.timestamp_ms);
     }
 
+    #[test]
+    fn get_most_likely_location_should_compare_timestamps_with_millisecond_precision() {
+        let mut locations: BTreeMap<i64, Location> = BTreeMap::new();
+        locations.insert(
+            3000,
+            Location {
+                timestamp_ms: 3000,
+                latitude_e7: 520796733,
+                longitude_e7: 11965831,
+                accuracy: 18,
+            },
+        );
+        locations.insert(
+            4000,
+            Location {
+                timestamp_ms: 4000,
+                latitude_e7: 520567467,
+                longitude_e7: 11485831,
+                accuracy: 18,
+            },
+        );
+        let ghl = GoogleLocationHistory { locations };
+
+        let location = ghl.get_most_likely_location_ms(3600).unwrap();
+
+        assert_eq!(4000, location.timestamp_ms);
+    }
+
     #[test]
     fn get_most_likely_location_should_return_the_older_location_if_exactly_between_two() {
         let mut locations: BTreeMap<i64, Location> = BTreeMap::new();
@@ -499,7 +652,7 @@ mod tests {
         );
         let ghl = GoogleLocationHistory { locations };
 
-        let location = ghl.get_most_likely_location(2).unwrap();
+        let location = ghl.get_most_likely_location_ms(2000).unwrap();
 
         assert_eq!(1000, location.timestamp_ms);
     }
@@ -510,7 +663,7 @@ mod tests {
             locations: BTreeMap::new(),
         };
 
-        let location = ghl.interpolate_location(0);
+        let location = ghl.interpolate_location_ms(0);
 
         assert_eq!(None, location);
     }
@@ -529,10 +682,10 @@ mod tests {
         );
         let ghl = GoogleLocationHistory { locations };
 
-        let location = ghl.interpolate_location(0);
+        let location = ghl.interpolate_location_ms(0);
         assert_eq!(None, location);
 
-        let location = ghl.interpolate_location(2000);
+        let location = ghl.interpolate_location_ms(2000);
         assert_eq!(None, location);
     }
 
@@ -550,7 +703,7 @@ mod tests {
         );
         let ghl = GoogleLocationHistory { locations };
 
-        let location = ghl.interpolate_location(1).unwrap();
+        let location = ghl.interpolate_location_ms(1000).unwrap();
 
         assert_eq!(1000, location.timestamp_ms);
     }
@@ -578,7 +731,7 @@ mod tests {
         );
         let ghl = GoogleLocationHistory { locations };
 
-        let location = ghl.interpolate_location(4).unwrap();
+        let location = ghl.interpolate_location_ms(4000).unwrap();
 
         assert_eq!(4000, location.timestamp_ms);
         assert_eq!(520720311, location.latitude_e7);
@@ -700,4 +853,83 @@ mod tests {
         assert_eq!(52.0796733, coordinates.latitude());
         assert_eq!(1.1965831, coordinates.longitude());
     }
+
+    #[test]
+    fn location_new_should_convert_coordinates_to_e7_integers() {
+        let coordinates = coordinates::Coordinates::new(52.0796733, 1.1965831);
+
+        let location = Location::new(1000, &coordinates, 18);
+
+        assert_eq!(
+            Location {
+                timestamp_ms: 1000,
+                latitude_e7: 520796733,
+                longitude_e7: 11965831,
+                accuracy: 18,
+            },
+            location
+        );
+    }
+
+    #[test]
+    fn from_photos_should_only_include_photos_with_gps_coordinates() {
+        let photos = vec![
+            Photo::new(Path::new("tests/assets/photo.jpg")).unwrap(),
+            Photo::new(Path::new("tests/assets/photo_without_gps.jpg")).unwrap(),
+        ];
+
+        let history = GoogleLocationHistory::from_photos(&photos);
+
+        assert_eq!(1, history.len());
+        let location = history.locations().next().unwrap();
+        assert_eq!(photos[0].timestamp_ms(), location.timestamp_ms());
+        assert_eq!(PHOTO_LOCATION_ACCURACY, location.accuracy());
+        let distance = photos[0]
+            .gps_coordinates()
+            .unwrap()
+            .distance_in_km(&location.coordinates());
+        assert!(distance < 0.001);
+    }
+
+    #[test]
+    fn extend_should_keep_existing_locations_with_the_same_timestamp() {
+        let mut history = GoogleLocationHistory {
+            locations: BTreeMap::from_iter(vec![(
+                1000,
+                Location {
+                    timestamp_ms: 1000,
+                    latitude_e7: 520796733,
+                    longitude_e7: 11965831,
+                    accuracy: 18,
+                },
+            )]),
+        };
+        let other = GoogleLocationHistory {
+            locations: BTreeMap::from_iter(vec![
+                (
+                    1000,
+                    Location {
+                        timestamp_ms: 1000,
+                        latitude_e7: 0,
+                        longitude_e7: 0,
+                        accuracy: 20,
+                    },
+                ),
+                (
+                    2000,
+                    Location {
+                        timestamp_ms: 2000,
+                        latitude_e7: 0,
+                        longitude_e7: 0,
+                        accuracy: 20,
+                    },
+                ),
+            ]),
+        };
+
+        history.extend(other);
+
+        assert_eq!(2, history.len());
+        assert_eq!(18, history.locations().next().unwrap().accuracy());
+    }
 }
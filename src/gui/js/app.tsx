@@ -4,16 +4,19 @@ import * as ReactDOM from 'react-dom';
 import 'whatwg-fetch';
 import { Page, PageProps } from './page';
 import {
+  getCopyFromPhotos,
   getInterpolate,
   getLocationHistoryPath,
-  getPhotos,
-  getRootPath
+  getRootPath,
+  getTiles
 } from './requests';
 
 function getInitialState() {
   const state: PageProps = {
+    copyFromPhotos: false,
     interpolate: false,
     locationHistoryPath: undefined,
+    offlineTiles: false,
     photos: [],
     rootPath: undefined
   };
@@ -22,21 +25,26 @@ function getInitialState() {
     .then(responseBody => {
       state.rootPath = responseBody.rootPath || '';
 
-      return getPhotos();
-    })
-    .then(photos => {
-      state.photos = photos;
-
       return getInterpolate();
     })
     .then(responseBody => {
       state.interpolate = responseBody.interpolate;
 
+      return getCopyFromPhotos();
+    })
+    .then(responseBody => {
+      state.copyFromPhotos = responseBody.copyFromPhotos;
+
       return getLocationHistoryPath();
     })
     .then(responseBody => {
       state.locationHistoryPath = responseBody.locationHistoryPath || '';
 
+      return getTiles();
+    })
+    .then(responseBody => {
+      state.offlineTiles = responseBody.available;
+
       return state;
     });
 }
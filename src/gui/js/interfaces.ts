@@ -6,7 +6,9 @@ export interface Coordinates {
 export interface Photo {
   path: string;
   location?: Location; // eslint-disable-line no-restricted-globals
+  place?: string;
   error?: string;
+  userSet?: boolean;
   src: string;
   height?: number;
   width?: number;
@@ -18,7 +20,63 @@ export interface Location {
   Suggested?: [Coordinates, LocationAccuracy];
 }
 
+export interface SaveProgress {
+  Started?: { total: number };
+  Saved?: { path: string; coordinates: Coordinates };
+  Failed?: { path: string; error: string };
+}
+
+export interface GuiEvent {
+  ScanStarted?: { rootPath: string };
+  PhotosFound?: { photos: Photo[] };
+  PhotoFailed?: { path: string; error: string };
+  ScanFinished?: { total: number };
+  PhotoChanged?: { photo: Photo };
+  PhotosRemoved?: { paths: string[] };
+  LocationHistoryReloaded?: { locationHistoryPath: string };
+}
+
+export type TimestampSource =
+  | 'GpsTime'
+  | 'DateTimeOriginal'
+  | 'DateTime'
+  | 'VideoCreationTime'
+  | 'FileName'
+  | 'XmpCreateDate'
+  | 'FileModificationTime';
+
 export interface LocationAccuracy {
   meters: number;
   seconds: number;
+  timestamp_source: TimestampSource;
+}
+
+export interface TrackPointProperties {
+  timestampMs: number;
+  offsetSeconds: number;
+  accuracy: number;
+}
+
+// GeoJSON positions are [longitude, latitude].
+export type Position = [number, number];
+
+export interface TrackPointFeature {
+  type: 'Feature';
+  geometry: { type: 'Point'; coordinates: Position };
+  properties: TrackPointProperties;
+}
+
+export interface TrackLineFeature {
+  type: 'Feature';
+  geometry: { type: 'LineString'; coordinates: Position[] };
+  properties: {};
+}
+
+export interface Track {
+  type: 'FeatureCollection';
+  features: (TrackPointFeature | TrackLineFeature)[];
+}
+
+export interface TrackPoint extends TrackPointProperties {
+  coordinates: Coordinates;
 }
@@ -2,15 +2,19 @@ import GoogleMapReact from 'google-map-react';
 import * as React from 'react';
 import { FiMapPin } from 'react-icons/fi';
 
-import { Photo } from './interfaces';
+import { Coordinates, Photo, Track } from './interfaces';
+import { OfflineMap } from './offline-map';
 import { googleMapsCoordinates } from './photo';
+import { TrackOverlay, trackPoints } from './track-overlay';
 
-interface MapMarkerProps {
+const DEFAULT_ZOOM = 5;
+
+interface MapPoint {
   lat: number;
   lng: number;
 }
 
-function MapMarker(props: MapMarkerProps) {
+function MapMarker(props: MapPoint) {
   return (
     <FiMapPin
       {...props}
@@ -26,29 +30,203 @@ function MapMarker(props: MapMarkerProps) {
   );
 }
 
+function toCoordinates(point: MapPoint): Coordinates {
+  return {
+    latitude: point.lat,
+    longitude: point.lng
+  };
+}
+
+interface MapChange {
+  zoom: number;
+}
+
 export interface MapAreaProps {
   photo?: Photo;
+  track?: Track;
+  offlineTiles?: boolean;
+  handleLocationSet?: (coordinates: Coordinates) => void;
 }
 
-export function MapArea(props: MapAreaProps) {
-  let coord = { lat: 0, lng: 0 };
-  let marker;
-  if (props.photo && props.photo.location) {
-    coord = googleMapsCoordinates(props.photo);
-    marker = <MapMarker {...coord} />;
+interface MapAreaState {
+  dragPosition?: MapPoint;
+  pendingPosition?: MapPoint;
+  zoom: number;
+}
+
+export class MapArea extends React.Component<MapAreaProps, MapAreaState> {
+  public constructor(props: MapAreaProps) {
+    super(props);
+
+    this.state = {
+      dragPosition: undefined,
+      pendingPosition: undefined,
+      zoom: DEFAULT_ZOOM
+    };
+
+    this.handleChange = this.handleChange.bind(this);
+    this.handleClick = this.handleClick.bind(this);
+    this.handleOfflineClick = this.handleOfflineClick.bind(this);
+    this.handleMarkerDrag = this.handleMarkerDrag.bind(this);
+    this.handleMarkerDragEnd = this.handleMarkerDragEnd.bind(this);
+    this.handleLocationConfirm = this.handleLocationConfirm.bind(this);
+    this.handleLocationCancel = this.handleLocationCancel.bind(this);
   }
 
-  const style: React.CSSProperties = {
-    height: '50%',
-    position: 'relative',
-    width: '100%'
-  };
+  // A pending location belongs to the photo it was picked for.
+  public componentDidUpdate(previousProps: MapAreaProps) {
+    const previousPath = previousProps.photo && previousProps.photo.path;
+    const path = this.props.photo && this.props.photo.path;
+    if (previousPath !== path && this.state.pendingPosition) {
+      this.setState({ pendingPosition: undefined });
+    }
+  }
 
-  return (
-    <div style={style}>
-      <GoogleMapReact center={coord} zoom={5}>
-        {marker}
-      </GoogleMapReact>
-    </div>
-  );
+  public render() {
+    let coord = { lat: 0, lng: 0 };
+    if (this.props.photo && this.props.photo.location) {
+      coord = googleMapsCoordinates(this.props.photo);
+    }
+
+    let markerPosition: MapPoint | undefined;
+    if (this.state.dragPosition || this.state.pendingPosition) {
+      markerPosition = this.state.dragPosition || this.state.pendingPosition;
+    } else if (this.props.photo && this.props.photo.location) {
+      markerPosition = coord;
+    }
+
+    let marker;
+    if (markerPosition) {
+      marker = <MapMarker key="marker" {...markerPosition} />;
+    }
+
+    const points = trackPoints(this.props.track);
+
+    const style: React.CSSProperties = {
+      height: '50%',
+      position: 'relative',
+      width: '100%'
+    };
+
+    if (this.props.offlineTiles) {
+      return (
+        <div style={style}>
+          <OfflineMap
+            center={toCoordinates(coord)}
+            marker={markerPosition ? toCoordinates(markerPosition) : undefined}
+            trackPoints={points.length > 0 ? points : undefined}
+            zoom={DEFAULT_ZOOM}
+            handleClick={
+              this.props.handleLocationSet ? this.handleOfflineClick : undefined
+            }
+          />
+          {this.renderConfirmation()}
+        </div>
+      );
+    }
+
+    let track;
+    if (points.length > 0) {
+      track = (
+        <TrackOverlay
+          key="track"
+          lat={points[0].coordinates.latitude}
+          lng={points[0].coordinates.longitude}
+          points={points}
+          zoom={this.state.zoom}
+        />
+      );
+    }
+
+    // The map can't be panned while the marker is being dragged, or it would
+    // move with the marker.
+    return (
+      <div style={style}>
+        <GoogleMapReact
+          center={coord}
+          zoom={DEFAULT_ZOOM}
+          draggable={this.state.dragPosition === undefined}
+          onChange={this.handleChange}
+          onClick={this.handleClick}
+          onChildMouseDown={this.handleMarkerDrag}
+          onChildMouseMove={this.handleMarkerDrag}
+          onChildMouseUp={this.handleMarkerDragEnd}
+        >
+          {track}
+          {marker}
+        </GoogleMapReact>
+        {this.renderConfirmation()}
+      </div>
+    );
+  }
+
+  // Clicking or dragging only moves the marker, so that a click meant to pan
+  // the map doesn't overwrite the locations of the selected photos.
+  private renderConfirmation() {
+    if (!this.state.pendingPosition) {
+      return null;
+    }
+
+    return (
+      <div
+        style={{
+          bottom: '8px',
+          left: '50%',
+          position: 'absolute',
+          transform: 'translateX(-50%)'
+        }}
+      >
+        <button type="button" onClick={this.handleLocationConfirm}>
+          Set location here
+        </button>
+        <button type="button" onClick={this.handleLocationCancel}>
+          Cancel
+        </button>
+      </div>
+    );
+  }
+
+  private handleChange(change: MapChange) {
+    this.setState({ zoom: change.zoom });
+  }
+
+  private handleClick(point: MapPoint) {
+    if (this.props.handleLocationSet) {
+      this.setState({ pendingPosition: { lat: point.lat, lng: point.lng } });
+    }
+  }
+
+  private handleOfflineClick(coordinates: Coordinates) {
+    this.handleClick({ lat: coordinates.latitude, lng: coordinates.longitude });
+  }
+
+  // google-map-react reports mouse events for any child near the pointer, so
+  // ignore those that aren't for the marker.
+  private handleMarkerDrag(key: string, childProps: MapPoint, mouse: MapPoint) {
+    if (key === 'marker' && this.props.handleLocationSet) {
+      this.setState({ dragPosition: { lat: mouse.lat, lng: mouse.lng } });
+    }
+  }
+
+  private handleMarkerDragEnd() {
+    const dragPosition = this.state.dragPosition;
+    this.setState({ dragPosition: undefined });
+
+    if (dragPosition && this.props.handleLocationSet) {
+      this.setState({ pendingPosition: dragPosition });
+    }
+  }
+
+  private handleLocationConfirm() {
+    const pendingPosition = this.state.pendingPosition;
+    this.setState({ pendingPosition: undefined });
+
+    if (pendingPosition && this.props.handleLocationSet) {
+      this.props.handleLocationSet(toCoordinates(pendingPosition));
+    }
+  }
+
+  private handleLocationCancel() {
+    this.setState({ pendingPosition: undefined });
+  }
 }
@@ -0,0 +1,284 @@
+import * as React from 'react';
+import { FiMapPin } from 'react-icons/fi';
+
+import { Coordinates, TrackPoint } from './interfaces';
+import { PixelPoint, project, TILE_SIZE, unproject } from './projection';
+import { TrackOverlay } from './track-overlay';
+
+const MIN_ZOOM = 0;
+const MAX_ZOOM = 19;
+
+// The number of tiles drawn either side of the centre tile, which is enough to
+// fill the map area on large screens.
+const TILE_RADIUS = 3;
+
+// Pointer movements smaller than this many pixels count as clicks, not drags.
+const CLICK_TOLERANCE = 3;
+
+interface MapMouseEvent {
+  clientX: number;
+  clientY: number;
+  currentTarget: {
+    getBoundingClientRect: () => ClientRect;
+  };
+}
+
+interface Drag {
+  start: PixelPoint;
+  startPanOffset: PixelPoint;
+}
+
+function stopPropagation(event: { stopPropagation: () => void }) {
+  event.stopPropagation();
+}
+
+function offsetStyle(x: number, y: number): React.CSSProperties {
+  return {
+    left: `calc(50% + ${x}px)`,
+    position: 'absolute',
+    top: `calc(50% + ${y}px)`
+  };
+}
+
+export interface OfflineMapProps {
+  center: Coordinates;
+  marker?: Coordinates;
+  trackPoints?: TrackPoint[];
+  zoom: number;
+  handleClick?: (coordinates: Coordinates) => void;
+}
+
+interface OfflineMapState {
+  zoom: number;
+  panOffset: PixelPoint;
+  drag?: Drag;
+}
+
+// A map drawn from the tiles served by the GUI backend, for use without a
+// network connection.
+export class OfflineMap extends React.Component<
+  OfflineMapProps,
+  OfflineMapState
+> {
+  public constructor(props: OfflineMapProps) {
+    super(props);
+
+    this.state = {
+      drag: undefined,
+      panOffset: { x: 0, y: 0 },
+      zoom: props.zoom
+    };
+
+    this.handleMouseDown = this.handleMouseDown.bind(this);
+    this.handleMouseMove = this.handleMouseMove.bind(this);
+    this.handleMouseUp = this.handleMouseUp.bind(this);
+    this.handleDragCancel = this.handleDragCancel.bind(this);
+    this.handleZoomIn = this.handleZoomIn.bind(this);
+    this.handleZoomOut = this.handleZoomOut.bind(this);
+  }
+
+  public componentDidUpdate(previousProps: OfflineMapProps) {
+    if (
+      previousProps.center.latitude !== this.props.center.latitude ||
+      previousProps.center.longitude !== this.props.center.longitude
+    ) {
+      this.setState({ panOffset: { x: 0, y: 0 } });
+    }
+  }
+
+  public render() {
+    const { zoom } = this.state;
+    const centre = this.viewCentre();
+    const tileCount = 2 ** zoom;
+    const centreTileX = Math.floor(centre.x / TILE_SIZE);
+    const centreTileY = Math.floor(centre.y / TILE_SIZE);
+
+    const tiles = [];
+    for (let dy = -TILE_RADIUS; dy <= TILE_RADIUS; dy += 1) {
+      const y = centreTileY + dy;
+      if (y >= 0 && y < tileCount) {
+        for (let dx = -TILE_RADIUS; dx <= TILE_RADIUS; dx += 1) {
+          const x = centreTileX + dx;
+          const wrappedX = ((x % tileCount) + tileCount) % tileCount;
+          tiles.push(
+            <img
+              key={`${zoom}/${x}/${y}`}
+              src={`/tiles/${zoom}/${wrappedX}/${y}`}
+              alt=""
+              draggable={false}
+              style={offsetStyle(
+                x * TILE_SIZE - centre.x,
+                y * TILE_SIZE - centre.y
+              )}
+            />
+          );
+        }
+      }
+    }
+
+    let track;
+    if (this.props.trackPoints && this.props.trackPoints.length > 0) {
+      const point = project(this.props.trackPoints[0].coordinates, zoom);
+      track = (
+        <TrackOverlay
+          points={this.props.trackPoints}
+          zoom={zoom}
+          style={offsetStyle(point.x - centre.x, point.y - centre.y)}
+        />
+      );
+    }
+
+    let marker;
+    if (this.props.marker) {
+      const point = project(this.props.marker, zoom);
+      marker = (
+        <FiMapPin
+          style={{
+            ...offsetStyle(point.x - centre.x - 12, point.y - centre.y - 24),
+            color: 'crimson',
+            height: '24px',
+            width: '24px'
+          }}
+        />
+      );
+    }
+
+    return (
+      <div
+        role="presentation"
+        style={{
+          cursor: this.state.drag ? 'grabbing' : 'grab',
+          height: '100%',
+          overflow: 'hidden',
+          position: 'relative',
+          width: '100%'
+        }}
+        onMouseDown={this.handleMouseDown}
+        onMouseMove={this.handleMouseMove}
+        onMouseUp={this.handleMouseUp}
+        onMouseLeave={this.handleDragCancel}
+      >
+        {tiles}
+        {track}
+        {marker}
+        <div style={{ left: '8px', position: 'absolute', top: '8px' }}>
+          <button
+            type="button"
+            disabled={zoom >= MAX_ZOOM}
+            onClick={this.handleZoomIn}
+            onMouseDown={stopPropagation}
+          >
+            +
+          </button>
+          <button
+            type="button"
+            disabled={zoom <= MIN_ZOOM}
+            onClick={this.handleZoomOut}
+            onMouseDown={stopPropagation}
+          >
+            -
+          </button>
+        </div>
+      </div>
+    );
+  }
+
+  private viewCentre(panOffset = this.state.panOffset): PixelPoint {
+    const centre = project(this.props.center, this.state.zoom);
+
+    return {
+      x: centre.x - panOffset.x,
+      y: centre.y - panOffset.y
+    };
+  }
+
+  private handleClickAt(
+    offsetFromCentre: PixelPoint,
+    panOffset = this.state.panOffset
+  ) {
+    if (this.props.handleClick) {
+      const centre = this.viewCentre(panOffset);
+      const point = {
+        x: centre.x + offsetFromCentre.x,
+        y: centre.y + offsetFromCentre.y
+      };
+      this.props.handleClick(unproject(point, this.state.zoom));
+    }
+  }
+
+  private handleMouseDown(event: MapMouseEvent) {
+    this.setState(previousState => ({
+      drag: {
+        start: { x: event.clientX, y: event.clientY },
+        startPanOffset: previousState.panOffset
+      }
+    }));
+  }
+
+  private handleMouseMove(event: MapMouseEvent) {
+    const { drag } = this.state;
+    if (drag) {
+      this.setState({
+        panOffset: {
+          x: drag.startPanOffset.x + event.clientX - drag.start.x,
+          y: drag.startPanOffset.y + event.clientY - drag.start.y
+        }
+      });
+    }
+  }
+
+  private handleMouseUp(event: MapMouseEvent) {
+    const { drag } = this.state;
+    if (!drag) {
+      return;
+    }
+
+    this.handleDragCancel();
+
+    if (
+      Math.abs(event.clientX - drag.start.x) < CLICK_TOLERANCE &&
+      Math.abs(event.clientY - drag.start.y) < CLICK_TOLERANCE
+    ) {
+      this.setState({ panOffset: drag.startPanOffset });
+
+      const rect = event.currentTarget.getBoundingClientRect();
+      this.handleClickAt(
+        {
+          x: event.clientX - rect.left - rect.width / 2,
+          y: event.clientY - rect.top - rect.height / 2
+        },
+        drag.startPanOffset
+      );
+    }
+  }
+
+  private handleDragCancel() {
+    this.setState({ drag: undefined });
+  }
+
+  private handleZoomIn() {
+    this.zoomBy(1);
+  }
+
+  private handleZoomOut() {
+    this.zoomBy(-1);
+  }
+
+  private zoomBy(levels: number) {
+    this.setState(previousState => {
+      const zoom = Math.max(
+        MIN_ZOOM,
+        Math.min(MAX_ZOOM, previousState.zoom + levels)
+      );
+      const scale = 2 ** (zoom - previousState.zoom);
+
+      return {
+        panOffset: {
+          x: previousState.panOffset.x * scale,
+          y: previousState.panOffset.y * scale
+        },
+        zoom
+      };
+    });
+  }
+}
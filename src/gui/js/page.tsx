@@ -1,5 +1,12 @@
 import * as React from 'react';
-import { Photo } from './interfaces';
+import {
+  Coordinates,
+  GuiEvent,
+  Photo,
+  SaveProgress,
+  Track
+} from './interfaces';
+import { hasSuggestion } from './photo';
 import { PhotoLocationViewer } from './photo-location-viewer';
 import * as requests from './requests';
 import { PhotosGrid } from './photos-grid';
@@ -10,47 +17,83 @@ interface CheckboxEvent {
   };
 }
 
+interface InputEvent {
+  target: {
+    value: string;
+  };
+}
+
 export interface PageProps {
+  copyFromPhotos: boolean;
   interpolate: boolean;
   locationHistoryPath: string;
+  offlineTiles: boolean;
   photos: Photo[];
   rootPath: string;
 }
 
 export interface PageState {
+  copyFromPhotos: boolean;
   currentPhoto: Photo;
   filterPhotos: boolean;
   interpolate: boolean;
   locationHistoryPath: string;
+  maxAccuracy: string;
   photos: Photo[];
   rootPath: string;
+  saveStatus: string;
+  scanStatus: string;
+  selectedPhotos: Photo[];
+  track: Track;
 }
 
 export class Page extends React.Component<PageProps, PageState> {
   private photosGrid: PhotosGrid;
+  private eventSource: EventSource;
 
   public constructor(props: PageProps) {
     super(props);
 
     this.state = {
+      copyFromPhotos: props.copyFromPhotos,
       currentPhoto: undefined,
       filterPhotos: false,
       interpolate: props.interpolate,
       locationHistoryPath: props.locationHistoryPath,
+      maxAccuracy: '',
       photos: props.photos,
-      rootPath: props.rootPath
+      rootPath: props.rootPath,
+      saveStatus: '',
+      scanStatus: '',
+      selectedPhotos: [],
+      track: undefined
     };
 
     this.getAndStoreLocations = this.getAndStoreLocations.bind(this);
+    this.handleCopyFromPhotosToggle = this.handleCopyFromPhotosToggle.bind(
+      this
+    );
+    this.handleEvent = this.handleEvent.bind(this);
     this.handleFilterToggle = this.handleFilterToggle.bind(this);
     this.handleInterpolateToggle = this.handleInterpolateToggle.bind(this);
+    this.handleLocationSet = this.handleLocationSet.bind(this);
+    this.handleMaxAccuracyChange = this.handleMaxAccuracyChange.bind(this);
     this.handlePhotoSelect = this.handlePhotoSelect.bind(this);
     this.handleSuggestionApply = this.handleSuggestionApply.bind(this);
     this.handleSuggestionDiscard = this.handleSuggestionDiscard.bind(this);
+    this.handleSaveAll = this.handleSaveAll.bind(this);
     this.getNewRootPath = this.getNewRootPath.bind(this);
     this.getNewLocationHistory = this.getNewLocationHistory.bind(this);
   }
 
+  public componentDidMount() {
+    this.eventSource = requests.subscribeToEvents(this.handleEvent);
+  }
+
+  public componentWillUnmount() {
+    this.eventSource.close();
+  }
+
   public render() {
     return (
       <div>
@@ -60,6 +103,9 @@ export class Page extends React.Component<PageProps, PageState> {
             <div>
               Photos path:
               {this.state.rootPath}
+              {this.state.scanStatus ? (
+                <span>{this.state.scanStatus}</span>
+              ) : null}
             </div>
             <div>
               Location history path:
@@ -94,6 +140,33 @@ export class Page extends React.Component<PageProps, PageState> {
                 />
                 Interpolate locations
               </label>
+              <label htmlFor="copyFromPhotosCheckbox">
+                <input
+                  type="checkbox"
+                  id="copyFromPhotosCheckbox"
+                  checked={this.state.copyFromPhotos}
+                  onChange={this.handleCopyFromPhotosToggle}
+                />
+                Copy locations from nearby photos
+              </label>
+            </div>
+            <div>
+              <button type="button" onClick={this.handleSaveAll}>
+                Save All Suggestions
+              </button>
+              <label htmlFor="maxAccuracyInput">
+                Max. accuracy (m):
+                <input
+                  type="number"
+                  id="maxAccuracyInput"
+                  min={0}
+                  value={this.state.maxAccuracy}
+                  onChange={this.handleMaxAccuracyChange}
+                />
+              </label>
+              {this.state.saveStatus ? (
+                <span>{this.state.saveStatus}</span>
+              ) : null}
             </div>
           </div>
         </header>
@@ -103,12 +176,15 @@ export class Page extends React.Component<PageProps, PageState> {
               this.photosGrid = photosGrid;
             }}
             photos={this.state.photos}
-            currentPhoto={this.state.currentPhoto}
+            selectedPhotos={this.state.selectedPhotos}
             handlePhotoSelect={this.handlePhotoSelect}
             getAndStoreLocations={this.getAndStoreLocations}
           />
           <PhotoLocationViewer
             photo={this.state.currentPhoto}
+            track={this.state.track}
+            offlineTiles={this.props.offlineTiles}
+            handleLocationSet={this.handleLocationSet}
             handleSuggestionApply={this.handleSuggestionApply}
             handleSuggestionDiscard={this.handleSuggestionDiscard}
           />
@@ -142,7 +218,9 @@ export class Page extends React.Component<PageProps, PageState> {
           // Assign these here instead of using Object.assign to set any undefined
           // values.
           photos[i].location = locations[i - startIndex].location;
+          photos[i].place = locations[i - startIndex].place;
           photos[i].error = locations[i - startIndex].error;
+          photos[i].userSet = locations[i - startIndex].userSet;
           photos[i].loaded = true;
         }
 
@@ -151,16 +229,30 @@ export class Page extends React.Component<PageProps, PageState> {
     });
   }
 
+  // Get the location history around a photo with a suggested location, so the
+  // map can show where the suggestion came from.
+  private getAndStoreTrack(photo: Photo) {
+    if (!hasSuggestion(photo)) {
+      this.setState({ track: undefined });
+      return Promise.resolve();
+    }
+
+    return requests.getTrack(photo.path).then(track => {
+      this.setState(previousState => {
+        if (previousState.currentPhoto.path !== photo.path) {
+          return null;
+        }
+        return { track };
+      });
+    });
+  }
+
+  // The photos under the new root path are found in the background and
+  // arrive as events.
   private getNewRootPath() {
     return requests.getNewRootPath().then(responseBody => {
       const rootPath = responseBody.rootPath;
       this.setState({ rootPath });
-
-      return this.handleFilterToggle({
-        target: {
-          checked: this.state.filterPhotos
-        }
-      });
     });
   }
 
@@ -168,19 +260,96 @@ export class Page extends React.Component<PageProps, PageState> {
     return requests.getNewLocationHistory().then(responseBody => {
       const locationHistoryPath = responseBody.locationHistoryPath;
 
-      this.setState(previousState => {
-        const photos = previousState.photos.map(photo =>
-          Object.assign({}, photo, { loaded: false })
-        );
+      this.setState({ locationHistoryPath });
+      this.reloadLocations();
+    });
+  }
+
+  private reloadLocations() {
+    this.setState(previousState => {
+      const photos = previousState.photos.map(photo =>
+        Object.assign({}, photo, { loaded: false })
+      );
+
+      return { photos };
+    });
+
+    this.photosGrid.forceUpdate();
+  }
 
-        return {
-          locationHistoryPath,
-          photos
-        };
+  private handleEvent(event: GuiEvent) {
+    if (event.ScanStarted) {
+      this.setState({
+        currentPhoto: undefined,
+        photos: [],
+        rootPath: event.ScanStarted.rootPath,
+        scanStatus: 'Scanning for photos...',
+        selectedPhotos: [],
+        track: undefined
       });
+    } else if (event.PhotosFound && !this.state.filterPhotos) {
+      // While filtering, the photos are fetched again once the scan finishes.
+      const foundPhotos = event.PhotosFound.photos;
+      this.setState(previousState => ({
+        photos: previousState.photos.concat(foundPhotos)
+      }));
+    } else if (event.ScanFinished) {
+      this.setState({ scanStatus: `Found ${event.ScanFinished.total} photos` });
+
+      if (this.state.filterPhotos) {
+        return this.handleFilterToggle({ target: { checked: true } });
+      }
+    } else if (event.PhotoChanged) {
+      this.updateChangedPhoto(event.PhotoChanged.photo);
+    } else if (event.PhotosRemoved) {
+      this.removePhotos(event.PhotosRemoved.paths);
+    } else if (event.LocationHistoryReloaded) {
+      this.reloadLocations();
+
+      if (this.state.currentPhoto) {
+        return this.getAndStoreTrack(this.state.currentPhoto);
+      }
+    }
 
-      this.photosGrid.forceUpdate();
+    return Promise.resolve();
+  }
+
+  // The photo's file changed on disk, so its location needs to be loaded again.
+  private updateChangedPhoto(changedPhoto: Photo) {
+    const updatePhoto = (photo: Photo) =>
+      photo.path === changedPhoto.path
+        ? Object.assign({}, photo, changedPhoto, { loaded: false })
+        : photo;
+
+    this.setState(previousState => ({
+      currentPhoto:
+        previousState.currentPhoto && updatePhoto(previousState.currentPhoto),
+      photos: previousState.photos.map(updatePhoto),
+      selectedPhotos: previousState.selectedPhotos.map(updatePhoto)
+    }));
+
+    this.photosGrid.forceUpdate();
+  }
+
+  private removePhotos(paths: string[]) {
+    const isKept = (photo: Photo) => paths.indexOf(photo.path) === -1;
+
+    this.setState(previousState => {
+      const removesCurrentPhoto =
+        previousState.currentPhoto !== undefined &&
+        !isKept(previousState.currentPhoto);
+
+      return {
+        currentPhoto: removesCurrentPhoto
+          ? undefined
+          : previousState.currentPhoto,
+        photos: previousState.photos.filter(isKept),
+        selectedPhotos: previousState.selectedPhotos.filter(isKept),
+        track: removesCurrentPhoto ? undefined : previousState.track
+      };
     });
+
+    this.photosGrid.forceUpdate();
   }
 
   private handleFilterToggle(event: CheckboxEvent) {
@@ -196,6 +365,15 @@ export class Page extends React.Component<PageProps, PageState> {
     });
   }
 
+  private handleCopyFromPhotosToggle(event: CheckboxEvent) {
+    const copyFromPhotos = event.target.checked;
+
+    return requests.putCopyFromPhotos(copyFromPhotos).then(() => {
+      this.setState({ copyFromPhotos });
+      this.reloadLocations();
+    });
+  }
+
   private handleInterpolateToggle(event: CheckboxEvent) {
     const interpolate = event.target.checked;
 
@@ -204,9 +382,55 @@ export class Page extends React.Component<PageProps, PageState> {
     });
   }
 
-  private handlePhotoSelect(photo: Photo) {
-    this.setState({ currentPhoto: photo });
+  private handlePhotoSelect(photo: Photo, addToSelection: boolean) {
+    this.setState(previousState => {
+      if (!addToSelection) {
+        return { currentPhoto: photo, selectedPhotos: [photo] };
+      }
+
+      const selectedPhotos = previousState.selectedPhotos.filter(
+        selectedPhoto => selectedPhoto.path !== photo.path
+      );
+      if (selectedPhotos.length === previousState.selectedPhotos.length) {
+        selectedPhotos.push(photo);
+      }
+
+      return { currentPhoto: photo, selectedPhotos };
+    });
     this.photosGrid.forceUpdate();
+
+    return this.getAndStoreTrack(photo);
+  }
+
+  private handleLocationSet(coordinates: Coordinates) {
+    const paths = this.state.selectedPhotos.map(photo => photo.path);
+
+    return requests.writeUserCoordinates(paths, coordinates).then(() => {
+      this.setState(previousState => {
+        const photos = previousState.photos.map(photo => {
+          if (paths.indexOf(photo.path) === -1) {
+            return photo;
+          }
+          return Object.assign({}, photo, {
+            error: undefined,
+            location: { Existing: coordinates },
+            place: undefined,
+            userSet: true
+          });
+        });
+
+        const selectedPhotos = photos.filter(
+          photo => paths.indexOf(photo.path) !== -1
+        );
+        const currentPhoto = photos.find(
+          photo => photo.path === previousState.currentPhoto.path
+        );
+
+        return { currentPhoto, photos, selectedPhotos, track: undefined };
+      });
+
+      this.photosGrid.forceUpdate();
+    });
   }
 
   private handleSuggestionApply() {
@@ -220,7 +444,8 @@ export class Page extends React.Component<PageProps, PageState> {
           const currentPhoto = Object.assign({}, previousState.currentPhoto, {
             location: {
               Existing: previousState.currentPhoto.location.Suggested[0]
-            }
+            },
+            userSet: false
           });
 
           const photos = previousState.photos.slice();
@@ -236,6 +461,73 @@ export class Page extends React.Component<PageProps, PageState> {
       });
   }
 
+  private handleMaxAccuracyChange(event: InputEvent) {
+    this.setState({ maxAccuracy: event.target.value });
+  }
+
+  private handleSaveAll() {
+    const maxAccuracyMeters =
+      this.state.maxAccuracy === ''
+        ? undefined
+        : Number(this.state.maxAccuracy);
+
+    let total = 0;
+    let saved = 0;
+    let failed = 0;
+
+    const handleProgress = (progress: SaveProgress) => {
+      if (progress.Started) {
+        total = progress.Started.total;
+      } else if (progress.Saved) {
+        saved += 1;
+        this.setExistingLocation(
+          progress.Saved.path,
+          progress.Saved.coordinates
+        );
+      } else if (progress.Failed) {
+        failed += 1;
+      }
+
+      let saveStatus = `Saved ${saved} of ${total} locations`;
+      if (failed > 0) {
+        saveStatus += `, ${failed} failed`;
+      }
+      this.setState({ saveStatus });
+    };
+
+    return requests
+      .saveSuggestedLocations(maxAccuracyMeters, handleProgress)
+      .then(() => {
+        this.photosGrid.forceUpdate();
+      });
+  }
+
+  private setExistingLocation(path: string, coordinates: Coordinates) {
+    this.setState(previousState => {
+      const index = previousState.photos.findIndex(
+        photo => photo.path === path
+      );
+      if (index === -1) {
+        return null;
+      }
+
+      const photo = Object.assign({}, previousState.photos[index], {
+        location: { Existing: coordinates },
+        userSet: false
+      });
+
+      const photos = previousState.photos.slice();
+      photos[index] = photo;
+
+      const currentPhoto =
+        previousState.currentPhoto && previousState.currentPhoto.path === path
+          ? photo
+          : previousState.currentPhoto;
+
+      return { currentPhoto, photos };
+    });
+  }
+
   private handleSuggestionDiscard() {
     this.setState(previousState => {
       const currentPhoto = Object.assign({}, previousState.currentPhoto);
@@ -245,7 +537,7 @@ export class Page extends React.Component<PageProps, PageState> {
       const index = photos.findIndex(photo => photo.path === currentPhoto.path);
       photos[index] = currentPhoto;
 
-      return { currentPhoto, photos };
+      return { currentPhoto, photos, track: undefined };
     });
 
     this.photosGrid.forceUpdate();
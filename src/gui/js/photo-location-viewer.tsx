@@ -1,10 +1,13 @@
 import * as React from 'react';
-import { Photo } from './interfaces';
+import { Coordinates, Photo, Track } from './interfaces';
 import { MapArea } from './map-area';
 import { hasSuggestion, locationDescription } from './photo';
 
 export interface PhotoLocationViewerProps {
   photo?: Photo;
+  track?: Track;
+  offlineTiles?: boolean;
+  handleLocationSet: (coordinates: Coordinates) => Promise<void>;
   handleSuggestionApply: () => Promise<void>;
   handleSuggestionDiscard: () => void;
 }
@@ -15,7 +18,12 @@ export function PhotoLocationViewer(props: PhotoLocationViewerProps) {
       <div id="main">
         <section>
           <img src={props.photo.src} alt="Selected" />
-          <MapArea photo={props.photo} />
+          <MapArea
+            photo={props.photo}
+            track={props.track}
+            offlineTiles={props.offlineTiles}
+            handleLocationSet={props.handleLocationSet}
+          />
         </section>
         <footer>
           <div>{locationDescription(props.photo)}</div>
@@ -2,11 +2,16 @@ import * as React from 'react';
 import { Photo } from './interfaces';
 import { chooseIcon } from './photo';
 
+export interface SelectionModifiers {
+  ctrlKey: boolean;
+  metaKey: boolean;
+}
+
 export interface PhotoThumbnailProps {
   isSelected: boolean;
   photo: Photo;
   style: React.CSSProperties;
-  handleSelect: () => void;
+  handleSelect: (modifiers: SelectionModifiers) => void;
 }
 
 export function PhotoThumbnail(props: PhotoThumbnailProps) {
@@ -1,16 +1,36 @@
 import * as React from 'react';
 import { FiAlertCircle, FiNavigation, FiMapPin } from 'react-icons/fi';
-import { Photo } from './interfaces';
+import { LocationAccuracy, Photo } from './interfaces';
+
+const FALLBACK_TIMESTAMP_SOURCES = [
+  'FileName',
+  'XmpCreateDate',
+  'FileModificationTime'
+];
+
+export function isLowConfidence(accuracy: LocationAccuracy) {
+  return FALLBACK_TIMESTAMP_SOURCES.indexOf(accuracy.timestamp_source) !== -1;
+}
 
 export function locationDescription(photo: Photo) {
+  const near = photo.place ? ` near ${photo.place}` : '';
+
   if (photo.location && photo.location.Suggested) {
     const accuracy = photo.location.Suggested[1];
-    return `Suggested location: accuracy is ${accuracy.meters} meters and ${
-      accuracy.seconds
-    } seconds`;
+    const description = `Suggested location${near}: accuracy is ${
+      accuracy.meters
+    } meters and ${accuracy.seconds} seconds`;
+
+    if (isLowConfidence(accuracy)) {
+      return `${description} (low confidence, timestamp estimated)`;
+    }
+    return description;
+  }
+  if (photo.location && photo.userSet) {
+    return `Existing location${near} (set manually)`;
   }
   if (photo.location) {
-    return 'Existing location';
+    return `Existing location${near}`;
   }
   if (photo.error) {
     return photo.error;
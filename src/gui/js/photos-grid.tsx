@@ -13,8 +13,8 @@ const COLUMN_WIDTH = 150;
 
 export interface PhotosGridProps {
   photos: Photo[];
-  currentPhoto?: Photo;
-  handlePhotoSelect: (photo: Photo) => void;
+  selectedPhotos: Photo[];
+  handlePhotoSelect: (photo: Photo, addToSelection: boolean) => void;
   getAndStoreLocations: (
     startIndex: number,
     stopIndex: number
@@ -130,9 +130,16 @@ export class PhotosGrid extends React.Component<PhotosGridProps, {}> {
       <PhotoThumbnail
         key={key}
         style={style}
-        isSelected={photo === this.props.currentPhoto}
+        isSelected={this.props.selectedPhotos.some(
+          selectedPhoto => selectedPhoto.path === photo.path
+        )}
         photo={photo}
-        handleSelect={() => this.props.handlePhotoSelect(photo)}
+        handleSelect={modifiers =>
+          this.props.handlePhotoSelect(
+            photo,
+            modifiers.ctrlKey || modifiers.metaKey
+          )
+        }
       />
     );
   }
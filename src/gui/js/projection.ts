@@ -0,0 +1,48 @@
+import { Coordinates } from './interfaces';
+
+export const TILE_SIZE = 256;
+const MAX_LATITUDE = 85.0511287798;
+const EARTH_CIRCUMFERENCE_METERS = 40075016.686;
+
+export interface PixelPoint {
+  x: number;
+  y: number;
+}
+
+// Get the position of the given coordinates in Web Mercator pixels at the
+// given zoom level, as used by XYZ map tiles.
+export function project(coordinates: Coordinates, zoom: number): PixelPoint {
+  const scale = TILE_SIZE * 2 ** zoom;
+  const latitude = Math.max(
+    -MAX_LATITUDE,
+    Math.min(MAX_LATITUDE, coordinates.latitude)
+  );
+  const sinLatitude = Math.sin((latitude * Math.PI) / 180);
+
+  return {
+    x: ((coordinates.longitude + 180) / 360) * scale,
+    y:
+      (0.5 -
+        Math.log((1 + sinLatitude) / (1 - sinLatitude)) / (4 * Math.PI)) *
+      scale
+  };
+}
+
+export function unproject(point: PixelPoint, zoom: number): Coordinates {
+  const scale = TILE_SIZE * 2 ** zoom;
+  const n = Math.PI * (1 - (2 * point.y) / scale);
+
+  return {
+    latitude: (Math.atan(Math.sinh(n)) * 180) / Math.PI,
+    longitude: (point.x / scale) * 360 - 180
+  };
+}
+
+// Get the length in meters that a Web Mercator pixel covers at the given
+// latitude and zoom level.
+export function metersPerPixel(latitude: number, zoom: number) {
+  return (
+    (EARTH_CIRCUMFERENCE_METERS * Math.cos((latitude * Math.PI) / 180)) /
+    (TILE_SIZE * 2 ** zoom)
+  );
+}
@@ -1,4 +1,4 @@
-import { Coordinates, Photo } from './interfaces';
+import { Coordinates, GuiEvent, Photo, SaveProgress } from './interfaces';
 
 interface PhotosResponseBody {
   photos: Photo[];
@@ -29,6 +29,32 @@ function put(url: string, body: any) {
   });
 }
 
+function readLines(response: Response, handleLine: (line: string) => void) {
+  const reader = response.body.getReader();
+  const decoder = new TextDecoder();
+  let buffer = '';
+
+  function read(): Promise<void> {
+    return reader.read().then(({ done, value }) => {
+      if (done) {
+        if (buffer) {
+          handleLine(buffer);
+        }
+        return undefined;
+      }
+
+      buffer += decoder.decode(value, { stream: true });
+      const lines = buffer.split('\n');
+      buffer = lines.pop();
+      lines.forEach(handleLine);
+
+      return read();
+    });
+  }
+
+  return read();
+}
+
 function setPhotoSrc(photo: Photo) {
   return Object.assign(photo, {
     src: `/thumbnail?path=${encodeURIComponent(
@@ -37,6 +63,13 @@ function setPhotoSrc(photo: Photo) {
   });
 }
 
+// Give a changed photo's thumbnail a different URL so that the browser doesn't
+// show the thumbnail it already has.
+function setChangedPhotoSrc(photo: Photo) {
+  const { src } = setPhotoSrc(photo);
+  return Object.assign(photo, { src: `${src}&changed=${Date.now()}` });
+}
+
 function mapPhotos(responseBody: PhotosResponseBody) {
   return responseBody.photos.map(setPhotoSrc);
 }
@@ -65,6 +98,37 @@ export function putInterpolate(interpolate: boolean) {
   return put('/interpolate', { interpolate });
 }
 
+export function getTiles() {
+  return get('/tiles');
+}
+
+export function getCopyFromPhotos() {
+  return get('/copyFromPhotos');
+}
+
+export function putCopyFromPhotos(copyFromPhotos: boolean) {
+  return put('/copyFromPhotos', { copyFromPhotos });
+}
+
+// Listen for changes made by the server in the background, such as photos
+// being found while it scans the photos path or files changing on disk. The
+// returned EventSource should be closed when the events are no longer needed.
+export function subscribeToEvents(handleEvent: (event: GuiEvent) => void) {
+  const eventSource = new EventSource('/events');
+
+  eventSource.onmessage = message => {
+    const event: GuiEvent = JSON.parse(message.data);
+    if (event.PhotosFound) {
+      event.PhotosFound.photos.forEach(setPhotoSrc);
+    } else if (event.PhotoChanged) {
+      setChangedPhotoSrc(event.PhotoChanged.photo);
+    }
+    handleEvent(event);
+  };
+
+  return eventSource;
+}
+
 export function getPhotos() {
   return get('/photos').then(mapPhotos);
 }
@@ -77,6 +141,35 @@ export function writeCoordinates(path: string, coordinates: Coordinates) {
   return put(`/location?path=${encodeURIComponent(path)}`, coordinates);
 }
 
+export function writeUserCoordinates(
+  paths: string[],
+  coordinates: Coordinates
+) {
+  return put('/locations', { paths, coordinates });
+}
+
+export function saveSuggestedLocations(
+  maxAccuracyMeters: number | undefined,
+  handleProgress: (progress: SaveProgress) => void
+) {
+  const url = '/locations';
+  const init = {
+    body: JSON.stringify({ maxAccuracyMeters }),
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json'
+    }
+  };
+
+  return fetch(url, init).then(response => {
+    if (!response.ok) {
+      throw new Error(`Failed to ${init.method} ${url} with body ${init.body}`);
+    }
+
+    return readLines(response, line => handleProgress(JSON.parse(line)));
+  });
+}
+
 export function getLocations(startIndex: number, endIndex: number) {
   return get(`/locations?start=${startIndex}&end=${endIndex}`).then(
     responseBody => responseBody.locations
@@ -86,3 +179,7 @@ export function getLocations(startIndex: number, endIndex: number) {
 export function getLocation(path: string) {
   return get(`/location?path=${encodeURIComponent(path)}`);
 }
+
+export function getTrack(path: string) {
+  return get(`/track?path=${encodeURIComponent(path)}`);
+}
@@ -0,0 +1,102 @@
+import * as React from 'react';
+
+import {
+  Coordinates,
+  Position,
+  Track,
+  TrackPoint,
+  TrackPointFeature
+} from './interfaces';
+import { metersPerPixel, project } from './projection';
+
+const TRACK_COLOUR = 'royalblue';
+const MIN_CIRCLE_RADIUS = 3;
+
+function toCoordinates(position: Position): Coordinates {
+  return {
+    latitude: position[1],
+    longitude: position[0]
+  };
+}
+
+// Get the location history points of a track, in time order.
+export function trackPoints(track?: Track): TrackPoint[] {
+  if (!track) {
+    return [];
+  }
+
+  return track.features
+    .filter(
+      (feature): feature is TrackPointFeature =>
+        feature.geometry.type === 'Point'
+    )
+    .map(feature => ({
+      ...feature.properties,
+      coordinates: toCoordinates(feature.geometry.coordinates)
+    }));
+}
+
+export interface TrackOverlayProps {
+  points: TrackPoint[];
+  // Used by google-map-react to position the overlay.
+  lat?: number;
+  lng?: number;
+  zoom: number;
+  style?: React.CSSProperties;
+}
+
+// Draws the path between location history points and their accuracy circles,
+// relative to the first point so that it can be positioned on a map by the
+// first point's coordinates. Circles are drawn with a minimum radius so that
+// accurate points are still visible when zoomed out.
+export function TrackOverlay(props: TrackOverlayProps) {
+  if (props.points.length === 0) {
+    return null;
+  }
+
+  const origin = project(props.points[0].coordinates, props.zoom);
+  const pixels = props.points.map(point => {
+    const pixel = project(point.coordinates, props.zoom);
+    return {
+      point,
+      radius:
+        point.accuracy /
+        metersPerPixel(point.coordinates.latitude, props.zoom),
+      x: pixel.x - origin.x,
+      y: pixel.y - origin.y
+    };
+  });
+
+  return (
+    <svg
+      width={1}
+      height={1}
+      style={{
+        left: 0,
+        overflow: 'visible',
+        pointerEvents: 'none',
+        position: 'absolute',
+        top: 0,
+        ...props.style
+      }}
+    >
+      <polyline
+        points={pixels.map(pixel => `${pixel.x},${pixel.y}`).join(' ')}
+        fill="none"
+        stroke={TRACK_COLOUR}
+        strokeWidth={2}
+      />
+      {pixels.map(pixel => (
+        <circle
+          key={pixel.point.timestampMs}
+          cx={pixel.x}
+          cy={pixel.y}
+          r={Math.max(pixel.radius, MIN_CIRCLE_RADIUS)}
+          fill={TRACK_COLOUR}
+          fillOpacity={0.2}
+          stroke={TRACK_COLOUR}
+        />
+      ))}
+    </svg>
+  );
+}
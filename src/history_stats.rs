@@ -0,0 +1,440 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+use golo::GoogleLocationHistory;
+
+const MS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+/// The upper bounds of the accuracy distribution's buckets, in metres.
+const ACCURACY_BUCKETS: &[u32] = &[10, 50, 100, 500, 1000, 5000];
+
+/// A period between two consecutive locations that is longer than the gap threshold.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gap {
+    start_ms: i64,
+    end_ms: i64,
+}
+
+impl Gap {
+    pub fn start_ms(&self) -> i64 {
+        self.start_ms
+    }
+
+    pub fn end_ms(&self) -> i64 {
+        self.end_ms
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::milliseconds(self.end_ms.saturating_sub(self.start_ms))
+    }
+
+    fn contains(&self, timestamp_ms: i64) -> bool {
+        timestamp_ms > self.start_ms && timestamp_ms < self.end_ms
+    }
+}
+
+/// The number of locations with an accuracy no worse than `max_meters` but worse than the
+/// previous bucket's. The last bucket has no maximum.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AccuracyBucket {
+    max_meters: Option<u32>,
+    count: usize,
+}
+
+impl AccuracyBucket {
+    pub fn max_meters(&self) -> Option<u32> {
+        self.max_meters
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+/// How much of a UTC day the location history covers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DayCoverage {
+    date: NaiveDate,
+    locations: usize,
+    covered_ms: i64,
+}
+
+impl DayCoverage {
+    pub fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    /// The number of locations recorded during the day.
+    pub fn locations(&self) -> usize {
+        self.locations
+    }
+
+    /// The fraction of the day that is between the first and last locations and not in a gap.
+    pub fn fraction_covered(&self) -> f64 {
+        self.covered_ms as f64 / MS_PER_DAY as f64
+    }
+}
+
+/// Statistics about what a location history covers. Any time between the first and last locations
+/// that isn't in a gap is covered.
+#[derive(Debug, PartialEq)]
+pub struct HistoryStats {
+    count: usize,
+    first_timestamp_ms: Option<i64>,
+    last_timestamp_ms: Option<i64>,
+    accuracy_distribution: Vec<AccuracyBucket>,
+    gaps: Vec<Gap>,
+    day_count: i64,
+    days: Vec<DayCoverage>,
+}
+
+impl HistoryStats {
+    /// Calculate statistics for the given location history, treating any time between consecutive
+    /// locations that is longer than `gap_threshold` as a gap.
+    pub fn new(location_history: &GoogleLocationHistory, gap_threshold: Duration) -> HistoryStats {
+        let gap_threshold_ms = gap_threshold.num_milliseconds();
+
+        let mut accuracy_distribution: Vec<AccuracyBucket> = ACCURACY_BUCKETS
+            .iter()
+            .map(|max| Some(*max))
+            .chain(Some(None))
+            .map(|max_meters| AccuracyBucket {
+                max_meters,
+                count: 0,
+            }).collect();
+
+        let mut gaps = Vec::new();
+        let mut previous_timestamp_ms: Option<i64> = None;
+        for location in location_history.locations() {
+            let index = ACCURACY_BUCKETS
+                .iter()
+                .position(|max| location.accuracy() <= *max)
+                .unwrap_or(ACCURACY_BUCKETS.len());
+            accuracy_distribution[index].count += 1;
+
+            if let Some(previous) = previous_timestamp_ms {
+                if location.timestamp_ms().saturating_sub(previous) > gap_threshold_ms {
+                    gaps.push(Gap {
+                        start_ms: previous,
+                        end_ms: location.timestamp_ms(),
+                    });
+                }
+            }
+            previous_timestamp_ms = Some(location.timestamp_ms());
+        }
+
+        let mut locations = location_history.locations();
+        let first_timestamp_ms = locations.next().map(|l| l.timestamp_ms());
+        let last_timestamp_ms = locations
+            .next_back()
+            .map(|l| l.timestamp_ms())
+            .or(first_timestamp_ms);
+
+        let (day_count, days) = match (first_timestamp_ms, last_timestamp_ms) {
+            (Some(first), Some(last)) => (
+                day_number(last) - day_number(first) + 1,
+                day_coverage(location_history, &gaps, first, last),
+            ),
+            _ => (0, Vec::new()),
+        };
+
+        HistoryStats {
+            count: location_history.len(),
+            first_timestamp_ms,
+            last_timestamp_ms,
+            accuracy_distribution,
+            gaps,
+            day_count,
+            days,
+        }
+    }
+
+    /// The number of locations in the history.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn first_timestamp_ms(&self) -> Option<i64> {
+        self.first_timestamp_ms
+    }
+
+    pub fn last_timestamp_ms(&self) -> Option<i64> {
+        self.last_timestamp_ms
+    }
+
+    /// The time between the first and last locations.
+    pub fn span(&self) -> Duration {
+        match (self.first_timestamp_ms, self.last_timestamp_ms) {
+            (Some(first), Some(last)) => Duration::milliseconds(last.saturating_sub(first)),
+            _ => Duration::zero(),
+        }
+    }
+
+    pub fn accuracy_distribution(&self) -> &[AccuracyBucket] {
+        &self.accuracy_distribution
+    }
+
+    pub fn gaps(&self) -> &[Gap] {
+        &self.gaps
+    }
+
+    /// The number of days from the first location's day to the last location's day.
+    pub fn day_count(&self) -> i64 {
+        self.day_count
+    }
+
+    /// Coverage for each day that has locations or is partly covered, in date order. Days that
+    /// are entirely in a gap are left out, as are days too far from the Unix epoch to have a
+    /// date.
+    pub fn days(&self) -> &[DayCoverage] {
+        &self.days
+    }
+
+    /// Check if the given timestamp is between the first and last locations and not in a gap.
+    pub fn is_covered(&self, timestamp_ms: i64) -> bool {
+        let in_span = match (self.first_timestamp_ms, self.last_timestamp_ms) {
+            (Some(first), Some(last)) => timestamp_ms >= first && timestamp_ms <= last,
+            _ => false,
+        };
+        if !in_span {
+            return false;
+        }
+
+        // Gaps are sorted and don't overlap, so find the last gap starting before the timestamp.
+        let index = match self
+            .gaps
+            .binary_search_by_key(&timestamp_ms, |g| g.start_ms)
+        {
+            Ok(_) => return true,
+            Err(0) => return true,
+            Err(index) => index - 1,
+        };
+
+        !self.gaps[index].contains(timestamp_ms)
+    }
+}
+
+fn day_coverage(
+    location_history: &GoogleLocationHistory,
+    gaps: &[Gap],
+    first_timestamp_ms: i64,
+    last_timestamp_ms: i64,
+) -> Vec<DayCoverage> {
+    // Only the days that have locations or are partly covered are stored, so that a location
+    // with a corrupt timestamp far from the others doesn't need an entry for every day between.
+    let mut days: BTreeMap<i64, DayCoverage> = BTreeMap::new();
+
+    let covered_starts = Some(first_timestamp_ms)
+        .into_iter()
+        .chain(gaps.iter().map(|g| g.end_ms));
+    let covered_ends = gaps
+        .iter()
+        .map(|g| g.start_ms)
+        .chain(Some(last_timestamp_ms));
+    for (covered_start, covered_end) in covered_starts.zip(covered_ends) {
+        for day in day_number(covered_start)..=day_number(covered_end) {
+            if let Some(coverage) = day_entry(&mut days, day) {
+                let start = day * MS_PER_DAY;
+                coverage.covered_ms +=
+                    overlap(start, start + MS_PER_DAY, covered_start, covered_end);
+            }
+        }
+    }
+
+    for location in location_history.locations() {
+        if let Some(coverage) = day_entry(&mut days, day_number(location.timestamp_ms())) {
+            coverage.locations += 1;
+        }
+    }
+
+    days.values().cloned().collect()
+}
+
+/// Get the coverage of the given day, adding it if necessary, or `None` if the day doesn't have
+/// a date.
+fn day_entry(days: &mut BTreeMap<i64, DayCoverage>, day: i64) -> Option<&mut DayCoverage> {
+    let date = NaiveDateTime::from_timestamp_opt(day.checked_mul(24 * 60 * 60)?, 0)?.date();
+
+    Some(days.entry(day).or_insert(DayCoverage {
+        date,
+        locations: 0,
+        covered_ms: 0,
+    }))
+}
+
+/// The number of days since the Unix epoch, rounding down.
+fn day_number(timestamp_ms: i64) -> i64 {
+    timestamp_ms.div_euclid(MS_PER_DAY)
+}
+
+fn overlap(start_a: i64, end_a: i64, start_b: i64, end_b: i64) -> i64 {
+    (end_a.min(end_b) - start_a.max(start_b)).max(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json;
+
+    const HOUR_MS: i64 = 60 * 60 * 1000;
+
+    fn history(locations: &[(i64, u32)]) -> GoogleLocationHistory {
+        let locations: Vec<String> = locations
+            .iter()
+            .map(|&(timestamp_ms, accuracy)| {
+                format!(
+                    r#"{{"timestampMs":"{}","latitudeE7":0,"longitudeE7":0,"accuracy":{}}}"#,
+                    timestamp_ms, accuracy
+                )
+            }).collect();
+
+        serde_json::from_str(&format!(r#"{{"locations":[{}]}}"#, locations.join(","))).unwrap()
+    }
+
+    fn stats(locations: &[(i64, u32)]) -> HistoryStats {
+        HistoryStats::new(&history(locations), Duration::hours(1))
+    }
+
+    #[test]
+    fn new_should_give_empty_stats_for_an_empty_history() {
+        let stats = stats(&[]);
+
+        assert_eq!(0, stats.count());
+        assert_eq!(None, stats.first_timestamp_ms());
+        assert_eq!(Duration::zero(), stats.span());
+        assert!(stats.gaps().is_empty());
+        assert!(stats.days().is_empty());
+        assert!(!stats.is_covered(0));
+    }
+
+    #[test]
+    fn new_should_count_locations_and_calculate_the_span() {
+        let stats = stats(&[(HOUR_MS, 10), (3 * HOUR_MS, 10), (2 * HOUR_MS, 10)]);
+
+        assert_eq!(3, stats.count());
+        assert_eq!(Some(HOUR_MS), stats.first_timestamp_ms());
+        assert_eq!(Some(3 * HOUR_MS), stats.last_timestamp_ms());
+        assert_eq!(Duration::hours(2), stats.span());
+    }
+
+    #[test]
+    fn new_should_bucket_locations_by_accuracy() {
+        let stats = stats(&[(0, 5), (1, 10), (2, 11), (3, 600), (4, 10000)]);
+
+        let counts: Vec<(Option<u32>, usize)> = stats
+            .accuracy_distribution()
+            .iter()
+            .map(|b| (b.max_meters(), b.count()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (Some(10), 2),
+                (Some(50), 1),
+                (Some(100), 0),
+                (Some(500), 0),
+                (Some(1000), 1),
+                (Some(5000), 0),
+                (None, 1),
+            ],
+            counts
+        );
+    }
+
+    #[test]
+    fn new_should_find_gaps_longer_than_the_threshold() {
+        let stats = stats(&[(0, 10), (HOUR_MS, 10), (3 * HOUR_MS, 10), (5 * HOUR_MS, 10)]);
+
+        assert_eq!(
+            vec![
+                Gap {
+                    start_ms: HOUR_MS,
+                    end_ms: 3 * HOUR_MS,
+                },
+                Gap {
+                    start_ms: 3 * HOUR_MS,
+                    end_ms: 5 * HOUR_MS,
+                },
+            ],
+            stats.gaps()
+        );
+        assert_eq!(Duration::hours(2), stats.gaps()[0].duration());
+    }
+
+    #[test]
+    fn new_should_calculate_coverage_for_each_day() {
+        // Day 0 is covered from 12:00 to 24:00, except for a gap from 14:00 to 20:00.
+        let hours = [12, 13, 14, 20, 21, 22, 23, 24];
+        let mut locations: Vec<(i64, u32)> = hours.iter().map(|h| (h * HOUR_MS, 10)).collect();
+        locations.push((24 * HOUR_MS + HOUR_MS / 2, 10));
+        let stats = stats(&locations);
+
+        let days = stats.days();
+        assert_eq!(2, days.len());
+
+        assert_eq!(NaiveDate::from_ymd(1970, 1, 1), days[0].date());
+        assert_eq!(7, days[0].locations());
+        assert_eq!(6.0 / 24.0, days[0].fraction_covered());
+
+        assert_eq!(NaiveDate::from_ymd(1970, 1, 2), days[1].date());
+        assert_eq!(2, days[1].locations());
+        assert_eq!(0.5 / 24.0, days[1].fraction_covered());
+    }
+
+    #[test]
+    fn new_should_count_but_not_store_days_that_are_entirely_in_a_gap() {
+        let stats = stats(&[(0, 10), (10 * 24 * HOUR_MS, 10)]);
+
+        assert_eq!(11, stats.day_count());
+
+        let days = stats.days();
+        assert_eq!(2, days.len());
+        assert_eq!(NaiveDate::from_ymd(1970, 1, 1), days[0].date());
+        assert_eq!(NaiveDate::from_ymd(1970, 1, 11), days[1].date());
+        assert_eq!(1, days[1].locations());
+    }
+
+    #[test]
+    fn new_should_skip_the_days_of_timestamps_too_extreme_to_have_a_date() {
+        let stats = stats(&[
+            (i64::min_value(), 10),
+            (HOUR_MS, 10),
+            (2 * HOUR_MS, 10),
+            (i64::max_value(), 10),
+        ]);
+
+        assert_eq!(4, stats.count());
+        assert_eq!(2, stats.gaps().len());
+        assert_eq!(Duration::milliseconds(i64::max_value()), stats.span());
+
+        let days = stats.days();
+        assert_eq!(1, days.len());
+        assert_eq!(NaiveDate::from_ymd(1970, 1, 1), days[0].date());
+        assert_eq!(2, days[0].locations());
+        assert_eq!(1.0 / 24.0, days[0].fraction_covered());
+    }
+
+    #[test]
+    fn is_covered_should_be_false_outside_the_span_or_inside_a_gap() {
+        let stats = stats(&[(HOUR_MS, 10), (2 * HOUR_MS, 10), (5 * HOUR_MS, 10)]);
+
+        assert!(!stats.is_covered(0));
+        assert!(stats.is_covered(HOUR_MS));
+        assert!(stats.is_covered(HOUR_MS + 1));
+        assert!(stats.is_covered(2 * HOUR_MS));
+        assert!(!stats.is_covered(3 * HOUR_MS));
+        assert!(stats.is_covered(5 * HOUR_MS));
+        assert!(!stats.is_covered(5 * HOUR_MS + 1));
+    }
+
+    #[test]
+    fn day_number_should_round_down() {
+        assert_eq!(0, day_number(0));
+        assert_eq!(0, day_number(MS_PER_DAY - 1));
+        assert_eq!(1, day_number(MS_PER_DAY));
+        assert_eq!(-1, day_number(-1));
+        assert_eq!(-1, day_number(-MS_PER_DAY));
+    }
+}
@@ -0,0 +1,687 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use chrono::Utc;
+use serde_json;
+use sha2::{Digest, Sha256};
+
+use coordinates::Coordinates;
+use geocoder::Place;
+use media_format::read_metadata;
+use safe_write::{replace_file, write_coordinates_safely, WriteError, WriteOptions};
+
+const JOURNAL_FILE_NAME: &str = "journal.jsonl";
+const ORIGINALS_DIRECTORY_NAME: &str = "originals";
+
+/// The number of most recent sessions whose writes are kept in the journal. Older sessions are
+/// pruned when a new session first writes, along with any copies of original files that only
+/// they used.
+pub const MAX_JOURNAL_SESSIONS: usize = 20;
+
+#[derive(Debug)]
+pub enum JournalError {
+    IoError(io::Error),
+    SerializationError(serde_json::Error),
+    WriteError(WriteError),
+    /// The file has been changed since the journalled write, so restoring it would lose those
+    /// changes.
+    FileChanged(PathBuf),
+}
+
+impl From<io::Error> for JournalError {
+    fn from(error: io::Error) -> Self {
+        JournalError::IoError(error)
+    }
+}
+
+impl From<serde_json::Error> for JournalError {
+    fn from(error: serde_json::Error) -> Self {
+        JournalError::SerializationError(error)
+    }
+}
+
+impl From<WriteError> for JournalError {
+    fn from(error: WriteError) -> Self {
+        match error {
+            WriteError::IoError(e) => JournalError::IoError(e),
+            e => JournalError::WriteError(e),
+        }
+    }
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JournalError::IoError(ref x) => x.fmt(f),
+            JournalError::SerializationError(ref x) => x.fmt(f),
+            JournalError::WriteError(ref x) => x.fmt(f),
+            JournalError::FileChanged(ref x) => write!(
+                f,
+                "{} has changed since its location was saved",
+                x.display()
+            ),
+        }
+    }
+}
+
+impl error::Error for JournalError {
+    fn description(&self) -> &str {
+        match self {
+            JournalError::IoError(ref x) => x.description(),
+            JournalError::SerializationError(ref x) => x.description(),
+            JournalError::WriteError(ref x) => x.description(),
+            JournalError::FileChanged(_) => "The file has changed since its location was saved",
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            JournalError::IoError(ref x) => Some(x),
+            JournalError::SerializationError(ref x) => Some(x),
+            JournalError::WriteError(ref x) => Some(x),
+            JournalError::FileChanged(_) => None,
+        }
+    }
+}
+
+/// A record of a location that was written to a file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    session: String,
+    timestamp_ms: i64,
+    path: PathBuf,
+    original_coordinates: Option<Coordinates>,
+    coordinates: Coordinates,
+    original_hash: String,
+    hash: String,
+}
+
+impl JournalEntry {
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    /// The Unix timestamp in milliseconds of when the location was written.
+    pub fn timestamp_ms(&self) -> i64 {
+        self.timestamp_ms
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The location that the file had before it was written, if any.
+    pub fn original_coordinates(&self) -> Option<&Coordinates> {
+        self.original_coordinates.as_ref()
+    }
+
+    pub fn coordinates(&self) -> &Coordinates {
+        &self.coordinates
+    }
+}
+
+/// A journal of location writes, so that they can be undone. A copy of each file is kept from
+/// before it was written, so undoing a write restores the file byte-for-byte.
+///
+/// Every journal instance has its own session ID, so all the writes made through it can be
+/// rolled back together.
+pub struct Journal {
+    directory: PathBuf,
+    session: String,
+    lock: Mutex<()>,
+    pruned: Mutex<bool>,
+    /// The paths that are being written, with the hashes of their originals once they've been
+    /// read. Only one write to a path is recorded at a time, and originals that are in use by a
+    /// write are not removed before its entry is added.
+    writing: Mutex<HashMap<PathBuf, Option<String>>>,
+    write_finished: Condvar,
+}
+
+/// Marks a path as being written until it is dropped.
+struct WriteGuard<'a> {
+    journal: &'a Journal,
+    path: PathBuf,
+}
+
+impl<'a> Drop for WriteGuard<'a> {
+    fn drop(&mut self) {
+        self.journal.writing().remove(&self.path);
+        self.journal.write_finished.notify_all();
+    }
+}
+
+impl Journal {
+    pub fn new(directory: &Path) -> Journal {
+        let session = format!(
+            "{}-{}",
+            Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+            process::id()
+        );
+
+        Journal {
+            directory: directory.to_path_buf(),
+            session,
+            lock: Mutex::new(()),
+            pruned: Mutex::new(false),
+            writing: Mutex::new(HashMap::new()),
+            write_finished: Condvar::new(),
+        }
+    }
+
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    /// Write the given coordinates, and the names of the place they are in if given, to the file
+    /// at the given path using the given options, recording the write in the journal.
+    pub fn write_coordinates(
+        &self,
+        path: &Path,
+        coordinates: &Coordinates,
+        place: Option<&Place>,
+        options: &WriteOptions,
+    ) -> Result<JournalEntry, JournalError> {
+        self.record(path, coordinates, || {
+            write_coordinates_safely(path, coordinates, place, options).map(|_| ())
+        })
+    }
+
+    /// Get all the journalled writes, oldest first.
+    pub fn entries(&self) -> Result<Vec<JournalEntry>, JournalError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.read_entries()
+    }
+
+    /// Get the ID of the session that most recently wrote a location.
+    pub fn latest_session(&self) -> Result<Option<String>, JournalError> {
+        Ok(self.entries()?.pop().map(|e| e.session))
+    }
+
+    /// Undo the most recent journalled write, returning its entry.
+    pub fn undo_last(&self) -> Result<Option<JournalEntry>, JournalError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut entries = self.read_entries()?;
+        let entry = match entries.pop() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        self.restore(&entry)?;
+        self.write_entries(&entries)?;
+        self.remove_unused_originals(&entries, &[&entry])?;
+
+        Ok(Some(entry))
+    }
+
+    /// Undo all the journalled writes made in the given session, most recent first, returning
+    /// their entries.
+    pub fn rollback(&self, session: &str) -> Result<Vec<JournalEntry>, JournalError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut entries = self.read_entries()?;
+        let mut undone = Vec::new();
+
+        let result: Result<(), JournalError> = (|| {
+            while let Some(index) = entries.iter().rposition(|e| e.session == session) {
+                self.restore(&entries[index])?;
+                undone.push(entries.remove(index));
+            }
+            Ok(())
+        })();
+
+        if !undone.is_empty() {
+            self.write_entries(&entries)?;
+            self.remove_unused_originals(&entries, &undone.iter().collect::<Vec<_>>())?;
+        }
+
+        result.map(|()| undone)
+    }
+
+    fn record<F, E>(
+        &self,
+        path: &Path,
+        coordinates: &Coordinates,
+        write: F,
+    ) -> Result<JournalEntry, JournalError>
+    where
+        F: FnOnce() -> Result<(), E>,
+        JournalError: From<E>,
+    {
+        self.prune_old_sessions()?;
+
+        let path = path.canonicalize()?;
+        let _write_guard = self.start_writing(&path);
+
+        let original_coordinates = read_metadata(&path)
+            .ok()
+            .and_then(|metadata| metadata.coordinates().cloned());
+
+        let original = fs::read(&path)?;
+        let original_hash = hash(&original);
+        self.writing()
+            .insert(path.clone(), Some(original_hash.clone()));
+
+        let original_path = self.original_path(&original_hash);
+        if !original_path.exists() {
+            fs::create_dir_all(self.directory.join(ORIGINALS_DIRECTORY_NAME))?;
+            fs::write(&original_path, &original)?;
+        }
+
+        write()?;
+
+        let entry = JournalEntry {
+            session: self.session.clone(),
+            timestamp_ms: Utc::now().timestamp_millis(),
+            hash: hash(&fs::read(&path)?),
+            path,
+            original_coordinates,
+            coordinates: coordinates.clone(),
+            original_hash,
+        };
+
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.journal_path())?;
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+
+        Ok(entry)
+    }
+
+    /// Wait until the given path is not being written, then mark it as being written.
+    fn start_writing(&self, path: &Path) -> WriteGuard<'_> {
+        let mut writing = self.writing();
+        while writing.contains_key(path) {
+            writing = self
+                .write_finished
+                .wait(writing)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        writing.insert(path.to_path_buf(), None);
+
+        WriteGuard {
+            journal: self,
+            path: path.to_path_buf(),
+        }
+    }
+
+    fn writing(&self) -> MutexGuard<'_, HashMap<PathBuf, Option<String>>> {
+        self.writing.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn restore(&self, entry: &JournalEntry) -> Result<(), JournalError> {
+        if hash(&fs::read(&entry.path)?) != entry.hash {
+            return Err(JournalError::FileChanged(entry.path.clone()));
+        }
+
+        let original = fs::read(self.original_path(&entry.original_hash))?;
+        replace_file(&entry.path, &original)?;
+
+        Ok(())
+    }
+
+    /// Remove the entries of sessions older than the most recent `MAX_JOURNAL_SESSIONS`,
+    /// counting this one, the first time this session writes. This is done before the first
+    /// original is copied, so that it can't be removed as unused.
+    fn prune_old_sessions(&self) -> Result<(), JournalError> {
+        let mut pruned = self.pruned.lock().unwrap_or_else(|e| e.into_inner());
+        if *pruned {
+            return Ok(());
+        }
+
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let entries = self.read_entries()?;
+
+        let mut kept_sessions: Vec<&str> = Vec::new();
+        for entry in entries.iter().rev() {
+            if kept_sessions.len() == MAX_JOURNAL_SESSIONS - 1 {
+                break;
+            }
+            if !kept_sessions.contains(&entry.session()) {
+                kept_sessions.push(entry.session());
+            }
+        }
+
+        let (kept, removed): (Vec<&JournalEntry>, Vec<&JournalEntry>) = entries
+            .iter()
+            .partition(|e| kept_sessions.contains(&e.session()));
+
+        if !removed.is_empty() {
+            let kept: Vec<JournalEntry> = kept.into_iter().cloned().collect();
+            self.write_entries(&kept)?;
+            self.remove_unused_originals(&kept, &removed)?;
+        }
+
+        *pruned = true;
+        Ok(())
+    }
+
+    fn read_entries(&self) -> Result<Vec<JournalEntry>, JournalError> {
+        let file = match File::open(self.journal_path()) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(&line)?);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Replace the journal's entries, writing to a temporary file first so that the journal
+    /// can't be left half-written.
+    fn write_entries(&self, entries: &[JournalEntry]) -> Result<(), JournalError> {
+        let temp_path = self.directory.join(format!("{}.tmp", JOURNAL_FILE_NAME));
+
+        {
+            let mut file = File::create(&temp_path)?;
+            for entry in entries {
+                let mut line = serde_json::to_vec(entry)?;
+                line.push(b'\n');
+                file.write_all(&line)?;
+            }
+            file.sync_all()?;
+        }
+
+        fs::rename(temp_path, self.journal_path())?;
+
+        Ok(())
+    }
+
+    fn remove_unused_originals(
+        &self,
+        entries: &[JournalEntry],
+        removed: &[&JournalEntry],
+    ) -> io::Result<()> {
+        // Holding this lock stops a write from finding an original that is about to be removed.
+        let writing = self.writing();
+        let is_pending = |hash: &str| {
+            writing
+                .values()
+                .any(|h| h.as_ref().map(String::as_str) == Some(hash))
+        };
+
+        for entry in removed {
+            if entries
+                .iter()
+                .all(|e| e.original_hash != entry.original_hash)
+                && !is_pending(&entry.original_hash)
+            {
+                match fs::remove_file(self.original_path(&entry.original_hash)) {
+                    Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                    x => x?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn journal_path(&self) -> PathBuf {
+        self.directory.join(JOURNAL_FILE_NAME)
+    }
+
+    fn original_path(&self, hash: &str) -> PathBuf {
+        self.directory.join(ORIGINALS_DIRECTORY_NAME).join(hash)
+    }
+}
+
+fn hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+    use self::tempfile::{tempdir, TempDir};
+
+    fn write_file(path: &Path, content: &[u8]) -> io::Result<()> {
+        fs::write(path, content)
+    }
+
+    fn journal_with_session(directory: &Path, session: &str) -> Journal {
+        Journal {
+            directory: directory.to_path_buf(),
+            session: session.to_string(),
+            lock: Mutex::new(()),
+            pruned: Mutex::new(false),
+            writing: Mutex::new(HashMap::new()),
+            write_finished: Condvar::new(),
+        }
+    }
+
+    fn setup() -> (TempDir, Journal, PathBuf) {
+        let tmp_dir = tempdir().unwrap();
+        let journal = Journal::new(&tmp_dir.path().join("journal"));
+        fs::create_dir(tmp_dir.path().join("journal")).unwrap();
+
+        let photo_path = tmp_dir.path().join("photo.jpg");
+        fs::copy("tests/assets/photo.jpg", &photo_path).unwrap();
+
+        (tmp_dir, journal, photo_path)
+    }
+
+    #[test]
+    fn hash_should_be_a_lowercase_hex_sha256_digest() {
+        assert_eq!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            hash(b"")
+        );
+    }
+
+    #[test]
+    fn record_should_append_an_entry_with_the_original_coordinates() {
+        let (_tmp_dir, journal, path) = setup();
+
+        let coordinates = Coordinates::new(1.0, 2.0);
+        let entry = journal
+            .record(&path, &coordinates, || write_file(&path, b"written"))
+            .unwrap();
+
+        assert_eq!(journal.session(), entry.session());
+        assert_eq!(path.canonicalize().unwrap(), entry.path());
+        assert_eq!(&coordinates, entry.coordinates());
+        assert_eq!(
+            Some(&Coordinates::new(38.76544, -9.094802222222222)),
+            entry.original_coordinates()
+        );
+        assert_eq!(hash(b"written"), entry.hash);
+        assert_eq!(vec![entry], journal.entries().unwrap());
+    }
+
+    #[test]
+    fn record_should_not_add_an_entry_if_the_write_fails() {
+        let (_tmp_dir, journal, path) = setup();
+
+        let result = journal.record(&path, &Coordinates::new(1.0, 2.0), || {
+            Err(io::Error::new(io::ErrorKind::Other, "failed"))
+        });
+
+        assert!(result.is_err());
+        assert!(journal.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn record_should_prune_sessions_older_than_the_most_recent_sessions() {
+        let (tmp_dir, _, path) = setup();
+        let directory = tmp_dir.path().join("journal");
+
+        let coordinates = Coordinates::new(1.0, 2.0);
+        for session in 0..MAX_JOURNAL_SESSIONS + 1 {
+            let journal = journal_with_session(&directory, &session.to_string());
+            write_file(&path, session.to_string().as_bytes()).unwrap();
+            journal
+                .record(&path, &coordinates, || write_file(&path, b"written"))
+                .unwrap();
+        }
+
+        let entries = journal_with_session(&directory, "").entries().unwrap();
+        assert_eq!(MAX_JOURNAL_SESSIONS, entries.len());
+        assert_eq!("1", entries[0].session());
+        assert!(!directory.join("originals").join(hash(b"0")).exists());
+        assert!(directory.join("originals").join(hash(b"1")).exists());
+    }
+
+    #[test]
+    fn undo_last_should_restore_the_original_file_and_remove_the_entry() {
+        let (_tmp_dir, journal, path) = setup();
+        let original = fs::read(&path).unwrap();
+
+        let coordinates = Coordinates::new(1.0, 2.0);
+        journal
+            .record(&path, &coordinates, || write_file(&path, b"first"))
+            .unwrap();
+        journal
+            .record(&path, &coordinates, || write_file(&path, b"second"))
+            .unwrap();
+
+        journal.undo_last().unwrap();
+        assert_eq!(b"first", fs::read(&path).unwrap().as_slice());
+        assert_eq!(1, journal.entries().unwrap().len());
+
+        journal.undo_last().unwrap();
+        assert_eq!(original, fs::read(&path).unwrap());
+        assert!(journal.entries().unwrap().is_empty());
+        assert!(journal.undo_last().unwrap().is_none());
+    }
+
+    #[test]
+    fn undo_last_should_error_if_the_file_has_changed_since_it_was_written() {
+        let (_tmp_dir, journal, path) = setup();
+
+        journal
+            .record(&path, &Coordinates::new(1.0, 2.0), || {
+                write_file(&path, b"written")
+            }).unwrap();
+        write_file(&path, b"changed").unwrap();
+
+        match journal.undo_last() {
+            Err(JournalError::FileChanged(_)) => {}
+            x => panic!("Expected a file changed error, got {:?}", x),
+        }
+        assert_eq!(b"changed", fs::read(&path).unwrap().as_slice());
+        assert_eq!(1, journal.entries().unwrap().len());
+    }
+
+    #[test]
+    fn undo_last_should_not_remove_an_original_used_by_a_write_in_progress() {
+        let (tmp_dir, journal, path) = setup();
+        let other_path = tmp_dir.path().join("other.jpg");
+        fs::copy(&path, &other_path).unwrap();
+        let original = fs::read(&path).unwrap();
+
+        let coordinates = Coordinates::new(1.0, 2.0);
+        journal
+            .record(&path, &coordinates, || write_file(&path, b"written"))
+            .unwrap();
+        journal
+            .record(&other_path, &coordinates, || -> Result<(), JournalError> {
+                journal.undo_last()?;
+                write_file(&other_path, b"other written")?;
+                Ok(())
+            }).unwrap();
+
+        journal.undo_last().unwrap();
+        assert_eq!(original, fs::read(&other_path).unwrap());
+    }
+
+    #[test]
+    fn record_should_record_concurrent_writes_to_a_file_one_at_a_time() {
+        let (_tmp_dir, journal, path) = setup();
+        let journal = Arc::new(journal);
+
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let journal = Arc::clone(&journal);
+                let path = path.clone();
+                thread::spawn(move || {
+                    journal
+                        .record(&path, &Coordinates::new(1.0, 2.0), || {
+                            write_file(&path, format!("written {}", i).as_bytes())
+                        }).unwrap();
+                })
+            }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let entries = journal.entries().unwrap();
+        assert_eq!(4, entries.len());
+        for pair in entries.windows(2) {
+            assert_eq!(pair[0].hash, pair[1].original_hash);
+        }
+    }
+
+    #[test]
+    fn rollback_should_only_undo_writes_from_the_given_session() {
+        let (tmp_dir, journal, path) = setup();
+        let other_journal = journal_with_session(&tmp_dir.path().join("journal"), "other");
+        let other_path = tmp_dir.path().join("other.jpg");
+        write_file(&other_path, b"other").unwrap();
+
+        let coordinates = Coordinates::new(1.0, 2.0);
+        let original = fs::read(&path).unwrap();
+        journal
+            .record(&path, &coordinates, || write_file(&path, b"first"))
+            .unwrap();
+        other_journal
+            .record(&other_path, &coordinates, || {
+                write_file(&other_path, b"other written")
+            }).unwrap();
+        journal
+            .record(&path, &coordinates, || write_file(&path, b"second"))
+            .unwrap();
+
+        let undone = journal.rollback(journal.session()).unwrap();
+
+        assert_eq!(2, undone.len());
+        assert_eq!(original, fs::read(&path).unwrap());
+        assert_eq!(b"other written", fs::read(&other_path).unwrap().as_slice());
+
+        let entries = journal.entries().unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!("other", entries[0].session());
+    }
+
+    #[test]
+    fn latest_session_should_be_the_session_of_the_last_entry() {
+        let (_tmp_dir, journal, path) = setup();
+
+        assert_eq!(None, journal.latest_session().unwrap());
+
+        journal
+            .record(&path, &Coordinates::new(1.0, 2.0), || {
+                write_file(&path, b"written")
+            }).unwrap();
+
+        assert_eq!(
+            Some(journal.session().to_string()),
+            journal.latest_session().unwrap()
+        );
+    }
+}
@@ -1,17 +1,28 @@
 extern crate chrono;
 extern crate exif;
+extern crate flate2;
+extern crate image;
+extern crate jpeg_decoder;
 extern crate memmap;
 extern crate serde;
 extern crate serde_json;
+extern crate sha2;
 extern crate walkdir;
 
 #[macro_use]
 extern crate serde_derive;
 
 mod coordinates;
+mod geocoder;
 mod golo;
+mod history_stats;
+mod journal;
+mod media_format;
 mod photo;
+mod safe_write;
 mod suggestion_accuracy;
+mod timestamp_fallback;
+mod video;
 
 use std::path::Path;
 use std::path::PathBuf;
@@ -19,10 +30,22 @@ use std::path::PathBuf;
 use walkdir::WalkDir;
 
 pub use coordinates::Coordinates;
+pub use geocoder::{GeocoderError, Place, ReverseGeocoder};
 pub use golo::{load_location_history, GoogleLocationHistory, HistoryError, Location};
+pub use history_stats::{AccuracyBucket, DayCoverage, Gap, HistoryStats};
+pub use journal::{Journal, JournalEntry, JournalError};
+pub use media_format::{
+    is_supported_file, media_format, read_metadata, write_coordinates, write_location, MediaFormat,
+    Metadata,
+};
 pub use photo::Photo;
 pub use photo::PhotoError;
+pub use photo::Timestamp;
+pub use photo::TimestampSource;
+pub use safe_write::{write_coordinates_safely, BackupLocation, WriteError, WriteOptions};
 pub use suggestion_accuracy::SuggestionAccuracy;
+pub use timestamp_fallback::{read_fallback_timestamp, TimestampFallback};
+pub use video::{read_video_metadata, VideoMetadata};
 
 #[derive(Debug, PartialEq, Serialize)]
 pub enum PhotoLocation {
@@ -31,24 +54,14 @@ pub enum PhotoLocation {
     None,
 }
 
-fn is_jpeg_file(path: &Path) -> bool {
-    if !path.is_file() {
-        return false;
-    }
-
-    match path.extension() {
-        Some(x) => x == "jpg" || x == "jpeg" || x == "JPG" || x == "JPEG",
-        _ => false,
-    }
-}
-
-pub fn find_jpegs(root_directory: &Path) -> Vec<PathBuf> {
+/// Recursively find all files of a supported media format in the given directory.
+pub fn find_photos(root_directory: &Path) -> Vec<PathBuf> {
     WalkDir::new(root_directory)
         .sort_by(|a, b| a.file_name().cmp(b.file_name()))
         .into_iter()
         .filter_map(|e| e.ok())
         .map(|e| e.path().to_path_buf())
-        .filter(|p| is_jpeg_file(p.as_path()))
+        .filter(|p| is_supported_file(p.as_path()))
         .collect()
 }
 
@@ -56,32 +69,40 @@ pub fn get_location_suggestion(
     path: &Path,
     location_history: &GoogleLocationHistory,
     interpolate: bool,
+    timestamp_fallbacks: &[TimestampFallback],
 ) -> Result<PhotoLocation, PhotoError> {
-    let photo = Photo::new(path)?;
+    let photo = Photo::with_fallbacks(path, timestamp_fallbacks)?;
 
+    Ok(suggest_location(&photo, location_history, interpolate))
+}
+
+/// Get the location of a photo whose metadata has already been read.
+pub fn suggest_location(
+    photo: &Photo,
+    location_history: &GoogleLocationHistory,
+    interpolate: bool,
+) -> PhotoLocation {
     if let Some(coordinates) = photo.gps_coordinates() {
-        return Ok(PhotoLocation::Existing(coordinates.clone()));
+        return PhotoLocation::Existing(coordinates.clone());
     }
 
     let suggested_location = if interpolate {
-        location_history.interpolate_location(photo.timestamp())
+        location_history.interpolate_location_ms(photo.timestamp_ms())
     } else {
         location_history
-            .get_most_likely_location(photo.timestamp())
+            .get_most_likely_location_ms(photo.timestamp_ms())
             .cloned()
     };
 
     match suggested_location {
-        None => Ok(PhotoLocation::None),
+        None => PhotoLocation::None,
         Some(suggested_location) => {
             let accuracy = SuggestionAccuracy::new(
                 suggested_location.accuracy(),
-                suggested_location.timestamp() - photo.timestamp(),
+                (suggested_location.timestamp_ms() - photo.timestamp_ms()) / 1000,
+                photo.timestamp_source(),
             );
-            Ok(PhotoLocation::Suggested(
-                suggested_location.coordinates(),
-                accuracy,
-            ))
+            PhotoLocation::Suggested(suggested_location.coordinates(), accuracy)
         }
     }
 }
@@ -97,49 +118,7 @@ mod tests {
     use self::tempfile::tempdir;
 
     #[test]
-    fn is_jpeg_file_should_return_false_for_a_file_that_does_not_exist() {
-        assert!(!is_jpeg_file(Path::new("nonexistent")));
-    }
-
-    #[test]
-    fn is_jpeg_file_should_return_false_for_a_file_that_does_not_have_a_jpg_or_jpeg_extension() {
-        assert!(!is_jpeg_file(Path::new("Cargo.toml")));
-    }
-
-    #[test]
-    fn is_jpeg_file_should_return_false_for_a_directory() {
-        assert!(!is_jpeg_file(Path::new("Cargo.toml")));
-    }
-
-    #[test]
-    fn is_jpeg_file_should_return_true_for_a_file_with_jpg_file_extension() {
-        let tmp_dir = tempdir().unwrap();
-
-        assert!(is_jpeg_file(Path::new("tests/assets/photo.jpg")));
-
-        let jpg_file = tmp_dir.path().join("photo.JPG");
-        copy("tests/assets/photo.jpg", &jpg_file).unwrap();
-
-        assert!(is_jpeg_file(jpg_file.as_path()));
-    }
-
-    #[test]
-    fn is_jpeg_file_should_return_true_for_a_file_with_jpeg_file_extension() {
-        let tmp_dir = tempdir().unwrap();
-
-        let jpeg_file = tmp_dir.path().join("photo.jpeg");
-        copy("tests/assets/photo.jpg", &jpeg_file).unwrap();
-
-        assert!(is_jpeg_file(jpeg_file.as_path()));
-
-        let jpeg_file = tmp_dir.path().join("photo.JPEG");
-        copy("tests/assets/photo.jpg", &jpeg_file).unwrap();
-
-        assert!(is_jpeg_file(jpeg_file.as_path()));
-    }
-
-    #[test]
-    fn find_jpegs_should_return_all_jpeg_files_in_directory_recusively() {
+    fn find_photos_should_return_all_supported_files_in_directory_recusively() {
         let tmp_dir = tempdir().unwrap();
         let tmp_subdir = tmp_dir.path().join("subdir");
 
@@ -150,16 +129,20 @@ mod tests {
         copy("tests/assets/photo.jpg", &jpeg_file).unwrap();
         let jpg_file = tmp_subdir.join("photo.JPG");
         copy("tests/assets/photo.jpg", &jpg_file).unwrap();
+        let png_file = tmp_subdir.join("photo.png");
+        copy("tests/assets/photo.png", &png_file).unwrap();
+        let video_file = tmp_subdir.join("video.mp4");
+        copy("tests/assets/video.mp4", &video_file).unwrap();
 
-        let jpegs = find_jpegs(tmp_dir.path());
+        let photos = find_photos(tmp_dir.path());
 
-        assert_eq!(vec![jpeg_file, jpg_file], jpegs);
+        assert_eq!(vec![jpeg_file, jpg_file, png_file, video_file], photos);
     }
 
     #[test]
     fn get_location_suggestion_should_error_if_passed_a_non_jpeg_file() {
         let history = GoogleLocationHistory::default();
-        let location = get_location_suggestion(Path::new("Cargo.toml"), &history, false);
+        let location = get_location_suggestion(Path::new("Cargo.toml"), &history, false, &[]);
 
         assert!(location.is_err());
     }
@@ -168,7 +151,7 @@ mod tests {
     fn get_location_suggestion_should_error_if_passed_a_jpeg_with_no_exif_metadata() {
         let history = GoogleLocationHistory::default();
         let path = Path::new("tests/assets/photo_without_exif.jpg");
-        let location = get_location_suggestion(path, &history, false);
+        let location = get_location_suggestion(path, &history, false, &[]);
 
         assert!(location.is_err());
     }
@@ -176,8 +159,8 @@ mod tests {
     #[test]
     fn get_location_suggestion_should_error_if_passed_a_jpeg_with_no_timestamp_metadata() {
         let history = GoogleLocationHistory::default();
-        let path = Path::new("tests/assets/photo_without_timestamp.jpg");
-        let location = get_location_suggestion(path, &history, false);
+        let path = Path::new("tests/assets/photo_without_any_timestamp.jpg");
+        let location = get_location_suggestion(path, &history, false, &[]);
 
         assert!(location.is_err());
     }
@@ -186,7 +169,7 @@ mod tests {
     fn get_location_suggestion_should_return_none_if_the_location_history_is_empty() {
         let history = GoogleLocationHistory::default();
         let path = Path::new("tests/assets/photo_without_gps.jpg");
-        let location = get_location_suggestion(path, &history, false);
+        let location = get_location_suggestion(path, &history, false, &[]);
 
         assert_eq!(PhotoLocation::None, location.unwrap());
     }
@@ -195,7 +178,7 @@ mod tests {
     fn get_location_suggestion_should_return_existing_if_the_photo_has_gps_metadata() {
         let history = GoogleLocationHistory::default();
         let path = Path::new("tests/assets/photo.jpg");
-        let location = get_location_suggestion(path, &history, false);
+        let location = get_location_suggestion(path, &history, false, &[]);
 
         assert_eq!(
             PhotoLocation::Existing(Coordinates::new(38.76544, -9.094802222222222)),
@@ -203,6 +186,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn get_location_suggestion_should_return_existing_if_the_video_has_a_location() {
+        let history = GoogleLocationHistory::default();
+        let path = Path::new("tests/assets/video.mp4");
+        let location = get_location_suggestion(path, &history, false, &[]);
+
+        assert_eq!(
+            PhotoLocation::Existing(Coordinates::new(38.76544, -9.094802)),
+            location.unwrap()
+        );
+    }
+
+    #[test]
+    fn get_location_suggestion_should_return_suggested_for_a_video_without_a_location() {
+        let history = unsafe {
+            golo::load_location_history(&File::open("tests/assets/location_history.json").unwrap())
+                .unwrap()
+        };
+        let path = Path::new("tests/assets/video_without_gps.mov");
+        let location = get_location_suggestion(path, &history, false, &[]);
+
+        assert_eq!(
+            PhotoLocation::Suggested(
+                Coordinates::new(52.0796733, 1.1965831),
+                SuggestionAccuracy::new(18, -470321, TimestampSource::VideoCreationTime),
+            ),
+            location.unwrap()
+        );
+    }
+
     #[test]
     fn get_location_suggestion_should_return_suggested_if_a_suggestion_is_possible() {
         let history = unsafe {
@@ -210,12 +223,36 @@ mod tests {
                 .unwrap()
         };
         let path = Path::new("tests/assets/photo_without_gps.jpg");
-        let location = get_location_suggestion(path, &history, false);
+        let location = get_location_suggestion(path, &history, false, &[]);
+
+        assert_eq!(
+            PhotoLocation::Suggested(
+                Coordinates::new(52.0796733, 1.1965831),
+                SuggestionAccuracy::new(18, -470321, TimestampSource::DateTimeOriginal),
+            ),
+            location.unwrap()
+        );
+    }
+
+    #[test]
+    fn get_location_suggestion_should_use_timestamp_fallbacks_if_the_photo_has_no_timestamp() {
+        let history = unsafe {
+            golo::load_location_history(&File::open("tests/assets/location_history.json").unwrap())
+                .unwrap()
+        };
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("IMG_20160906_103841.jpg");
+        copy("tests/assets/photo_without_exif.jpg", &path).unwrap();
+
+        let location = get_location_suggestion(&path, &history, false, &[]);
+        assert!(location.is_err());
 
+        let location =
+            get_location_suggestion(&path, &history, false, &[TimestampFallback::FileName]);
         assert_eq!(
             PhotoLocation::Suggested(
                 Coordinates::new(52.0796733, 1.1965831),
-                SuggestionAccuracy::new(18, -470321),
+                SuggestionAccuracy::new(18, -470321, TimestampSource::FileName),
             ),
             location.unwrap()
         );
@@ -0,0 +1,1486 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::str;
+
+use chrono::offset::TimeZone;
+use chrono::offset::Utc;
+use chrono::NaiveDate;
+use exif::{self, Tag};
+use flate2::read::ZlibDecoder;
+use image;
+use jpeg_decoder;
+
+use coordinates::Coordinates;
+use geocoder::Place;
+use photo::{PhotoError, Timestamp, TimestampSource};
+use video::{read_video_metadata, read_video_xmp, write_video_coordinates, VIDEO_EXTENSIONS};
+
+/// The IPTC-IIM coded character set escape sequence for UTF-8.
+const IPTC_UTF8_CHARACTER_SET: &str = "\x1b%G";
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The namespace that starts a JPEG APP1 segment holding an XMP packet.
+const JPEG_XMP_NAMESPACE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// The keyword of the PNG text chunk that Exiv2 and ImageMagick write Exif data to, as a
+/// hex-encoded "raw profile".
+const PNG_RAW_EXIF_PROFILE_KEYWORD: &[u8] = b"Raw profile type exif";
+
+/// The identifier that prefixes the Exif data in a JPEG APP1 segment.
+const EXIF_IDENTIFIER: &[u8] = b"Exif\0\0";
+
+/// The keyword of a PNG iTXt chunk holding an XMP packet.
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+
+/// The TIFF tag that holds an XMP packet.
+const TIFF_XMP_TAG: u16 = 700;
+
+/// A file format that yore can read timestamps and locations from, and write locations to.
+pub trait MediaFormat: Sync {
+    fn name(&self) -> &'static str;
+
+    /// The lowercase file extensions that identify files of this format.
+    fn extensions(&self) -> &'static [&'static str];
+
+    fn read_metadata(&self, path: &Path) -> Result<Metadata, PhotoError>;
+
+    /// Read the file's XMP packet, if it has one. Only the file's metadata is read, not its
+    /// image data.
+    fn read_xmp(&self, _path: &Path) -> io::Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    fn write_coordinates(&self, path: &Path, coordinates: &Coordinates) -> io::Result<()>;
+
+    /// Write the given coordinates along with the names of the town, region and country they
+    /// are in, if given, in a single write. Formats without standard fields for place names
+    /// only get the coordinates.
+    fn write_location(
+        &self,
+        path: &Path,
+        coordinates: &Coordinates,
+        _place: Option<&Place>,
+    ) -> io::Result<()> {
+        self.write_coordinates(path, coordinates)
+    }
+
+    /// Decode the given file data's pixels, so that they can be compared before and after
+    /// writing a location. Formats that can't be decoded give `None`.
+    fn decode_pixels(&self, _data: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// The image crate format that this format's image data is decoded as. Formats without
+    /// image data, i.e. videos, give `None`.
+    fn image_format(&self) -> Option<image::ImageFormat> {
+        None
+    }
+
+    /// Decode the given file data as an image to display.
+    fn decode_image(&self, data: &[u8]) -> image::ImageResult<image::DynamicImage> {
+        match self.image_format() {
+            Some(format) => image::load_from_memory_with_format(data, format),
+            None => Err(image::ImageError::UnsupportedError(format!(
+                "{} files have no image data",
+                self.name()
+            ))),
+        }
+    }
+}
+
+static MEDIA_FORMATS: &[&dyn MediaFormat] = &[&Jpeg, &Png, &Tiff, &WebP, &Video];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Metadata {
+    timestamp: Option<Timestamp>,
+    coordinates: Option<Coordinates>,
+    orientation: Option<u16>,
+    camera_model: Option<String>,
+}
+
+impl Metadata {
+    fn from_exif_fields(fields: &[exif::Field]) -> Result<Metadata, PhotoError> {
+        let mut metadata = Metadata::default();
+        let mut date_time_original: Option<i64> = None;
+        let mut date_time: Option<i64> = None;
+        let mut subsec_time_original: Option<i64> = None;
+        let mut subsec_time: Option<i64> = None;
+        let mut gps_date: Option<i64> = None;
+        let mut gps_time: Option<i64> = None;
+        let mut latitude: Option<f64> = None;
+        let mut longitude: Option<f64> = None;
+        let mut latitude_sign: f64 = 1.0;
+        let mut longitude_sign: f64 = 1.0;
+
+        for field in fields.iter().filter(|f| !f.thumbnail) {
+            match field.tag {
+                Tag::DateTimeOriginal => date_time_original = date_time_ms(field)?,
+                Tag::DateTime => date_time = date_time_ms(field)?,
+                Tag::SubSecTimeOriginal => subsec_time_original = ascii_value(field).map(subsec_ms),
+                Tag::SubSecTime => subsec_time = ascii_value(field).map(subsec_ms),
+                // Many cameras write blank GPS date stamps, so GPS date and time stamps that
+                // can't be read are ignored and the date taken is used instead.
+                Tag::GPSDateStamp => {
+                    gps_date = ascii_value(field)
+                        .and_then(|value| NaiveDate::parse_from_str(value, "%Y:%m:%d").ok())
+                        .map(|date| date.and_hms(0, 0, 0).timestamp() * 1000);
+                }
+                Tag::GPSTimeStamp => {
+                    if let exif::Value::Rational(ref x) = field.value {
+                        if x.len() == 3 {
+                            let seconds =
+                                x[0].to_f64() * 3600.0 + x[1].to_f64() * 60.0 + x[2].to_f64();
+                            gps_time = Some(seconds)
+                                .filter(|s| s.is_finite())
+                                .map(|s| (s * 1000.0).round() as i64);
+                        }
+                    }
+                }
+                Tag::GPSLatitude => {
+                    if let exif::Value::Rational(ref x) = field.value {
+                        latitude = Some(to_decimal_coordinate(x));
+                    }
+                }
+                Tag::GPSLatitudeRef => {
+                    let string_value = format!("{}", field.value.display_as(field.tag));
+                    if let "S" = string_value.as_str() {
+                        latitude_sign = -1.0
+                    }
+                }
+                Tag::GPSLongitude => {
+                    if let exif::Value::Rational(ref x) = field.value {
+                        longitude = Some(to_decimal_coordinate(x));
+                    }
+                }
+                Tag::GPSLongitudeRef => {
+                    let string_value = format!("{}", field.value.display_as(field.tag));
+                    if let "W" = string_value.as_str() {
+                        longitude_sign = -1.0
+                    }
+                }
+                Tag::Orientation => {
+                    if let exif::Value::Short(ref x) = field.value {
+                        metadata.orientation = x.first().cloned();
+                    }
+                }
+                Tag::Model => {
+                    metadata.camera_model = ascii_value(field)
+                        .map(str::trim)
+                        .filter(|model| !model.is_empty())
+                        .map(str::to_string);
+                }
+                _ => {}
+            }
+        }
+
+        // The GPS time stamp is always UTC, while the date taken is in an unknown time zone, so
+        // prefer the former.
+        metadata.timestamp = match (gps_date, gps_time) {
+            (Some(date), Some(time)) => Some(Timestamp::new(date + time, TimestampSource::GpsTime)),
+            _ => date_time_original
+                .map(|t| {
+                    Timestamp::new(
+                        t + subsec_time_original.unwrap_or(0),
+                        TimestampSource::DateTimeOriginal,
+                    )
+                }).or_else(|| {
+                    date_time.map(|t| {
+                        Timestamp::new(t + subsec_time.unwrap_or(0), TimestampSource::DateTime)
+                    })
+                }),
+        };
+
+        if let (Some(longitude), Some(latitude)) = (longitude, latitude) {
+            metadata.coordinates = Some(Coordinates::new(
+                latitude * latitude_sign,
+                longitude * longitude_sign,
+            ));
+        }
+
+        Ok(metadata)
+    }
+
+    /// The time the photo was taken.
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+
+    /// The time the photo was taken as a Unix timestamp in milliseconds.
+    pub fn timestamp_ms(&self) -> Option<i64> {
+        self.timestamp.map(|t| t.ms())
+    }
+
+    pub fn timestamp_source(&self) -> Option<TimestampSource> {
+        self.timestamp.map(|t| t.source())
+    }
+
+    pub fn coordinates(&self) -> Option<&Coordinates> {
+        self.coordinates.as_ref()
+    }
+
+    /// The EXIF orientation value.
+    pub fn orientation(&self) -> Option<u16> {
+        self.orientation
+    }
+
+    /// The model name of the camera that took the photo.
+    pub fn camera_model(&self) -> Option<&str> {
+        self.camera_model.as_deref()
+    }
+}
+
+/// Get the media format of the given path from its file extension.
+pub fn media_format(path: &Path) -> Option<&'static dyn MediaFormat> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+
+    MEDIA_FORMATS
+        .iter()
+        .find(|f| f.extensions().contains(&extension.as_str()))
+        .cloned()
+}
+
+pub fn is_supported_file(path: &Path) -> bool {
+    path.is_file() && media_format(path).is_some()
+}
+
+pub fn read_metadata(path: &Path) -> Result<Metadata, PhotoError> {
+    media_format(path)
+        .ok_or(PhotoError::UnsupportedFormat)?
+        .read_metadata(path)
+}
+
+pub fn read_xmp(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match media_format(path) {
+        Some(format) => format.read_xmp(path),
+        None => Ok(None),
+    }
+}
+
+pub fn write_coordinates(path: &Path, coordinates: &Coordinates) -> io::Result<()> {
+    match media_format(path) {
+        Some(format) => format.write_coordinates(path, coordinates),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a supported file format", path.display()),
+        )),
+    }
+}
+
+pub fn write_location(
+    path: &Path,
+    coordinates: &Coordinates,
+    place: Option<&Place>,
+) -> io::Result<()> {
+    match media_format(path) {
+        Some(format) => format.write_location(path, coordinates, place),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a supported file format", path.display()),
+        )),
+    }
+}
+
+struct Jpeg;
+
+impl MediaFormat for Jpeg {
+    fn name(&self) -> &'static str {
+        "JPEG"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["jpg", "jpeg"]
+    }
+
+    fn read_metadata(&self, path: &Path) -> Result<Metadata, PhotoError> {
+        read_exif_file(path)
+    }
+
+    fn read_xmp(&self, path: &Path) -> io::Result<Option<Vec<u8>>> {
+        jpeg_xmp_segment(&mut BufReader::new(File::open(path)?))
+    }
+
+    fn write_coordinates(&self, path: &Path, coordinates: &Coordinates) -> io::Result<()> {
+        exiv2_write_location(path, coordinates, None, true)
+    }
+
+    fn write_location(
+        &self,
+        path: &Path,
+        coordinates: &Coordinates,
+        place: Option<&Place>,
+    ) -> io::Result<()> {
+        exiv2_write_location(path, coordinates, place, true)
+    }
+
+    fn decode_pixels(&self, data: &[u8]) -> Option<Vec<u8>> {
+        jpeg_decoder::Decoder::new(data).decode().ok()
+    }
+
+    fn image_format(&self) -> Option<image::ImageFormat> {
+        Some(image::ImageFormat::JPEG)
+    }
+}
+
+struct Tiff;
+
+impl MediaFormat for Tiff {
+    fn name(&self) -> &'static str {
+        "TIFF"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["tif", "tiff"]
+    }
+
+    fn read_metadata(&self, path: &Path) -> Result<Metadata, PhotoError> {
+        read_exif_file(path)
+    }
+
+    fn read_xmp(&self, path: &Path) -> io::Result<Option<Vec<u8>>> {
+        tiff_xmp_field(&mut BufReader::new(File::open(path)?))
+    }
+
+    fn write_coordinates(&self, path: &Path, coordinates: &Coordinates) -> io::Result<()> {
+        exiv2_write_location(path, coordinates, None, true)
+    }
+
+    fn write_location(
+        &self,
+        path: &Path,
+        coordinates: &Coordinates,
+        place: Option<&Place>,
+    ) -> io::Result<()> {
+        exiv2_write_location(path, coordinates, place, true)
+    }
+
+    fn decode_pixels(&self, data: &[u8]) -> Option<Vec<u8>> {
+        image::load_from_memory_with_format(data, image::ImageFormat::TIFF)
+            .ok()
+            .map(|i| i.raw_pixels())
+    }
+
+    fn image_format(&self) -> Option<image::ImageFormat> {
+        Some(image::ImageFormat::TIFF)
+    }
+}
+
+struct Png;
+
+impl MediaFormat for Png {
+    fn name(&self) -> &'static str {
+        "PNG"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["png"]
+    }
+
+    /// Exiv2 writes Exif data to a raw profile text chunk instead of an eXIf chunk, so a raw
+    /// profile is read in preference to an eXIf chunk that it may have superseded.
+    fn read_metadata(&self, path: &Path) -> Result<Metadata, PhotoError> {
+        let data = fs::read(path)?;
+        let raw_profile = png_raw_exif_profile(&data)?;
+        let exif_data = match raw_profile {
+            Some(ref exif_data) => exif_data.as_slice(),
+            None => png_exif_chunk(&data)?,
+        };
+        let (fields, _) = exif::parse_exif(exif_data)?;
+
+        Metadata::from_exif_fields(&fields)
+    }
+
+    fn read_xmp(&self, path: &Path) -> io::Result<Option<Vec<u8>>> {
+        png_xmp_chunk(&mut BufReader::new(File::open(path)?))
+    }
+
+    fn write_coordinates(&self, path: &Path, coordinates: &Coordinates) -> io::Result<()> {
+        exiv2_write_location(path, coordinates, None, true)
+    }
+
+    fn write_location(
+        &self,
+        path: &Path,
+        coordinates: &Coordinates,
+        place: Option<&Place>,
+    ) -> io::Result<()> {
+        exiv2_write_location(path, coordinates, place, true)
+    }
+
+    fn decode_pixels(&self, data: &[u8]) -> Option<Vec<u8>> {
+        image::load_from_memory_with_format(data, image::ImageFormat::PNG)
+            .ok()
+            .map(|i| i.raw_pixels())
+    }
+
+    fn image_format(&self) -> Option<image::ImageFormat> {
+        Some(image::ImageFormat::PNG)
+    }
+}
+
+struct WebP;
+
+impl MediaFormat for WebP {
+    fn name(&self) -> &'static str {
+        "WebP"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["webp"]
+    }
+
+    fn read_metadata(&self, path: &Path) -> Result<Metadata, PhotoError> {
+        let data = fs::read(path)?;
+        let exif_data = webp_exif_chunk(&data)?;
+        let (fields, _) = exif::parse_exif(exif_data)?;
+
+        Metadata::from_exif_fields(&fields)
+    }
+
+    fn read_xmp(&self, path: &Path) -> io::Result<Option<Vec<u8>>> {
+        webp_xmp_chunk(&mut BufReader::new(File::open(path)?))
+    }
+
+    fn write_coordinates(&self, path: &Path, coordinates: &Coordinates) -> io::Result<()> {
+        exiv2_write_location(path, coordinates, None, false)
+    }
+
+    /// WebP files can't hold IPTC-IIM metadata, so only XMP place names are written.
+    fn write_location(
+        &self,
+        path: &Path,
+        coordinates: &Coordinates,
+        place: Option<&Place>,
+    ) -> io::Result<()> {
+        exiv2_write_location(path, coordinates, place, false)
+    }
+
+    fn image_format(&self) -> Option<image::ImageFormat> {
+        Some(image::ImageFormat::WEBP)
+    }
+
+    /// The image crate's WebP decoder can only read files that contain nothing but a lossy
+    /// bitstream, so that is extracted from extended format files (e.g. those with Exif
+    /// metadata) first.
+    fn decode_image(&self, data: &[u8]) -> image::ImageResult<image::DynamicImage> {
+        let data = webp_lossy_bitstream(data)?;
+        image::load_from_memory_with_format(&data, image::ImageFormat::WEBP)
+    }
+}
+
+struct Video;
+
+impl MediaFormat for Video {
+    fn name(&self) -> &'static str {
+        "Video"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        VIDEO_EXTENSIONS
+    }
+
+    fn read_metadata(&self, path: &Path) -> Result<Metadata, PhotoError> {
+        let metadata = read_video_metadata(path)?;
+
+        Ok(Metadata {
+            timestamp: metadata
+                .creation_time()
+                .map(|t| Timestamp::new(t * 1000, TimestampSource::VideoCreationTime)),
+            coordinates: metadata.coordinates().cloned(),
+            orientation: None,
+            camera_model: None,
+        })
+    }
+
+    fn read_xmp(&self, path: &Path) -> io::Result<Option<Vec<u8>>> {
+        read_video_xmp(path)
+    }
+
+    fn write_coordinates(&self, path: &Path, coordinates: &Coordinates) -> io::Result<()> {
+        write_video_coordinates(path, coordinates)
+    }
+}
+
+fn read_exif_file(path: &Path) -> Result<Metadata, PhotoError> {
+    let file = File::open(path)?;
+    let reader = exif::Reader::new(&mut BufReader::new(&file))?;
+
+    Metadata::from_exif_fields(reader.fields())
+}
+
+fn png_exif_chunk(data: &[u8]) -> Result<&[u8], exif::Error> {
+    if !data.starts_with(PNG_SIGNATURE) {
+        return Err(exif::Error::InvalidFormat("Not a PNG file"));
+    }
+
+    let mut position = PNG_SIGNATURE.len();
+    while position + 8 <= data.len() {
+        let length = read_be_u32(&data[position..]) as usize;
+        let kind = &data[position + 4..position + 8];
+        let chunk_data = data
+            .get(position + 8..position + 8 + length)
+            .ok_or(exif::Error::InvalidFormat("Truncated PNG chunk"))?;
+
+        // The eXIf chunk is allowed to come after the image data, so search up to the end.
+        match kind {
+            b"eXIf" => return Ok(chunk_data),
+            b"IEND" => break,
+            _ => {}
+        }
+
+        // Skip the chunk data and its CRC.
+        position += 12 + length;
+    }
+
+    Err(exif::Error::NotFound("PNG"))
+}
+
+/// Find a tEXt or zTXt chunk holding a raw Exif profile and decode its Exif data.
+fn png_raw_exif_profile(data: &[u8]) -> Result<Option<Vec<u8>>, exif::Error> {
+    if !data.starts_with(PNG_SIGNATURE) {
+        return Err(exif::Error::InvalidFormat("Not a PNG file"));
+    }
+
+    let mut position = PNG_SIGNATURE.len();
+    while position + 8 <= data.len() {
+        let length = read_be_u32(&data[position..]) as usize;
+        let kind = &data[position + 4..position + 8];
+        let chunk_data = data
+            .get(position + 8..position + 8 + length)
+            .ok_or(exif::Error::InvalidFormat("Truncated PNG chunk"))?;
+
+        let profile = match kind {
+            b"tEXt" => {
+                png_text_value(chunk_data, PNG_RAW_EXIF_PROFILE_KEYWORD).map(|text| text.to_vec())
+            }
+            b"zTXt" => match png_text_value(chunk_data, PNG_RAW_EXIF_PROFILE_KEYWORD) {
+                // The text is preceded by its compression method, which must be zlib.
+                Some(text) if text.first() == Some(&0) => {
+                    let mut decompressed = Vec::new();
+                    ZlibDecoder::new(&text[1..])
+                        .read_to_end(&mut decompressed)
+                        .map_err(|_| exif::Error::InvalidFormat("Invalid compressed PNG text"))?;
+                    Some(decompressed)
+                }
+                Some(_) => {
+                    return Err(exif::Error::InvalidFormat(
+                        "Unknown PNG text compression method",
+                    ))
+                }
+                None => None,
+            },
+            b"IEND" => break,
+            _ => None,
+        };
+
+        if let Some(profile) = profile {
+            return decode_raw_profile(&profile).map(Some);
+        }
+
+        // Skip the chunk data and its CRC.
+        position += 12 + length;
+    }
+
+    Ok(None)
+}
+
+/// Get the value of a tEXt or zTXt chunk if it has the given keyword.
+fn png_text_value<'a>(chunk_data: &'a [u8], keyword: &[u8]) -> Option<&'a [u8]> {
+    if chunk_data.starts_with(keyword) && chunk_data.get(keyword.len()) == Some(&0) {
+        Some(&chunk_data[keyword.len() + 1..])
+    } else {
+        None
+    }
+}
+
+/// Decode a raw profile, which is the profile type, its length in bytes and then its bytes in
+/// hex, all separated by whitespace. Exif profiles may start with the JPEG Exif identifier.
+fn decode_raw_profile(profile: &[u8]) -> Result<Vec<u8>, exif::Error> {
+    let invalid = || exif::Error::InvalidFormat("Invalid PNG raw profile");
+    let text = str::from_utf8(profile).map_err(|_| invalid())?;
+    let mut words = text.split_whitespace();
+
+    let length: usize = words
+        .nth(1)
+        .and_then(|length| length.parse().ok())
+        .ok_or_else(invalid)?;
+
+    let digits: Vec<u32> = words
+        .flat_map(str::chars)
+        .map(|c| c.to_digit(16))
+        .collect::<Option<_>>()
+        .ok_or_else(invalid)?;
+    if digits.len() != length * 2 {
+        return Err(invalid());
+    }
+
+    let bytes: Vec<u8> = digits
+        .chunks(2)
+        .map(|pair| (pair[0] << 4 | pair[1]) as u8)
+        .collect();
+
+    if bytes.starts_with(EXIF_IDENTIFIER) {
+        Ok(bytes[EXIF_IDENTIFIER.len()..].to_vec())
+    } else {
+        Ok(bytes)
+    }
+}
+
+fn webp_exif_chunk(data: &[u8]) -> Result<&[u8], exif::Error> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return Err(exif::Error::InvalidFormat("Not a WebP file"));
+    }
+
+    let mut position = 12;
+    while position + 8 <= data.len() {
+        let kind = &data[position..position + 4];
+        let length = read_le_u32(&data[position + 4..]) as usize;
+        let chunk_data = data
+            .get(position + 8..position + 8 + length)
+            .ok_or(exif::Error::InvalidFormat("Truncated WebP chunk"))?;
+
+        if kind == b"EXIF" {
+            // Some writers include the JPEG APP1 Exif identifier.
+            if chunk_data.starts_with(EXIF_IDENTIFIER) {
+                return Ok(&chunk_data[EXIF_IDENTIFIER.len()..]);
+            }
+            return Ok(chunk_data);
+        }
+
+        // Chunks are padded to an even size.
+        position += 8 + length + (length & 1);
+    }
+
+    Err(exif::Error::NotFound("WebP"))
+}
+
+/// Make a simple format WebP file from the lossy bitstream chunk of the given file data.
+fn webp_lossy_bitstream(data: &[u8]) -> image::ImageResult<Vec<u8>> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return Err(image::ImageError::FormatError(
+            "Not a WebP file".to_string(),
+        ));
+    }
+
+    let mut position = 12;
+    while position + 8 <= data.len() {
+        let kind = &data[position..position + 4];
+        let length = read_le_u32(&data[position + 4..]) as usize;
+        let end = position + 8 + length;
+
+        if kind == b"VP8 " && end <= data.len() {
+            let chunk = &data[position..end];
+            let riff_size = (4 + chunk.len()) as u32;
+
+            let mut webp = Vec::with_capacity(12 + chunk.len());
+            webp.extend_from_slice(b"RIFF");
+            webp.extend((0..4).map(|i| (riff_size >> (8 * i)) as u8));
+            webp.extend_from_slice(b"WEBP");
+            webp.extend_from_slice(chunk);
+
+            return Ok(webp);
+        }
+
+        // Chunks are padded to an even size.
+        position = end + (length & 1);
+    }
+
+    Err(image::ImageError::FormatError(
+        "Only lossy WebP images are supported".to_string(),
+    ))
+}
+
+/// Find the XMP APP1 segment among the JPEG segments that come before the compressed image data.
+fn jpeg_xmp_segment<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut marker = [0; 2];
+    reader.read_exact(&mut marker)?;
+    if marker != [0xFF, 0xD8] {
+        return Ok(None);
+    }
+
+    loop {
+        reader.read_exact(&mut marker)?;
+        if marker[0] != 0xFF {
+            return Ok(None);
+        }
+
+        match marker[1] {
+            // Start of scan and end of image: there are no more metadata segments.
+            0xDA | 0xD9 => return Ok(None),
+            // Markers without a length.
+            0x01 | 0xD0..=0xD7 => continue,
+            _ => {}
+        }
+
+        let mut length = [0; 2];
+        reader.read_exact(&mut length)?;
+        let length = (usize::from(length[0]) << 8 | usize::from(length[1])).saturating_sub(2);
+
+        if marker[1] == 0xE1 {
+            let mut segment = vec![0; length];
+            reader.read_exact(&mut segment)?;
+            if segment.starts_with(JPEG_XMP_NAMESPACE) {
+                return Ok(Some(segment.split_off(JPEG_XMP_NAMESPACE.len())));
+            }
+        } else {
+            reader.seek(SeekFrom::Current(length as i64))?;
+        }
+    }
+}
+
+/// Find the XMP iTXt chunk, skipping over the data of all other chunks.
+fn png_xmp_chunk<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut signature = [0; 8];
+    reader.read_exact(&mut signature)?;
+    if signature != PNG_SIGNATURE {
+        return Ok(None);
+    }
+
+    let mut header = [0; 8];
+    loop {
+        reader.read_exact(&mut header)?;
+        let length = read_be_u32(&header);
+
+        match &header[4..] {
+            b"iTXt" => {
+                let mut chunk_data = vec![0; length as usize];
+                reader.read_exact(&mut chunk_data)?;
+                if let Some(text) = png_xmp_text(&chunk_data) {
+                    return Ok(Some(text.to_vec()));
+                }
+                reader.seek(SeekFrom::Current(4))?;
+            }
+            b"IEND" => return Ok(None),
+            // Skip the chunk data and its CRC.
+            _ => {
+                reader.seek(SeekFrom::Current(i64::from(length) + 4))?;
+            }
+        }
+    }
+}
+
+/// Get the text of an iTXt chunk if it is an uncompressed XMP packet, as the XMP specification
+/// requires it to be.
+fn png_xmp_text(chunk_data: &[u8]) -> Option<&[u8]> {
+    if !chunk_data.starts_with(PNG_XMP_KEYWORD) {
+        return None;
+    }
+
+    // The keyword is followed by a null separator, the compression flag and method, then the
+    // null-terminated language tag and translated keyword.
+    let rest = &chunk_data[PNG_XMP_KEYWORD.len()..];
+    if rest.get(..2) != Some(&[0, 0][..]) {
+        return None;
+    }
+
+    let rest = rest.get(3..)?;
+    let language_end = rest.iter().position(|b| *b == 0)?;
+    let rest = &rest[language_end + 1..];
+    let keyword_end = rest.iter().position(|b| *b == 0)?;
+
+    Some(&rest[keyword_end + 1..])
+}
+
+/// Find the XMP chunk, skipping over the data of all other chunks.
+fn webp_xmp_chunk<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0; 12];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != b"RIFF" || &header[8..12] != b"WEBP" {
+        return Ok(None);
+    }
+
+    let end = 8 + u64::from(read_le_u32(&header[4..]));
+    let mut position = 12;
+    let mut header = [0; 8];
+    while position + 8 <= end {
+        reader.read_exact(&mut header)?;
+        let length = u64::from(read_le_u32(&header[4..]));
+
+        if &header[..4] == b"XMP " {
+            let mut chunk_data = vec![0; length as usize];
+            reader.read_exact(&mut chunk_data)?;
+            return Ok(Some(chunk_data));
+        }
+
+        // Chunks are padded to an even size.
+        let padded_length = length + (length & 1);
+        reader.seek(SeekFrom::Current(padded_length as i64))?;
+        position += 8 + padded_length;
+    }
+
+    Ok(None)
+}
+
+/// Read the XMP field of the first image file directory.
+fn tiff_xmp_field<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0; 8];
+    reader.read_exact(&mut header)?;
+
+    let big_endian = match &header[..4] {
+        b"MM\0*" => true,
+        b"II*\0" => false,
+        _ => return Ok(None),
+    };
+    let read_u16 = |data: &[u8]| {
+        let (high, low) = if big_endian { (0, 1) } else { (1, 0) };
+        u16::from(data[high]) << 8 | u16::from(data[low])
+    };
+    let read_u32 = |data: &[u8]| {
+        if big_endian {
+            read_be_u32(data)
+        } else {
+            read_le_u32(data)
+        }
+    };
+
+    reader.seek(SeekFrom::Start(u64::from(read_u32(&header[4..]))))?;
+    let mut count = [0; 2];
+    reader.read_exact(&mut count)?;
+    let mut entries = vec![0; usize::from(read_u16(&count)) * 12];
+    reader.read_exact(&mut entries)?;
+
+    let entry = match entries
+        .chunks(12)
+        .find(|entry| read_u16(entry) == TIFF_XMP_TAG)
+    {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    // The XMP field holds bytes, which are stored in the entry itself if there are no more
+    // than four of them.
+    let length = read_u32(&entry[4..]) as usize;
+    if length <= 4 {
+        return Ok(Some(entry[8..8 + length].to_vec()));
+    }
+
+    let mut field = vec![0; length];
+    reader.seek(SeekFrom::Start(u64::from(read_u32(&entry[8..]))))?;
+    reader.read_exact(&mut field)?;
+
+    Ok(Some(field))
+}
+
+fn read_be_u32(data: &[u8]) -> u32 {
+    (0..4).fold(0, |value, i| value << 8 | u32::from(data[i]))
+}
+
+fn read_le_u32(data: &[u8]) -> u32 {
+    (0..4)
+        .rev()
+        .fold(0, |value, i| value << 8 | u32::from(data[i]))
+}
+
+fn ascii_value<'a>(field: &exif::Field<'a>) -> Option<&'a str> {
+    match field.value {
+        exif::Value::Ascii(ref x) => x.first().and_then(|v| str::from_utf8(v).ok()),
+        _ => None,
+    }
+}
+
+fn date_time_ms(field: &exif::Field) -> Result<Option<i64>, PhotoError> {
+    match ascii_value(field) {
+        Some(value) => Ok(Some(
+            Utc.datetime_from_str(value, "%Y:%m:%d %T")?.timestamp() * 1000,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Subsecond times are the fractional digits of a second, e.g. "27" is 270 milliseconds.
+fn subsec_ms(value: &str) -> i64 {
+    value
+        .trim()
+        .chars()
+        .chain("000".chars())
+        .take(3)
+        .map(|c| c.to_digit(10).unwrap_or(0))
+        .fold(0, |ms, digit| ms * 10 + i64::from(digit))
+}
+
+fn to_decimal_coordinate(dms: &[exif::Rational]) -> f64 {
+    dms[0].to_f64() + dms[1].to_f64() / 60.0 + dms[2].to_f64() / 3600.0
+}
+
+/// Write the given coordinates to the file's Exif GPS fields and the given place names to its
+/// XMP Photoshop schema fields, and optionally to the equivalent IPTC-IIM datasets too.
+fn exiv2_write_location(
+    path: &Path,
+    coordinates: &Coordinates,
+    place: Option<&Place>,
+    iptc: bool,
+) -> io::Result<()> {
+    let latitude_degrees = dms_string(coordinates.latitude());
+    let longitude_degrees = dms_string(coordinates.longitude());
+
+    let mut commands = vec![
+        format!("set Exif.GPSInfo.GPSLatitude {}", latitude_degrees),
+        format!(
+            "set Exif.GPSInfo.GPSLatitudeRef {}",
+            coordinates.latitude_ref()
+        ),
+        format!("set Exif.GPSInfo.GPSLongitude {}", longitude_degrees),
+        format!(
+            "set Exif.GPSInfo.GPSLongitudeRef {}",
+            coordinates.longitude_ref()
+        ),
+    ];
+    if let Some(place) = place {
+        commands.extend(place_commands(place, iptc));
+    }
+
+    run_exiv2_commands(path, &commands)
+}
+
+fn place_commands(place: &Place, iptc: bool) -> Vec<String> {
+    let mut fields = vec![
+        ("Xmp.photoshop.City", "XmpText", place.name()),
+        ("Xmp.photoshop.Country", "XmpText", place.country()),
+        ("Xmp.iptc.CountryCode", "XmpText", place.country_code()),
+    ];
+    if iptc {
+        // Mark IPTC-IIM strings as UTF-8 so that readers don't assume they're ISO 8859-1.
+        fields.push((
+            "Iptc.Envelope.CharacterSet",
+            "String",
+            IPTC_UTF8_CHARACTER_SET,
+        ));
+        fields.push(("Iptc.Application2.City", "String", place.name()));
+        fields.push(("Iptc.Application2.CountryName", "String", place.country()));
+        fields.push((
+            "Iptc.Application2.CountryCode",
+            "String",
+            place.country_code(),
+        ));
+    }
+    if let Some(region) = place.region() {
+        fields.push(("Xmp.photoshop.State", "XmpText", region));
+        if iptc {
+            fields.push(("Iptc.Application2.ProvinceState", "String", region));
+        }
+    }
+
+    // The value type is given explicitly so that values starting with a type name are not
+    // misinterpreted.
+    fields
+        .into_iter()
+        .map(|(key, value_type, value)| format!("set {} {} {}", key, value_type, value))
+        .collect()
+}
+
+fn run_exiv2_commands(path: &Path, commands: &[String]) -> io::Result<()> {
+    let output = Command::new("exiv2")
+        .arg("-k")
+        .args(commands.iter().map(|c| format!("-M{}", c)))
+        .arg(path)
+        .stderr(Stdio::inherit())
+        .output()?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("Exiv2 exited with {}", output.status),
+        ))
+    }
+}
+
+/// Format the magnitude of the given coordinate as Exif degrees, minutes and seconds. Its sign
+/// is given by the coordinate's N/S or E/W reference instead.
+fn dms_string(coordinate: f64) -> String {
+    format!(
+        "{}/10000000 0/1 0/1",
+        (coordinate.abs() * 1e7).round() as u32
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    use std::fs::copy;
+    use std::io::{Cursor, Write};
+
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    use self::tempfile::tempdir;
+
+    fn png_chunk(kind: &[u8], chunk_data: &[u8]) -> Vec<u8> {
+        let mut chunk = (chunk_data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend(kind);
+        chunk.extend(chunk_data);
+        // The CRC isn't checked.
+        chunk.extend(&[0; 4]);
+        chunk
+    }
+
+    /// Build a zTXt chunk holding the given Exif data as a raw profile, like Exiv2 does.
+    fn png_raw_exif_profile_chunk(exif_data: &[u8]) -> Vec<u8> {
+        let hex: String = EXIF_IDENTIFIER
+            .iter()
+            .chain(exif_data)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let profile = format!(
+            "\nexif\n{:8}\n{}\n",
+            EXIF_IDENTIFIER.len() + exif_data.len(),
+            hex
+        );
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(profile.as_bytes()).unwrap();
+
+        let mut chunk_data = PNG_RAW_EXIF_PROFILE_KEYWORD.to_vec();
+        chunk_data.extend(&[0, 0]);
+        chunk_data.extend(encoder.finish().unwrap());
+        png_chunk(b"zTXt", &chunk_data)
+    }
+
+    fn assert_photo_asset_metadata(metadata: &Metadata) {
+        let coordinates = metadata.coordinates().unwrap();
+
+        assert_eq!(38.76544, coordinates.latitude());
+        assert_eq!(-9.094802222222222, coordinates.longitude());
+    }
+
+    #[test]
+    fn is_supported_file_should_return_false_for_a_file_that_does_not_exist() {
+        assert!(!is_supported_file(Path::new("nonexistent.jpg")));
+    }
+
+    #[test]
+    fn is_supported_file_should_return_false_for_a_file_with_an_unsupported_extension() {
+        assert!(!is_supported_file(Path::new("Cargo.toml")));
+    }
+
+    #[test]
+    fn is_supported_file_should_return_false_for_a_directory() {
+        let tmp_dir = tempdir().unwrap();
+        let dir = tmp_dir.path().join("photo.jpg");
+        fs::create_dir(&dir).unwrap();
+
+        assert!(!is_supported_file(&dir));
+    }
+
+    #[test]
+    fn is_supported_file_should_return_true_for_a_file_with_jpg_file_extension() {
+        let tmp_dir = tempdir().unwrap();
+
+        assert!(is_supported_file(Path::new("tests/assets/photo.jpg")));
+
+        let jpg_file = tmp_dir.path().join("photo.JPG");
+        copy("tests/assets/photo.jpg", &jpg_file).unwrap();
+
+        assert!(is_supported_file(jpg_file.as_path()));
+    }
+
+    #[test]
+    fn is_supported_file_should_return_true_for_a_file_with_jpeg_file_extension() {
+        let tmp_dir = tempdir().unwrap();
+
+        let jpeg_file = tmp_dir.path().join("photo.jpeg");
+        copy("tests/assets/photo.jpg", &jpeg_file).unwrap();
+
+        assert!(is_supported_file(jpeg_file.as_path()));
+
+        let jpeg_file = tmp_dir.path().join("photo.JPEG");
+        copy("tests/assets/photo.jpg", &jpeg_file).unwrap();
+
+        assert!(is_supported_file(jpeg_file.as_path()));
+    }
+
+    #[test]
+    fn is_supported_file_should_return_true_for_png_tiff_webp_and_video_files() {
+        assert!(is_supported_file(Path::new("tests/assets/photo.png")));
+        assert!(is_supported_file(Path::new("tests/assets/photo.tiff")));
+        assert!(is_supported_file(Path::new("tests/assets/photo.webp")));
+        assert!(is_supported_file(Path::new("tests/assets/video.mp4")));
+        assert!(is_supported_file(Path::new(
+            "tests/assets/video_without_gps.mov"
+        )));
+    }
+
+    #[test]
+    fn media_format_should_be_chosen_by_case_insensitive_file_extension() {
+        assert_eq!("JPEG", media_format(Path::new("a.JPG")).unwrap().name());
+        assert_eq!("PNG", media_format(Path::new("a.png")).unwrap().name());
+        assert_eq!("TIFF", media_format(Path::new("a.tif")).unwrap().name());
+        assert_eq!("WebP", media_format(Path::new("a.WebP")).unwrap().name());
+        assert_eq!("Video", media_format(Path::new("a.MOV")).unwrap().name());
+        assert!(media_format(Path::new("a.gif")).is_none());
+        assert!(media_format(Path::new("jpg")).is_none());
+    }
+
+    #[test]
+    fn read_metadata_should_error_for_an_unsupported_file() {
+        match read_metadata(Path::new("Cargo.toml")) {
+            Err(PhotoError::UnsupportedFormat) => {}
+            x => panic!("Unexpected result: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn read_metadata_should_read_jpeg_exif_metadata() {
+        let metadata = read_metadata(Path::new("tests/assets/photo.jpg")).unwrap();
+
+        assert_photo_asset_metadata(&metadata);
+        assert_eq!(Some(1473154755000), metadata.timestamp_ms());
+        assert_eq!(Some(TimestampSource::GpsTime), metadata.timestamp_source());
+        assert_eq!(Some(1), metadata.orientation());
+        assert_eq!(Some("Canon PowerShot G7 X"), metadata.camera_model());
+    }
+
+    #[test]
+    fn read_metadata_should_read_tiff_exif_metadata() {
+        let metadata = read_metadata(Path::new("tests/assets/photo.tiff")).unwrap();
+
+        assert_photo_asset_metadata(&metadata);
+        assert_eq!(Some(1473158321000), metadata.timestamp_ms());
+    }
+
+    #[test]
+    fn read_metadata_should_read_png_exif_chunk() {
+        let metadata = read_metadata(Path::new("tests/assets/photo.png")).unwrap();
+
+        assert_photo_asset_metadata(&metadata);
+        assert_eq!(Some(1473158321000), metadata.timestamp_ms());
+    }
+
+    #[test]
+    fn read_metadata_should_read_webp_exif_chunk() {
+        let metadata = read_metadata(Path::new("tests/assets/photo.webp")).unwrap();
+
+        assert_photo_asset_metadata(&metadata);
+        assert_eq!(Some(1473158321000), metadata.timestamp_ms());
+    }
+
+    #[test]
+    fn read_metadata_should_read_video_creation_time_and_location() {
+        let metadata = read_metadata(Path::new("tests/assets/video.mp4")).unwrap();
+
+        assert_eq!(Some(1473158321000), metadata.timestamp_ms());
+        assert_eq!(
+            Some(&Coordinates::new(38.76544, -9.094802)),
+            metadata.coordinates()
+        );
+        assert_eq!(None, metadata.orientation());
+        assert_eq!(None, metadata.camera_model());
+    }
+
+    #[test]
+    fn png_exif_chunk_should_error_if_there_is_no_exif_chunk() {
+        let data = b"\x89PNG\r\n\x1a\n\0\0\0\0IEND\xaeB`\x82";
+
+        match png_exif_chunk(data) {
+            Err(exif::Error::NotFound(_)) => {}
+            x => panic!("Unexpected result: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn png_exif_chunk_should_find_an_exif_chunk_after_the_image_data() {
+        let data = b"\x89PNG\r\n\x1a\n\0\0\0\x01IDAT\0\0\0\0\0\
+                     \0\0\0\x04eXIfII*\0\0\0\0\0\
+                     \0\0\0\0IEND\xaeB`\x82";
+
+        assert_eq!(b"II*\0", png_exif_chunk(data).unwrap());
+    }
+
+    #[test]
+    fn png_raw_exif_profile_should_be_none_if_there_is_no_raw_profile() {
+        let data = b"\x89PNG\r\n\x1a\n\0\0\0\x04eXIfII*\0\0\0\0\0\
+                     \0\0\0\0IEND\xaeB`\x82";
+
+        assert_eq!(None, png_raw_exif_profile(data).unwrap());
+    }
+
+    #[test]
+    fn png_raw_exif_profile_should_decode_a_compressed_raw_profile() {
+        let mut data = PNG_SIGNATURE.to_vec();
+        data.extend(png_raw_exif_profile_chunk(b"II*\0"));
+        data.extend(png_chunk(b"IEND", b""));
+
+        assert_eq!(
+            Some(b"II*\0".to_vec()),
+            png_raw_exif_profile(&data).unwrap()
+        );
+    }
+
+    #[test]
+    fn png_raw_exif_profile_should_decode_an_uncompressed_raw_profile() {
+        let mut chunk_data = PNG_RAW_EXIF_PROFILE_KEYWORD.to_vec();
+        chunk_data.extend(b"\0\nexif\n       4\n49492a00\n");
+        let mut data = PNG_SIGNATURE.to_vec();
+        data.extend(png_chunk(b"tEXt", &chunk_data));
+        data.extend(png_chunk(b"IEND", b""));
+
+        assert_eq!(
+            Some(b"II*\0".to_vec()),
+            png_raw_exif_profile(&data).unwrap()
+        );
+    }
+
+    #[test]
+    fn decode_raw_profile_should_error_if_the_length_does_not_match_the_data() {
+        assert!(decode_raw_profile(b"\nexif\n       5\n49492a00\n").is_err());
+        assert!(decode_raw_profile(b"\nexif\n       4\n49492a0g\n").is_err());
+    }
+
+    #[test]
+    fn read_metadata_should_read_a_png_raw_exif_profile_instead_of_an_exif_chunk() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("photo.png");
+        let original = fs::read("tests/assets/photo.png").unwrap();
+        let jpeg_exif = exif::Reader::new(&mut BufReader::new(
+            File::open("tests/assets/photo_without_gps.jpg").unwrap(),
+        ))
+        .unwrap()
+        .buf()
+        .to_vec();
+
+        // Put a raw profile without GPS coordinates before the eXIf chunk that has them.
+        let mut data = PNG_SIGNATURE.to_vec();
+        data.extend(png_raw_exif_profile_chunk(&jpeg_exif));
+        data.extend(&original[PNG_SIGNATURE.len()..]);
+        fs::write(&path, &data).unwrap();
+
+        let metadata = read_metadata(&path).unwrap();
+
+        assert_eq!(None, metadata.coordinates());
+        assert!(metadata.timestamp_ms().is_some());
+    }
+
+    #[test]
+    fn webp_exif_chunk_should_strip_an_exif_identifier_prefix() {
+        let data = b"RIFF\x16\0\0\0WEBPEXIF\x0a\0\0\0Exif\0\0II*\0";
+
+        assert_eq!(b"II*\0", webp_exif_chunk(data).unwrap());
+    }
+
+    #[test]
+    fn jpeg_xmp_segment_should_not_search_the_image_data() {
+        let data = b"\xff\xd8\xff\xe1\0\x26http://ns.adobe.com/xap/1.0/\0<x:xmp>\xff\xd9";
+        assert_eq!(
+            Some(b"<x:xmp>".to_vec()),
+            jpeg_xmp_segment(&mut Cursor::new(&data[..])).unwrap()
+        );
+
+        let data = b"\xff\xd8\xff\xda\0\x02<x:xmpmeta>\xff\xd9";
+        assert_eq!(None, jpeg_xmp_segment(&mut Cursor::new(&data[..])).unwrap());
+    }
+
+    #[test]
+    fn png_xmp_chunk_should_skip_other_chunks_to_find_an_uncompressed_xmp_chunk() {
+        let data = b"\x89PNG\r\n\x1a\n\0\0\0\x01IDAT\0\0\0\0\0\
+                     \0\0\0\x1diTXtXML:com.adobe.xmp\0\0\0\0\0<x:xmp>\0\0\0\0\
+                     \0\0\0\0IEND\xaeB`\x82";
+
+        assert_eq!(
+            Some(b"<x:xmp>".to_vec()),
+            png_xmp_chunk(&mut Cursor::new(&data[..])).unwrap()
+        );
+    }
+
+    #[test]
+    fn webp_lossy_bitstream_should_error_if_there_is_no_lossy_bitstream_chunk() {
+        let data = b"RIFF\x0c\0\0\0WEBPVP8L\0\0\0\0";
+
+        assert!(webp_lossy_bitstream(data).is_err());
+    }
+
+    #[test]
+    fn webp_xmp_chunk_should_skip_other_chunks_to_find_the_xmp_chunk() {
+        let data = b"RIFF\x1e\0\0\0WEBPVP8 \x01\0\0\0\0\0XMP \x07\0\0\0<x:xmp>\0";
+
+        assert_eq!(
+            Some(b"<x:xmp>".to_vec()),
+            webp_xmp_chunk(&mut Cursor::new(&data[..])).unwrap()
+        );
+    }
+
+    #[test]
+    fn tiff_xmp_field_should_read_the_xmp_field_of_the_first_directory() {
+        let data = b"MM\0*\0\0\0\x08\0\x01\x02\xbc\0\x01\0\0\0\x07\0\0\0\x1a\0\0\0\0<x:xmp>";
+
+        assert_eq!(
+            Some(b"<x:xmp>".to_vec()),
+            tiff_xmp_field(&mut Cursor::new(&data[..])).unwrap()
+        );
+    }
+
+    #[test]
+    fn read_xmp_should_read_the_xmp_packet_of_a_supported_file() {
+        let xmp = read_xmp(Path::new("tests/assets/photo.jpg"))
+            .unwrap()
+            .unwrap();
+
+        assert!(xmp.starts_with(b"<?xpacket"));
+        assert!(xmp.ends_with(b"<?xpacket end='w'?>"));
+    }
+
+    #[test]
+    fn read_xmp_should_be_none_if_the_file_has_no_xmp() {
+        for path in &[
+            "tests/assets/photo.png",
+            "tests/assets/video.mp4",
+            "Cargo.toml",
+        ] {
+            assert_eq!(None, read_xmp(Path::new(path)).unwrap());
+        }
+    }
+
+    #[test]
+    fn write_coordinates_should_error_for_an_unsupported_file() {
+        let coordinates = Coordinates::new(0.0, 0.0);
+
+        assert!(write_coordinates(Path::new("Cargo.toml"), &coordinates).is_err());
+    }
+
+    #[test]
+    fn write_location_should_error_for_an_unsupported_file() {
+        let coordinates = Coordinates::new(0.0, 0.0);
+        let place = Place::new("Sacavém", Some("Lisbon"), "Portugal", "PT");
+
+        assert!(write_location(Path::new("Cargo.toml"), &coordinates, Some(&place)).is_err());
+    }
+
+    #[test]
+    fn place_commands_should_set_xmp_fields_and_optionally_iptc_datasets() {
+        let place = Place::new("Sacavém", Some("Lisbon"), "Portugal", "PT");
+
+        assert_eq!(
+            vec![
+                "set Xmp.photoshop.City XmpText Sacavém",
+                "set Xmp.photoshop.Country XmpText Portugal",
+                "set Xmp.iptc.CountryCode XmpText PT",
+                "set Xmp.photoshop.State XmpText Lisbon",
+            ],
+            place_commands(&place, false)
+        );
+        assert_eq!(
+            vec![
+                "set Xmp.photoshop.City XmpText Sacavém",
+                "set Xmp.photoshop.Country XmpText Portugal",
+                "set Xmp.iptc.CountryCode XmpText PT",
+                "set Iptc.Envelope.CharacterSet String \x1b%G",
+                "set Iptc.Application2.City String Sacavém",
+                "set Iptc.Application2.CountryName String Portugal",
+                "set Iptc.Application2.CountryCode String PT",
+                "set Xmp.photoshop.State XmpText Lisbon",
+                "set Iptc.Application2.ProvinceState String Lisbon",
+            ],
+            place_commands(&place, true)
+        );
+    }
+
+    #[test]
+    fn place_commands_should_not_set_a_state_if_the_region_is_unknown() {
+        let place = Place::new("Ipswich", None, "GB", "GB");
+
+        assert!(place_commands(&place, true)
+            .iter()
+            .all(|c| !c.contains("State")));
+    }
+
+    #[test]
+    fn from_exif_fields_should_use_the_date_taken_if_the_gps_date_stamp_is_invalid() {
+        let gps_time = vec![
+            exif::Rational { num: 9, denom: 1 },
+            exif::Rational { num: 39, denom: 1 },
+            exif::Rational { num: 15, denom: 1 },
+        ];
+
+        for gps_date in &[&b""[..], b"    :  :  ", b"2016:13:45"] {
+            let fields = vec![
+                exif::Field {
+                    tag: Tag::DateTimeOriginal,
+                    thumbnail: false,
+                    value: exif::Value::Ascii(vec![b"2016:09:06 10:39:15"]),
+                },
+                exif::Field {
+                    tag: Tag::GPSDateStamp,
+                    thumbnail: false,
+                    value: exif::Value::Ascii(vec![gps_date]),
+                },
+                exif::Field {
+                    tag: Tag::GPSTimeStamp,
+                    thumbnail: false,
+                    value: exif::Value::Rational(gps_time.clone()),
+                },
+            ];
+
+            let metadata = Metadata::from_exif_fields(&fields).unwrap();
+
+            assert_eq!(Some(1473158355000), metadata.timestamp_ms());
+            assert_eq!(
+                Some(TimestampSource::DateTimeOriginal),
+                metadata.timestamp_source()
+            );
+        }
+    }
+
+    #[test]
+    fn subsec_ms_should_convert_fractional_second_digits_to_milliseconds() {
+        assert_eq!(0, subsec_ms(""));
+        assert_eq!(500, subsec_ms("5"));
+        assert_eq!(270, subsec_ms("27"));
+        assert_eq!(123, subsec_ms("123456"));
+        assert_eq!(40, subsec_ms("04  "));
+    }
+
+    #[test]
+    fn dms_string_should_print_coordinate_in_exif_degrees_minutes_seconds_format() {
+        assert_eq!("556382576/10000000 0/1 0/1", dms_string(55.6382576));
+    }
+
+    #[test]
+    fn dms_string_should_print_the_magnitude_of_a_negative_coordinate() {
+        assert_eq!("90948022/10000000 0/1 0/1", dms_string(-9.0948022));
+    }
+
+    #[test]
+    fn write_coordinates_should_write_negative_coordinates_with_south_and_west_refs() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("photo.jpg");
+        copy("tests/assets/photo_without_gps.jpg", &path).unwrap();
+        let coordinates = Coordinates::new(-33.8567844, -151.2152967);
+
+        let result = write_coordinates(&path, &coordinates);
+
+        if Command::new("exiv2").status().is_err() {
+            assert!(result.is_err());
+        } else {
+            result.unwrap();
+            let metadata = read_metadata(&path).unwrap();
+            let written = metadata.coordinates().unwrap();
+            assert!((written.latitude() - coordinates.latitude()).abs() < 1e-6);
+            assert!((written.longitude() - coordinates.longitude()).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn write_coordinates_should_write_png_coordinates_that_can_be_read_back() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("photo.png");
+        copy("tests/assets/photo.png", &path).unwrap();
+        let coordinates = Coordinates::new(-33.8567844, 151.2152967);
+
+        let result = write_coordinates(&path, &coordinates);
+
+        if Command::new("exiv2").status().is_err() {
+            assert!(result.is_err());
+        } else {
+            result.unwrap();
+            let metadata = read_metadata(&path).unwrap();
+            let written = metadata.coordinates().unwrap();
+            assert!((written.latitude() - coordinates.latitude()).abs() < 1e-6);
+            assert!((written.longitude() - coordinates.longitude()).abs() < 1e-6);
+        }
+    }
+}
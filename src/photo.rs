@@ -1,24 +1,91 @@
 //#![deny(warnings)]
 use std::error;
 use std::fmt;
-use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 
 use chrono::format::ParseError;
-use chrono::offset::TimeZone;
-use chrono::offset::Utc;
 
 use exif;
-use exif::Tag;
 
 use coordinates::Coordinates;
+use media_format::{read_metadata, Metadata};
+use timestamp_fallback::{read_fallback_timestamp, TimestampFallback};
 
 #[derive(Debug)]
 pub struct Photo {
     path: PathBuf,
-    timestamp: i64,
+    timestamp_ms: i64,
+    timestamp_source: TimestampSource,
     coordinates: Option<Coordinates>,
+    camera_model: Option<String>,
+}
+
+/// The metadata that a photo's timestamp was read from.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum TimestampSource {
+    /// The EXIF GPS date and time stamps, which are always in UTC.
+    GpsTime,
+    /// The EXIF date taken, plus any subsecond time.
+    DateTimeOriginal,
+    /// The EXIF date modified, plus any subsecond time.
+    DateTime,
+    /// The creation time in a video's movie header.
+    VideoCreationTime,
+    /// A date and time in the file name.
+    FileName,
+    /// The XMP create date.
+    XmpCreateDate,
+    /// The file's last modification time.
+    FileModificationTime,
+}
+
+impl TimestampSource {
+    /// Fallback sources are less likely to record when the photo was taken.
+    pub fn is_fallback(self) -> bool {
+        match self {
+            TimestampSource::FileName
+            | TimestampSource::XmpCreateDate
+            | TimestampSource::FileModificationTime => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for TimestampSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TimestampSource::GpsTime => write!(f, "GPS time stamp"),
+            TimestampSource::DateTimeOriginal => write!(f, "date taken"),
+            TimestampSource::DateTime => write!(f, "date modified"),
+            TimestampSource::VideoCreationTime => write!(f, "video creation time"),
+            TimestampSource::FileName => write!(f, "file name"),
+            TimestampSource::XmpCreateDate => write!(f, "XMP create date"),
+            TimestampSource::FileModificationTime => write!(f, "file modification time"),
+        }
+    }
+}
+
+/// When a photo was taken, and the metadata that was read from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timestamp {
+    ms: i64,
+    source: TimestampSource,
+}
+
+impl Timestamp {
+    pub fn new(ms: i64, source: TimestampSource) -> Timestamp {
+        Timestamp { ms, source }
+    }
+
+    /// The Unix timestamp in milliseconds.
+    pub fn ms(&self) -> i64 {
+        self.ms
+    }
+
+    pub fn source(&self) -> TimestampSource {
+        self.source
+    }
 }
 
 #[derive(Debug)]
@@ -27,6 +94,7 @@ pub enum PhotoError {
     IoError(io::Error),
     TimestampFormatError(ParseError),
     TimestampMissing,
+    UnsupportedFormat,
 }
 
 impl From<exif::Error> for PhotoError {
@@ -54,6 +122,7 @@ impl fmt::Display for PhotoError {
             PhotoError::IoError(ref x) => x.fmt(f),
             PhotoError::TimestampFormatError(ref x) => x.fmt(f),
             PhotoError::TimestampMissing => write!(f, "The image has no timestamp metadata"),
+            PhotoError::UnsupportedFormat => write!(f, "The file format is not supported"),
         }
     }
 }
@@ -65,6 +134,7 @@ impl error::Error for PhotoError {
             PhotoError::IoError(ref x) => x.description(),
             PhotoError::TimestampFormatError(ref x) => x.description(),
             PhotoError::TimestampMissing => "The image has no timestamp metadata",
+            PhotoError::UnsupportedFormat => "The file format is not supported",
         }
     }
 
@@ -80,70 +150,36 @@ impl error::Error for PhotoError {
 
 impl Photo {
     pub fn new(path: &Path) -> Result<Photo, PhotoError> {
-        let file = fs::File::open(path)?;
-
-        let reader = exif::Reader::new(&mut io::BufReader::new(&file))?;
-
-        let mut date_time: Option<i64> = None;
-        let mut latitude: Option<f64> = None;
-        let mut longitude: Option<f64> = None;
-        let mut latitude_sign: f64 = 1.0;
-        let mut longitude_sign: f64 = 1.0;
-        for field in reader.fields() {
-            match field.tag {
-                Tag::DateTimeOriginal | Tag::DateTime => {
-                    if let exif::Value::Ascii(_) = field.value {
-                        let string_value = format!("{}", field.value.display_as(field.tag));
-                        date_time = Some(
-                            Utc.datetime_from_str(string_value.as_str(), "%F %T")?
-                                .timestamp(),
-                        );
-                    }
-                }
-                Tag::GPSLatitude => {
-                    if let exif::Value::Rational(ref x) = field.value {
-                        latitude = Some(to_decimal_coordinate(x));
-                    }
-                }
-                Tag::GPSLatitudeRef => {
-                    let string_value = format!("{}", field.value.display_as(field.tag));
-                    if let "S" = string_value.as_str() {
-                        latitude_sign = -1.0
-                    }
-                }
-                Tag::GPSLongitude => {
-                    if let exif::Value::Rational(ref x) = field.value {
-                        longitude = Some(to_decimal_coordinate(x));
-                    }
-                }
-                Tag::GPSLongitudeRef => {
-                    let string_value = format!("{}", field.value.display_as(field.tag));
-                    if let "W" = string_value.as_str() {
-                        longitude_sign = -1.0
-                    }
-                }
-                _ => {}
-            }
-        }
+        Photo::with_fallbacks(path, &[])
+    }
 
-        let coordinates: Option<Coordinates>;
-        match (longitude, latitude) {
-            (Some(longitude), Some(latitude)) => {
-                coordinates = Some(Coordinates::new(
-                    latitude * latitude_sign,
-                    longitude * longitude_sign,
-                ));
+    /// Read the photo's metadata, using the given fallbacks in order if the metadata doesn't
+    /// include a timestamp.
+    pub fn with_fallbacks(
+        path: &Path,
+        fallbacks: &[TimestampFallback],
+    ) -> Result<Photo, PhotoError> {
+        let metadata = match read_metadata(path) {
+            Ok(x) => x,
+            Err(PhotoError::ExifError(exif::Error::NotFound(_))) if !fallbacks.is_empty() => {
+                Metadata::default()
             }
-            _ => coordinates = None,
-        }
+            Err(e) => return Err(e),
+        };
 
-        match date_time {
-            None => Err(PhotoError::TimestampMissing),
+        let timestamp = metadata
+            .timestamp()
+            .or_else(|| read_fallback_timestamp(path, fallbacks));
+
+        match timestamp {
             Some(timestamp) => Ok(Photo {
                 path: path.to_path_buf(),
-                timestamp,
-                coordinates,
+                timestamp_ms: timestamp.ms(),
+                timestamp_source: timestamp.source(),
+                coordinates: metadata.coordinates().cloned(),
+                camera_model: metadata.camera_model().map(str::to_string),
             }),
+            None => Err(PhotoError::TimestampMissing),
         }
     }
 
@@ -152,22 +188,36 @@ impl Photo {
     }
 
     pub fn timestamp(&self) -> i64 {
-        self.timestamp
+        self.timestamp_ms / 1000
+    }
+
+    pub fn timestamp_ms(&self) -> i64 {
+        self.timestamp_ms
+    }
+
+    pub fn timestamp_source(&self) -> TimestampSource {
+        self.timestamp_source
     }
 
     pub fn gps_coordinates(&self) -> Option<&Coordinates> {
         self.coordinates.as_ref()
     }
-}
 
-fn to_decimal_coordinate(dms: &[exif::Rational]) -> f64 {
-    dms[0].to_f64() + dms[1].to_f64() / 60.0 + dms[2].to_f64() / 3600.0
+    pub fn camera_model(&self) -> Option<&str> {
+        self.camera_model.as_deref()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    extern crate tempfile;
+
     use super::*;
 
+    use std::fs::copy;
+
+    use self::tempfile::tempdir;
+
     mod new {
         use super::*;
 
@@ -194,7 +244,7 @@ mod tests {
 
         #[test]
         fn should_error_if_passed_an_image_with_no_exif_timestamp() {
-            let photo = Photo::new(Path::new("tests/assets/photo_without_timestamp.jpg"));
+            let photo = Photo::new(Path::new("tests/assets/photo_without_any_timestamp.jpg"));
 
             assert!(photo.is_err());
         }
@@ -203,8 +253,27 @@ mod tests {
         fn should_return_a_photo_object_with_the_image_timestamp_from_exif_metadata() {
             let photo = Photo::new(Path::new("tests/assets/photo_without_gps.jpg")).unwrap();
 
-            assert_eq!(1473158321, photo.timestamp);
+            assert_eq!(1473158321000, photo.timestamp_ms);
+            assert_eq!(TimestampSource::DateTimeOriginal, photo.timestamp_source);
             assert_eq!(None, photo.coordinates);
+            assert_eq!(Some("Canon PowerShot G7 X"), photo.camera_model());
+        }
+
+        #[test]
+        fn should_return_a_photo_object_with_a_millisecond_timestamp_from_subsecond_time() {
+            let photo = Photo::new(Path::new("tests/assets/photo_with_subsec_time.jpg")).unwrap();
+
+            assert_eq!(1473158321270, photo.timestamp_ms);
+            assert_eq!(1473158321, photo.timestamp());
+            assert_eq!(TimestampSource::DateTimeOriginal, photo.timestamp_source);
+        }
+
+        #[test]
+        fn should_prefer_the_gps_timestamp_over_the_date_taken() {
+            let photo = Photo::new(Path::new("tests/assets/photo_with_gps_time.jpg")).unwrap();
+
+            assert_eq!(1473158321500, photo.timestamp_ms);
+            assert_eq!(TimestampSource::GpsTime, photo.timestamp_source);
         }
 
         #[test]
@@ -212,9 +281,89 @@ mod tests {
             let photo = Photo::new(Path::new("tests/assets/photo.jpg")).unwrap();
             let location = photo.coordinates.unwrap();
 
-            assert_eq!(1473158321, photo.timestamp);
+            assert_eq!(1473154755000, photo.timestamp_ms);
+            assert_eq!(TimestampSource::GpsTime, photo.timestamp_source);
             assert_eq!(38.76544, location.latitude());
             assert_eq!(-9.094802222222222, location.longitude());
         }
+
+        #[test]
+        fn should_return_a_photo_object_with_the_timestamp_and_gps_of_a_png_webp_or_tiff() {
+            for path in &[
+                "tests/assets/photo.png",
+                "tests/assets/photo.tiff",
+                "tests/assets/photo.webp",
+            ] {
+                let photo = Photo::new(Path::new(path)).unwrap();
+                let location = photo.coordinates.unwrap();
+
+                assert_eq!(1473158321000, photo.timestamp_ms);
+                assert_eq!(38.76544, location.latitude());
+                assert_eq!(-9.094802222222222, location.longitude());
+            }
+        }
+
+        #[test]
+        fn should_return_a_photo_object_with_the_creation_time_and_location_of_a_video() {
+            let photo = Photo::new(Path::new("tests/assets/video.mp4")).unwrap();
+            let location = photo.coordinates.unwrap();
+
+            assert_eq!(1473158321000, photo.timestamp_ms);
+            assert_eq!(TimestampSource::VideoCreationTime, photo.timestamp_source);
+            assert_eq!(38.76544, location.latitude());
+            assert_eq!(-9.094802, location.longitude());
+        }
+    }
+    mod with_fallbacks {
+        use super::*;
+
+        #[test]
+        fn should_prefer_the_metadata_timestamp() {
+            let tmp_dir = tempdir().unwrap();
+            let path = tmp_dir.path().join("IMG_20190704_153012.jpg");
+            copy("tests/assets/photo_without_gps.jpg", &path).unwrap();
+
+            let photo = Photo::with_fallbacks(&path, &[TimestampFallback::FileName]).unwrap();
+
+            assert_eq!(1473158321000, photo.timestamp_ms);
+            assert_eq!(TimestampSource::DateTimeOriginal, photo.timestamp_source);
+        }
+
+        #[test]
+        fn should_use_a_fallback_if_the_metadata_has_no_timestamp() {
+            let tmp_dir = tempdir().unwrap();
+            let path = tmp_dir.path().join("IMG_20190704_153012.jpg");
+            copy("tests/assets/photo_without_any_timestamp.jpg", &path).unwrap();
+
+            let photo = Photo::with_fallbacks(&path, &[TimestampFallback::FileName]).unwrap();
+
+            assert_eq!(1562254212000, photo.timestamp_ms);
+            assert_eq!(TimestampSource::FileName, photo.timestamp_source);
+            assert!(photo.coordinates.is_some());
+        }
+
+        #[test]
+        fn should_use_a_fallback_if_the_photo_has_no_exif_metadata() {
+            let path = Path::new("tests/assets/photo_without_exif.jpg");
+            let fallbacks = [
+                TimestampFallback::FileName,
+                TimestampFallback::FileModificationTime,
+            ];
+            let photo = Photo::with_fallbacks(path, &fallbacks).unwrap();
+
+            assert_eq!(
+                TimestampSource::FileModificationTime,
+                photo.timestamp_source
+            );
+            assert!(photo.timestamp_source.is_fallback());
+        }
+
+        #[test]
+        fn should_error_if_no_fallback_gives_a_timestamp() {
+            let path = Path::new("tests/assets/photo_without_any_timestamp.jpg");
+            let photo = Photo::with_fallbacks(path, &[TimestampFallback::FileName]);
+
+            assert!(photo.is_err());
+        }
     }
 }
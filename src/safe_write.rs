@@ -0,0 +1,435 @@
+use std::error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use coordinates::Coordinates;
+use geocoder::Place;
+use media_format::{media_format, read_metadata, write_location};
+
+/// Written coordinates are rounded to seven decimal places, so allow for that when checking them.
+const COORDINATE_TOLERANCE: f64 = 1e-6;
+
+/// Where to copy a file to before writing its location.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BackupLocation {
+    /// Next to the original file, with `.orig` appended to its file name.
+    Sibling,
+    /// In the given directory, with the original file's name.
+    Directory(PathBuf),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WriteOptions {
+    backup: Option<BackupLocation>,
+    verify: bool,
+}
+
+impl WriteOptions {
+    /// If `verify` is true, the file is re-read after its location is written to check that the
+    /// location was written correctly and that its image data is unchanged.
+    pub fn new(backup: Option<BackupLocation>, verify: bool) -> WriteOptions {
+        WriteOptions { backup, verify }
+    }
+
+    pub fn backup(&self) -> Option<&BackupLocation> {
+        self.backup.as_ref()
+    }
+
+    pub fn verify(&self) -> bool {
+        self.verify
+    }
+}
+
+#[derive(Debug)]
+pub enum WriteError {
+    IoError(io::Error),
+    /// The written file failed verification, so its original content was restored.
+    VerificationFailed(PathBuf, String),
+}
+
+impl From<io::Error> for WriteError {
+    fn from(error: io::Error) -> Self {
+        WriteError::IoError(error)
+    }
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WriteError::IoError(ref x) => x.fmt(f),
+            WriteError::VerificationFailed(ref path, ref reason) => write!(
+                f,
+                "{} failed verification after its location was written ({}), so it has been \
+                 restored",
+                path.display(),
+                reason
+            ),
+        }
+    }
+}
+
+impl error::Error for WriteError {
+    fn description(&self) -> &str {
+        match self {
+            WriteError::IoError(ref x) => x.description(),
+            WriteError::VerificationFailed(_, _) => {
+                "The file failed verification after its location was written"
+            }
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            WriteError::IoError(ref x) => Some(x),
+            WriteError::VerificationFailed(_, _) => None,
+        }
+    }
+}
+
+/// Write the given coordinates to the file at the given path, along with the names of the place
+/// they are in if given, backing up and verifying the file as configured. Returns the path of
+/// the backup, if one was made.
+pub fn write_coordinates_safely(
+    path: &Path,
+    coordinates: &Coordinates,
+    place: Option<&Place>,
+    options: &WriteOptions,
+) -> Result<Option<PathBuf>, WriteError> {
+    write_with(path, coordinates, options, || {
+        write_location(path, coordinates, place)
+    })
+}
+
+/// Replace the content of the file at the given path by writing the new content to a temporary
+/// file next to it and renaming that over the original, so that the file is never left
+/// half-written.
+pub fn replace_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    replace_file_with(path, |file| file.write_all(content))
+}
+
+/// Like `replace_file`, but the new content is written to the temporary file by the given
+/// function, so that it doesn't need to be held in memory. The temporary file is removed and
+/// the original is left untouched if the function fails, and the original's permissions are
+/// kept.
+pub fn replace_file_with<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    let temp_path = temporary_path(path);
+
+    let result = File::create(&temp_path)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            file.sync_all()
+        }).and_then(|_| fs::set_permissions(&temp_path, fs::metadata(path)?.permissions()))
+        .and_then(|_| fs::rename(&temp_path, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".yore-tmp");
+    path.with_file_name(file_name)
+}
+
+fn write_with<F>(
+    path: &Path,
+    coordinates: &Coordinates,
+    options: &WriteOptions,
+    write: F,
+) -> Result<Option<PathBuf>, WriteError>
+where
+    F: FnOnce() -> io::Result<()>,
+{
+    let original = fs::read(path)?;
+
+    let backup_path = match options.backup {
+        Some(ref location) => {
+            let backup_path = backup_path(path, location)?;
+            fs::copy(path, &backup_path)?;
+            Some(backup_path)
+        }
+        None => None,
+    };
+
+    if let Err(error) = write() {
+        // The write may have failed after changing the file, e.g. if it was truncated.
+        if fs::read(path).ok().as_ref() != Some(&original) {
+            replace_file(path, &original)?;
+        }
+        return Err(error.into());
+    }
+
+    if options.verify {
+        if let Err(reason) = verify(path, &original, coordinates) {
+            replace_file(path, &original)?;
+            return Err(WriteError::VerificationFailed(path.to_path_buf(), reason));
+        }
+    }
+
+    Ok(backup_path)
+}
+
+fn verify(path: &Path, original: &[u8], coordinates: &Coordinates) -> Result<(), String> {
+    // Formats that can't be decoded can't have their image data compared.
+    if let Some(format) = media_format(path) {
+        if let Some(original_pixels) = format.decode_pixels(original) {
+            let written = fs::read(path).map_err(|e| format!("it could not be read: {}", e))?;
+            match format.decode_pixels(&written) {
+                Some(ref pixels) if *pixels == original_pixels => {}
+                Some(_) => return Err("its image data has changed".to_string()),
+                None => return Err("its image data could not be decoded".to_string()),
+            }
+        }
+    }
+
+    let metadata =
+        read_metadata(path).map_err(|e| format!("its metadata could not be read: {}", e))?;
+
+    match metadata.coordinates() {
+        Some(c) if is_close(c, coordinates) => Ok(()),
+        Some(c) => Err(format!("its location is {}, not {}", c, coordinates)),
+        None => Err("it has no location".to_string()),
+    }
+}
+
+fn is_close(a: &Coordinates, b: &Coordinates) -> bool {
+    (a.latitude() - b.latitude()).abs() < COORDINATE_TOLERANCE
+        && (a.longitude() - b.longitude()).abs() < COORDINATE_TOLERANCE
+}
+
+/// Get a path to back up the given file to that doesn't overwrite any existing backups. If a
+/// backup already exists, a number is added before the backup's extension.
+fn backup_path(path: &Path, location: &BackupLocation) -> io::Result<PathBuf> {
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} has no file name", path.display()),
+        )
+    })?;
+
+    let backup_path = match location {
+        BackupLocation::Sibling => {
+            let mut file_name = file_name.to_os_string();
+            file_name.push(".orig");
+            path.with_file_name(file_name)
+        }
+        BackupLocation::Directory(ref directory) => {
+            fs::create_dir_all(directory)?;
+            directory.join(file_name)
+        }
+    };
+
+    let mut candidate = backup_path.clone();
+    let mut number = 1;
+    while candidate.exists() {
+        let mut file_name = backup_path.file_stem().unwrap_or_default().to_os_string();
+        file_name.push(format!(".{}", number));
+        if let Some(extension) = backup_path.extension() {
+            file_name.push(".");
+            file_name.push(extension);
+        }
+        candidate = backup_path.with_file_name(file_name);
+        number += 1;
+    }
+
+    Ok(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    use self::tempfile::{tempdir, TempDir};
+
+    fn photo_without_gps() -> (TempDir, PathBuf) {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("photo.jpg");
+        fs::copy("tests/assets/photo_without_gps.jpg", &path).unwrap();
+
+        (tmp_dir, path)
+    }
+
+    fn photo_coordinates() -> Coordinates {
+        Coordinates::new(38.76544, -9.094802222222222)
+    }
+
+    #[test]
+    fn backup_path_should_append_orig_to_the_file_name_for_a_sibling_backup() {
+        let (tmp_dir, path) = photo_without_gps();
+
+        let backup_path = backup_path(&path, &BackupLocation::Sibling).unwrap();
+
+        assert_eq!(tmp_dir.path().join("photo.jpg.orig"), backup_path);
+    }
+
+    #[test]
+    fn backup_path_should_use_the_file_name_in_a_backup_directory() {
+        let (tmp_dir, path) = photo_without_gps();
+        let directory = tmp_dir.path().join("backups");
+
+        let backup_path =
+            backup_path(&path, &BackupLocation::Directory(directory.clone())).unwrap();
+
+        assert_eq!(directory.join("photo.jpg"), backup_path);
+        assert!(directory.is_dir());
+    }
+
+    #[test]
+    fn backup_path_should_number_backups_to_avoid_overwriting_existing_ones() {
+        let (tmp_dir, path) = photo_without_gps();
+        fs::write(tmp_dir.path().join("photo.jpg.orig"), b"").unwrap();
+        fs::write(tmp_dir.path().join("photo.jpg.1.orig"), b"").unwrap();
+
+        let backup_path = backup_path(&path, &BackupLocation::Sibling).unwrap();
+
+        assert_eq!(tmp_dir.path().join("photo.jpg.2.orig"), backup_path);
+    }
+
+    #[test]
+    fn replace_file_should_replace_the_content_without_leaving_a_temporary_file() {
+        let (tmp_dir, path) = photo_without_gps();
+
+        replace_file(&path, b"replaced").unwrap();
+
+        assert_eq!(b"replaced", fs::read(&path).unwrap().as_slice());
+        assert_eq!(1, fs::read_dir(tmp_dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn replace_file_with_should_leave_the_original_and_no_temporary_file_if_writing_fails() {
+        let (tmp_dir, path) = photo_without_gps();
+        let original = fs::read(&path).unwrap();
+
+        let result = replace_file_with(&path, |file| {
+            file.write_all(b"partial")?;
+            Err(io::Error::new(io::ErrorKind::Other, "failed"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(original, fs::read(&path).unwrap());
+        assert_eq!(1, fs::read_dir(tmp_dir.path()).unwrap().count());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replace_file_with_should_keep_the_permissions_of_the_original() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (_tmp_dir, path) = photo_without_gps();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        replace_file_with(&path, |file| file.write_all(b"replaced")).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o640, mode & 0o777);
+    }
+
+    #[test]
+    fn write_with_should_back_up_the_file_before_writing() {
+        let (_tmp_dir, path) = photo_without_gps();
+        let original = fs::read(&path).unwrap();
+        let options = WriteOptions::new(Some(BackupLocation::Sibling), false);
+
+        let backup_path = write_with(&path, &photo_coordinates(), &options, || {
+            fs::write(&path, b"written")
+        }).unwrap()
+        .unwrap();
+
+        assert_eq!(original, fs::read(backup_path).unwrap());
+        assert_eq!(b"written", fs::read(&path).unwrap().as_slice());
+    }
+
+    #[test]
+    fn write_with_should_succeed_if_the_location_is_written_and_the_image_is_unchanged() {
+        let (_tmp_dir, path) = photo_without_gps();
+        let options = WriteOptions::new(None, true);
+
+        let result = write_with(&path, &photo_coordinates(), &options, || {
+            fs::copy("tests/assets/photo.jpg", &path).map(|_| ())
+        });
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn write_with_should_restore_the_file_if_the_location_was_not_written() {
+        let (_tmp_dir, path) = photo_without_gps();
+        let original = fs::read(&path).unwrap();
+        let options = WriteOptions::new(Some(BackupLocation::Sibling), true);
+
+        let result = write_with(&path, &photo_coordinates(), &options, || Ok(()));
+
+        match result {
+            Err(WriteError::VerificationFailed(_, reason)) => {
+                assert_eq!("it has no location", reason)
+            }
+            x => panic!("Expected a verification failure, got {:?}", x),
+        }
+        assert_eq!(original, fs::read(&path).unwrap());
+    }
+
+    #[test]
+    fn write_with_should_restore_the_file_if_its_image_data_changed() {
+        let (_tmp_dir, path) = photo_without_gps();
+        let original = fs::read(&path).unwrap();
+        let options = WriteOptions::new(None, true);
+
+        // Change a byte of the last block of image data.
+        let mut changed = original.clone();
+        let index = changed.len() - 4;
+        changed[index] ^= 0x01;
+
+        let result = write_with(&path, &photo_coordinates(), &options, || {
+            fs::write(&path, &changed)
+        });
+
+        match result {
+            Err(WriteError::VerificationFailed(_, reason)) => {
+                assert_eq!("its image data has changed", reason)
+            }
+            x => panic!("Expected a verification failure, got {:?}", x),
+        }
+        assert_eq!(original, fs::read(&path).unwrap());
+    }
+
+    #[test]
+    fn write_with_should_restore_the_file_if_the_write_fails_after_changing_it() {
+        let (_tmp_dir, path) = photo_without_gps();
+        let original = fs::read(&path).unwrap();
+        let options = WriteOptions::default();
+
+        let result = write_with(&path, &photo_coordinates(), &options, || {
+            File::create(&path)?;
+            Err(io::Error::new(io::ErrorKind::Other, "failed"))
+        });
+
+        match result {
+            Err(WriteError::IoError(ref e)) => assert_eq!("failed", e.to_string()),
+            x => panic!("Expected an IO error, got {:?}", x),
+        }
+        assert_eq!(original, fs::read(&path).unwrap());
+    }
+
+    #[test]
+    fn write_with_should_not_verify_the_file_unless_configured_to() {
+        let (_tmp_dir, path) = photo_without_gps();
+        let options = WriteOptions::default();
+
+        let result = write_with(&path, &photo_coordinates(), &options, || {
+            fs::write(&path, b"written")
+        });
+
+        assert!(result.unwrap().is_none());
+        assert_eq!(b"written", fs::read(&path).unwrap().as_slice());
+    }
+}
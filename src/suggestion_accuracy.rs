@@ -2,15 +2,22 @@ use std::fmt;
 
 use chrono::Duration;
 
+use photo::TimestampSource;
+
 #[derive(Debug, PartialEq, Serialize)]
 pub struct SuggestionAccuracy {
     meters: u32,
     seconds: i64,
+    timestamp_source: TimestampSource,
 }
 
 impl SuggestionAccuracy {
-    pub fn new(meters: u32, seconds: i64) -> SuggestionAccuracy {
-        SuggestionAccuracy { meters, seconds }
+    pub fn new(meters: u32, seconds: i64, timestamp_source: TimestampSource) -> SuggestionAccuracy {
+        SuggestionAccuracy {
+            meters,
+            seconds,
+            timestamp_source,
+        }
     }
 
     pub fn meters(&self) -> u32 {
@@ -21,6 +28,15 @@ impl SuggestionAccuracy {
         self.seconds
     }
 
+    pub fn timestamp_source(&self) -> TimestampSource {
+        self.timestamp_source
+    }
+
+    /// A suggestion is low confidence if the photo's timestamp came from a fallback source.
+    pub fn is_low_confidence(&self) -> bool {
+        self.timestamp_source.is_fallback()
+    }
+
     fn pretty_print_time(&self) -> String {
         if self.seconds == 0 {
             return "0 seconds".to_string();
@@ -67,7 +83,17 @@ impl SuggestionAccuracy {
 
 impl fmt::Display for SuggestionAccuracy {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} metres, {}", self.meters, self.pretty_print_time())
+        write!(f, "{} metres, {}", self.meters, self.pretty_print_time())?;
+
+        if self.is_low_confidence() {
+            write!(
+                f,
+                " (low confidence, timestamp from {})",
+                self.timestamp_source
+            )?;
+        }
+
+        Ok(())
     }
 }
 
@@ -89,19 +115,29 @@ mod tests {
 
     #[test]
     fn suggestion_accuracy_display_should_format_value_correctly() {
-        let accuracy = SuggestionAccuracy::new(18, 0);
+        let accuracy = SuggestionAccuracy::new(18, 0, TimestampSource::DateTimeOriginal);
         assert_eq!("18 metres, 0 seconds", format!("{}", accuracy));
 
-        let accuracy = SuggestionAccuracy::new(18, 3600);
+        let accuracy = SuggestionAccuracy::new(18, 3600, TimestampSource::GpsTime);
         assert_eq!("18 metres, 1 hour", format!("{}", accuracy));
 
-        let accuracy = SuggestionAccuracy::new(18, 90);
+        let accuracy = SuggestionAccuracy::new(18, 90, TimestampSource::GpsTime);
         assert_eq!("18 metres, 1 minute, 30 seconds", format!("{}", accuracy));
 
-        let accuracy = SuggestionAccuracy::new(18, 20499642);
+        let accuracy = SuggestionAccuracy::new(18, 20499642, TimestampSource::GpsTime);
         assert_eq!(
             "18 metres, 33 weeks, 6 days, 6 hours, 20 minutes, 42 seconds",
             format!("{}", accuracy)
         );
     }
+
+    #[test]
+    fn suggestion_accuracy_display_should_mention_a_fallback_timestamp_source() {
+        let accuracy = SuggestionAccuracy::new(18, 90, TimestampSource::FileName);
+
+        assert_eq!(
+            "18 metres, 1 minute, 30 seconds (low confidence, timestamp from file name)",
+            format!("{}", accuracy)
+        );
+    }
 }
@@ -0,0 +1,307 @@
+use std::fs;
+use std::path::Path;
+use std::str::{self, FromStr};
+use std::time::UNIX_EPOCH;
+
+use chrono::{DateTime, NaiveDateTime};
+
+use media_format::read_xmp;
+use photo::{Timestamp, TimestampSource};
+
+/// A lower-confidence source of a photo's timestamp, for use when the photo's metadata doesn't
+/// record when it was taken.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimestampFallback {
+    /// A date and time in the file name, e.g. `IMG_20190704_153012.jpg` or
+    /// `PXL_20190704_153012345.jpg`.
+    FileName,
+    /// The `xmp:CreateDate` property of the file's XMP metadata.
+    XmpCreateDate,
+    /// The file's last modification time.
+    FileModificationTime,
+}
+
+impl TimestampFallback {
+    /// Read a Unix timestamp in milliseconds for the given file.
+    pub fn read(self, path: &Path) -> Option<i64> {
+        match self {
+            TimestampFallback::FileName => path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(file_name_timestamp),
+            TimestampFallback::XmpCreateDate => read_xmp(path)
+                .ok()
+                .and_then(|xmp| xmp)
+                .and_then(|xmp| xmp_create_date(&xmp)),
+            TimestampFallback::FileModificationTime => fs::metadata(path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64 * 1000 + i64::from(d.subsec_millis())),
+        }
+    }
+
+    pub fn source(self) -> TimestampSource {
+        match self {
+            TimestampFallback::FileName => TimestampSource::FileName,
+            TimestampFallback::XmpCreateDate => TimestampSource::XmpCreateDate,
+            TimestampFallback::FileModificationTime => TimestampSource::FileModificationTime,
+        }
+    }
+}
+
+impl FromStr for TimestampFallback {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "filename" => Ok(TimestampFallback::FileName),
+            "xmp" => Ok(TimestampFallback::XmpCreateDate),
+            "mtime" => Ok(TimestampFallback::FileModificationTime),
+            _ => Err(format!(
+                "Unrecognised timestamp fallback \"{}\", expected one of filename, xmp or mtime",
+                s
+            )),
+        }
+    }
+}
+
+/// Get a timestamp from the first of the given fallbacks that can provide one.
+pub fn read_fallback_timestamp(path: &Path, fallbacks: &[TimestampFallback]) -> Option<Timestamp> {
+    fallbacks
+        .iter()
+        .filter_map(|f| f.read(path).map(|ms| Timestamp::new(ms, f.source())))
+        .next()
+}
+
+/// Find a `YYYYMMDD_HHMMSS` date and time in the given string, optionally followed by digits of
+/// fractional seconds, as used by Android and Pixel camera apps. Fractional seconds are
+/// truncated to milliseconds.
+fn file_name_timestamp(file_stem: &str) -> Option<i64> {
+    const PATTERN_LENGTH: usize = 15;
+
+    let bytes = file_stem.as_bytes();
+    if bytes.len() < PATTERN_LENGTH {
+        return None;
+    }
+
+    (0..bytes.len() - PATTERN_LENGTH + 1)
+        .filter(|&i| i == 0 || !bytes[i - 1].is_ascii_digit())
+        .filter_map(|i| {
+            let candidate = &bytes[i..i + PATTERN_LENGTH];
+            let is_match = candidate.iter().enumerate().all(|(j, b)| {
+                if j == 8 {
+                    *b == b'_'
+                } else {
+                    b.is_ascii_digit()
+                }
+            });
+            if !is_match {
+                return None;
+            }
+
+            let date_time = str::from_utf8(candidate).ok()?;
+            let timestamp = NaiveDateTime::parse_from_str(date_time, "%Y%m%d_%H%M%S").ok()?;
+
+            let digits: Vec<i64> = bytes[i + PATTERN_LENGTH..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .take(3)
+                .map(|b| i64::from(b - b'0'))
+                .collect();
+            let millis = (0..3).fold(0, |ms, j| ms * 10 + digits.get(j).cloned().unwrap_or(0));
+
+            Some(timestamp.timestamp() * 1000 + millis)
+        }).next()
+}
+
+/// Read the `xmp:CreateDate` property from any XMP packet in the given data. The property may be
+/// written as either an attribute or an element.
+fn xmp_create_date(data: &[u8]) -> Option<i64> {
+    const PROPERTY: &[u8] = b"xmp:CreateDate";
+
+    let start = find(data, b"<x:xmpmeta")?;
+    let end = find(&data[start..], b"</x:xmpmeta>").map_or(data.len(), |i| start + i);
+    let xmp = &data[start..end];
+
+    let mut position = 0;
+    while let Some(offset) = find(&xmp[position..], PROPERTY) {
+        position += offset + PROPERTY.len();
+
+        let value_start = match xmp.get(position) {
+            Some(b'=') => position + 2,
+            Some(b'>') => position + 1,
+            _ => continue,
+        };
+        let value_end = xmp[value_start..]
+            .iter()
+            .position(|b| *b == b'"' || *b == b'\'' || *b == b'<')
+            .map(|i| value_start + i)?;
+
+        return str::from_utf8(&xmp[value_start..value_end])
+            .ok()
+            .and_then(parse_xmp_date);
+    }
+
+    None
+}
+
+/// XMP dates are ISO 8601 strings that may omit the seconds and time zone. Dates without a time
+/// zone are treated as UTC, like EXIF dates.
+fn parse_xmp_date(value: &str) -> Option<i64> {
+    let value = value.trim();
+
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Some(date_time.timestamp_millis());
+    }
+
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .filter_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|date_time| date_time.timestamp_millis())
+        .next()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    use std::fs::File;
+    use std::io::Write;
+    use std::time::SystemTime;
+
+    use self::tempfile::tempdir;
+
+    #[test]
+    fn from_str_should_parse_fallback_names() {
+        assert_eq!(Ok(TimestampFallback::FileName), "filename".parse());
+        assert_eq!(Ok(TimestampFallback::XmpCreateDate), "xmp".parse());
+        assert_eq!(Ok(TimestampFallback::FileModificationTime), "mtime".parse());
+        assert!("exif".parse::<TimestampFallback>().is_err());
+    }
+
+    #[test]
+    fn file_name_timestamp_should_read_android_camera_file_names() {
+        assert_eq!(
+            Some(1562254212000),
+            file_name_timestamp("IMG_20190704_153012")
+        );
+        assert_eq!(Some(1562254212000), file_name_timestamp("20190704_153012"));
+        assert_eq!(
+            Some(1562254212345),
+            file_name_timestamp("PXL_20190704_153012345")
+        );
+        assert_eq!(
+            Some(1562254212345),
+            file_name_timestamp("PXL_20190704_153012345.MP")
+        );
+    }
+
+    #[test]
+    fn file_name_timestamp_should_read_fractional_seconds_as_milliseconds() {
+        assert_eq!(
+            Some(1562254212500),
+            file_name_timestamp("PXL_20190704_1530125")
+        );
+        assert_eq!(
+            Some(1562254212340),
+            file_name_timestamp("PXL_20190704_15301234")
+        );
+        assert_eq!(
+            Some(1562254212345),
+            file_name_timestamp("PXL_20190704_153012345")
+        );
+        assert_eq!(
+            Some(1562254212345),
+            file_name_timestamp("PXL_20190704_153012345678")
+        );
+    }
+
+    #[test]
+    fn file_name_timestamp_should_be_none_if_there_is_no_valid_date_and_time() {
+        assert_eq!(None, file_name_timestamp("photo"));
+        assert_eq!(None, file_name_timestamp("IMG_1234"));
+        assert_eq!(None, file_name_timestamp("IMG_20191304_153012"));
+        assert_eq!(None, file_name_timestamp("120190704_153012"));
+    }
+
+    #[test]
+    fn xmp_create_date_should_read_an_attribute_or_element_value() {
+        let attribute =
+            br#"<x:xmpmeta><rdf:Description xmp:CreateDate="2019-07-04T15:30:12"/></x:xmpmeta>"#;
+        assert_eq!(Some(1562254212000), xmp_create_date(attribute));
+
+        let element =
+            b"<x:xmpmeta><xmp:CreateDate>2019-07-04T16:30:12.5+01:00</xmp:CreateDate></x:xmpmeta>";
+        assert_eq!(Some(1562254212500), xmp_create_date(element));
+    }
+
+    #[test]
+    fn xmp_create_date_should_be_none_if_there_is_no_create_date() {
+        let xmp = br#"<x:xmpmeta><rdf:Description xmp:ModifyDate="2019-07-04T15:30"/></x:xmpmeta>"#;
+
+        assert_eq!(None, xmp_create_date(xmp));
+        assert_eq!(
+            None,
+            xmp_create_date(b"xmp:CreateDate=\"2019-07-04T15:30\"")
+        );
+    }
+
+    #[test]
+    fn parse_xmp_date_should_accept_dates_without_seconds_or_a_time_zone() {
+        assert_eq!(Some(1562254200000), parse_xmp_date("2019-07-04T15:30"));
+        assert_eq!(Some(1562254212000), parse_xmp_date("2019-07-04T15:30:12Z"));
+        assert_eq!(None, parse_xmp_date("2019"));
+    }
+
+    #[test]
+    fn read_should_read_the_file_modification_time() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("photo.jpg");
+        File::create(&path).unwrap();
+
+        let expected = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+            * 1000;
+        let timestamp = TimestampFallback::FileModificationTime.read(&path).unwrap();
+
+        assert!((timestamp - expected).abs() < 10000);
+    }
+
+    #[test]
+    fn read_fallback_timestamp_should_use_the_first_fallback_that_gives_a_timestamp() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("IMG_20190704_153012.jpg");
+        let xmp = br#"<x:xmpmeta xmp:CreateDate="2019-07-04T15:30"/></x:xmpmeta>"#;
+        let mut file = File::create(&path).unwrap();
+        file.write_all(&[0xFF, 0xD8, 0xFF, 0xE1, 0, 29 + xmp.len() as u8 + 2])
+            .unwrap();
+        file.write_all(b"http://ns.adobe.com/xap/1.0/\0").unwrap();
+        file.write_all(xmp).unwrap();
+        file.write_all(&[0xFF, 0xD9]).unwrap();
+
+        let fallbacks = [
+            TimestampFallback::XmpCreateDate,
+            TimestampFallback::FileName,
+        ];
+        assert_eq!(
+            Some(Timestamp::new(
+                1562254200000,
+                TimestampSource::XmpCreateDate
+            )),
+            read_fallback_timestamp(&path, &fallbacks)
+        );
+
+        let path = tmp_dir.path().join("photo.jpg");
+        File::create(&path).unwrap();
+        assert_eq!(None, read_fallback_timestamp(&path, &fallbacks));
+    }
+}
@@ -0,0 +1,770 @@
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use coordinates::Coordinates;
+use safe_write::replace_file_with;
+
+/// The number of seconds between the QuickTime epoch (1904-01-01) and the Unix epoch.
+const QUICKTIME_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+const ISO6709_KEY: &[u8] = b"com.apple.quicktime.location.ISO6709";
+const LOCATION_ATOM: [u8; 4] = [0xA9, b'x', b'y', b'z'];
+const ENGLISH_LANGUAGE_CODE: u16 = 0x15C7;
+const UTF8_DATA_TYPE: u32 = 1;
+
+/// The user type of the top-level `uuid` atom that MP4 files store XMP packets in.
+const XMP_UUID: [u8; 16] = [
+    0xBE, 0x7A, 0xCF, 0xCB, 0x97, 0xA9, 0x42, 0xE8, 0x9C, 0x71, 0x99, 0x94, 0x91, 0xE3, 0xAF, 0xAC,
+];
+
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "mov"];
+
+const CONTAINER_ATOMS: &[&[u8; 4]] = &[
+    b"moov", b"trak", b"mdia", b"minf", b"stbl", b"udta", b"edts", b"dinf", b"meta", b"ilst",
+];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct VideoMetadata {
+    creation_time: Option<i64>,
+    coordinates: Option<Coordinates>,
+    dimensions: Option<(u32, u32)>,
+}
+
+impl VideoMetadata {
+    /// The movie creation time as a Unix timestamp in seconds.
+    pub fn creation_time(&self) -> Option<i64> {
+        self.creation_time
+    }
+
+    pub fn coordinates(&self) -> Option<&Coordinates> {
+        self.coordinates.as_ref()
+    }
+
+    /// The width and height of the first video track.
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        self.dimensions
+    }
+}
+
+pub fn read_video_metadata(path: &Path) -> io::Result<VideoMetadata> {
+    let mut file = File::open(path)?;
+    let (_, moov) = read_moov(&mut file)?;
+
+    let mut metadata = VideoMetadata::default();
+
+    if let Some(mvhd) = moov.child(b"mvhd").and_then(Atom::data) {
+        metadata.creation_time = read_creation_time(mvhd)?;
+    }
+
+    metadata.coordinates = moov
+        .child(b"udta")
+        .and_then(|udta| udta.child(&LOCATION_ATOM))
+        .and_then(Atom::data)
+        .and_then(read_location_atom)
+        .or_else(|| moov.child(b"meta").and_then(read_location_metadata));
+
+    metadata.dimensions = moov
+        .children()
+        .iter()
+        .filter(|a| a.kind == *b"trak")
+        .find(|trak| is_video_track(trak))
+        .and_then(|trak| trak.child(b"tkhd"))
+        .and_then(Atom::data)
+        .and_then(read_track_dimensions);
+
+    Ok(metadata)
+}
+
+/// Read the XMP packet from the movie's user data, as QuickTime files store it, or from a
+/// top-level XMP `uuid` atom, as MP4 files store it. Media data atoms are skipped over.
+pub fn read_video_xmp(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    let mut file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+
+    let mut offset = 0;
+    while offset < file_size {
+        let header = read_atom_header(&mut file, offset, file_size)?;
+        let content_size = header.size - header.header_size;
+
+        if header.kind == *b"moov" {
+            let mut content = vec![0; content_size as usize];
+            file.read_exact(&mut content)?;
+
+            let moov = Atom::parse(header.kind, &content)?;
+            let xmp = moov
+                .child(b"udta")
+                .and_then(|udta| udta.child(b"XMP_"))
+                .and_then(Atom::data);
+            if let Some(xmp) = xmp {
+                return Ok(Some(xmp.to_vec()));
+            }
+        } else if header.kind == *b"uuid" && content_size >= 16 {
+            let mut user_type = [0; 16];
+            file.read_exact(&mut user_type)?;
+
+            if user_type == XMP_UUID {
+                let mut xmp = vec![0; (content_size - 16) as usize];
+                file.read_exact(&mut xmp)?;
+                return Ok(Some(xmp));
+            }
+        }
+
+        offset += header.size;
+    }
+
+    Ok(None)
+}
+
+/// Write the given coordinates into the movie's user data and, if the file already has
+/// QuickTime metadata, into its ISO 6709 location key. The whole file is rewritten to a
+/// temporary file that then replaces the original, as growing the movie atom shifts any
+/// media data that follows it.
+pub fn write_video_coordinates(path: &Path, coordinates: &Coordinates) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let (moov_header, mut moov) = read_moov(&mut file)?;
+
+    let iso6709 = coordinates.to_iso6709();
+    set_location_atom(&mut moov, &iso6709);
+    if let Some(meta) = moov.child_mut(b"meta") {
+        set_location_metadata(meta, &iso6709)?;
+    }
+
+    let old_moov_end = moov_header.offset + moov_header.size;
+    let mut new_moov = moov.to_bytes();
+    let delta = new_moov.len() as i64 - moov_header.size as i64;
+    if delta != 0 {
+        moov.shift_chunk_offsets(old_moov_end, delta)?;
+        new_moov = moov.to_bytes();
+    }
+
+    // The original is closed before it's replaced, as Windows can't rename over an open file.
+    replace_file_with(path, move |temp_file| {
+        file.seek(SeekFrom::Start(0))?;
+        io::copy(&mut (&mut file).take(moov_header.offset), temp_file)?;
+        temp_file.write_all(&new_moov)?;
+        file.seek(SeekFrom::Start(old_moov_end))?;
+        io::copy(&mut file, temp_file)?;
+        Ok(())
+    })
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+struct AtomHeader {
+    kind: [u8; 4],
+    offset: u64,
+    size: u64,
+    header_size: u64,
+}
+
+fn read_atom_header(file: &mut File, offset: u64, file_size: u64) -> io::Result<AtomHeader> {
+    let mut buffer = [0; 8];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+
+    let mut kind = [0; 4];
+    kind.copy_from_slice(&buffer[4..]);
+
+    let (size, header_size) = match read_u32(&buffer, 0) {
+        0 => (file_size - offset, 8),
+        1 => {
+            file.read_exact(&mut buffer)?;
+            (read_u64(&buffer, 0), 16)
+        }
+        x => (u64::from(x), 8),
+    };
+
+    if size < header_size || offset + size > file_size {
+        return Err(invalid_data("Atom size is out of bounds"));
+    }
+
+    Ok(AtomHeader {
+        kind,
+        offset,
+        size,
+        header_size,
+    })
+}
+
+fn read_moov(file: &mut File) -> io::Result<(AtomHeader, Atom)> {
+    let file_size = file.metadata()?.len();
+
+    let mut offset = 0;
+    while offset < file_size {
+        let header = read_atom_header(file, offset, file_size)?;
+
+        if header.kind == *b"moov" {
+            let mut content = vec![0; (header.size - header.header_size) as usize];
+            file.read_exact(&mut content)?;
+
+            let moov = Atom::parse(header.kind, &content)?;
+            return Ok((header, moov));
+        }
+
+        offset += header.size;
+    }
+
+    Err(invalid_data("The file has no movie atom"))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Content {
+    Container { prefix: Vec<u8>, children: Vec<Atom> },
+    Leaf(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Atom {
+    kind: [u8; 4],
+    content: Content,
+}
+
+impl Atom {
+    fn leaf(kind: [u8; 4], data: Vec<u8>) -> Atom {
+        Atom {
+            kind,
+            content: Content::Leaf(data),
+        }
+    }
+
+    fn container(kind: [u8; 4]) -> Atom {
+        Atom {
+            kind,
+            content: Content::Container {
+                prefix: Vec::new(),
+                children: Vec::new(),
+            },
+        }
+    }
+
+    fn parse(kind: [u8; 4], content: &[u8]) -> io::Result<Atom> {
+        if !CONTAINER_ATOMS.contains(&&kind) {
+            return Ok(Atom::leaf(kind, content.to_vec()));
+        }
+
+        // An ISO base media meta atom has version and flags before its children, but a
+        // QuickTime one doesn't. Both start with a handler atom.
+        let prefix_length = if kind == *b"meta" && content.get(4..8) != Some(&b"hdlr"[..]) {
+            4.min(content.len())
+        } else {
+            0
+        };
+
+        Ok(Atom {
+            kind,
+            content: Content::Container {
+                prefix: content[..prefix_length].to_vec(),
+                children: parse_atoms(&content[prefix_length..])?,
+            },
+        })
+    }
+
+    fn data(&self) -> Option<&[u8]> {
+        match self.content {
+            Content::Leaf(ref data) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn children(&self) -> &[Atom] {
+        match self.content {
+            Content::Container { ref children, .. } => children,
+            _ => &[],
+        }
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<Atom>> {
+        match self.content {
+            Content::Container {
+                ref mut children, ..
+            } => Some(children),
+            _ => None,
+        }
+    }
+
+    fn child(&self, kind: &[u8; 4]) -> Option<&Atom> {
+        self.children().iter().find(|a| a.kind == *kind)
+    }
+
+    fn child_mut(&mut self, kind: &[u8; 4]) -> Option<&mut Atom> {
+        self.children_mut()
+            .and_then(|children| children.iter_mut().find(|a| a.kind == *kind))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut content = Vec::new();
+        match self.content {
+            Content::Leaf(ref data) => content.extend_from_slice(data),
+            Content::Container {
+                ref prefix,
+                ref children,
+            } => {
+                content.extend_from_slice(prefix);
+                for child in children {
+                    content.extend(child.to_bytes());
+                }
+            }
+        }
+
+        let mut bytes = Vec::with_capacity(content.len() + 16);
+        let size = content.len() as u64 + 8;
+        if size > u64::from(u32::max_value()) {
+            bytes.extend_from_slice(&1u32.to_be_bytes());
+            bytes.extend_from_slice(&self.kind);
+            bytes.extend_from_slice(&(size + 8).to_be_bytes());
+        } else {
+            bytes.extend_from_slice(&(size as u32).to_be_bytes());
+            bytes.extend_from_slice(&self.kind);
+        }
+        bytes.extend(content);
+
+        bytes
+    }
+
+    /// Add the given delta to all chunk offsets that point at or past the given file offset.
+    fn shift_chunk_offsets(&mut self, threshold: u64, delta: i64) -> io::Result<()> {
+        match self.content {
+            Content::Container {
+                ref mut children, ..
+            } => {
+                for child in children {
+                    child.shift_chunk_offsets(threshold, delta)?;
+                }
+            }
+            Content::Leaf(ref mut data) => {
+                let entry_size = match &self.kind {
+                    b"stco" => 4,
+                    b"co64" => 8,
+                    _ => return Ok(()),
+                };
+
+                if data.len() < 8 {
+                    return Err(invalid_data("Chunk offset atom is too short"));
+                }
+
+                let count = read_u32(data, 4) as usize;
+                if data.len() < 8 + count * entry_size {
+                    return Err(invalid_data("Chunk offset atom is too short"));
+                }
+
+                for i in 0..count {
+                    let position = 8 + i * entry_size;
+                    if entry_size == 4 {
+                        let offset = u64::from(read_u32(data, position));
+                        if offset >= threshold {
+                            let offset = u32::try_from(shifted_offset(offset, delta)?)
+                                .map_err(|_| invalid_data("Chunk offset would overflow stco"))?;
+                            data[position..position + 4].copy_from_slice(&offset.to_be_bytes());
+                        }
+                    } else {
+                        let offset = read_u64(data, position);
+                        if offset >= threshold {
+                            let offset = shifted_offset(offset, delta)?;
+                            data[position..position + 8].copy_from_slice(&offset.to_be_bytes());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn shifted_offset(offset: u64, delta: i64) -> io::Result<u64> {
+    let shifted = if delta >= 0 {
+        offset.checked_add(delta as u64)
+    } else {
+        offset.checked_sub(delta.unsigned_abs())
+    };
+
+    shifted.ok_or_else(|| invalid_data("Chunk offset is out of bounds"))
+}
+
+fn parse_atoms(mut data: &[u8]) -> io::Result<Vec<Atom>> {
+    let mut atoms = Vec::new();
+
+    // User data lists may be terminated by a 32-bit zero, so stop when there isn't room for
+    // another atom header.
+    while data.len() >= 8 {
+        let mut kind = [0; 4];
+        kind.copy_from_slice(&data[4..8]);
+
+        let (size, header_size) = match read_u32(data, 0) {
+            0 => (data.len() as u64, 8),
+            1 if data.len() >= 16 => (read_u64(data, 8), 16),
+            1 => return Err(invalid_data("Atom size is out of bounds")),
+            x => (u64::from(x), 8),
+        };
+
+        if size < header_size || size > data.len() as u64 {
+            return Err(invalid_data("Atom size is out of bounds"));
+        }
+
+        atoms.push(Atom::parse(
+            kind,
+            &data[header_size as usize..size as usize],
+        )?);
+        data = &data[size as usize..];
+    }
+
+    Ok(atoms)
+}
+
+fn read_u16(data: &[u8], position: usize) -> u16 {
+    u16::from(data[position]) << 8 | u16::from(data[position + 1])
+}
+
+fn read_u32(data: &[u8], position: usize) -> u32 {
+    (0..4).fold(0, |value, i| value << 8 | u32::from(data[position + i]))
+}
+
+fn read_u64(data: &[u8], position: usize) -> u64 {
+    (0..8).fold(0, |value, i| value << 8 | u64::from(data[position + i]))
+}
+
+fn read_creation_time(mvhd: &[u8]) -> io::Result<Option<i64>> {
+    let creation_time = match mvhd.first() {
+        Some(0) if mvhd.len() >= 8 => i64::from(read_u32(mvhd, 4)),
+        Some(1) if mvhd.len() >= 12 => read_u64(mvhd, 4) as i64,
+        _ => return Err(invalid_data("Movie header atom is invalid")),
+    };
+
+    // Many devices write zero if they don't know the time.
+    if creation_time == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(creation_time - QUICKTIME_EPOCH_OFFSET))
+    }
+}
+
+fn read_location_atom(data: &[u8]) -> Option<Coordinates> {
+    if data.len() < 4 {
+        return None;
+    }
+
+    let length = read_u16(data, 0) as usize;
+    let value = data.get(4..4 + length)?;
+
+    String::from_utf8(value.to_vec())
+        .ok()
+        .and_then(|s| Coordinates::from_iso6709(&s))
+}
+
+fn location_key_index(meta: &Atom) -> Option<u32> {
+    let keys = meta.child(b"keys").and_then(Atom::data)?;
+
+    let count = read_u32(keys.get(..8)?, 4);
+    let mut position = 8;
+    for index in 1..=count {
+        let key_size = read_u32(keys.get(position..position + 4)?, 0) as usize;
+        let key = keys.get(position + 8..position + key_size)?;
+
+        if key == ISO6709_KEY {
+            return Some(index);
+        }
+
+        position += key_size;
+    }
+
+    None
+}
+
+fn read_location_metadata(meta: &Atom) -> Option<Coordinates> {
+    let index = location_key_index(meta)?;
+
+    let data = meta
+        .child(b"ilst")?
+        .children()
+        .iter()
+        .find(|item| item.kind == index.to_be_bytes())
+        .and_then(Atom::data)?;
+
+    let data = parse_atoms(data).ok()?;
+    let value = data
+        .iter()
+        .find(|a| a.kind == *b"data")
+        .and_then(Atom::data)?;
+
+    String::from_utf8(value.get(8..)?.to_vec())
+        .ok()
+        .and_then(|s| Coordinates::from_iso6709(&s))
+}
+
+fn is_video_track(trak: &Atom) -> bool {
+    trak.child(b"mdia")
+        .and_then(|mdia| mdia.child(b"hdlr"))
+        .and_then(Atom::data)
+        .and_then(|hdlr| hdlr.get(8..12))
+        .map(|handler_type| handler_type == b"vide")
+        .unwrap_or(false)
+}
+
+/// The track width and height are the last two values in the track header, and are 16.16
+/// fixed-point numbers.
+fn read_track_dimensions(tkhd: &[u8]) -> Option<(u32, u32)> {
+    if tkhd.len() < 84 {
+        return None;
+    }
+
+    let width = read_u32(tkhd, tkhd.len() - 8) >> 16;
+    let height = read_u32(tkhd, tkhd.len() - 4) >> 16;
+
+    Some((width, height))
+}
+
+fn set_location_atom(moov: &mut Atom, iso6709: &str) {
+    if moov.child(b"udta").is_none() {
+        if let Some(children) = moov.children_mut() {
+            children.push(Atom::container(*b"udta"));
+        }
+    }
+
+    let mut data = Vec::with_capacity(iso6709.len() + 4);
+    data.extend_from_slice(&(iso6709.len() as u16).to_be_bytes());
+    data.extend_from_slice(&ENGLISH_LANGUAGE_CODE.to_be_bytes());
+    data.extend_from_slice(iso6709.as_bytes());
+
+    if let Some(children) = moov.child_mut(b"udta").and_then(Atom::children_mut) {
+        children.retain(|a| a.kind != LOCATION_ATOM);
+        children.push(Atom::leaf(LOCATION_ATOM, data));
+    }
+}
+
+fn set_location_metadata(meta: &mut Atom, iso6709: &str) -> io::Result<()> {
+    if meta.child(b"keys").is_none() || meta.child(b"ilst").is_none() {
+        return Ok(());
+    }
+
+    let index = match location_key_index(meta) {
+        Some(index) => index,
+        None => add_location_key(meta)?,
+    };
+
+    let mut value = Vec::with_capacity(iso6709.len() + 8);
+    value.extend_from_slice(&UTF8_DATA_TYPE.to_be_bytes());
+    value.extend_from_slice(&0u32.to_be_bytes());
+    value.extend_from_slice(iso6709.as_bytes());
+    let item = Atom::leaf(index.to_be_bytes(), Atom::leaf(*b"data", value).to_bytes());
+
+    if let Some(items) = meta.child_mut(b"ilst").and_then(Atom::children_mut) {
+        items.retain(|a| a.kind != item.kind);
+        items.push(item);
+    }
+
+    Ok(())
+}
+
+fn add_location_key(meta: &mut Atom) -> io::Result<u32> {
+    let keys = match meta.child_mut(b"keys").map(|a| &mut a.content) {
+        Some(Content::Leaf(ref mut keys)) if keys.len() >= 8 => keys,
+        _ => return Err(invalid_data("Metadata keys atom is invalid")),
+    };
+
+    let index = read_u32(keys, 4) + 1;
+    keys[4..8].copy_from_slice(&index.to_be_bytes());
+
+    keys.extend_from_slice(&(ISO6709_KEY.len() as u32 + 8).to_be_bytes());
+    keys.extend_from_slice(b"mdta");
+    keys.extend_from_slice(ISO6709_KEY);
+
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    use std::fs::{copy, read, read_dir};
+
+    use self::tempfile::tempdir;
+
+    fn atom_bytes(kind: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        Atom::leaf(*kind, content.to_vec()).to_bytes()
+    }
+
+    fn metadata_atom(keys: &[&[u8]], values: &[&str]) -> Vec<u8> {
+        let mut keys_content = vec![0, 0, 0, 0];
+        keys_content.extend_from_slice(&(keys.len() as u32).to_be_bytes());
+        for key in keys {
+            keys_content.extend_from_slice(&(key.len() as u32 + 8).to_be_bytes());
+            keys_content.extend_from_slice(b"mdta");
+            keys_content.extend_from_slice(key);
+        }
+
+        let mut ilst_content = Vec::new();
+        for (i, value) in values.iter().enumerate() {
+            let mut data = vec![0, 0, 0, 1, 0, 0, 0, 0];
+            data.extend_from_slice(value.as_bytes());
+            let item = atom_bytes(&(i as u32 + 1).to_be_bytes(), &atom_bytes(b"data", &data));
+            ilst_content.extend(item);
+        }
+
+        let mut hdlr_content = vec![0; 8];
+        hdlr_content.extend_from_slice(b"mdta");
+        hdlr_content.extend_from_slice(&[0; 13]);
+
+        let mut meta_content = atom_bytes(b"hdlr", &hdlr_content);
+        meta_content.extend(atom_bytes(b"keys", &keys_content));
+        meta_content.extend(atom_bytes(b"ilst", &ilst_content));
+
+        atom_bytes(b"meta", &meta_content)
+    }
+
+    #[test]
+    fn read_video_metadata_should_error_if_the_file_is_not_a_video() {
+        assert!(read_video_metadata(Path::new("tests/assets/photo.jpg")).is_err());
+    }
+
+    #[test]
+    fn read_video_metadata_should_read_creation_time_location_and_dimensions() {
+        let metadata = read_video_metadata(Path::new("tests/assets/video.mp4")).unwrap();
+
+        assert_eq!(Some(1473158321), metadata.creation_time());
+        assert_eq!(
+            Some(&Coordinates::new(38.76544, -9.094802)),
+            metadata.coordinates()
+        );
+        assert_eq!(Some((64, 48)), metadata.dimensions());
+    }
+
+    #[test]
+    fn read_video_metadata_should_not_set_coordinates_if_there_is_no_location() {
+        let path = Path::new("tests/assets/video_without_gps.mov");
+        let metadata = read_video_metadata(path).unwrap();
+
+        assert_eq!(Some(1473158321), metadata.creation_time());
+        assert_eq!(None, metadata.coordinates());
+    }
+
+    #[test]
+    fn read_video_xmp_should_read_a_top_level_xmp_uuid_atom() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("video.mp4");
+
+        let mut uuid_content = XMP_UUID.to_vec();
+        uuid_content.extend_from_slice(b"<x:xmp>");
+        let mut data = atom_bytes(b"ftyp", b"isom");
+        data.extend(atom_bytes(b"uuid", &[0; 16]));
+        data.extend(atom_bytes(b"uuid", &uuid_content));
+        data.extend(atom_bytes(b"mdat", &[0; 8]));
+        File::create(&path).unwrap().write_all(&data).unwrap();
+
+        assert_eq!(Some(b"<x:xmp>".to_vec()), read_video_xmp(&path).unwrap());
+    }
+
+    #[test]
+    fn read_location_metadata_should_read_the_iso6709_key_value() {
+        let meta = metadata_atom(
+            &[b"com.apple.quicktime.make", ISO6709_KEY],
+            &["Apple", "+52.0796+001.1965+012.345/"],
+        );
+        let meta = parse_atoms(&meta).unwrap().remove(0);
+
+        assert_eq!(
+            Some(Coordinates::new(52.0796, 1.1965)),
+            read_location_metadata(&meta)
+        );
+    }
+
+    #[test]
+    fn set_location_metadata_should_add_a_location_key_if_one_does_not_exist() {
+        let meta = metadata_atom(&[b"com.apple.quicktime.make"], &["Apple"]);
+        let mut meta = parse_atoms(&meta).unwrap().remove(0);
+
+        set_location_metadata(&mut meta, "+52.0796+001.1965/").unwrap();
+
+        assert_eq!(Some(2), location_key_index(&meta));
+        assert_eq!(
+            Some(Coordinates::new(52.0796, 1.1965)),
+            read_location_metadata(&meta)
+        );
+    }
+
+    #[test]
+    fn atoms_should_serialise_to_the_bytes_they_were_parsed_from() {
+        let bytes = read("tests/assets/video.mp4").unwrap();
+        let atoms = parse_atoms(&bytes).unwrap();
+
+        let serialised: Vec<u8> = atoms.iter().flat_map(Atom::to_bytes).collect();
+
+        assert_eq!(bytes, serialised);
+    }
+
+    #[test]
+    fn write_video_coordinates_should_replace_an_existing_location() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("video.mp4");
+        copy("tests/assets/video.mp4", &path).unwrap();
+
+        let coordinates = Coordinates::new(52.0796733, 1.1965831);
+        write_video_coordinates(&path, &coordinates).unwrap();
+
+        let metadata = read_video_metadata(&path).unwrap();
+        assert_eq!(Some(1473158321), metadata.creation_time());
+        assert_eq!(
+            Some(&Coordinates::new(52.079673, 1.196583)),
+            metadata.coordinates()
+        );
+        assert_eq!(1, read_dir(tmp_dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn write_video_coordinates_should_shift_chunk_offsets_of_media_after_the_movie_atom() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("video.mov");
+        copy("tests/assets/video_without_gps.mov", &path).unwrap();
+
+        let coordinates = Coordinates::new(52.0796733, 1.1965831);
+        write_video_coordinates(&path, &coordinates).unwrap();
+
+        let metadata = read_video_metadata(&path).unwrap();
+        assert_eq!(
+            Some(&Coordinates::new(52.079673, 1.196583)),
+            metadata.coordinates()
+        );
+
+        let bytes = read(&path).unwrap();
+        let atoms = parse_atoms(&bytes).unwrap();
+        let stco = atoms[1]
+            .child(b"trak")
+            .and_then(|a| a.child(b"mdia"))
+            .and_then(|a| a.child(b"minf"))
+            .and_then(|a| a.child(b"stbl"))
+            .and_then(|a| a.child(b"stco"))
+            .and_then(Atom::data)
+            .unwrap();
+        let chunk_offset = read_u32(stco, 8) as usize;
+
+        assert_eq!(&[0, 1, 2, 3, 4, 5, 6, 7], &bytes[chunk_offset..]);
+    }
+
+    #[test]
+    fn shift_chunk_offsets_should_error_if_a_32_bit_offset_would_overflow() {
+        let mut data = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        data.extend_from_slice(&(u32::max_value() - 4).to_be_bytes());
+        let mut stco = Atom::leaf(*b"stco", data);
+
+        assert!(stco.shift_chunk_offsets(0, 8).is_err());
+    }
+
+    #[test]
+    fn shift_chunk_offsets_should_shift_offsets_at_or_past_the_threshold() {
+        let mut data = vec![0, 0, 0, 0, 0, 0, 0, 2];
+        data.extend_from_slice(&10u32.to_be_bytes());
+        data.extend_from_slice(&20u32.to_be_bytes());
+        let mut stco = Atom::leaf(*b"stco", data);
+
+        stco.shift_chunk_offsets(20, -4).unwrap();
+
+        let data = stco.data().unwrap();
+        assert_eq!(10, read_u32(data, 8));
+        assert_eq!(16, read_u32(data, 12));
+    }
+}
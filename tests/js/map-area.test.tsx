@@ -1,6 +1,6 @@
 import * as React from 'react';
 import * as renderer from 'react-test-renderer';
-import { Photo } from '../../src/gui/js/interfaces';
+import { Photo, Track } from '../../src/gui/js/interfaces';
 
 jest.mock('google-map-react', () => ({
   default: 'GoogleMapReact'
@@ -8,8 +8,23 @@ jest.mock('google-map-react', () => ({
 jest.mock('react-icons/fi', () => ({
   FiMapPin: 'FiMapPin'
 }));
+jest.mock('../../src/gui/js/offline-map', () => ({
+  OfflineMap: 'OfflineMap'
+}));
 
 import { MapArea } from '../../src/gui/js/map-area'; // eslint-disable-line import/first
+import { TrackOverlay } from '../../src/gui/js/track-overlay'; // eslint-disable-line import/first
+
+const track: Track = {
+  type: 'FeatureCollection',
+  features: [
+    {
+      type: 'Feature',
+      geometry: { type: 'Point', coordinates: [36.1, 51.9] },
+      properties: { timestampMs: 1000, offsetSeconds: -60, accuracy: 18 }
+    }
+  ]
+};
 
 describe('MapArea', () => {
   test('renders a map centered at (0,0) if no photo prop is set', () => {
@@ -40,4 +55,175 @@ describe('MapArea', () => {
     const mapArea = renderer.create(<MapArea photo={photo} />).toJSON();
     expect(mapArea).toMatchSnapshot();
   });
+
+  test('renders an offline map with a marker if offlineTiles is true', () => {
+    const photo = {
+      location: {
+        Existing: {
+          latitude: 52.0,
+          longitude: 36.2
+        }
+      },
+      path: '',
+      src: ''
+    };
+    const mapArea = renderer
+      .create(<MapArea photo={photo} offlineTiles />)
+      .toJSON();
+    expect(mapArea).toMatchSnapshot();
+  });
+
+  test('clicking on the map alone moves the marker without setting any locations', () => {
+    const handleLocationSet = jest.fn();
+    const mapArea = renderer.create(
+      <MapArea handleLocationSet={handleLocationSet} />
+    );
+
+    mapArea.root.instance.handleClick({ lat: 12.5, lng: -3 });
+
+    expect(handleLocationSet.mock.calls.length).toBe(0);
+    expect(mapArea.root.instance.state.pendingPosition).toEqual({
+      lat: 12.5,
+      lng: -3
+    });
+
+    const marker = mapArea.root.findByType('FiMapPin');
+    expect(marker.props.lat).toBe(12.5);
+    expect(marker.props.lng).toBe(-3);
+  });
+
+  test('confirming a clicked location calls handleLocationSet with its coordinates', () => {
+    const handleLocationSet = jest.fn();
+    const mapArea = renderer.create(
+      <MapArea handleLocationSet={handleLocationSet} />
+    );
+
+    mapArea.root.instance.handleClick({ lat: 12.5, lng: -3 });
+    const confirmButton = mapArea.root
+      .findAllByType('button')
+      .find(button => button.props.children === 'Set location here');
+    confirmButton.props.onClick();
+
+    expect(handleLocationSet.mock.calls).toEqual([
+      [{ latitude: 12.5, longitude: -3 }]
+    ]);
+    expect(mapArea.root.instance.state.pendingPosition).toBe(undefined);
+  });
+
+  test('cancelling a clicked location does not call handleLocationSet', () => {
+    const handleLocationSet = jest.fn();
+    const mapArea = renderer.create(
+      <MapArea handleLocationSet={handleLocationSet} />
+    );
+
+    mapArea.root.instance.handleClick({ lat: 12.5, lng: -3 });
+    mapArea.root.instance.handleLocationCancel();
+
+    expect(handleLocationSet.mock.calls.length).toBe(0);
+    expect(mapArea.root.instance.state.pendingPosition).toBe(undefined);
+    expect(mapArea.root.findAllByType('button').length).toBe(0);
+  });
+
+  test('clicking on the offline map moves the marker without calling handleLocationSet', () => {
+    const handleLocationSet = jest.fn();
+    const mapArea = renderer.create(
+      <MapArea handleLocationSet={handleLocationSet} offlineTiles />
+    );
+
+    const offlineMap = mapArea.root.findByType('OfflineMap');
+    offlineMap.props.handleClick({ latitude: 12.5, longitude: -3 });
+
+    expect(handleLocationSet.mock.calls.length).toBe(0);
+    expect(mapArea.root.findByType('OfflineMap').props.marker).toEqual({
+      latitude: 12.5,
+      longitude: -3
+    });
+  });
+
+  test('selecting a different photo discards a pending location', () => {
+    const photo = { path: 'a', src: '' };
+    const mapArea = renderer.create(
+      <MapArea photo={photo} handleLocationSet={jest.fn()} />
+    );
+
+    mapArea.root.instance.handleClick({ lat: 12.5, lng: -3 });
+    mapArea.update(
+      <MapArea photo={{ path: 'b', src: '' }} handleLocationSet={jest.fn()} />
+    );
+
+    expect(mapArea.root.instance.state.pendingPosition).toBe(undefined);
+  });
+
+  test('dragging the marker only calls handleLocationSet with the coordinates it was dropped at once confirmed', () => {
+    const photo = {
+      location: {
+        Existing: {
+          latitude: 52.0,
+          longitude: 36.2
+        }
+      },
+      path: '',
+      src: ''
+    };
+    const handleLocationSet = jest.fn();
+    const mapArea = renderer.create(
+      <MapArea photo={photo} handleLocationSet={handleLocationSet} />
+    ).root.instance;
+
+    mapArea.handleMarkerDrag('marker', {}, { lat: 52, lng: 36.2 });
+    mapArea.handleMarkerDrag('marker', {}, { lat: 50, lng: 30 });
+
+    expect(mapArea.state.dragPosition).toEqual({ lat: 50, lng: 30 });
+    expect(handleLocationSet.mock.calls.length).toBe(0);
+
+    mapArea.handleMarkerDragEnd();
+
+    expect(mapArea.state.dragPosition).toBe(undefined);
+    expect(mapArea.state.pendingPosition).toEqual({ lat: 50, lng: 30 });
+    expect(handleLocationSet.mock.calls.length).toBe(0);
+
+    mapArea.handleLocationConfirm();
+
+    expect(handleLocationSet.mock.calls).toEqual([
+      [{ latitude: 50, longitude: 30 }]
+    ]);
+  });
+
+  test('renders a track overlay at the first track point at the current zoom level', () => {
+    const mapArea = renderer.create(<MapArea track={track} />);
+
+    mapArea.root.instance.handleChange({ zoom: 8 });
+
+    const overlay = mapArea.root.findByType(TrackOverlay);
+    expect(overlay.props.lat).toBe(51.9);
+    expect(overlay.props.lng).toBe(36.1);
+    expect(overlay.props.zoom).toBe(8);
+  });
+
+  test('passes track points to the offline map', () => {
+    const mapArea = renderer.create(<MapArea track={track} offlineTiles />);
+
+    const offlineMap = mapArea.root.findByType('OfflineMap');
+    expect(offlineMap.props.trackPoints.length).toBe(1);
+  });
+
+  test('mouse events for children other than the marker do not drag it', () => {
+    const mapArea = renderer.create(
+      <MapArea track={track} handleLocationSet={jest.fn()} />
+    ).root.instance;
+
+    mapArea.handleMarkerDrag('track', {}, { lat: 50, lng: 30 });
+
+    expect(mapArea.state.dragPosition).toBe(undefined);
+  });
+
+  test('dragging the marker does nothing if there is no handleLocationSet callback', () => {
+    const mapArea = renderer.create(<MapArea />).root.instance;
+
+    mapArea.handleMarkerDrag('marker', {}, { lat: 50, lng: 30 });
+    mapArea.handleMarkerDragEnd();
+
+    expect(mapArea.state.dragPosition).toBe(undefined);
+    expect(mapArea.state.pendingPosition).toBe(undefined);
+  });
 });
@@ -0,0 +1,117 @@
+import * as React from 'react';
+import * as renderer from 'react-test-renderer';
+
+jest.mock('react-icons/fi', () => ({
+  FiMapPin: 'FiMapPin'
+}));
+
+import { OfflineMap } from '../../src/gui/js/offline-map'; // eslint-disable-line import/first
+
+const center = { latitude: 52.0, longitude: 36.2 };
+
+function mouseEvent(clientX: number, clientY: number) {
+  return {
+    clientX,
+    clientY,
+    currentTarget: {
+      getBoundingClientRect: () => ({
+        bottom: 200,
+        height: 200,
+        left: 0,
+        right: 400,
+        top: 0,
+        width: 400
+      })
+    }
+  };
+}
+
+describe('OfflineMap', () => {
+  test('renders tiles from the server around the centre tile', () => {
+    const map = renderer.create(<OfflineMap center={center} zoom={5} />);
+
+    const sources = map.root
+      .findAllByType('img')
+      .map(image => image.props.src);
+
+    expect(sources.length).toBe(49);
+    expect(sources).toContain('/tiles/5/19/10');
+  });
+
+  test('renders only the tiles that exist at zoom level 0', () => {
+    const map = renderer.create(<OfflineMap center={center} zoom={0} />);
+
+    const sources = map.root
+      .findAllByType('img')
+      .map(image => image.props.src);
+
+    expect(sources.length).toBe(7);
+    expect(sources.every(source => source === '/tiles/0/0/0')).toBe(true);
+  });
+
+  test('clicking on the map calls handleClick with the clicked coordinates', () => {
+    const handleClick = jest.fn();
+    const map = renderer.create(
+      <OfflineMap center={center} zoom={5} handleClick={handleClick} />
+    ).root.instance;
+
+    map.handleMouseDown(mouseEvent(200, 100));
+    map.handleMouseUp(mouseEvent(200, 100));
+
+    expect(handleClick.mock.calls.length).toBe(1);
+    expect(handleClick.mock.calls[0][0].latitude).toBeCloseTo(52.0, 6);
+    expect(handleClick.mock.calls[0][0].longitude).toBeCloseTo(36.2, 6);
+  });
+
+  test('dragging the map pans it without calling handleClick', () => {
+    const handleClick = jest.fn();
+    const map = renderer.create(
+      <OfflineMap center={center} zoom={5} handleClick={handleClick} />
+    ).root.instance;
+
+    map.handleMouseDown(mouseEvent(200, 100));
+    map.handleMouseMove(mouseEvent(250, 80));
+    map.handleMouseUp(mouseEvent(250, 80));
+
+    expect(map.state.panOffset).toEqual({ x: 50, y: -20 });
+    expect(map.state.drag).toBe(undefined);
+    expect(handleClick.mock.calls.length).toBe(0);
+  });
+
+  test('zooming scales the pan offset and stays within the zoom limits', () => {
+    const map = renderer.create(<OfflineMap center={center} zoom={0} />).root
+      .instance;
+
+    map.setState({ panOffset: { x: 10, y: -4 } });
+    map.handleZoomIn();
+
+    expect(map.state.zoom).toBe(1);
+    expect(map.state.panOffset).toEqual({ x: 20, y: -8 });
+
+    map.handleZoomOut();
+    map.handleZoomOut();
+
+    expect(map.state.zoom).toBe(0);
+    expect(map.state.panOffset).toEqual({ x: 10, y: -4 });
+  });
+
+  test('renders a track overlay positioned at its first point', () => {
+    const trackPoints = [
+      {
+        accuracy: 18,
+        coordinates: center,
+        offsetSeconds: -60,
+        timestampMs: 1000
+      }
+    ];
+    const map = renderer.create(
+      <OfflineMap center={center} zoom={5} trackPoints={trackPoints} />
+    );
+
+    const overlay = map.root.findByType('svg');
+
+    expect(overlay.props.style.left).toBe('calc(50% + 0px)');
+    expect(overlay.props.style.top).toBe('calc(50% + 0px)');
+    expect(map.root.findAllByType('circle').length).toBe(1);
+  });
+});
@@ -40,6 +40,7 @@ const photos: Photo[] = [
 
 const requests = {
   writeCoordinates: jest.fn().mockReturnValueOnce(Promise.resolve()),
+  writeUserCoordinates: jest.fn().mockReturnValueOnce(Promise.resolve()),
   getFilteredPhotos: jest
     .fn()
     .mockReturnValueOnce(Promise.resolve([photos[1]])),
@@ -53,18 +54,46 @@ const requests = {
             latitude: 5,
             longitude: 30
           }
-        }
+        },
+        place: 'Suva, Central, Fiji'
       }
     ])
   ),
   getPhotos: jest.fn().mockReturnValue(Promise.resolve(photos)),
+  getTrack: jest.fn().mockReturnValue(
+    Promise.resolve({
+      type: 'FeatureCollection',
+      features: [
+        {
+          type: 'Feature',
+          geometry: { type: 'Point', coordinates: [13.2, 52.0] },
+          properties: { timestampMs: 1000, offsetSeconds: -60, accuracy: 18 }
+        }
+      ]
+    })
+  ),
   getNewRootPath: jest
     .fn()
     .mockReturnValue(Promise.resolve({ rootPath: 'foo' })),
   getNewLocationHistory: jest
     .fn()
     .mockReturnValueOnce(Promise.resolve({ locationHistoryPath: 'bar' })),
-  putInterpolate: jest.fn().mockReturnValueOnce(Promise.resolve())
+  putInterpolate: jest.fn().mockReturnValueOnce(Promise.resolve()),
+  putCopyFromPhotos: jest.fn().mockReturnValueOnce(Promise.resolve()),
+  subscribeToEvents: jest.fn().mockReturnValue({ close: jest.fn() }),
+  saveSuggestedLocations: jest
+    .fn()
+    .mockImplementation((maxAccuracyMeters, handleProgress) => {
+      handleProgress({ Started: { total: 2 } });
+      handleProgress({
+        Saved: {
+          path: 'path 2',
+          coordinates: { latitude: 52.0, longitude: 13.2 }
+        }
+      });
+      handleProgress({ Failed: { path: 'path 1', error: 'Oh no!' } });
+      return Promise.resolve();
+    })
 };
 
 jest.mock('../../src/gui/js/requests', () => requests);
@@ -81,6 +110,8 @@ describe('Page', () => {
         locationHistoryPath=""
         photos={photos}
         interpolate={false}
+        copyFromPhotos={false}
+        offlineTiles={false}
       />,
       {
         createNodeMock: element => {
@@ -97,20 +128,30 @@ describe('Page', () => {
 
   beforeEach(() => {
     requests.writeCoordinates.mockClear();
+    requests.writeUserCoordinates.mockClear();
     requests.getFilteredPhotos.mockClear();
     requests.getLocation.mockClear();
     requests.getLocations.mockClear();
     requests.getPhotos.mockClear();
+    requests.getTrack.mockClear();
     requests.getNewRootPath.mockClear();
     requests.getNewLocationHistory.mockClear();
     requests.putInterpolate.mockClear();
+    requests.putCopyFromPhotos.mockClear();
+    requests.saveSuggestedLocations.mockClear();
 
     page.root.instance.setState({
       rootPath: '',
       interpolate: false,
+      copyFromPhotos: false,
       filterPhotos: false,
       currentPhoto: undefined,
-      photos
+      maxAccuracy: '',
+      photos,
+      saveStatus: '',
+      scanStatus: '',
+      selectedPhotos: [],
+      track: undefined
     });
   });
 
@@ -123,16 +164,83 @@ describe('Page', () => {
 
     expect(pageInstance.state.currentPhoto).toBe(undefined);
 
-    pageInstance.handlePhotoSelect(pageInstance.state.photos[1]);
+    pageInstance.handlePhotoSelect(pageInstance.state.photos[1], false);
 
     expect(pageInstance.state.currentPhoto).toBe(pageInstance.state.photos[1]);
+    expect(pageInstance.state.selectedPhotos).toEqual([photos[1]]);
     expect(pageInstance.photosGrid.forceUpdate.mock.calls.length).toBe(1);
   });
 
+  test('handlePhotoSelect should toggle the given photo in the selection if adding to it', () => {
+    const pageInstance = page.root.instance;
+
+    pageInstance.handlePhotoSelect(photos[0], false);
+    pageInstance.handlePhotoSelect(photos[1], true);
+
+    expect(pageInstance.state.currentPhoto).toBe(photos[1]);
+    expect(pageInstance.state.selectedPhotos).toEqual([photos[0], photos[1]]);
+
+    pageInstance.handlePhotoSelect(photos[0], true);
+
+    expect(pageInstance.state.currentPhoto).toBe(photos[0]);
+    expect(pageInstance.state.selectedPhotos).toEqual([photos[1]]);
+  });
+
+  test('handlePhotoSelect should get the track around a photo with a suggested location', () => {
+    const pageInstance = page.root.instance;
+
+    return pageInstance.handlePhotoSelect(photos[1], false).then(() => {
+      expect(requests.getTrack.mock.calls).toEqual([['path 2']]);
+      expect(pageInstance.state.track.features.length).toBe(1);
+    });
+  });
+
+  test('handlePhotoSelect should clear the track for a photo without a suggested location', () => {
+    const pageInstance = page.root.instance;
+
+    return pageInstance
+      .handlePhotoSelect(photos[1], false)
+      .then(() => pageInstance.handlePhotoSelect(photos[0], false))
+      .then(() => {
+        expect(requests.getTrack.mock.calls.length).toBe(1);
+        expect(pageInstance.state.track).toBe(undefined);
+      });
+  });
+
+  test('handleLocationSet calls writeUserCoordinates then sets the location of the selected photos', () => {
+    const pageInstance = page.root.instance;
+    const coordinates = { latitude: 10, longitude: 20 };
+
+    pageInstance.handlePhotoSelect(photos[0], false);
+    pageInstance.handlePhotoSelect(photos[1], true);
+
+    return pageInstance.handleLocationSet(coordinates).then(() => {
+      expect(requests.writeUserCoordinates.mock.calls.length).toBe(1);
+      expect(requests.writeUserCoordinates.mock.calls[0]).toEqual([
+        [photos[0].path, photos[1].path],
+        coordinates
+      ]);
+
+      expect(pageInstance.state.photos).not.toBe(photos);
+      pageInstance.state.photos.forEach((photo: Photo) => {
+        expect(photo.location).toEqual({ Existing: coordinates });
+        expect(photo.place).toBe(undefined);
+        expect(photo.userSet).toBe(true);
+      });
+
+      expect(pageInstance.state.currentPhoto).toBe(
+        pageInstance.state.photos[1]
+      );
+      expect(pageInstance.state.selectedPhotos).toEqual(
+        pageInstance.state.photos
+      );
+    });
+  });
+
   test('handleSuggestionApply calls writeCoordinates then moves suggested location to existing', () => {
     const pageInstance = page.root.instance;
 
-    pageInstance.handlePhotoSelect(pageInstance.state.photos[1]);
+    pageInstance.handlePhotoSelect(pageInstance.state.photos[1], false);
 
     const currentPhoto = pageInstance.state.currentPhoto;
     const path = currentPhoto.path;
@@ -166,7 +274,7 @@ describe('Page', () => {
   test("handleSuggestionDiscard should set the current photo's location to be undefined", () => {
     const pageInstance = page.root.instance;
 
-    pageInstance.handlePhotoSelect(pageInstance.state.photos[1]);
+    pageInstance.handlePhotoSelect(pageInstance.state.photos[1], false);
 
     pageInstance.handleSuggestionDiscard();
 
@@ -180,6 +288,37 @@ describe('Page', () => {
     expect(pageInstance.photosGrid.forceUpdate.mock.calls.length).toBe(1);
   });
 
+  test('handleSaveAll calls saveSuggestedLocations and updates saved photos and status', () => {
+    const pageInstance = page.root.instance;
+
+    pageInstance.handlePhotoSelect(photos[1], false);
+    pageInstance.handleMaxAccuracyChange({ target: { value: '50' } });
+
+    return pageInstance.handleSaveAll().then(() => {
+      expect(requests.saveSuggestedLocations.mock.calls.length).toBe(1);
+      expect(requests.saveSuggestedLocations.mock.calls[0][0]).toBe(50);
+
+      expect(pageInstance.state.photos[0]).toBe(photos[0]);
+      expect(pageInstance.state.photos[1].location).toEqual({
+        Existing: { latitude: 52.0, longitude: 13.2 }
+      });
+      expect(pageInstance.state.currentPhoto).toBe(
+        pageInstance.state.photos[1]
+      );
+      expect(pageInstance.state.saveStatus).toBe(
+        'Saved 1 of 2 locations, 1 failed'
+      );
+    });
+  });
+
+  test('handleSaveAll should not limit accuracy if no maximum is given', () => {
+    const pageInstance = page.root.instance;
+
+    return pageInstance.handleSaveAll().then(() => {
+      expect(requests.saveSuggestedLocations.mock.calls[0][0]).toBe(undefined);
+    });
+  });
+
   test('handleFilterToggle should call getFilteredPhotos if the filter is enabled', () => {
     const pageInstance = page.root.instance;
 
@@ -220,28 +359,82 @@ describe('Page', () => {
       });
   });
 
-  test('getNewRootPath should make a getNewRootPath request and update state', () => {
+  test('handleCopyFromPhotosToggle should call putCopyFromPhotos then reload locations', () => {
     const pageInstance = page.root.instance;
 
-    pageInstance.handleFilterToggle = jest
-      .fn()
-      .mockReturnValueOnce(Promise.resolve());
+    return pageInstance
+      .handleCopyFromPhotosToggle({ target: { checked: true } })
+      .then(() => {
+        expect(requests.putCopyFromPhotos.mock.calls.length).toBe(1);
+        expect(requests.putCopyFromPhotos.mock.calls[0]).toEqual([true]);
+        expect(pageInstance.state.copyFromPhotos).toBe(true);
+        expect(pageInstance.state.photos[1].path).toBe(photos[1].path);
+        expect(pageInstance.state.photos[1].loaded).toBe(false);
+      });
+  });
+
+  test('getNewRootPath should make a getNewRootPath request and update state', () => {
+    const pageInstance = page.root.instance;
 
     return pageInstance.getNewRootPath().then(() => {
       expect(requests.getNewRootPath.mock.calls.length).toBe(1);
 
       expect(pageInstance.state.rootPath).toBe('foo');
+    });
+  });
 
-      expect(pageInstance.handleFilterToggle.mock.calls.length).toBe(1);
-      expect(pageInstance.handleFilterToggle.mock.calls[0]).toEqual([
-        {
-          target: { checked: false }
-        }
-      ]);
+  test('handleEvent should clear photos when a scan starts', () => {
+    const pageInstance = page.root.instance;
+
+    pageInstance.handlePhotoSelect(photos[0], false);
+
+    return pageInstance
+      .handleEvent({ ScanStarted: { rootPath: 'bar' } })
+      .then(() => {
+        expect(pageInstance.state.rootPath).toBe('bar');
+        expect(pageInstance.state.photos).toEqual([]);
+        expect(pageInstance.state.currentPhoto).toBe(undefined);
+        expect(pageInstance.state.selectedPhotos).toEqual([]);
+        expect(pageInstance.state.scanStatus).toBe('Scanning for photos...');
+      });
+  });
+
+  test('handleEvent should add found photos to the end of the photos list', () => {
+    const pageInstance = page.root.instance;
+
+    return pageInstance
+      .handleEvent({ PhotosFound: { photos: [photos[0]] } })
+      .then(() => {
+        expect(pageInstance.state.photos).toEqual([
+          photos[0],
+          photos[1],
+          photos[0]
+        ]);
+      });
+  });
+
+  test('handleEvent should not add found photos while filtering photos', () => {
+    const pageInstance = page.root.instance;
+
+    pageInstance.setState({ filterPhotos: true });
+
+    return pageInstance
+      .handleEvent({ PhotosFound: { photos: [photos[0]] } })
+      .then(() => {
+        expect(pageInstance.state.photos).toEqual(photos);
+      });
+  });
+
+  test('handleEvent should show the number of photos found when a scan finishes', () => {
+    const pageInstance = page.root.instance;
+
+    return pageInstance.handleEvent({ ScanFinished: { total: 2 } }).then(() => {
+      expect(pageInstance.state.scanStatus).toBe('Found 2 photos');
+      expect(requests.getFilteredPhotos.mock.calls.length).toBe(0);
     });
   });
 
-  test('getNewRootPath should filter photos according to current filter state', () => {
+  test('handleEvent should filter photos again when a scan finishes while filtering', () => {
     const pageInstance = page.root.instance;
 
     pageInstance.handleFilterToggle = jest
@@ -250,20 +443,80 @@ describe('Page', () => {
 
     pageInstance.setState({ filterPhotos: true });
 
-    return pageInstance.getNewRootPath().then(() => {
-      expect(requests.getNewRootPath.mock.calls.length).toBe(1);
-
-      expect(pageInstance.state.rootPath).toBe('foo');
-
-      expect(pageInstance.handleFilterToggle.mock.calls.length).toBe(1);
-      expect(pageInstance.handleFilterToggle.mock.calls[0]).toEqual([
-        {
-          target: { checked: true }
-        }
+    return pageInstance.handleEvent({ ScanFinished: { total: 2 } }).then(() => {
+      expect(pageInstance.handleFilterToggle.mock.calls).toEqual([
+        [{ target: { checked: true } }]
       ]);
     });
   });
 
+  test('handleEvent should replace a changed photo and reload its location', () => {
+    const pageInstance = page.root.instance;
+
+    pageInstance.handlePhotoSelect(photos[1], false);
+
+    const changedPhoto = {
+      height: 10,
+      width: 5,
+      path: 'path 2',
+      src: 'changed source 2'
+    };
+
+    return pageInstance
+      .handleEvent({ PhotoChanged: { photo: changedPhoto } })
+      .then(() => {
+        const expectedPhoto = Object.assign({}, photos[1], changedPhoto, {
+          loaded: false
+        });
+        expect(pageInstance.state.photos).toEqual([photos[0], expectedPhoto]);
+        expect(pageInstance.state.currentPhoto).toEqual(expectedPhoto);
+        expect(pageInstance.state.selectedPhotos).toEqual([expectedPhoto]);
+      });
+  });
+
+  test('handleEvent should forget removed photos', () => {
+    const pageInstance = page.root.instance;
+
+    pageInstance.handlePhotoSelect(photos[1], false);
+
+    return pageInstance
+      .handleEvent({ PhotosRemoved: { paths: ['path 2'] } })
+      .then(() => {
+        expect(pageInstance.state.photos).toEqual([photos[0]]);
+        expect(pageInstance.state.currentPhoto).toBe(undefined);
+        expect(pageInstance.state.selectedPhotos).toEqual([]);
+        expect(pageInstance.state.track).toBe(undefined);
+      });
+  });
+
+  test('handleEvent should keep the current photo if it was not removed', () => {
+    const pageInstance = page.root.instance;
+
+    pageInstance.handlePhotoSelect(photos[0], false);
+
+    return pageInstance
+      .handleEvent({ PhotosRemoved: { paths: ['path 2'] } })
+      .then(() => {
+        expect(pageInstance.state.photos).toEqual([photos[0]]);
+        expect(pageInstance.state.currentPhoto).toBe(photos[0]);
+        expect(pageInstance.state.selectedPhotos).toEqual([photos[0]]);
+      });
+  });
+
+  test('handleEvent should reload locations when the location history is reloaded', () => {
+    const pageInstance = page.root.instance;
+
+    pageInstance.handlePhotoSelect(photos[1], false);
+    requests.getTrack.mockClear();
+
+    return pageInstance
+      .handleEvent({ LocationHistoryReloaded: { locationHistoryPath: 'bar' } })
+      .then(() => {
+        expect(pageInstance.state.photos[1].loaded).toBe(false);
+        expect(requests.getTrack.mock.calls).toEqual([['path 2']]);
+      });
+  });
+
   test('getNewLocationHistory should make a request and update photos grid state', () => {
     const pageInstance = page.root.instance;
     const initialPhotos = pageInstance.state.photos;
@@ -304,7 +557,7 @@ describe('Page', () => {
     });
   });
 
-  test('getAndStoreLocations sets location, error and loaded photo fields', () => {
+  test('getAndStoreLocations sets location, place, error and loaded photo fields', () => {
     const pageInstance = page.root.instance;
 
     return pageInstance.getAndStoreLocations(0, 2).then(() => {
@@ -312,6 +565,7 @@ describe('Page', () => {
 
       expect(pageInstance.state.photos[0]).not.toBe(photos[0]);
       expect(pageInstance.state.photos[0].location).toBe(undefined);
+      expect(pageInstance.state.photos[0].place).toBe(undefined);
       expect(pageInstance.state.photos[0].error).toBe('Oh no!');
       expect(pageInstance.state.photos[0].loaded).toBe(true);
       expect(pageInstance.state.photos[1]).not.toBe(photos[1]);
@@ -321,6 +575,7 @@ describe('Page', () => {
           longitude: 30
         }
       });
+      expect(pageInstance.state.photos[1].place).toBe('Suva, Central, Fiji');
       expect(pageInstance.state.photos[1].error).toBe(undefined);
       expect(pageInstance.state.photos[1].loaded).toBe(true);
     });
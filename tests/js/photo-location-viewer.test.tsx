@@ -14,6 +14,10 @@ function apply() {
 
 function discard() {}
 
+function setLocation() {
+  return Promise.resolve();
+}
+
 describe('PhotoLocationViewer', () => {
   test('renders an image, map, location description and disabled buttons if photo location is not suggested', () => {
     const photo = {
@@ -30,6 +34,7 @@ describe('PhotoLocationViewer', () => {
       .create(
         <PhotoLocationViewer
           photo={photo}
+          handleLocationSet={setLocation}
           handleSuggestionApply={apply}
           handleSuggestionDiscard={discard}
         />
@@ -47,6 +52,7 @@ describe('PhotoLocationViewer', () => {
       .create(
         <PhotoLocationViewer
           photo={photo}
+          handleLocationSet={setLocation}
           handleSuggestionApply={apply}
           handleSuggestionDiscard={discard}
         />
@@ -76,6 +82,7 @@ describe('PhotoLocationViewer', () => {
       .create(
         <PhotoLocationViewer
           photo={photo}
+          handleLocationSet={setLocation}
           handleSuggestionApply={apply}
           handleSuggestionDiscard={discard}
         />
@@ -88,6 +95,7 @@ describe('PhotoLocationViewer', () => {
     const photoLocationViewer = renderer
       .create(
         <PhotoLocationViewer
+          handleLocationSet={setLocation}
           handleSuggestionApply={apply}
           handleSuggestionDiscard={discard}
         />
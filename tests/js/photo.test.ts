@@ -26,7 +26,8 @@ describe('locationDescription()', () => {
           {},
           {
             meters: 5,
-            seconds: 20
+            seconds: 20,
+            timestamp_source: 'DateTimeOriginal'
           }
         ] as [Coordinates, LocationAccuracy]
       }
@@ -36,6 +37,26 @@ describe('locationDescription()', () => {
     );
   });
 
+  test('marks a suggestion as low confidence if its timestamp came from a fallback', () => {
+    const photo = {
+      path: '',
+      src: '',
+      location: {
+        Suggested: [
+          {},
+          {
+            meters: 5,
+            seconds: 20,
+            timestamp_source: 'FileName'
+          }
+        ] as [Coordinates, LocationAccuracy]
+      }
+    };
+    expect(locationDescription(photo)).toBe(
+      'Suggested location: accuracy is 5 meters and 20 seconds (low confidence, timestamp estimated)'
+    );
+  });
+
   test('returns existing location if photo has a location but no suggestion', () => {
     const photo = {
       path: '',
@@ -45,6 +66,47 @@ describe('locationDescription()', () => {
     expect(locationDescription(photo)).toBe('Existing location');
   });
 
+  test('notes if an existing location was set manually', () => {
+    const photo = {
+      path: '',
+      src: '',
+      location: {},
+      userSet: true
+    };
+    expect(locationDescription(photo)).toBe('Existing location (set manually)');
+  });
+
+  test('includes the name of the place nearest to the location if known', () => {
+    const suggested = {
+      path: '',
+      src: '',
+      place: 'Ipswich, England, United Kingdom',
+      location: {
+        Suggested: [
+          {},
+          {
+            meters: 5,
+            seconds: 20,
+            timestamp_source: 'DateTimeOriginal'
+          }
+        ] as [Coordinates, LocationAccuracy]
+      }
+    };
+    expect(locationDescription(suggested)).toBe(
+      'Suggested location near Ipswich, England, United Kingdom: accuracy is 5 meters and 20 seconds'
+    );
+
+    const existing = {
+      path: '',
+      src: '',
+      place: 'Sacavém, Lisbon, Portugal',
+      location: {}
+    };
+    expect(locationDescription(existing)).toBe(
+      'Existing location near Sacavém, Lisbon, Portugal'
+    );
+  });
+
   test('returns error if photo has one', () => {
     const photo = {
       path: '',
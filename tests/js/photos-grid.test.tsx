@@ -31,6 +31,7 @@ describe('PhotosGrid', () => {
     filteredPhotosGrid = renderer.create(
       <PhotosGrid
         photos={photos}
+        selectedPhotos={[]}
         getAndStoreLocations={mockGetAndStoreLocations}
         handlePhotoSelect={mockHandlePhotoSelect}
       />
@@ -47,6 +48,7 @@ describe('PhotosGrid', () => {
       .create(
         <PhotosGrid
           photos={photos}
+          selectedPhotos={[]}
           getAndStoreLocations={mockGetAndStoreLocations}
           handlePhotoSelect={mockHandlePhotoSelect}
         />
@@ -121,11 +123,30 @@ describe('PhotosGrid', () => {
       style: { color: 'black' }
     });
 
-    photoThumbnail.props.handleSelect();
+    photoThumbnail.props.handleSelect({ ctrlKey: false, metaKey: false });
 
     expect(mockHandlePhotoSelect.mock.calls.length).toBe(1);
-    expect(mockHandlePhotoSelect.mock.calls[0].length).toBe(1);
+    expect(mockHandlePhotoSelect.mock.calls[0].length).toBe(2);
     expect(mockHandlePhotoSelect.mock.calls[0][0]).toBe(photos[0]);
+    expect(mockHandlePhotoSelect.mock.calls[0][1]).toBe(false);
+  });
+
+  test('rowRender should add to the selection if the ctrl or meta key is held', () => {
+    const photosGrid = filteredPhotosGrid.root.instance;
+
+    const photoThumbnail = photosGrid.rowRenderer({
+      index: 0,
+      key: 1,
+      style: { color: 'black' }
+    });
+
+    photoThumbnail.props.handleSelect({ ctrlKey: true, metaKey: false });
+    photoThumbnail.props.handleSelect({ ctrlKey: false, metaKey: true });
+
+    expect(mockHandlePhotoSelect.mock.calls).toEqual([
+      [photos[0], true],
+      [photos[0], true]
+    ]);
   });
 
   test('rowHeight scales the height of the photo at the given index to match a width of 272', () => {
@@ -0,0 +1,31 @@
+import {
+  metersPerPixel,
+  project,
+  unproject
+} from '../../src/gui/js/projection';
+
+const center = { latitude: 52.0, longitude: 36.2 };
+
+describe('project', () => {
+  test('maps (0,0) to the centre of the world at zoom level 0', () => {
+    expect(project({ latitude: 0, longitude: 0 }, 0)).toEqual({
+      x: 128,
+      y: 128
+    });
+  });
+
+  test('is reversed by unproject', () => {
+    const coordinates = unproject(project(center, 5), 5);
+
+    expect(coordinates.latitude).toBeCloseTo(center.latitude, 6);
+    expect(coordinates.longitude).toBeCloseTo(center.longitude, 6);
+  });
+});
+
+describe('metersPerPixel', () => {
+  test('halves with each zoom level and shrinks away from the equator', () => {
+    expect(metersPerPixel(0, 0)).toBeCloseTo(156543.03, 2);
+    expect(metersPerPixel(0, 1)).toBeCloseTo(78271.52, 2);
+    expect(metersPerPixel(60, 0)).toBeCloseTo(78271.52, 2);
+  });
+});
@@ -0,0 +1,93 @@
+import * as React from 'react';
+import * as renderer from 'react-test-renderer';
+import { Track } from '../../src/gui/js/interfaces';
+import { TrackOverlay, trackPoints } from '../../src/gui/js/track-overlay';
+
+const track: Track = {
+  type: 'FeatureCollection',
+  features: [
+    {
+      type: 'Feature',
+      geometry: {
+        type: 'LineString',
+        coordinates: [[0, 0], [0.001, 0]]
+      },
+      properties: {}
+    },
+    {
+      type: 'Feature',
+      geometry: { type: 'Point', coordinates: [0, 0] },
+      properties: { timestampMs: 1000, offsetSeconds: -60, accuracy: 10 }
+    },
+    {
+      type: 'Feature',
+      geometry: { type: 'Point', coordinates: [0.001, 0] },
+      properties: { timestampMs: 2000, offsetSeconds: -59, accuracy: 1000 }
+    }
+  ]
+};
+
+describe('trackPoints', () => {
+  test('returns an empty array if there is no track', () => {
+    expect(trackPoints(undefined)).toEqual([]);
+  });
+
+  test('returns the point features of a track with their coordinates', () => {
+    expect(trackPoints(track)).toEqual([
+      {
+        accuracy: 10,
+        coordinates: { latitude: 0, longitude: 0 },
+        offsetSeconds: -60,
+        timestampMs: 1000
+      },
+      {
+        accuracy: 1000,
+        coordinates: { latitude: 0, longitude: 0.001 },
+        offsetSeconds: -59,
+        timestampMs: 2000
+      }
+    ]);
+  });
+});
+
+describe('TrackOverlay', () => {
+  test('renders nothing if there are no points', () => {
+    const overlay = renderer.create(<TrackOverlay points={[]} zoom={5} />);
+
+    expect(overlay.toJSON()).toBe(null);
+  });
+
+  test('renders a path and accuracy circles relative to the first point', () => {
+    const overlay = renderer.create(
+      <TrackOverlay points={trackPoints(track)} zoom={10} />
+    ).root;
+
+    const circles = overlay.findAllByType('circle');
+    const [x, y] = overlay
+      .findByType('polyline')
+      .props.points.split(' ')[1]
+      .split(',')
+      .map(Number);
+
+    expect(circles.length).toBe(2);
+    expect(circles[0].props.cx).toBe(0);
+    expect(circles[0].props.cy).toBe(0);
+    expect(circles[1].props.cx).toBeCloseTo(x, 6);
+    expect(circles[1].props.cy).toBeCloseTo(y, 6);
+    expect(x).toBeCloseTo(0.73, 2);
+    expect(y).toBe(0);
+  });
+
+  test('scales accuracy circles to the zoom level but keeps them visible', () => {
+    const overlay = renderer.create(
+      <TrackOverlay points={trackPoints(track)} zoom={10} />
+    ).root;
+
+    const radii = overlay
+      .findAllByType('circle')
+      .map(circle => circle.props.r);
+
+    expect(radii[0]).toBe(3);
+    expect(radii[1]).toBeCloseTo(6.54, 2);
+  });
+});